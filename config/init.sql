@@ -7,19 +7,96 @@ CREATE TABLE users (
   uid UUID NOT NULL,
   PRIMARY KEY (id)
 );
+-- the names and the addresses differing only in case are taken as the same
+CREATE UNIQUE INDEX users_user_name ON users (LOWER(user_name));
+CREATE UNIQUE INDEX users_email ON users (LOWER(email));
 
-DROP TABLE IF EXISTS reviews;
+-- the settings of a user, the defaults apply until the user saves them
+DROP TABLE IF EXISTS preferences;
+CREATE TABLE preferences (
+  uid UUID NOT NULL,
+  -- an IANA time zone name, where the days of the user start
+  timezone VARCHAR(64) NOT NULL DEFAULT 'UTC',
+  locale VARCHAR(35) NOT NULL DEFAULT 'en',
+  -- the number of reviews a day the review sessions draw at most
+  daily_review_limit SMALLINT NOT NULL DEFAULT 20,
+  default_platform SMALLINT,
+  -- off, daily or weekly
+  email_digest VARCHAR(6) NOT NULL DEFAULT 'off',
+  -- the hour of the day in the time zone the digest is sent at
+  digest_hour SMALLINT NOT NULL DEFAULT 8,
+  PRIMARY KEY (uid)
+);
+
+-- Postgres has no parser for Japanese, so every run of kana and kanji is indexed
+-- as overlapping character bigrams plus its last character, each spelled in ASCII
+-- (e.g. '凸包' becomes 'j51f8x5305 j5305') to be tokenized regardless of the locale.
+CREATE OR REPLACE FUNCTION cjk_bigrams(doc TEXT) RETURNS TEXT AS $$
+DECLARE
+  run TEXT;
+  tokens TEXT[] := '{}';
+BEGIN
+  FOR run IN SELECT m[1] FROM regexp_matches(COALESCE(doc, ''), '([\u3040-\u30ff\u3400-\u9fff\uf900-\ufaff\uff66-\uff9f]+)', 'g') AS m LOOP
+    FOR i IN 1 .. char_length(run) - 1 LOOP
+      tokens := tokens || ('j' || to_hex(ascii(substr(run, i, 1))) || 'x' || to_hex(ascii(substr(run, i + 1, 1))));
+    END LOOP;
+    tokens := tokens || ('j' || to_hex(ascii(substr(run, char_length(run), 1))));
+  END LOOP;
+  RETURN array_to_string(tokens, ' ');
+END;
+$$ LANGUAGE plpgsql IMMUTABLE;
+
+-- the problems shared by the reviews of every user, keyed by what their URL identifies
+DROP TABLE IF EXISTS problems CASCADE;
+CREATE TABLE problems (
+  id SERIAL,
+  -- 1: AtCoder, 2: Codeforces, 3: AOJ, 4: yukicoder
+  platform SMALLINT NOT NULL,
+  -- the ID of the problem on the platform, e.g. abc200_a or 1520A
+  external_id VARCHAR(100) NOT NULL,
+  -- derived from the ID, the names given by the users are kept with their reviews
+  title VARCHAR(255) NOT NULL,
+  url VARCHAR(255) NOT NULL,
+  contest VARCHAR(100),
+  -- the difficulty given by the platform, e.g. the Codeforces rating
+  difficulty INTEGER,
+  -- the tags given by the platform
+  tags TEXT[] NOT NULL DEFAULT '{}',
+  PRIMARY KEY (id),
+  UNIQUE (platform, external_id)
+);
+
+DROP TABLE IF EXISTS reviews CASCADE;
 CREATE TABLE reviews (
   id SERIAL,
   problem_name VARCHAR(255) NOT NULL,
   url VARCHAR(255) NOT NULL,
-  memo VARCHAR(255),
+  -- Markdown with $...$ math
+  memo TEXT,
+  -- the memo rendered into sanitized HTML
+  memo_html TEXT,
   uid UUID NOT NULL,
   platform SMALLINT NOT NULL,
-  created_at TIMESTAMP NOT NULL,
-  updated_at TIMESTAMP,
+  -- absent when the URL is not of a known platform
+  problem_id INTEGER REFERENCES problems (id) ON DELETE SET NULL,
+  -- the personal rating from 1 (easy) to 5 (hard)
+  difficulty SMALLINT,
+  -- the difficulty given by the platform in an imported history, the catalog is not filled by users
+  imported_difficulty INTEGER,
+  solved_without_hints BOOLEAN NOT NULL DEFAULT FALSE,
+  created_at TIMESTAMPTZ NOT NULL,
+  updated_at TIMESTAMPTZ,
+  search_vector TSVECTOR GENERATED ALWAYS AS (
+    setweight(to_tsvector('english', problem_name), 'A') ||
+    setweight(to_tsvector('simple', cjk_bigrams(problem_name)), 'A') ||
+    setweight(to_tsvector('english', COALESCE(memo, '')), 'B') ||
+    setweight(to_tsvector('simple', cjk_bigrams(memo)), 'B')
+  ) STORED,
   PRIMARY KEY (id)
 );
+CREATE INDEX reviews_uid ON reviews (uid, id);
+CREATE INDEX reviews_problem ON reviews (problem_id);
+CREATE INDEX reviews_search ON reviews USING GIN (search_vector);
 
 DROP TABLE IF EXISTS tmp_users;
 CREATE TABLE tmp_users (
@@ -28,6 +105,330 @@ CREATE TABLE tmp_users (
   password VARCHAR(255) NOT NULL,
   email VARCHAR(255) NOT NULL,
   uid UUID NOT NULL,
-  created_at TIMESTAMP NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL,
+  PRIMARY KEY (id)
+);
+
+DROP TABLE IF EXISTS sessions;
+CREATE TABLE sessions (
+  id SERIAL,
+  token UUID NOT NULL,
+  uid UUID NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL,
+  PRIMARY KEY (id)
+);
+
+-- named lists of the reviews of a user, shared by link or on the profile of the user
+DROP TABLE IF EXISTS collections CASCADE;
+CREATE TABLE collections (
+  id SERIAL,
+  uid UUID NOT NULL,
+  name VARCHAR(100) NOT NULL,
+  description TEXT,
+  -- private, unlisted (anyone with the link) or public (listed on the profile too)
+  visibility VARCHAR(8) NOT NULL,
+  -- the secret part of the link
+  share_token UUID NOT NULL UNIQUE,
+  created_at TIMESTAMPTZ NOT NULL,
+  updated_at TIMESTAMPTZ,
+  PRIMARY KEY (id)
+);
+CREATE UNIQUE INDEX collections_name ON collections (uid, name);
+
+DROP TABLE IF EXISTS collection_entries;
+CREATE TABLE collection_entries (
+  id SERIAL,
+  collection_id INTEGER NOT NULL REFERENCES collections (id) ON DELETE CASCADE,
+  review_id INTEGER NOT NULL REFERENCES reviews (id) ON DELETE CASCADE,
+  -- shown along with the problem, unlike the memo of the review
+  note VARCHAR(500),
+  added_at TIMESTAMPTZ NOT NULL,
+  PRIMARY KEY (id),
+  UNIQUE (collection_id, review_id)
+);
+
+-- the groups of users training together, owned by the user who created the group
+DROP TABLE IF EXISTS study_groups CASCADE;
+CREATE TABLE study_groups (
+  id SERIAL,
+  -- the owner
+  uid UUID NOT NULL,
+  name VARCHAR(100) NOT NULL,
+  description TEXT,
+  created_at TIMESTAMPTZ NOT NULL,
   PRIMARY KEY (id)
 );
+
+-- the members of a group, the owner included
+DROP TABLE IF EXISTS group_members;
+CREATE TABLE group_members (
+  group_id INTEGER NOT NULL REFERENCES study_groups (id) ON DELETE CASCADE,
+  uid UUID NOT NULL,
+  joined_at TIMESTAMPTZ NOT NULL,
+  PRIMARY KEY (group_id, uid)
+);
+CREATE INDEX group_members_uid ON group_members (uid);
+
+-- the invitations mailed to join a group, accepted by the user of the address invited
+DROP TABLE IF EXISTS group_invitations;
+CREATE TABLE group_invitations (
+  id SERIAL,
+  group_id INTEGER NOT NULL REFERENCES study_groups (id) ON DELETE CASCADE,
+  email VARCHAR(255) NOT NULL,
+  -- the secret of the link in the mail
+  token UUID NOT NULL UNIQUE,
+  created_at TIMESTAMPTZ NOT NULL,
+  -- absent until the invitation is accepted
+  accepted_at TIMESTAMPTZ,
+  PRIMARY KEY (id)
+);
+CREATE INDEX group_invitations_group_id ON group_invitations (group_id, id);
+
+-- the problems a group works on together, each member importing them into their reviews
+DROP TABLE IF EXISTS problem_sets CASCADE;
+CREATE TABLE problem_sets (
+  id SERIAL,
+  group_id INTEGER NOT NULL REFERENCES study_groups (id) ON DELETE CASCADE,
+  name VARCHAR(100) NOT NULL,
+  description TEXT,
+  created_at TIMESTAMPTZ NOT NULL,
+  PRIMARY KEY (id)
+);
+
+DROP TABLE IF EXISTS problem_set_items;
+CREATE TABLE problem_set_items (
+  problem_set_id INTEGER NOT NULL REFERENCES problem_sets (id) ON DELETE CASCADE,
+  problem_id INTEGER NOT NULL REFERENCES problems (id) ON DELETE CASCADE,
+  -- the title given by the owner of the group
+  title VARCHAR(255) NOT NULL,
+  added_at TIMESTAMPTZ NOT NULL,
+  PRIMARY KEY (problem_set_id, problem_id)
+);
+
+-- the discussions on a problem of the catalog or on an entry of a shared collection
+DROP TABLE IF EXISTS comments CASCADE;
+CREATE TABLE comments (
+  id SERIAL,
+  -- the author
+  uid UUID NOT NULL,
+  -- either the problem or the entry the thread is about
+  problem_id INTEGER REFERENCES problems (id) ON DELETE CASCADE,
+  entry_id INTEGER REFERENCES collection_entries (id) ON DELETE CASCADE,
+  -- the comment replied to, absent for the comments starting a thread
+  parent_id INTEGER REFERENCES comments (id) ON DELETE CASCADE,
+  -- Markdown with $...$ math, absent once deleted
+  body TEXT,
+  -- the body rendered into sanitized HTML
+  body_html TEXT,
+  created_at TIMESTAMPTZ NOT NULL,
+  updated_at TIMESTAMPTZ,
+  -- a deleted comment with replies is kept, without its body, to hold the thread together
+  deleted_at TIMESTAMPTZ,
+  PRIMARY KEY (id),
+  CHECK ((problem_id IS NULL) <> (entry_id IS NULL))
+);
+CREATE INDEX comments_problem_id ON comments (problem_id, id);
+CREATE INDEX comments_entry_id ON comments (entry_id, id);
+
+-- the secret token of the calendar feed of a user, regenerated to invalidate the old URL
+DROP TABLE IF EXISTS calendar_feeds;
+CREATE TABLE calendar_feeds (
+  uid UUID NOT NULL,
+  token UUID NOT NULL UNIQUE,
+  created_at TIMESTAMPTZ NOT NULL,
+  PRIMARY KEY (uid)
+);
+
+-- the digest mails of a user, kept so that a restart does not send the same digest twice
+DROP TABLE IF EXISTS email_digests;
+CREATE TABLE email_digests (
+  uid UUID NOT NULL,
+  -- the secret of the unsubscribe links
+  token UUID NOT NULL UNIQUE,
+  -- the local date the period of the last digest sent starts on
+  last_sent_on DATE,
+  -- the failures since the last digest sent, retried with a backoff like the mails
+  attempts INTEGER NOT NULL DEFAULT 0,
+  next_attempt_at TIMESTAMPTZ,
+  last_error TEXT,
+  PRIMARY KEY (uid)
+);
+
+DROP TABLE IF EXISTS email_changes;
+CREATE TABLE email_changes (
+  id SERIAL,
+  uid UUID NOT NULL,
+  email VARCHAR(255) NOT NULL,
+  token UUID NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL,
+  -- the confirmation link stops working afterwards
+  expires_at TIMESTAMPTZ NOT NULL,
+  PRIMARY KEY (id)
+);
+
+DROP TABLE IF EXISTS account_deletions;
+CREATE TABLE account_deletions (
+  id SERIAL,
+  uid UUID NOT NULL,
+  token UUID NOT NULL,
+  confirmed_at TIMESTAMPTZ,
+  created_at TIMESTAMPTZ NOT NULL,
+  PRIMARY KEY (id)
+);
+
+DROP TABLE IF EXISTS email_outbox;
+CREATE TABLE email_outbox (
+  id SERIAL,
+  recipient VARCHAR(255) NOT NULL,
+  subject VARCHAR(255) NOT NULL,
+  body TEXT NOT NULL,
+  status SMALLINT NOT NULL,
+  attempts INTEGER NOT NULL,
+  next_attempt_at TIMESTAMPTZ NOT NULL,
+  last_error TEXT,
+  created_at TIMESTAMPTZ NOT NULL,
+  sent_at TIMESTAMPTZ,
+  PRIMARY KEY (id)
+);
+CREATE INDEX email_outbox_due ON email_outbox (status, next_attempt_at);
+
+-- the endpoints the events on the reviews of a user are posted to
+DROP TABLE IF EXISTS webhooks CASCADE;
+CREATE TABLE webhooks (
+  id SERIAL,
+  uid UUID NOT NULL,
+  url VARCHAR(2048) NOT NULL,
+  -- the key the payloads are signed with
+  secret VARCHAR(64) NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL,
+  PRIMARY KEY (id)
+);
+CREATE INDEX webhooks_uid ON webhooks (uid);
+
+-- the events to post to the webhooks, kept as the delivery log once posted
+DROP TABLE IF EXISTS webhook_deliveries;
+CREATE TABLE webhook_deliveries (
+  id SERIAL,
+  webhook_id INTEGER NOT NULL REFERENCES webhooks (id) ON DELETE CASCADE,
+  uid UUID NOT NULL,
+  event VARCHAR(32) NOT NULL,
+  payload TEXT NOT NULL,
+  status SMALLINT NOT NULL,
+  attempts INTEGER NOT NULL,
+  next_attempt_at TIMESTAMPTZ NOT NULL,
+  -- the HTTP status of the last attempt, absent when no response came
+  response_status SMALLINT,
+  last_error TEXT,
+  created_at TIMESTAMPTZ NOT NULL,
+  delivered_at TIMESTAMPTZ,
+  PRIMARY KEY (id)
+);
+CREATE INDEX webhook_deliveries_due ON webhook_deliveries (status, next_attempt_at);
+CREATE INDEX webhook_deliveries_webhook ON webhook_deliveries (webhook_id, id);
+
+DROP TABLE IF EXISTS tags CASCADE;
+CREATE TABLE tags (
+  id SERIAL,
+  name VARCHAR(50) NOT NULL,
+  -- NULL for the curated tags shared by every user
+  uid UUID,
+  PRIMARY KEY (id)
+);
+CREATE UNIQUE INDEX tags_name ON tags (name, COALESCE(uid, '00000000-0000-0000-0000-000000000000'));
+
+INSERT INTO tags (name) VALUES
+  ('dp'), ('greedy'), ('graph'), ('tree'), ('shortest-path'), ('dfs'), ('bfs'),
+  ('union-find'), ('segment-tree'), ('fenwick-tree'), ('binary-search'), ('two-pointers'),
+  ('sorting'), ('brute-force'), ('bit-manipulation'), ('math'), ('number-theory'),
+  ('combinatorics'), ('probability'), ('geometry'), ('string'), ('hashing'),
+  ('data-structures'), ('constructive'), ('implementation'), ('simulation'),
+  ('game-theory'), ('flow'), ('matching'), ('divide-and-conquer'), ('sqrt-decomposition'),
+  ('bit-dp'), ('digit-dp'), ('tree-dp'), ('interactive');
+
+DROP TABLE IF EXISTS review_tags;
+CREATE TABLE review_tags (
+  review_id INTEGER NOT NULL REFERENCES reviews (id) ON DELETE CASCADE,
+  tag_id INTEGER NOT NULL REFERENCES tags (id) ON DELETE CASCADE,
+  PRIMARY KEY (review_id, tag_id)
+);
+
+DROP TABLE IF EXISTS schedules;
+CREATE TABLE schedules (
+  review_id INTEGER NOT NULL REFERENCES reviews (id) ON DELETE CASCADE,
+  due_at TIMESTAMPTZ NOT NULL,
+  interval_days INTEGER NOT NULL,
+  ease REAL NOT NULL,
+  repetitions INTEGER NOT NULL,
+  PRIMARY KEY (review_id)
+);
+CREATE INDEX schedules_due_at ON schedules (due_at);
+
+DROP TABLE IF EXISTS submissions;
+CREATE TABLE submissions (
+  id SERIAL,
+  review_id INTEGER NOT NULL REFERENCES reviews (id) ON DELETE CASCADE,
+  source_code TEXT NOT NULL,
+  language VARCHAR(50) NOT NULL,
+  -- AC, WA, TLE, MLE, RE, CE, OLE or IE
+  verdict VARCHAR(3) NOT NULL,
+  runtime_ms INTEGER,
+  without_editorial BOOLEAN NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL,
+  PRIMARY KEY (id)
+);
+CREATE INDEX submissions_review_id ON submissions (review_id, id);
+
+-- every version of the problem name and the memo of a review, append-only
+DROP TABLE IF EXISTS review_revisions;
+CREATE TABLE review_revisions (
+  id SERIAL,
+  review_id INTEGER NOT NULL REFERENCES reviews (id) ON DELETE CASCADE,
+  problem_name VARCHAR(255) NOT NULL,
+  memo TEXT,
+  updated_at TIMESTAMPTZ NOT NULL,
+  PRIMARY KEY (id)
+);
+CREATE INDEX review_revisions_review_id ON review_revisions (review_id, id);
+
+-- the timed attempts at solving the problem of a review again
+DROP TABLE IF EXISTS review_attempts;
+CREATE TABLE review_attempts (
+  id SERIAL,
+  review_id INTEGER NOT NULL REFERENCES reviews (id) ON DELETE CASCADE,
+  started_at TIMESTAMPTZ NOT NULL,
+  -- absent while the attempt is running
+  stopped_at TIMESTAMPTZ,
+  seconds INTEGER,
+  PRIMARY KEY (id)
+);
+CREATE INDEX review_attempts_review_id ON review_attempts (review_id, id);
+-- a review is attempted once at a time
+CREATE UNIQUE INDEX review_attempts_running ON review_attempts (review_id) WHERE stopped_at IS NULL;
+
+DROP TABLE IF EXISTS review_sessions CASCADE;
+CREATE TABLE review_sessions (
+  id SERIAL,
+  uid UUID NOT NULL,
+  started_at TIMESTAMPTZ NOT NULL,
+  -- absent while the session can be resumed
+  finished_at TIMESTAMPTZ,
+  PRIMARY KEY (id)
+);
+CREATE INDEX review_sessions_uid ON review_sessions (uid, id);
+-- a user has at most one session left unfinished
+CREATE UNIQUE INDEX review_sessions_unfinished ON review_sessions (uid) WHERE finished_at IS NULL;
+
+-- the reviews drawn for a session, served in the order of the position
+DROP TABLE IF EXISTS session_items;
+CREATE TABLE session_items (
+  session_id INTEGER NOT NULL REFERENCES review_sessions (id) ON DELETE CASCADE,
+  position SMALLINT NOT NULL,
+  review_id INTEGER NOT NULL REFERENCES reviews (id) ON DELETE CASCADE,
+  -- when the item was last served, the time spent is measured from it
+  served_at TIMESTAMPTZ,
+  -- the SM-2 grade from 0 (blackout) to 5 (perfect)
+  grade SMALLINT,
+  seconds INTEGER,
+  graded_at TIMESTAMPTZ,
+  PRIMARY KEY (session_id, position)
+);
@@ -0,0 +1,52 @@
+-- Upgrade a database created by an older init.sql: move the daily review limit into
+-- the preferences and store every timestamp with its time zone.
+-- The timestamps without one were always written in UTC.
+BEGIN;
+
+CREATE TABLE preferences (
+  uid UUID NOT NULL,
+  timezone VARCHAR(64) NOT NULL DEFAULT 'UTC',
+  locale VARCHAR(35) NOT NULL DEFAULT 'en',
+  daily_review_limit SMALLINT NOT NULL DEFAULT 20,
+  default_platform SMALLINT,
+  email_digest VARCHAR(6) NOT NULL DEFAULT 'off',
+  digest_hour SMALLINT NOT NULL DEFAULT 8,
+  PRIMARY KEY (uid)
+);
+INSERT INTO preferences (uid, daily_review_limit)
+  SELECT uid, daily_review_limit FROM users WHERE daily_review_limit <> 20;
+ALTER TABLE users DROP COLUMN daily_review_limit;
+
+ALTER TABLE reviews
+  ALTER COLUMN created_at TYPE TIMESTAMPTZ USING created_at AT TIME ZONE 'UTC',
+  ALTER COLUMN updated_at TYPE TIMESTAMPTZ USING updated_at AT TIME ZONE 'UTC';
+ALTER TABLE tmp_users
+  ALTER COLUMN created_at TYPE TIMESTAMPTZ USING created_at AT TIME ZONE 'UTC';
+ALTER TABLE sessions
+  ALTER COLUMN created_at TYPE TIMESTAMPTZ USING created_at AT TIME ZONE 'UTC';
+ALTER TABLE email_changes
+  ALTER COLUMN created_at TYPE TIMESTAMPTZ USING created_at AT TIME ZONE 'UTC';
+ALTER TABLE account_deletions
+  ALTER COLUMN confirmed_at TYPE TIMESTAMPTZ USING confirmed_at AT TIME ZONE 'UTC',
+  ALTER COLUMN created_at TYPE TIMESTAMPTZ USING created_at AT TIME ZONE 'UTC';
+ALTER TABLE email_outbox
+  ALTER COLUMN next_attempt_at TYPE TIMESTAMPTZ USING next_attempt_at AT TIME ZONE 'UTC',
+  ALTER COLUMN created_at TYPE TIMESTAMPTZ USING created_at AT TIME ZONE 'UTC',
+  ALTER COLUMN sent_at TYPE TIMESTAMPTZ USING sent_at AT TIME ZONE 'UTC';
+ALTER TABLE schedules
+  ALTER COLUMN due_at TYPE TIMESTAMPTZ USING due_at AT TIME ZONE 'UTC';
+ALTER TABLE submissions
+  ALTER COLUMN created_at TYPE TIMESTAMPTZ USING created_at AT TIME ZONE 'UTC';
+ALTER TABLE review_revisions
+  ALTER COLUMN updated_at TYPE TIMESTAMPTZ USING updated_at AT TIME ZONE 'UTC';
+ALTER TABLE review_attempts
+  ALTER COLUMN started_at TYPE TIMESTAMPTZ USING started_at AT TIME ZONE 'UTC',
+  ALTER COLUMN stopped_at TYPE TIMESTAMPTZ USING stopped_at AT TIME ZONE 'UTC';
+ALTER TABLE review_sessions
+  ALTER COLUMN started_at TYPE TIMESTAMPTZ USING started_at AT TIME ZONE 'UTC',
+  ALTER COLUMN finished_at TYPE TIMESTAMPTZ USING finished_at AT TIME ZONE 'UTC';
+ALTER TABLE session_items
+  ALTER COLUMN served_at TYPE TIMESTAMPTZ USING served_at AT TIME ZONE 'UTC',
+  ALTER COLUMN graded_at TYPE TIMESTAMPTZ USING graded_at AT TIME ZONE 'UTC';
+
+COMMIT;
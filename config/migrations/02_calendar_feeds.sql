@@ -0,0 +1,6 @@
+CREATE TABLE calendar_feeds (
+  uid UUID NOT NULL,
+  token UUID NOT NULL UNIQUE,
+  created_at TIMESTAMPTZ NOT NULL,
+  PRIMARY KEY (uid)
+);
@@ -0,0 +1,8 @@
+CREATE TABLE email_digests (
+  uid UUID NOT NULL,
+  -- the secret of the unsubscribe links
+  token UUID NOT NULL UNIQUE,
+  -- the local date the period of the last digest sent starts on
+  last_sent_on DATE,
+  PRIMARY KEY (uid)
+);
@@ -0,0 +1,31 @@
+-- the endpoints the events on the reviews of a user are posted to
+CREATE TABLE webhooks (
+  id SERIAL,
+  uid UUID NOT NULL,
+  url VARCHAR(2048) NOT NULL,
+  -- the key the payloads are signed with
+  secret VARCHAR(64) NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL,
+  PRIMARY KEY (id)
+);
+CREATE INDEX webhooks_uid ON webhooks (uid);
+
+-- the events to post to the webhooks, kept as the delivery log once posted
+CREATE TABLE webhook_deliveries (
+  id SERIAL,
+  webhook_id INTEGER NOT NULL REFERENCES webhooks (id) ON DELETE CASCADE,
+  uid UUID NOT NULL,
+  event VARCHAR(32) NOT NULL,
+  payload TEXT NOT NULL,
+  status SMALLINT NOT NULL,
+  attempts INTEGER NOT NULL,
+  next_attempt_at TIMESTAMPTZ NOT NULL,
+  -- the HTTP status of the last attempt, absent when no response came
+  response_status SMALLINT,
+  last_error TEXT,
+  created_at TIMESTAMPTZ NOT NULL,
+  delivered_at TIMESTAMPTZ,
+  PRIMARY KEY (id)
+);
+CREATE INDEX webhook_deliveries_due ON webhook_deliveries (status, next_attempt_at);
+CREATE INDEX webhook_deliveries_webhook ON webhook_deliveries (webhook_id, id);
@@ -0,0 +1,26 @@
+-- named lists of the reviews of a user, shared by link or on the profile of the user
+CREATE TABLE collections (
+  id SERIAL,
+  uid UUID NOT NULL,
+  name VARCHAR(100) NOT NULL,
+  description TEXT,
+  -- private, unlisted (anyone with the link) or public (listed on the profile too)
+  visibility VARCHAR(8) NOT NULL,
+  -- the secret part of the link
+  share_token UUID NOT NULL UNIQUE,
+  created_at TIMESTAMPTZ NOT NULL,
+  updated_at TIMESTAMPTZ,
+  PRIMARY KEY (id)
+);
+CREATE UNIQUE INDEX collections_name ON collections (uid, name);
+
+CREATE TABLE collection_entries (
+  id SERIAL,
+  collection_id INTEGER NOT NULL REFERENCES collections (id) ON DELETE CASCADE,
+  review_id INTEGER NOT NULL REFERENCES reviews (id) ON DELETE CASCADE,
+  -- shown along with the problem, unlike the memo of the review
+  note VARCHAR(500),
+  added_at TIMESTAMPTZ NOT NULL,
+  PRIMARY KEY (id),
+  UNIQUE (collection_id, review_id)
+);
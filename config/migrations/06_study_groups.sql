@@ -0,0 +1,50 @@
+-- the groups of users training together, owned by the user who created the group
+CREATE TABLE study_groups (
+  id SERIAL,
+  -- the owner
+  uid UUID NOT NULL,
+  name VARCHAR(100) NOT NULL,
+  description TEXT,
+  created_at TIMESTAMPTZ NOT NULL,
+  PRIMARY KEY (id)
+);
+
+-- the members of a group, the owner included
+CREATE TABLE group_members (
+  group_id INTEGER NOT NULL REFERENCES study_groups (id) ON DELETE CASCADE,
+  uid UUID NOT NULL,
+  joined_at TIMESTAMPTZ NOT NULL,
+  PRIMARY KEY (group_id, uid)
+);
+CREATE INDEX group_members_uid ON group_members (uid);
+
+-- the invitations mailed to join a group, accepted by the user of the address invited
+CREATE TABLE group_invitations (
+  id SERIAL,
+  group_id INTEGER NOT NULL REFERENCES study_groups (id) ON DELETE CASCADE,
+  email VARCHAR(255) NOT NULL,
+  -- the secret of the link in the mail
+  token UUID NOT NULL UNIQUE,
+  created_at TIMESTAMPTZ NOT NULL,
+  -- absent until the invitation is accepted
+  accepted_at TIMESTAMPTZ,
+  PRIMARY KEY (id)
+);
+CREATE INDEX group_invitations_group_id ON group_invitations (group_id, id);
+
+-- the problems a group works on together, each member importing them into their reviews
+CREATE TABLE problem_sets (
+  id SERIAL,
+  group_id INTEGER NOT NULL REFERENCES study_groups (id) ON DELETE CASCADE,
+  name VARCHAR(100) NOT NULL,
+  description TEXT,
+  created_at TIMESTAMPTZ NOT NULL,
+  PRIMARY KEY (id)
+);
+
+CREATE TABLE problem_set_items (
+  problem_set_id INTEGER NOT NULL REFERENCES problem_sets (id) ON DELETE CASCADE,
+  problem_id INTEGER NOT NULL REFERENCES problems (id) ON DELETE CASCADE,
+  added_at TIMESTAMPTZ NOT NULL,
+  PRIMARY KEY (problem_set_id, problem_id)
+);
@@ -0,0 +1,23 @@
+-- the discussions on a problem of the catalog or on an entry of a shared collection
+CREATE TABLE comments (
+  id SERIAL,
+  -- the author
+  uid UUID NOT NULL,
+  -- either the problem or the entry the thread is about
+  problem_id INTEGER REFERENCES problems (id) ON DELETE CASCADE,
+  entry_id INTEGER REFERENCES collection_entries (id) ON DELETE CASCADE,
+  -- the comment replied to, absent for the comments starting a thread
+  parent_id INTEGER REFERENCES comments (id) ON DELETE CASCADE,
+  -- Markdown with $...$ math, absent once deleted
+  body TEXT,
+  -- the body rendered into sanitized HTML
+  body_html TEXT,
+  created_at TIMESTAMPTZ NOT NULL,
+  updated_at TIMESTAMPTZ,
+  -- a deleted comment with replies is kept, without its body, to hold the thread together
+  deleted_at TIMESTAMPTZ,
+  PRIMARY KEY (id),
+  CHECK ((problem_id IS NULL) <> (entry_id IS NULL))
+);
+CREATE INDEX comments_problem_id ON comments (problem_id, id);
+CREATE INDEX comments_entry_id ON comments (entry_id, id);
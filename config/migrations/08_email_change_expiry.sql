@@ -0,0 +1,9 @@
+-- Upgrade a database created by an older init.sql: email change links expire.
+-- The pending requests keep a day from when they were made.
+BEGIN;
+
+ALTER TABLE email_changes ADD COLUMN expires_at TIMESTAMPTZ;
+UPDATE email_changes SET expires_at = created_at + INTERVAL '1 day';
+ALTER TABLE email_changes ALTER COLUMN expires_at SET NOT NULL;
+
+COMMIT;
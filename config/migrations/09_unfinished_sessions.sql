@@ -0,0 +1,10 @@
+-- Upgrade a database created by an older init.sql: a user has at most one session
+-- left unfinished. Of those started concurrently, all but the latest are finished.
+BEGIN;
+
+UPDATE review_sessions rs SET finished_at = rs.started_at
+  WHERE rs.finished_at IS NULL
+  AND EXISTS (SELECT 1 FROM review_sessions o WHERE o.uid = rs.uid AND o.finished_at IS NULL AND o.id > rs.id);
+CREATE UNIQUE INDEX review_sessions_unfinished ON review_sessions (uid) WHERE finished_at IS NULL;
+
+COMMIT;
@@ -0,0 +1,12 @@
+-- Upgrade a database created by an older init.sql: the catalog names the problems by
+-- their IDs on the platforms instead of by the names given in the first reviews.
+-- The titles given to the problems of the sets are kept with the sets.
+BEGIN;
+
+ALTER TABLE problem_set_items ADD COLUMN title VARCHAR(255);
+UPDATE problem_set_items i SET title = p.title FROM problems p WHERE p.id = i.problem_id;
+ALTER TABLE problem_set_items ALTER COLUMN title SET NOT NULL;
+
+UPDATE problems SET title = CASE WHEN platform = 4 THEN 'No.' || external_id ELSE external_id END;
+
+COMMIT;
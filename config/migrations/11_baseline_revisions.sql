@@ -0,0 +1,9 @@
+-- Upgrade a database created by an older init.sql: the reviews written before their
+-- revisions were kept get their current content as their first revision.
+BEGIN;
+
+INSERT INTO review_revisions (review_id, problem_name, memo, updated_at)
+  SELECT r.id, r.problem_name, r.memo, COALESCE(r.updated_at, r.created_at) FROM reviews r
+  WHERE NOT EXISTS (SELECT 1 FROM review_revisions v WHERE v.review_id = r.id);
+
+COMMIT;
@@ -0,0 +1,7 @@
+-- Upgrade a database created by an older init.sql: the difficulty given in an imported
+-- history is kept with the review of the user instead of filling in the catalog.
+BEGIN;
+
+ALTER TABLE reviews ADD COLUMN imported_difficulty INTEGER;
+
+COMMIT;
@@ -0,0 +1,10 @@
+-- Upgrade a database created by an older init.sql: the user names and the addresses are
+-- unique whatever their case. The accounts sharing either have to be merged or renamed first,
+-- they are listed by
+--   SELECT LOWER(email), ARRAY_AGG(user_name) FROM users GROUP BY LOWER(email) HAVING COUNT(*) > 1;
+BEGIN;
+
+CREATE UNIQUE INDEX users_user_name ON users (LOWER(user_name));
+CREATE UNIQUE INDEX users_email ON users (LOWER(email));
+
+COMMIT;
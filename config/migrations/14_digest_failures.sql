@@ -0,0 +1,9 @@
+-- Upgrade a database created by an older init.sql: the digests failing to be queued are
+-- retried with a backoff instead of on every run.
+BEGIN;
+
+ALTER TABLE email_digests ADD COLUMN attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE email_digests ADD COLUMN next_attempt_at TIMESTAMPTZ;
+ALTER TABLE email_digests ADD COLUMN last_error TEXT;
+
+COMMIT;
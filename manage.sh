@@ -1,6 +1,31 @@
 #!/bin/bash
 export PGPASSWORD=password
+# the server run locally
+API=${API:-http://127.0.0.1:8000}
+
+# Percent-encode every byte but the unreserved characters, for the query of a request
+# whose body is a file, which `curl -G --data-urlencode` cannot send.
+urlencode() {
+	local LC_ALL=C s=$1 i c
+	for ((i = 0; i < ${#s}; i++)); do
+		c=${s:i:1}
+		case $c in
+			[A-Za-z0-9.~_-]) printf '%s' "$c" ;;
+			*) printf '%%%02X' "'$c" ;;
+		esac
+	done
+}
 
 if [ $1 = "enterdb" ]; then
 	psql -h 127.0.0.1 -p 5432 -U postgres test
+elif [ $1 = "migrate" ]; then
+	# ./manage.sh migrate config/migrations/<file>.sql
+	psql -h 127.0.0.1 -p 5432 -U postgres test -v ON_ERROR_STOP=1 -f $2
+elif [ $1 = "importreviews" ]; then
+	# ./manage.sh importreviews <token> <csv|json> <file> [mapping]
+	curl -sS -X POST -H "Authorization: Bearer $2" --data-binary "@$4" "$API/reviews/bulk?format=$(urlencode "$3")&mapping=$(urlencode "$5")"
+	echo
+elif [ $1 = "exportreviews" ]; then
+	# ./manage.sh exportreviews <token> <csv|json>
+	curl -sS -G -H "Authorization: Bearer $2" --data-urlencode "format=$3" "$API/reviews/export"
 fi
@@ -0,0 +1,143 @@
+use super::infrastructures;
+use crate::auth::AuthenticatedUser;
+use crate::error::ApiError;
+use crate::reviews;
+use actix_web::{get, post, web, HttpResponse};
+use anyhow::Result;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// The attempts can only be reached through a review of the user.
+async fn check_review(pool: &PgPool, uid: &Uuid, review_id: i32) -> Result<(), ApiError> {
+    match reviews::infrastructures::find_review(pool, uid, review_id).await {
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[post("/reviews/{id:\\d+}/attempts")]
+pub async fn start_attempt(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path(id): web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    check_review(pool.get_ref(), &user.uid, id).await?;
+
+    match infrastructures::start_attempt(pool.get_ref(), id).await {
+        Ok(Some(attempt)) => Ok(HttpResponse::Created().json(attempt)),
+        Ok(None) => Err(ApiError::Conflict),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[post("/reviews/{id:\\d+}/attempts/stop")]
+pub async fn stop_attempt(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path(id): web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    check_review(pool.get_ref(), &user.uid, id).await?;
+
+    match infrastructures::stop_attempt(pool.get_ref(), id).await {
+        Ok(Some(attempt)) => Ok(HttpResponse::Ok().json(attempt)),
+        Ok(None) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[get("/reviews/{id:\\d+}/attempts")]
+pub async fn list_attempts(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path(id): web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    check_review(pool.get_ref(), &user.uid, id).await?;
+
+    match infrastructures::find_attempts(pool.get_ref(), id).await {
+        Ok(attempts) => Ok(HttpResponse::Ok().json(attempts)),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::attempts::model::Attempt;
+    use crate::{config, utils};
+    use actix_web::{test, App};
+
+    #[actix_rt::test]
+    async fn attempt_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .data(pool.clone())
+                .service(start_attempt)
+                .service(stop_attempt)
+                .service(list_attempts),
+        )
+        .await;
+        let (uid, token) = utils::sign_in(&pool).await;
+        utils::insert_review(&pool, 0, &uid).await;
+
+        let req = test::TestRequest::post()
+            .uri("/reviews/0/attempts")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(201, resp.status());
+        let req = test::TestRequest::post()
+            .uri("/reviews/0/attempts")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(409, resp.status());
+
+        let req = test::TestRequest::post()
+            .uri("/reviews/0/attempts/stop")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let attempt: Attempt = test::read_body_json(resp).await;
+        assert!(attempt.stopped_at.is_some());
+        let req = test::TestRequest::post()
+            .uri("/reviews/0/attempts/stop")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(404, resp.status());
+
+        let req = test::TestRequest::get()
+            .uri("/reviews/0/attempts")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let attempts: Vec<Attempt> = test::read_body_json(resp).await;
+        assert_eq!(vec![attempt], attempts);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn start_attempt_of_other_user() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app =
+            test::init_service(App::new().data(pool.clone()).service(start_attempt)).await;
+        let (_, token) = utils::sign_in(&pool).await;
+        utils::insert_review(&pool, 0, &Uuid::new_v4()).await;
+
+        let req = test::TestRequest::post()
+            .uri("/reviews/0/attempts")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(404, resp.status());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+}
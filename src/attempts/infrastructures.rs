@@ -0,0 +1,119 @@
+use super::model::Attempt;
+use anyhow::Result;
+use chrono::Utc;
+use sqlx::PgPool;
+
+// None when the review is being attempted already.
+pub async fn start_attempt(pool: &PgPool, review_id: i32) -> Result<Option<Attempt>> {
+    let attempt = sqlx::query_as::<_, Attempt>(
+        r#"INSERT INTO review_attempts (review_id, started_at) VALUES ($1, $2) ON CONFLICT DO NOTHING
+        RETURNING id, review_id, started_at, stopped_at, seconds"#,
+    )
+    .bind(review_id)
+    .bind(Utc::now())
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(attempt)
+}
+
+// Stop the running attempt and store how long it took. None when no attempt is running.
+pub async fn stop_attempt(pool: &PgPool, review_id: i32) -> Result<Option<Attempt>> {
+    let attempt = sqlx::query_as::<_, Attempt>(
+        r#"UPDATE review_attempts SET stopped_at = $1, seconds = GREATEST(EXTRACT(EPOCH FROM $1 - started_at), 0)::INTEGER
+        WHERE review_id = $2 AND stopped_at IS NULL
+        RETURNING id, review_id, started_at, stopped_at, seconds"#,
+    )
+    .bind(Utc::now())
+    .bind(review_id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(attempt)
+}
+
+// The attempts at the review, oldest first.
+pub async fn find_attempts(pool: &PgPool, review_id: i32) -> Result<Vec<Attempt>> {
+    let attempts = sqlx::query_as::<_, Attempt>(
+        r#"SELECT id, review_id, started_at, stopped_at, seconds FROM review_attempts WHERE review_id = $1 ORDER BY id"#,
+    )
+    .bind(review_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(attempts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config, utils};
+    use chrono::Duration;
+    use sqlx::postgres::PgPoolOptions;
+    use uuid::Uuid;
+
+    #[actix_rt::test]
+    async fn start_and_stop_attempts() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        utils::insert_review(&pool, 0, &Uuid::new_v4()).await;
+
+        assert_eq!(None, stop_attempt(&pool, 0).await.unwrap());
+        let started = start_attempt(&pool, 0).await.unwrap().unwrap();
+        assert_eq!(None, started.stopped_at);
+        assert_eq!(None, start_attempt(&pool, 0).await.unwrap());
+        sqlx::query("UPDATE review_attempts SET started_at = $1 WHERE id = $2")
+            .bind(Utc::now() - Duration::minutes(25))
+            .bind(started.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let stopped = stop_attempt(&pool, 0).await.unwrap().unwrap();
+        assert_eq!(started.id, stopped.id);
+        let seconds = stopped.seconds.unwrap();
+        assert!((1500..1510).contains(&seconds));
+        // another attempt can be started once the last one is stopped
+        let again = start_attempt(&pool, 0).await.unwrap().unwrap();
+        assert_eq!(
+            vec![started.id, again.id],
+            find_attempts(&pool, 0)
+                .await
+                .unwrap()
+                .iter()
+                .map(|a| a.id)
+                .collect::<Vec<i32>>()
+        );
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn stop_attempt_outside_utc() {
+        let config = config::Config::new();
+        // a single connection, so that the time zone set applies to every query
+        let pool = PgPoolOptions::new()
+            .max_connections(1)
+            .connect(&config.database_url)
+            .await
+            .unwrap();
+        sqlx::query("SET TIME ZONE 'Asia/Tokyo'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        utils::insert_review(&pool, 0, &Uuid::new_v4()).await;
+
+        let started = start_attempt(&pool, 0).await.unwrap().unwrap();
+        sqlx::query("UPDATE review_attempts SET started_at = $1 WHERE id = $2")
+            .bind(Utc::now() - Duration::minutes(25))
+            .bind(started.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let stopped = stop_attempt(&pool, 0).await.unwrap().unwrap();
+        assert!((1500..1510).contains(&stopped.seconds.unwrap()));
+        assert!((Utc::now() - stopped.stopped_at.unwrap()).num_seconds() < 10);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+}
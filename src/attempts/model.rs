@@ -0,0 +1,12 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, PartialEq)]
+pub struct Attempt {
+    pub id: i32,
+    pub review_id: i32,
+    pub started_at: DateTime<Utc>,
+    // absent while the attempt is running
+    pub stopped_at: Option<DateTime<Utc>>,
+    pub seconds: Option<i32>,
+}
@@ -0,0 +1,222 @@
+use super::infrastructures;
+use super::model::{Session, SignIn};
+use super::AuthenticatedUser;
+use crate::error::ApiError;
+use crate::password::{needs_rehash, verify, DUMMY_HASH};
+use crate::users;
+use actix_web::{post, web, HttpResponse};
+use anyhow::Result;
+use sqlx::PgPool;
+
+#[post("/sign-in")]
+pub async fn sign_in(
+    pool: web::Data<PgPool>,
+    form: web::Form<SignIn>,
+) -> Result<HttpResponse, ApiError> {
+    // unknown user names and wrong passwords are indistinguishable for the client,
+    // in the time taken as well, so an unknown name is verified against a dummy hash
+    let credentials = match infrastructures::find_credentials(pool.get_ref(), &form.user_name).await
+    {
+        Ok(Some(c)) => c,
+        Ok(None) => {
+            let _ = verify(&form.password, &DUMMY_HASH).await;
+            return Err(ApiError::Unauthorized);
+        }
+        Err(_) => return Err(ApiError::InternalError),
+    };
+    match verify(&form.password, &credentials.password).await {
+        Ok(true) => (),
+        Ok(false) => return Err(ApiError::Unauthorized),
+        Err(_) => return Err(ApiError::InternalError),
+    }
+
+    // upgrade hashes made with an outdated cost while the plain password is at hand
+    if needs_rehash(&credentials.password) {
+        // the user can still sign in with the old hash, so a failure is not fatal
+        let _ = users::infrastructures::update_password(
+            pool.get_ref(),
+            &credentials.uid,
+            &form.password,
+        )
+        .await;
+    }
+
+    match infrastructures::create_session(pool.get_ref(), &credentials.uid).await {
+        Ok(token) => Ok(HttpResponse::Ok().json(Session { token })),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[post("/sign-out")]
+pub async fn sign_out(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, ApiError> {
+    match infrastructures::delete_session(pool.get_ref(), &user.token).await {
+        Ok(_) => Ok(HttpResponse::Ok().json("")),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config, utils};
+    use actix_web::{body::Body, test, App};
+    use bcrypt::hash;
+    use serde_json::json;
+    use uuid::Uuid;
+
+    #[actix_rt::test]
+    async fn sign_in_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(App::new().data(pool.clone()).service(sign_in)).await;
+        // insert predataset
+        let uid = Uuid::new_v4();
+        let hashed_password = hash("password", 4).unwrap();
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'test_user', $1, 'test@gmail.com', $2)"#)
+			.bind(hashed_password)
+			.bind(uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+        let form = SignIn {
+            user_name: "test_user".to_string(),
+            password: "password".to_string(),
+        };
+        let req = test::TestRequest::post()
+            .uri("/sign-in")
+            .set_form(&form)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let session: Session = test::read_body_json(resp).await;
+
+        // check the issued token identifies the user
+        let actual = infrastructures::find_session(&pool, &session.token)
+            .await
+            .unwrap();
+        assert_eq!(Some(uid), actual);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn sign_in_rehash_outdated_cost() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(App::new().data(pool.clone()).service(sign_in)).await;
+        // insert predataset hashed with a lower cost than the configured one
+        let uid = Uuid::new_v4();
+        let hashed_password = hash("password", 4).unwrap();
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'test_user', $1, 'test@gmail.com', $2)"#)
+			.bind(hashed_password)
+			.bind(uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+        let form = SignIn {
+            user_name: "test_user".to_string(),
+            password: "password".to_string(),
+        };
+        let req = test::TestRequest::post()
+            .uri("/sign-in")
+            .set_form(&form)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+
+        // check the password is hashed again with the configured cost
+        let user = sqlx::query!("SELECT * FROM users")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert!(user
+            .password
+            .starts_with(&format!("$2b${}$", config.bcrypt_cost)));
+        assert!(bcrypt::verify("password", &user.password).unwrap());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn sign_in_wrong_password() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(App::new().data(pool.clone()).service(sign_in)).await;
+        // insert predataset
+        let uid = Uuid::new_v4();
+        let hashed_password = hash("password", 4).unwrap();
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'test_user', $1, 'test@gmail.com', $2)"#)
+			.bind(hashed_password)
+			.bind(uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+        let form = SignIn {
+            user_name: "test_user".to_string(),
+            password: "wrong_password".to_string(),
+        };
+        let req = test::TestRequest::post()
+            .uri("/sign-in")
+            .set_form(&form)
+            .to_request();
+        let mut resp = test::call_service(&mut app, req).await;
+        assert_eq!(401, resp.status());
+        let resp_body = resp.take_body();
+        let resp_body = resp_body.as_ref().unwrap();
+        assert_eq!(
+            &Body::from(json!({"code": 401, "message": "unauthorized"})),
+            resp_body
+        );
+
+        // check no session is issued
+        let sessions = sqlx::query("SELECT * FROM sessions")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(0, sessions.len());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn sign_in_user_not_exist() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(App::new().data(pool.clone()).service(sign_in)).await;
+        let form = SignIn {
+            user_name: "test_user".to_string(),
+            password: "password".to_string(),
+        };
+        let req = test::TestRequest::post()
+            .uri("/sign-in")
+            .set_form(&form)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(401, resp.status());
+    }
+
+    #[actix_rt::test]
+    async fn sign_out_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(App::new().data(pool.clone()).service(sign_out)).await;
+        let uid = Uuid::new_v4();
+        let token = infrastructures::create_session(&pool, &uid).await.unwrap();
+
+        let req = test::TestRequest::post()
+            .uri("/sign-out")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+
+        // check the token is no longer valid
+        let actual = infrastructures::find_session(&pool, &token).await.unwrap();
+        assert!(actual.is_none());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+}
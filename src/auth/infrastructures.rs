@@ -0,0 +1,215 @@
+use super::model::Credentials;
+use anyhow::Result;
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// sessions older than this are treated as signed out
+const SESSION_LIFETIME_DAYS: i64 = 30;
+
+pub async fn find_credentials(pool: &PgPool, user_name: &str) -> Result<Option<Credentials>> {
+    let credentials =
+        sqlx::query_as::<_, Credentials>("SELECT uid, password FROM users WHERE user_name = $1")
+            .bind(user_name)
+            .fetch_optional(pool)
+            .await?;
+
+    Ok(credentials)
+}
+
+pub async fn create_session(pool: &PgPool, uid: &Uuid) -> Result<Uuid> {
+    let token = Uuid::new_v4();
+    let now = Utc::now();
+    sqlx::query(r#"INSERT INTO sessions (token, uid, created_at) VALUES ($1, $2, $3)"#)
+        .bind(token)
+        .bind(uid)
+        .bind(now)
+        .execute(pool)
+        .await?;
+
+    Ok(token)
+}
+
+pub async fn find_session(pool: &PgPool, token: &Uuid) -> Result<Option<Uuid>> {
+    let expired_before = Utc::now() - Duration::days(SESSION_LIFETIME_DAYS);
+    let uid: Option<(Uuid,)> =
+        sqlx::query_as("SELECT uid FROM sessions WHERE token = $1 AND created_at > $2")
+            .bind(token)
+            .bind(expired_before)
+            .fetch_optional(pool)
+            .await?;
+
+    Ok(uid.map(|(uid,)| uid))
+}
+
+pub async fn delete_session(pool: &PgPool, token: &Uuid) -> Result<()> {
+    sqlx::query("DELETE FROM sessions WHERE token = $1")
+        .bind(token)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+// Sign out every other device of the user, e.g. after the password has been changed.
+pub async fn delete_other_sessions(pool: &PgPool, uid: &Uuid, token: &Uuid) -> Result<()> {
+    sqlx::query("DELETE FROM sessions WHERE uid = $1 AND token <> $2")
+        .bind(uid)
+        .bind(token)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn delete_all_sessions(pool: &PgPool, uid: &Uuid) -> Result<()> {
+    sqlx::query("DELETE FROM sessions WHERE uid = $1")
+        .bind(uid)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config, utils};
+
+    #[actix_rt::test]
+    async fn find_credentials_exist() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'test_user', 'password', 'test@gmail.com', $1)"#)
+			.bind(uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+
+        let expected = Some(Credentials {
+            uid,
+            password: "password".to_string(),
+        });
+        let actual = find_credentials(&pool, "test_user").await.unwrap();
+        assert_eq!(expected, actual);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn find_credentials_not_exist() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+
+        let actual = find_credentials(&pool, "test_user").await.unwrap();
+        assert!(actual.is_none());
+    }
+
+    #[actix_rt::test]
+    async fn create_session_test() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+
+        let token = create_session(&pool, &uid).await.unwrap();
+
+        let sessions = sqlx::query!("SELECT * FROM sessions")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(1, sessions.len());
+        assert_eq!(token, sessions[0].token);
+        assert_eq!(uid, sessions[0].uid);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn find_session_exist() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        let token = create_session(&pool, &uid).await.unwrap();
+
+        let actual = find_session(&pool, &token).await.unwrap();
+        assert_eq!(Some(uid), actual);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn find_session_expired() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        let token = Uuid::new_v4();
+        let created_at = Utc::now() - Duration::days(SESSION_LIFETIME_DAYS + 1);
+        sqlx::query(r#"INSERT INTO sessions (id, token, uid, created_at) VALUES (0, $1, $2, $3)"#)
+            .bind(token)
+            .bind(uid)
+            .bind(created_at)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let actual = find_session(&pool, &token).await.unwrap();
+        assert!(actual.is_none());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn delete_session_test() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        let token = create_session(&pool, &uid).await.unwrap();
+        let other = create_session(&pool, &uid).await.unwrap();
+
+        delete_session(&pool, &token).await.unwrap();
+
+        assert!(find_session(&pool, &token).await.unwrap().is_none());
+        assert_eq!(Some(uid), find_session(&pool, &other).await.unwrap());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn delete_other_sessions_test() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        let token = create_session(&pool, &uid).await.unwrap();
+        let other = create_session(&pool, &uid).await.unwrap();
+
+        delete_other_sessions(&pool, &uid, &token).await.unwrap();
+
+        assert_eq!(Some(uid), find_session(&pool, &token).await.unwrap());
+        assert!(find_session(&pool, &other).await.unwrap().is_none());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn delete_all_sessions_test() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        let another_uid = Uuid::new_v4();
+        let token = create_session(&pool, &uid).await.unwrap();
+        let other = create_session(&pool, &uid).await.unwrap();
+        let another = create_session(&pool, &another_uid).await.unwrap();
+
+        delete_all_sessions(&pool, &uid).await.unwrap();
+
+        assert!(find_session(&pool, &token).await.unwrap().is_none());
+        assert!(find_session(&pool, &other).await.unwrap().is_none());
+        assert_eq!(
+            Some(another_uid),
+            find_session(&pool, &another).await.unwrap()
+        );
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+}
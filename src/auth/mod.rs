@@ -0,0 +1,173 @@
+pub mod handler;
+pub mod infrastructures;
+mod model;
+
+use crate::config;
+use crate::error::ApiError;
+use actix_web::{dev::Payload, http::header, web, FromRequest, HttpRequest};
+use sqlx::PgPool;
+use std::future::{ready, Future, Ready};
+use std::pin::Pin;
+use uuid::Uuid;
+
+// The user identified by the session token in the `Authorization: Bearer <token>` header.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AuthenticatedUser {
+    pub uid: Uuid,
+    pub token: Uuid,
+}
+
+impl FromRequest for AuthenticatedUser {
+    type Error = ApiError;
+    type Future = Pin<Box<dyn Future<Output = Result<Self, Self::Error>>>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let pool = req.app_data::<web::Data<PgPool>>().cloned();
+        let token = bearer_token(req);
+        Box::pin(async move {
+            let pool = match pool {
+                Some(p) => p,
+                None => return Err(ApiError::InternalError),
+            };
+            let token = match token {
+                Some(t) => t,
+                None => return Err(ApiError::Unauthorized),
+            };
+            match infrastructures::find_session(pool.get_ref(), &token).await {
+                Ok(Some(uid)) => Ok(AuthenticatedUser { uid, token }),
+                Ok(None) => Err(ApiError::Unauthorized),
+                Err(_) => Err(ApiError::InternalError),
+            }
+        })
+    }
+}
+
+// The operator, identified by the `ADMIN_TOKEN` in the `Authorization: Bearer <token>` header.
+// Any other token, such as the session of a user, is forbidden.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Admin;
+
+impl FromRequest for Admin {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let config = config::Config::new();
+        let result = match (config.admin_token, bearer(req)) {
+            (_, None) => Err(ApiError::Unauthorized),
+            (Some(expected), Some(actual)) if expected == actual => Ok(Admin),
+            _ => Err(ApiError::Forbidden),
+        };
+        ready(result)
+    }
+}
+
+fn bearer(req: &HttpRequest) -> Option<&str> {
+    let value = req.headers().get(header::AUTHORIZATION)?.to_str().ok()?;
+    value.strip_prefix("Bearer ")
+}
+
+fn bearer_token(req: &HttpRequest) -> Option<Uuid> {
+    Uuid::parse_str(bearer(req)?).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config, utils};
+    use actix_web::{body::Body, get, test, App, HttpResponse};
+    use serde_json::json;
+
+    #[get("/whoami")]
+    async fn whoami(user: AuthenticatedUser) -> HttpResponse {
+        HttpResponse::Ok().json(user.uid)
+    }
+
+    #[get("/admin")]
+    async fn admin(_: Admin) -> HttpResponse {
+        HttpResponse::Ok().json("")
+    }
+
+    #[actix_rt::test]
+    async fn authenticated_user_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(App::new().data(pool.clone()).service(whoami)).await;
+        let uid = Uuid::new_v4();
+        let token = infrastructures::create_session(&pool, &uid).await.unwrap();
+
+        let req = test::TestRequest::get()
+            .uri("/whoami")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let mut resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let resp_body = resp.take_body();
+        let resp_body = resp_body.as_ref().unwrap();
+        assert_eq!(&Body::from(json!(uid)), resp_body);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn authenticated_user_missing_header() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(App::new().data(pool.clone()).service(whoami)).await;
+
+        let req = test::TestRequest::get().uri("/whoami").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(401, resp.status());
+        let resp_body = test::read_body(resp).await;
+        assert_eq!(
+            json!({"code": 401, "message": "unauthorized"}).to_string(),
+            resp_body
+        );
+    }
+
+    #[actix_rt::test]
+    async fn authenticated_user_unknown_token() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(App::new().data(pool.clone()).service(whoami)).await;
+
+        let req = test::TestRequest::get()
+            .uri("/whoami")
+            .header("Authorization", format!("Bearer {}", Uuid::new_v4()))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(401, resp.status());
+    }
+
+    #[actix_rt::test]
+    async fn admin_ok() {
+        let mut app = test::init_service(App::new().service(admin)).await;
+        let req = test::TestRequest::get()
+            .uri("/admin")
+            .header("Authorization", "Bearer dummy_admin_token")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+    }
+
+    #[actix_rt::test]
+    async fn admin_wrong_token() {
+        let mut app = test::init_service(App::new().service(admin)).await;
+        let req = test::TestRequest::get()
+            .uri("/admin")
+            .header("Authorization", "Bearer wrong_token")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(403, resp.status());
+    }
+
+    #[actix_rt::test]
+    async fn admin_missing_header() {
+        let mut app = test::init_service(App::new().service(admin)).await;
+        let req = test::TestRequest::get().uri("/admin").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(401, resp.status());
+    }
+}
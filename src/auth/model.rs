@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct SignIn {
+    pub user_name: String,
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Session {
+    pub token: Uuid,
+}
+
+#[derive(Debug, sqlx::FromRow, PartialEq)]
+pub struct Credentials {
+    pub uid: Uuid,
+    pub password: String,
+}
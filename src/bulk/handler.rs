@@ -0,0 +1,134 @@
+use super::infrastructures;
+use super::model::{read_rows, BulkQuery, ExportQuery};
+use crate::auth::AuthenticatedUser;
+use crate::error::ApiError;
+use actix_web::{get, post, web, HttpResponse};
+use anyhow::Result;
+use futures::TryStreamExt;
+use sqlx::PgPool;
+
+// The body is the CSV or the JSON file as it is.
+#[post("/reviews/bulk")]
+pub async fn import_reviews(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    query: web::Query<BulkQuery>,
+    body: web::Bytes,
+) -> Result<HttpResponse, ApiError> {
+    let (format, mapping) = match query.parse() {
+        Ok(q) => q,
+        Err(e) => return Err(e.into()),
+    };
+    let rows = match read_rows(format, &body) {
+        Ok(rows) => rows,
+        Err(_) => return Err(ApiError::BadRequest),
+    };
+
+    match infrastructures::import_reviews(pool.get_ref(), &user.uid, &mapping, rows).await {
+        Ok(report) => Ok(HttpResponse::Created().json(report)),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[get("/reviews/export")]
+pub async fn export_reviews(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    query: web::Query<ExportQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let format = match query.parse() {
+        Ok(f) => f,
+        Err(e) => return Err(e.into()),
+    };
+
+    let stream = infrastructures::export_reviews(pool.get_ref().clone(), user.uid, format)
+        .map_err(|_| ApiError::InternalError);
+    // the rows are already being sent when a page fails, so the response is just cut short
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .streaming(Box::pin(stream)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bulk::model::BulkReport;
+    use crate::{auth, config, utils};
+    use actix_web::{test, App};
+    use uuid::Uuid;
+
+    #[actix_rt::test]
+    async fn import_and_export_csv() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .data(pool.clone())
+                .service(import_reviews)
+                .service(export_reviews),
+        )
+        .await;
+        let uid = Uuid::new_v4();
+        let token = auth::infrastructures::create_session(&pool, &uid)
+            .await
+            .unwrap();
+
+        let req = test::TestRequest::post()
+            .uri("/reviews/bulk?format=csv&mapping=problem_name:Problem,memo:Notes")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_payload("Problem,url,Notes,difficulty\nA,https://yukicoder.me/problems/no/1,sort it,2\nB,https://yukicoder.me/problems/no/2,,6\n")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(201, resp.status());
+        let report: BulkReport = test::read_body_json(resp).await;
+        assert_eq!(1, report.created);
+        assert_eq!(vec!["difficulty".to_string()], report.rows[1].fields);
+
+        let req = test::TestRequest::get()
+            .uri("/reviews/export?format=csv")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        assert_eq!(
+            "text/csv; charset=utf-8",
+            resp.headers().get("content-type").unwrap()
+        );
+        let body = test::read_body(resp).await;
+        let csv = String::from_utf8(body.to_vec()).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(2, lines.len());
+        assert!(lines[1].contains(",A,https://yukicoder.me/problems/no/1,sort it,4,2,false,,"));
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn import_reviews_invalid() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app =
+            test::init_service(App::new().data(pool.clone()).service(import_reviews)).await;
+        let token = auth::infrastructures::create_session(&pool, &Uuid::new_v4())
+            .await
+            .unwrap();
+
+        let req = test::TestRequest::post()
+            .uri("/reviews/bulk?format=json")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_payload("problem_name,url\n")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(400, resp.status());
+
+        let req = test::TestRequest::post()
+            .uri("/reviews/bulk?format=xml")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_payload("[]")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(400, resp.status());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+}
@@ -0,0 +1,232 @@
+use super::model::{BulkReport, Format, ImportedReview, Mapping, Row, RowReport};
+use crate::reviews::model::{ReviewFilter, SortValue};
+use crate::webhooks::model::EVENT_CREATED;
+use crate::{reviews, tags};
+use actix_web::web::Bytes;
+use anyhow::Result;
+use futures::stream::{self, Stream};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+// the number of reviews read at once while exporting
+const EXPORT_PAGE: i64 = 100;
+
+// Create the review of each valid row; the invalid rows are reported and skipped.
+// The reviews are written in a single transaction, so that a failed import leaves none behind.
+pub async fn import_reviews(
+    pool: &PgPool,
+    uid: &Uuid,
+    mapping: &Mapping,
+    rows: Vec<Row>,
+) -> Result<BulkReport> {
+    let mut reports: Vec<RowReport> = Vec::new();
+    let mut tx = pool.begin().await?;
+    for (i, row) in rows.into_iter().enumerate() {
+        let row_number = i + 1;
+        let imported = match row {
+            Ok(row) => mapping.review(&row),
+            Err(reason) => {
+                reports.push(RowReport::unreadable(row_number, reason));
+                continue;
+            }
+        };
+        match imported {
+            Ok(imported) => {
+                let id = create_review(&mut tx, uid, &imported).await?;
+                reports.push(RowReport::created(row_number, id));
+            }
+            Err(errors) => reports.push(RowReport::invalid(row_number, errors)),
+        }
+    }
+    tx.commit().await?;
+
+    Ok(BulkReport::new(reports))
+}
+
+// the tags not defined yet are defined as the tags of the user
+async fn create_review(
+    tx: &mut Transaction<'_, Postgres>,
+    uid: &Uuid,
+    imported: &ImportedReview,
+) -> Result<i32> {
+    let id = reviews::infrastructures::insert_review(tx, uid, &imported.review).await?;
+    for name in &imported.tags {
+        let tag = match tags::infrastructures::find_tag(&mut *tx, uid, name).await? {
+            Some(tag) => tag,
+            None => tags::infrastructures::create_tag(&mut *tx, uid, name).await?,
+        };
+        tags::infrastructures::attach_tag(&mut *tx, id, tag.id).await?;
+    }
+    // the event carries the tags attached
+    reviews::infrastructures::dispatch_review(tx, uid, id, EVENT_CREATED).await?;
+
+    Ok(id)
+}
+
+struct Export {
+    pool: PgPool,
+    uid: Uuid,
+    format: Format,
+    // the id of the last review written
+    after: Option<i32>,
+    opened: bool,
+    closed: bool,
+}
+
+// Stream every review of the user page by page, so that the whole export is never in memory.
+pub fn export_reviews(
+    pool: PgPool,
+    uid: Uuid,
+    format: Format,
+) -> impl Stream<Item = Result<Bytes>> {
+    let export = Export {
+        pool,
+        uid,
+        format,
+        after: None,
+        opened: false,
+        closed: false,
+    };
+    stream::unfold(export, |mut export| async move {
+        if export.closed {
+            return None;
+        }
+        let mut chunk = Vec::new();
+        if !export.opened {
+            chunk.extend(export.format.open());
+        }
+
+        let filter = ReviewFilter {
+            limit: EXPORT_PAGE,
+            after: export.after.map(|id| vec![SortValue::Int(Some(id as i64))]),
+            ..ReviewFilter::default()
+        };
+        let mut page = match reviews::infrastructures::find_reviews(
+            &export.pool,
+            &export.uid,
+            &filter,
+        )
+        .await
+        {
+            Ok(page) => page,
+            Err(e) => {
+                export.closed = true;
+                return Some((Err(e), export));
+            }
+        };
+        let last = page.len() as i64 <= EXPORT_PAGE;
+        page.truncate(EXPORT_PAGE as usize);
+
+        chunk.extend(export.format.write(&page, !export.opened));
+        export.opened = true;
+        export.after = page.last().map(|review| review.id);
+        if last {
+            chunk.extend(export.format.close());
+            export.closed = true;
+        }
+        Some((Ok(Bytes::from(chunk)), export))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bulk::model::{read_rows, BulkQuery};
+    use crate::reviews::model::Review;
+    use crate::{config, utils, webhooks};
+    use futures::TryStreamExt;
+
+    #[actix_rt::test]
+    async fn import_and_export_reviews() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        let webhook =
+            webhooks::infrastructures::create_webhook(&pool, &uid, "https://203.0.113.7/hook")
+                .await
+                .unwrap();
+        let csv = "Title,Link,tags\nA,https://atcoder.jp/contests/abc200/tasks/abc200_a,dp my-own\n,not a url,\n";
+        let rows = read_rows(Format::Csv, csv.as_bytes()).unwrap();
+        let mapping = Mapping::default();
+
+        let report = import_reviews(&pool, &uid, &mapping, rows).await.unwrap();
+        // the default mapping reads no Title nor Link column
+        assert_eq!(0, report.created);
+        assert_eq!(2, report.failed);
+
+        let rows = read_rows(Format::Csv, csv.as_bytes()).unwrap();
+        let mapping = BulkQuery {
+            format: None,
+            mapping: Some("problem_name:Title,url:Link".to_string()),
+        }
+        .parse()
+        .unwrap()
+        .1;
+        let report = import_reviews(&pool, &uid, &mapping, rows).await.unwrap();
+        assert_eq!(1, report.created);
+        assert_eq!(
+            vec!["problem_name".to_string(), "url".to_string()],
+            report.rows[1].fields
+        );
+        let review =
+            reviews::infrastructures::find_review(&pool, &uid, report.rows[0].review_id.unwrap())
+                .await
+                .unwrap()
+                .unwrap();
+        assert_eq!(1, review.platform);
+        assert_eq!(vec!["dp".to_string(), "my-own".to_string()], review.tags);
+        // the event is queued once the tags are attached
+        let deliveries = webhooks::infrastructures::find_deliveries(&pool, &uid, webhook.id)
+            .await
+            .unwrap();
+        assert_eq!(1, deliveries.len());
+        let payload: serde_json::Value = serde_json::from_str(&deliveries[0].payload).unwrap();
+        assert_eq!(serde_json::json!(["dp", "my-own"]), payload["data"]["tags"]);
+
+        let chunks: Vec<Bytes> = export_reviews(pool.clone(), uid, Format::Json)
+            .try_collect()
+            .await
+            .unwrap();
+        let exported: Vec<Review> = serde_json::from_slice(&chunks.concat()).unwrap();
+        assert_eq!(vec![review], exported);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn export_reviews_paged() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        let rows: Vec<Row> = (0..EXPORT_PAGE + 1)
+            .map(|i| {
+                Ok(vec![
+                    ("problem_name".to_string(), format!("problem {}", i)),
+                    ("url".to_string(), "https://example.com".to_string()),
+                ]
+                .into_iter()
+                .collect())
+            })
+            .collect();
+        import_reviews(&pool, &uid, &Mapping::default(), rows)
+            .await
+            .unwrap();
+
+        let chunks: Vec<Bytes> = export_reviews(pool.clone(), uid, Format::Csv)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(2, chunks.len());
+        let csv = chunks.concat();
+        let rows = read_rows(Format::Csv, &csv).unwrap();
+        assert_eq!(EXPORT_PAGE as usize + 1, rows.len());
+
+        let chunks: Vec<Bytes> = export_reviews(pool.clone(), Uuid::new_v4(), Format::Json)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(b"[]".to_vec(), chunks.concat());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+}
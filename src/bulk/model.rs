@@ -0,0 +1,586 @@
+use crate::error::{extract_field, extract_reasons};
+use crate::problems::model::ProblemKey;
+use crate::reviews::model::{invalid, NewReview, Review};
+use crate::utils::RE_TAG;
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use validator::{Validate, ValidationErrors, ValidationErrorsKind};
+
+// the fields of a review a column can be mapped to
+const FIELDS: [&str; 7] = [
+    "problem_name",
+    "url",
+    "memo",
+    "platform",
+    "difficulty",
+    "solved_without_hints",
+    "tags",
+];
+// the columns of an exported CSV, which can be imported back as they are
+const EXPORT_COLUMNS: [&str; 11] = [
+    "id",
+    "problem_name",
+    "url",
+    "memo",
+    "platform",
+    "difficulty",
+    "solved_without_hints",
+    "tags",
+    "created_at",
+    "updated_at",
+    "due_at",
+];
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BulkQuery {
+    // csv or json
+    pub format: Option<String>,
+    // comma separated pairs of a field and the column holding it, e.g. `url:Link,memo:Notes`;
+    // the fields not mapped are read from the column of their own name
+    pub mapping: Option<String>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ExportQuery {
+    // csv or json
+    pub format: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Mapping {
+    columns: HashMap<&'static str, String>,
+}
+
+impl Default for Mapping {
+    fn default() -> Mapping {
+        Mapping {
+            columns: FIELDS.iter().map(|&f| (f, f.to_string())).collect(),
+        }
+    }
+}
+
+fn parse_format(errors: &mut ValidationErrors, format: &Option<String>) -> Format {
+    match format.as_deref() {
+        None | Some("json") => Format::Json,
+        Some("csv") => Format::Csv,
+        Some(_) => {
+            invalid(errors, "format", "format must be csv or json");
+            Format::Json
+        }
+    }
+}
+
+impl BulkQuery {
+    pub fn parse(&self) -> Result<(Format, Mapping), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        let format = parse_format(&mut errors, &self.format);
+        let mut mapping = Mapping::default();
+        let pairs = self.mapping.iter().flat_map(|m| m.split(','));
+        for pair in pairs.filter(|pair| !pair.trim().is_empty()) {
+            let (field, column) = match pair.split_once(':') {
+                Some((field, column)) => (field.trim(), column.trim()),
+                None => (pair.trim(), ""),
+            };
+            match FIELDS.iter().find(|&&f| f == field) {
+                Some(field) if !column.is_empty() => {
+                    mapping.columns.insert(field, column.to_string());
+                }
+                _ => invalid(
+                    &mut errors,
+                    "mapping",
+                    &format!(
+                        "mapping must be comma separated pairs of a field and a column like url:Link, where the field is one of {}",
+                        FIELDS.join(", ")
+                    ),
+                ),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok((format, mapping))
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl ExportQuery {
+    pub fn parse(&self) -> Result<Format, ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        let format = parse_format(&mut errors, &self.format);
+        if errors.is_empty() {
+            Ok(format)
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+// A row of the table with its cells keyed by the column, or why it could not be read.
+pub type Row = Result<HashMap<String, String>, String>;
+
+// Read the rows of a CSV with a header line or of a JSON array of objects.
+// Only a file which is not a table at all is an error.
+pub fn read_rows(format: Format, body: &[u8]) -> Result<Vec<Row>, String> {
+    match format {
+        Format::Csv => {
+            let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(body);
+            let headers = reader.headers().map_err(|e| e.to_string())?.clone();
+            let rows = reader
+                .records()
+                .map(|record| {
+                    let record = record.map_err(|e| e.to_string())?;
+                    Ok(headers
+                        .iter()
+                        .zip(record.iter())
+                        .map(|(header, cell)| (header.to_string(), cell.to_string()))
+                        .collect())
+                })
+                .collect();
+            Ok(rows)
+        }
+        Format::Json => {
+            let values: Vec<Value> = serde_json::from_slice(body).map_err(|e| e.to_string())?;
+            let rows = values
+                .into_iter()
+                .map(|value| match value {
+                    Value::Object(object) => Ok(object
+                        .into_iter()
+                        .filter_map(|(key, value)| cell(value).map(|cell| (key, cell)))
+                        .collect()),
+                    _ => Err("the row is not an object".to_string()),
+                })
+                .collect();
+            Ok(rows)
+        }
+    }
+}
+
+// the text of a JSON value as it would be written in a CSV cell
+fn cell(value: Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(s) => Some(s),
+        Value::Array(values) => Some(
+            values
+                .into_iter()
+                .filter_map(cell)
+                .collect::<Vec<String>>()
+                .join(" "),
+        ),
+        value => Some(value.to_string()),
+    }
+}
+
+// A review read out of a row together with the names of its tags.
+#[derive(Debug, PartialEq)]
+pub struct ImportedReview {
+    pub review: NewReview,
+    pub tags: Vec<String>,
+}
+
+impl Mapping {
+    fn get<'a>(&self, row: &'a HashMap<String, String>, field: &str) -> Option<&'a str> {
+        self.columns
+            .get(field)
+            .and_then(|column| row.get(column))
+            .map(|cell| cell.as_str())
+            .filter(|cell| !cell.trim().is_empty())
+    }
+
+    // Validate the row with the same rules as a review created by the form.
+    pub fn review(
+        &self,
+        row: &HashMap<String, String>,
+    ) -> Result<ImportedReview, ValidationErrors> {
+        let mut review = NewReview {
+            problem_name: self
+                .get(row, "problem_name")
+                .unwrap_or("")
+                .trim()
+                .to_string(),
+            url: self.get(row, "url").unwrap_or("").trim().to_string(),
+            // the memo is Markdown, where the spaces can matter
+            memo: self.get(row, "memo").map(String::from),
+            platform: 0,
+            difficulty: None,
+            solved_without_hints: false,
+        };
+        let mut errors = ValidationErrors::new();
+        match self.get(row, "platform") {
+            Some(platform) => match parse_platform(platform.trim()) {
+                Some(platform) => review.platform = platform,
+                None => invalid(
+                    &mut errors,
+                    "platform",
+                    "platform must be a number from 0 to 4 or one of other, atcoder, codeforces, aoj and yukicoder",
+                ),
+            },
+            // the platform of a known URL need not be given
+            None => {
+                if let Some(key) = ProblemKey::from_url(&review.url) {
+                    review.platform = key.platform;
+                }
+            }
+        }
+        if let Some(difficulty) = self.get(row, "difficulty") {
+            match difficulty.trim().parse::<i16>() {
+                Ok(difficulty) => review.difficulty = Some(difficulty),
+                Err(_) => invalid(
+                    &mut errors,
+                    "difficulty",
+                    "difficulty must be a number from 1 to 5",
+                ),
+            }
+        }
+        if let Some(solved) = self.get(row, "solved_without_hints") {
+            match solved.trim().to_lowercase().as_str() {
+                "true" | "yes" | "1" => review.solved_without_hints = true,
+                "false" | "no" | "0" => (),
+                _ => invalid(
+                    &mut errors,
+                    "solved_without_hints",
+                    "solved_without_hints must be true or false",
+                ),
+            }
+        }
+        let mut tags: Vec<String> = self
+            .get(row, "tags")
+            .unwrap_or("")
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|name| !name.is_empty())
+            .map(String::from)
+            .collect();
+        tags.sort();
+        tags.dedup();
+        if let Some(name) = tags
+            .iter()
+            .find(|name| name.len() > 50 || !RE_TAG.is_match(name))
+        {
+            invalid(
+                &mut errors,
+                "tags",
+                &format!(
+                    "{} is not a tag of lowercase letters, digits and hyphens",
+                    name
+                ),
+            );
+        }
+
+        if let Err(e) = review.validate() {
+            for (field, kind) in e.into_errors() {
+                if let ValidationErrorsKind::Field(field_errors) = kind {
+                    for error in field_errors {
+                        errors.add(field, error);
+                    }
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(ImportedReview { review, tags })
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+fn parse_platform(platform: &str) -> Option<i16> {
+    match platform.to_lowercase().as_str() {
+        "other" => Some(0),
+        "atcoder" => Some(1),
+        "codeforces" => Some(2),
+        "aoj" => Some(3),
+        "yukicoder" => Some(4),
+        p => p.parse::<i16>().ok().filter(|p| (0..=4).contains(p)),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct RowReport {
+    // the position of the row among the rows of data, from 1
+    pub row: usize,
+    // the review created, absent when the row is invalid
+    pub review_id: Option<i32>,
+    pub fields: Vec<String>,
+    pub reasons: Vec<String>,
+}
+
+impl RowReport {
+    pub fn created(row: usize, review_id: i32) -> RowReport {
+        RowReport {
+            row,
+            review_id: Some(review_id),
+            fields: vec![],
+            reasons: vec![],
+        }
+    }
+
+    pub fn invalid(row: usize, errors: ValidationErrors) -> RowReport {
+        let reasons = extract_reasons(&errors);
+        let mut fields = extract_field(errors);
+        fields.sort();
+        RowReport {
+            row,
+            review_id: None,
+            fields,
+            reasons,
+        }
+    }
+
+    pub fn unreadable(row: usize, reason: String) -> RowReport {
+        RowReport {
+            row,
+            review_id: None,
+            fields: vec![],
+            reasons: vec![reason],
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct BulkReport {
+    pub created: usize,
+    pub failed: usize,
+    pub rows: Vec<RowReport>,
+}
+
+impl BulkReport {
+    pub fn new(rows: Vec<RowReport>) -> BulkReport {
+        let created = rows.iter().filter(|r| r.review_id.is_some()).count();
+        BulkReport {
+            created,
+            failed: rows.len() - created,
+            rows,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ExportedRow<'a> {
+    id: i32,
+    problem_name: &'a str,
+    url: &'a str,
+    memo: Option<&'a str>,
+    platform: i16,
+    difficulty: Option<i16>,
+    solved_without_hints: bool,
+    tags: String,
+    created_at: String,
+    updated_at: Option<String>,
+    due_at: Option<String>,
+}
+
+// RFC 3339 in UTC, e.g. 2021-05-01T12:00:00Z
+fn timestamp(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+impl Format {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            Format::Csv => "text/csv; charset=utf-8",
+            Format::Json => "application/json",
+        }
+    }
+
+    // what is written before the first review
+    pub fn open(&self) -> Vec<u8> {
+        match self {
+            Format::Csv => {
+                let mut writer = csv::Writer::from_writer(vec![]);
+                writer.write_record(EXPORT_COLUMNS).unwrap();
+                writer.into_inner().unwrap()
+            }
+            Format::Json => b"[".to_vec(),
+        }
+    }
+
+    // what is written after the last review
+    pub fn close(&self) -> Vec<u8> {
+        match self {
+            Format::Csv => vec![],
+            Format::Json => b"]".to_vec(),
+        }
+    }
+
+    // Write the reviews, `first` telling whether any review has been written before.
+    pub fn write(&self, reviews: &[Review], first: bool) -> Vec<u8> {
+        match self {
+            Format::Csv => {
+                let mut writer = csv::WriterBuilder::new()
+                    .has_headers(false)
+                    .from_writer(vec![]);
+                for review in reviews {
+                    writer
+                        .serialize(ExportedRow {
+                            id: review.id,
+                            problem_name: &review.problem_name,
+                            url: &review.url,
+                            memo: review.memo.as_deref(),
+                            platform: review.platform,
+                            difficulty: review.difficulty,
+                            solved_without_hints: review.solved_without_hints,
+                            tags: review.tags.join(" "),
+                            created_at: timestamp(&review.created_at),
+                            updated_at: review.updated_at.as_ref().map(timestamp),
+                            due_at: review.due_at.as_ref().map(timestamp),
+                        })
+                        .unwrap();
+                }
+                writer.into_inner().unwrap()
+            }
+            Format::Json => {
+                let mut json: Vec<u8> = vec![];
+                for (i, review) in reviews.iter().enumerate() {
+                    if !first || i > 0 {
+                        json.push(b',');
+                    }
+                    json.extend(serde_json::to_vec(review).unwrap());
+                }
+                json
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn parse_query() {
+        let query = BulkQuery {
+            format: Some("csv".to_string()),
+            mapping: Some("problem_name:Title, url:Link".to_string()),
+        };
+        let (format, mapping) = query.parse().unwrap();
+        assert_eq!(Format::Csv, format);
+        assert_eq!(
+            Some(&"Title".to_string()),
+            mapping.columns.get("problem_name")
+        );
+        assert_eq!(Some(&"memo".to_string()), mapping.columns.get("memo"));
+
+        let query = BulkQuery {
+            format: Some("xlsx".to_string()),
+            mapping: Some("title:Title".to_string()),
+        };
+        let errors = query.parse().unwrap_err();
+        let mut fields = extract_field(errors);
+        fields.sort();
+        assert_eq!(vec!["format".to_string(), "mapping".to_string()], fields);
+    }
+
+    #[test]
+    fn read_csv_and_json() {
+        let csv = "Title,Link,Tags\nA,https://atcoder.jp/contests/abc200/tasks/abc200_a,\"dp, graph\"\nB\n";
+        let rows = read_rows(Format::Csv, csv.as_bytes()).unwrap();
+        assert_eq!(2, rows.len());
+        let row = rows[0].as_ref().unwrap();
+        assert_eq!(Some(&"dp, graph".to_string()), row.get("Tags"));
+        // the cells missing at the end of a row are left out
+        assert_eq!(None, rows[1].as_ref().unwrap().get("Link"));
+
+        let json =
+            r#"[{"problem_name": "A", "difficulty": 3, "tags": ["dp", "graph"], "memo": null}, 1]"#;
+        let rows = read_rows(Format::Json, json.as_bytes()).unwrap();
+        let row = rows[0].as_ref().unwrap();
+        assert_eq!(Some(&"3".to_string()), row.get("difficulty"));
+        assert_eq!(Some(&"dp graph".to_string()), row.get("tags"));
+        assert_eq!(None, row.get("memo"));
+        assert!(rows[1].is_err());
+
+        assert!(read_rows(Format::Json, b"{}").is_err());
+    }
+
+    #[test]
+    fn review_of_row() {
+        let mapping = BulkQuery {
+            format: None,
+            mapping: Some("problem_name:Title,url:Link".to_string()),
+        }
+        .parse()
+        .unwrap()
+        .1;
+        let row: HashMap<String, String> = vec![
+            ("Title", "A - Happy Birthday!"),
+            ("Link", "https://atcoder.jp/contests/abc200/tasks/abc200_a"),
+            ("memo", ""),
+            ("difficulty", "2"),
+            ("solved_without_hints", "Yes"),
+            ("tags", "greedy,dp dp"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        assert_eq!(
+            Ok(ImportedReview {
+                review: NewReview {
+                    problem_name: "A - Happy Birthday!".to_string(),
+                    url: "https://atcoder.jp/contests/abc200/tasks/abc200_a".to_string(),
+                    memo: None,
+                    platform: 1,
+                    difficulty: Some(2),
+                    solved_without_hints: true,
+                },
+                tags: vec!["dp".to_string(), "greedy".to_string()],
+            }),
+            mapping.review(&row)
+        );
+
+        let row: HashMap<String, String> = vec![
+            ("Link", "not a url"),
+            ("platform", "topcoder"),
+            ("difficulty", "9"),
+            ("tags", "Graph"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let report = RowReport::invalid(1, mapping.review(&row).unwrap_err());
+        assert_eq!(
+            vec!["difficulty", "platform", "problem_name", "tags", "url"],
+            report.fields
+        );
+    }
+
+    #[test]
+    fn write_reviews() {
+        let review = Review {
+            id: 3,
+            problem_name: "A, \"quoted\"".to_string(),
+            url: "https://atcoder.jp/contests/abc200/tasks/abc200_a".to_string(),
+            memo: Some("line\nbreak".to_string()),
+            memo_html: None,
+            platform: 1,
+            problem_id: None,
+            difficulty: Some(2),
+            platform_difficulty: None,
+            solved_without_hints: false,
+            created_at: Utc.ymd(2021, 5, 1).and_hms(12, 0, 0),
+            updated_at: None,
+            due_at: None,
+            tags: vec!["dp".to_string(), "graph".to_string()],
+        };
+        let mut csv = Format::Csv.open();
+        csv.extend(Format::Csv.write(&[review], true));
+        assert_eq!(
+            "id,problem_name,url,memo,platform,difficulty,solved_without_hints,tags,created_at,updated_at,due_at\n3,\"A, \"\"quoted\"\"\",https://atcoder.jp/contests/abc200/tasks/abc200_a,\"line\nbreak\",1,2,false,dp graph,2021-05-01T12:00:00Z,,\n",
+            String::from_utf8(csv).unwrap()
+        );
+        assert_eq!(
+            b"[]".to_vec(),
+            [Format::Json.open(), Format::Json.close()].concat()
+        );
+    }
+}
@@ -0,0 +1,141 @@
+use super::infrastructures;
+use super::model::{render, Feed};
+use crate::auth::AuthenticatedUser;
+use crate::error::ApiError;
+use crate::users;
+use actix_web::{delete, get, post, web, HttpResponse};
+use anyhow::Result;
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+#[post("/account/calendar-feed")]
+pub async fn regenerate_feed(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, ApiError> {
+    match infrastructures::regenerate_feed(pool.get_ref(), &user.uid).await {
+        Ok(token) => Ok(HttpResponse::Created().json(Feed::new(token))),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[delete("/account/calendar-feed")]
+pub async fn revoke_feed(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, ApiError> {
+    match infrastructures::revoke_feed(pool.get_ref(), &user.uid).await {
+        Ok(true) => Ok(HttpResponse::Ok().json("")),
+        Ok(false) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+// Calendar clients cannot sign in, so the token in the URL is the only credential.
+#[get("/calendar/{token}.ics")]
+pub async fn get_feed(
+    pool: web::Data<PgPool>,
+    web::Path(token): web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let uid = match infrastructures::find_feed_owner(pool.get_ref(), &token).await {
+        Ok(Some(uid)) => uid,
+        Ok(None) => return Err(ApiError::NotFound),
+        Err(_) => return Err(ApiError::InternalError),
+    };
+    let preferences = match users::infrastructures::find_preferences(pool.get_ref(), &uid).await {
+        Ok(preferences) => preferences,
+        Err(_) => return Err(ApiError::InternalError),
+    };
+
+    match infrastructures::find_due_reviews(pool.get_ref(), &uid, &preferences.timezone).await {
+        Ok(reviews) => Ok(HttpResponse::Ok()
+            .content_type("text/calendar; charset=utf-8")
+            .body(render(&uid, &reviews, Utc::now()))),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{auth, config, utils};
+    use actix_web::{test, App};
+
+    #[actix_rt::test]
+    async fn calendar_feed_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .data(pool.clone())
+                .service(regenerate_feed)
+                .service(revoke_feed)
+                .service(get_feed),
+        )
+        .await;
+        let uid = Uuid::new_v4();
+        let token = auth::infrastructures::create_session(&pool, &uid)
+            .await
+            .unwrap();
+        sqlx::query(r#"INSERT INTO reviews (id, problem_name, url, memo, uid, platform, created_at) VALUES (0, 'test_prob_name', 'test_url', 'test_memo', $1, 1, now())"#)
+			.bind(uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+        sqlx::query(r#"INSERT INTO schedules (review_id, due_at, interval_days, ease, repetitions) VALUES (0, now(), 1, 2.5, 0)"#)
+			.execute(&pool)
+			.await
+			.unwrap();
+
+        let req = test::TestRequest::post()
+            .uri("/account/calendar-feed")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(201, resp.status());
+        let feed: Feed = test::read_body_json(resp).await;
+
+        let req = test::TestRequest::get().uri(&feed.path).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        assert_eq!(
+            "text/calendar; charset=utf-8",
+            resp.headers().get("content-type").unwrap()
+        );
+        let body = test::read_body(resp).await;
+        let ics = String::from_utf8(body.to_vec()).unwrap();
+        assert!(ics.contains("\r\nSUMMARY:1 review due\r\n"));
+        assert!(ics.contains("DESCRIPTION:test_prob_name test_url"));
+
+        // the old URL stops working once the token is regenerated
+        let req = test::TestRequest::post()
+            .uri("/account/calendar-feed")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(201, resp.status());
+        let regenerated: Feed = test::read_body_json(resp).await;
+        let req = test::TestRequest::get().uri(&feed.path).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(404, resp.status());
+
+        let req = test::TestRequest::delete()
+            .uri("/account/calendar-feed")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let req = test::TestRequest::get().uri(&regenerated.path).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(404, resp.status());
+
+        let req = test::TestRequest::get()
+            .uri("/calendar/not-a-token.ics")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(404, resp.status());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+}
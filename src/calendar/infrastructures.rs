@@ -0,0 +1,129 @@
+use super::model::DueReview;
+use anyhow::Result;
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// how far ahead the feed lists the due reviews
+const FEED_DAYS: i32 = 60;
+
+// Issue a new token, which stops the URL of the previous one from working.
+pub async fn regenerate_feed(pool: &PgPool, uid: &Uuid) -> Result<Uuid> {
+    let token = Uuid::new_v4();
+    sqlx::query(
+        r#"INSERT INTO calendar_feeds (uid, token, created_at) VALUES ($1, $2, $3)
+        ON CONFLICT (uid) DO UPDATE SET token = $2, created_at = $3"#,
+    )
+    .bind(uid)
+    .bind(token)
+    .bind(Utc::now())
+    .execute(pool)
+    .await?;
+
+    Ok(token)
+}
+
+// Returns whether there was a feed to revoke.
+pub async fn revoke_feed(pool: &PgPool, uid: &Uuid) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM calendar_feeds WHERE uid = $1")
+        .bind(uid)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn find_feed_owner(pool: &PgPool, token: &Uuid) -> Result<Option<Uuid>> {
+    let uid: Option<(Uuid,)> = sqlx::query_as("SELECT uid FROM calendar_feeds WHERE token = $1")
+        .bind(token)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(uid.map(|(uid,)| uid))
+}
+
+// The reviews due from now on for the days of the feed, the overdue ones moved to today.
+pub async fn find_due_reviews(pool: &PgPool, uid: &Uuid, tz: &str) -> Result<Vec<DueReview>> {
+    let reviews = sqlx::query_as::<_, DueReview>(
+        r#"SELECT GREATEST((s.due_at AT TIME ZONE $2)::DATE, (NOW() AT TIME ZONE $2)::DATE) AS date,
+        r.id, r.problem_name, r.url
+        FROM reviews r
+        JOIN schedules s ON s.review_id = r.id
+        WHERE r.uid = $1 AND s.due_at < ((DATE_TRUNC('day', NOW() AT TIME ZONE $2) + $3 * INTERVAL '1 day') AT TIME ZONE $2)
+        ORDER BY date, s.due_at, r.id"#,
+    )
+    .bind(uid)
+    .bind(tz)
+    .bind(FEED_DAYS)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(reviews)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config, utils};
+    use chrono::Duration;
+
+    #[actix_rt::test]
+    async fn regenerate_and_revoke_feed() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+
+        let old = regenerate_feed(&pool, &uid).await.unwrap();
+        assert_eq!(Some(uid), find_feed_owner(&pool, &old).await.unwrap());
+        let new = regenerate_feed(&pool, &uid).await.unwrap();
+        assert_eq!(None, find_feed_owner(&pool, &old).await.unwrap());
+        assert_eq!(Some(uid), find_feed_owner(&pool, &new).await.unwrap());
+
+        assert!(revoke_feed(&pool, &uid).await.unwrap());
+        assert_eq!(None, find_feed_owner(&pool, &new).await.unwrap());
+        assert!(!revoke_feed(&pool, &uid).await.unwrap());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn find_due_reviews_by_day() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        let now = Utc::now();
+        for (id, due_at) in [
+            (0, now - Duration::days(3)),
+            (1, now + Duration::days(2)),
+            (2, now + Duration::days(FEED_DAYS as i64 + 1)),
+        ]
+        .iter()
+        {
+            sqlx::query(r#"INSERT INTO reviews (id, problem_name, url, memo, uid, platform, created_at) VALUES ($1, 'test_prob_name', 'test_url', 'test_memo', $2, 1, now())"#)
+				.bind(id)
+				.bind(uid)
+				.execute(&pool)
+				.await
+				.unwrap();
+            sqlx::query(r#"INSERT INTO schedules (review_id, due_at, interval_days, ease, repetitions) VALUES ($1, $2, 1, 2.5, 0)"#)
+				.bind(id)
+				.bind(due_at)
+				.execute(&pool)
+				.await
+				.unwrap();
+        }
+
+        let reviews = find_due_reviews(&pool, &uid, "UTC").await.unwrap();
+        assert_eq!(
+            vec![0, 1],
+            reviews.iter().map(|r| r.id).collect::<Vec<i32>>()
+        );
+        assert_eq!(now.date().naive_utc(), reviews[0].date);
+        assert_eq!(
+            (now + Duration::days(2)).date().naive_utc(),
+            reviews[1].date
+        );
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+}
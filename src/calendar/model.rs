@@ -0,0 +1,169 @@
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// RFC 5545 lines are folded at 75 octets, excluding the line break
+const MAX_LINE_OCTETS: usize = 75;
+const PRODID: &str = "-//competitive-programming-review//due reviews//EN";
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Feed {
+    pub token: Uuid,
+    // the URL path to subscribe to, relative to the server
+    pub path: String,
+}
+
+impl Feed {
+    pub fn new(token: Uuid) -> Feed {
+        Feed {
+            token,
+            path: format!("/calendar/{}.ics", token),
+        }
+    }
+}
+
+// A review on the day it is due in the time zone of the user, the overdue ones being due today.
+#[derive(Debug, sqlx::FromRow, PartialEq)]
+pub struct DueReview {
+    pub date: NaiveDate,
+    pub id: i32,
+    pub problem_name: String,
+    pub url: String,
+}
+
+// Render the calendar of an all-day event per day with reviews due, the reviews ordered by day.
+pub fn render(uid: &Uuid, reviews: &[DueReview], now: DateTime<Utc>) -> String {
+    let mut lines: Vec<String> = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:{}", PRODID),
+        "CALSCALE:GREGORIAN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+        "X-WR-CALNAME:Due reviews".to_string(),
+    ];
+    let stamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let mut start = 0;
+    while start < reviews.len() {
+        let date = reviews[start].date;
+        let end = start
+            + reviews[start..]
+                .iter()
+                .take_while(|r| r.date == date)
+                .count();
+        let day = &reviews[start..end];
+        let description: Vec<String> = day
+            .iter()
+            .map(|r| format!("{} {}", r.problem_name, r.url))
+            .collect();
+        lines.extend(vec![
+            "BEGIN:VEVENT".to_string(),
+            // stable across the fetches so that the clients update the event in place
+            format!(
+                "UID:{}-{}@competitive-programming-review",
+                date.format("%Y%m%d"),
+                uid
+            ),
+            format!("DTSTAMP:{}", stamp),
+            format!("DTSTART;VALUE=DATE:{}", date.format("%Y%m%d")),
+            format!(
+                "DTEND;VALUE=DATE:{}",
+                (date + Duration::days(1)).format("%Y%m%d")
+            ),
+            format!(
+                "SUMMARY:{}",
+                escape(&match day.len() {
+                    1 => "1 review due".to_string(),
+                    n => format!("{} reviews due", n),
+                })
+            ),
+            format!("DESCRIPTION:{}", escape(&description.join("\n"))),
+            "TRANSP:TRANSPARENT".to_string(),
+            "END:VEVENT".to_string(),
+        ]);
+        start = end;
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    lines.iter().map(|line| fold(line) + "\r\n").collect()
+}
+
+// Escape a TEXT value.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => (),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Fold a content line into lines of at most 75 octets, continued by a leading space,
+// never splitting a multibyte character.
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut octets = 0;
+    for c in line.chars() {
+        if octets + c.len_utf8() > MAX_LINE_OCTETS {
+            folded.push_str("\r\n ");
+            octets = 1;
+        }
+        folded.push(c);
+        octets += c.len_utf8();
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn due(day: u32, id: i32, problem_name: &str) -> DueReview {
+        DueReview {
+            date: NaiveDate::from_ymd(2021, 5, day),
+            id,
+            problem_name: problem_name.to_string(),
+            url: format!("https://yukicoder.me/problems/no/{}", id),
+        }
+    }
+
+    #[test]
+    fn render_calendar() {
+        let uid = Uuid::nil();
+        let reviews = vec![due(20, 1, "A, B; C"), due(20, 2, "D"), due(22, 3, "E")];
+        let ics = render(&uid, &reviews, Utc.ymd(2021, 5, 20).and_hms(9, 0, 0));
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(ics.ends_with("END:VEVENT\r\nEND:VCALENDAR\r\n"));
+        assert_eq!(2, ics.matches("BEGIN:VEVENT").count());
+        assert!(ics.contains("\r\nDTSTAMP:20210520T090000Z\r\n"));
+        assert!(ics.contains("\r\nDTSTART;VALUE=DATE:20210520\r\nDTEND;VALUE=DATE:20210521\r\n"));
+        assert!(ics.contains("\r\nSUMMARY:2 reviews due\r\n"));
+        assert!(ics.contains("\r\nSUMMARY:1 review due\r\n"));
+        assert!(ics.contains(
+            "\r\nDESCRIPTION:A\\, B\\; C https://yukicoder.me/problems/no/1\\nD https://yukicod\r\n er.me/problems/no/2\r\n"
+        ));
+        assert!(ics.contains(
+            "\r\nUID:20210522-00000000-0000-0000-0000-000000000000@competitive-programming-r\r\n eview\r\n"
+        ));
+
+        let empty = render(&uid, &[], Utc.ymd(2021, 5, 20).and_hms(9, 0, 0));
+        assert!(!empty.contains("BEGIN:VEVENT"));
+    }
+
+    #[test]
+    fn fold_line() {
+        let line = format!("DESCRIPTION:{}", "あ".repeat(30));
+        let folded = fold(&line);
+        for part in folded.split("\r\n") {
+            assert!(part.len() <= MAX_LINE_OCTETS);
+        }
+        assert_eq!(line, folded.replace("\r\n ", ""));
+        assert_eq!("SUMMARY:short", fold("SUMMARY:short"));
+    }
+}
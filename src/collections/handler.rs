@@ -0,0 +1,319 @@
+use super::infrastructures;
+use super::model::{NewCollection, NewEntry};
+use crate::auth::AuthenticatedUser;
+use crate::error::ApiError;
+use crate::reviews;
+use actix_web::{delete, get, post, put, web, HttpResponse};
+use anyhow::Result;
+use sqlx::PgPool;
+use uuid::Uuid;
+use validator::Validate;
+
+// The entries can only be reached through a collection of the user.
+async fn check_collection(pool: &PgPool, uid: &Uuid, id: i32) -> Result<(), ApiError> {
+    match infrastructures::find_collection(pool, uid, id).await {
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[post("/collections")]
+pub async fn create_collection(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    form: web::Form<NewCollection>,
+) -> Result<HttpResponse, ApiError> {
+    match form.validate() {
+        Ok(_) => (),
+        Err(e) => return Err(e.into()),
+    }
+
+    match infrastructures::find_collection_by_name(pool.get_ref(), &user.uid, &form.name).await {
+        Ok(Some(_)) => return Err(ApiError::Conflict),
+        Ok(None) => (),
+        Err(_) => return Err(ApiError::InternalError),
+    }
+
+    match infrastructures::create_collection(pool.get_ref(), &user.uid, &form).await {
+        Ok(collection) => Ok(HttpResponse::Created().json(collection)),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[get("/collections")]
+pub async fn list_collections(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, ApiError> {
+    match infrastructures::find_collections(pool.get_ref(), &user.uid).await {
+        Ok(collections) => Ok(HttpResponse::Ok().json(collections)),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[get("/collections/{id:\\d+}")]
+pub async fn get_collection(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path(id): web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    match infrastructures::find_collection(pool.get_ref(), &user.uid, id).await {
+        Ok(Some(collection)) => Ok(HttpResponse::Ok().json(collection)),
+        Ok(None) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+// Making a collection private stops its link from working, making it shared again restores it.
+#[put("/collections/{id:\\d+}")]
+pub async fn update_collection(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path(id): web::Path<i32>,
+    form: web::Form<NewCollection>,
+) -> Result<HttpResponse, ApiError> {
+    match form.validate() {
+        Ok(_) => (),
+        Err(e) => return Err(e.into()),
+    }
+
+    match infrastructures::find_collection_by_name(pool.get_ref(), &user.uid, &form.name).await {
+        Ok(Some(other)) if other != id => return Err(ApiError::Conflict),
+        Ok(_) => (),
+        Err(_) => return Err(ApiError::InternalError),
+    }
+    match infrastructures::update_collection(pool.get_ref(), &user.uid, id, &form).await {
+        Ok(true) => (),
+        Ok(false) => return Err(ApiError::NotFound),
+        Err(_) => return Err(ApiError::InternalError),
+    }
+    match infrastructures::find_collection(pool.get_ref(), &user.uid, id).await {
+        Ok(Some(collection)) => Ok(HttpResponse::Ok().json(collection)),
+        Ok(None) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[delete("/collections/{id:\\d+}")]
+pub async fn delete_collection(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path(id): web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    match infrastructures::delete_collection(pool.get_ref(), &user.uid, id).await {
+        Ok(true) => Ok(HttpResponse::Ok().json("")),
+        Ok(false) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[get("/collections/{id:\\d+}/entries")]
+pub async fn list_entries(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path(id): web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    check_collection(pool.get_ref(), &user.uid, id).await?;
+
+    match infrastructures::find_entries(pool.get_ref(), id).await {
+        Ok(entries) => Ok(HttpResponse::Ok().json(entries)),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+// Only the reviews of the owner of the collection can be added.
+#[post("/collections/{id:\\d+}/entries")]
+pub async fn add_entry(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path(id): web::Path<i32>,
+    form: web::Form<NewEntry>,
+) -> Result<HttpResponse, ApiError> {
+    match form.validate() {
+        Ok(_) => (),
+        Err(e) => return Err(e.into()),
+    }
+    check_collection(pool.get_ref(), &user.uid, id).await?;
+    match reviews::infrastructures::find_review(pool.get_ref(), &user.uid, form.review_id).await {
+        Ok(Some(_)) => (),
+        Ok(None) => return Err(ApiError::NotFound),
+        Err(_) => return Err(ApiError::InternalError),
+    }
+
+    match infrastructures::add_entry(pool.get_ref(), id, form.review_id, &form.note).await {
+        Ok(Some(entry)) => Ok(HttpResponse::Created().json(entry)),
+        Ok(None) => Err(ApiError::Conflict),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[delete("/collections/{id:\\d+}/entries/{entry_id:\\d+}")]
+pub async fn remove_entry(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path((id, entry_id)): web::Path<(i32, i32)>,
+) -> Result<HttpResponse, ApiError> {
+    check_collection(pool.get_ref(), &user.uid, id).await?;
+
+    match infrastructures::remove_entry(pool.get_ref(), id, entry_id).await {
+        Ok(true) => Ok(HttpResponse::Ok().json("")),
+        Ok(false) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+// Anyone with the link sees an unlisted or public collection, without signing in.
+#[get("/shared/collections/{token}")]
+pub async fn get_shared_collection(
+    pool: web::Data<PgPool>,
+    web::Path(token): web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    match infrastructures::find_shared_collection(pool.get_ref(), &token).await {
+        Ok(Some(collection)) => Ok(HttpResponse::Ok().json(collection)),
+        Ok(None) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collections::model::{Collection, Entry};
+    use crate::{auth, config, utils};
+    use actix_web::{test, App};
+
+    #[actix_rt::test]
+    async fn collections_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .data(pool.clone())
+                .service(create_collection)
+                .service(list_collections)
+                .service(get_collection)
+                .service(update_collection)
+                .service(delete_collection)
+                .service(list_entries)
+                .service(add_entry)
+                .service(remove_entry)
+                .service(get_shared_collection),
+        )
+        .await;
+        let uid = Uuid::new_v4();
+        let token = auth::infrastructures::create_session(&pool, &uid)
+            .await
+            .unwrap();
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'test_user', 'password', 'test@gmail.com', $1)"#)
+			.bind(uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+        sqlx::query(r#"INSERT INTO reviews (id, problem_name, url, memo, uid, platform, created_at) VALUES (0, 'test_prob_name', 'test_url', 'secret memo', $1, 1, now()), (1, 'others_prob_name', 'test_url', 'memo', $2, 1, now())"#)
+			.bind(uid)
+			.bind(Uuid::new_v4())
+			.execute(&pool)
+			.await
+			.unwrap();
+
+        let new_collection = |name: &str, visibility: &str| NewCollection {
+            name: name.to_string(),
+            description: None,
+            visibility: visibility.to_string(),
+        };
+        let req = test::TestRequest::post()
+            .uri("/collections")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&new_collection("redo", "private"))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(201, resp.status());
+        let collection: Collection = test::read_body_json(resp).await;
+        let req = test::TestRequest::post()
+            .uri("/collections")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&new_collection("redo", "public"))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(409, resp.status());
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/collections/{}/entries", collection.id))
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&NewEntry {
+                review_id: 0,
+                note: Some("redo with a segment tree".to_string()),
+            })
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(201, resp.status());
+        let entry: Entry = test::read_body_json(resp).await;
+        // the review of another user cannot be added
+        let req = test::TestRequest::post()
+            .uri(&format!("/collections/{}/entries", collection.id))
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&NewEntry {
+                review_id: 1,
+                note: None,
+            })
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(404, resp.status());
+
+        let shared = format!("/shared/collections/{}", collection.share_token);
+        let req = test::TestRequest::get().uri(&shared).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(404, resp.status());
+
+        let req = test::TestRequest::put()
+            .uri(&format!("/collections/{}", collection.id))
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&new_collection("redo", "unlisted"))
+            .to_request();
+        let updated: Collection = test::read_response_json(&mut app, req).await;
+        assert_eq!("unlisted", updated.visibility);
+        assert_eq!(1, updated.entries);
+
+        let req = test::TestRequest::get().uri(&shared).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let body = test::read_body(resp).await;
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("redo with a segment tree"));
+        assert!(body.contains("\"owner\":\"test_user\""));
+        assert!(!body.contains("secret memo"));
+        assert!(!body.contains("test@gmail.com"));
+
+        let req = test::TestRequest::delete()
+            .uri(&format!(
+                "/collections/{}/entries/{}",
+                collection.id, entry.id
+            ))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let req = test::TestRequest::get()
+            .uri(&format!("/collections/{}/entries", collection.id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let entries: Vec<Entry> = test::read_response_json(&mut app, req).await;
+        assert!(entries.is_empty());
+
+        let req = test::TestRequest::delete()
+            .uri(&format!("/collections/{}", collection.id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let req = test::TestRequest::get()
+            .uri("/collections")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let collections: Vec<Collection> = test::read_response_json(&mut app, req).await;
+        assert!(collections.is_empty());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+}
@@ -0,0 +1,383 @@
+use super::model::{Collection, Entry, NewCollection, SharedCollection};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// a collection together with the number of its entries
+const SELECT_COLLECTIONS: &str = r#"SELECT c.id, c.name, c.description, c.visibility, c.share_token,
+    COUNT(e.id) AS entries, c.created_at, c.updated_at
+    FROM collections c
+    LEFT JOIN collection_entries e ON e.collection_id = c.id"#;
+
+pub async fn create_collection(
+    pool: &PgPool,
+    uid: &Uuid,
+    collection: &NewCollection,
+) -> Result<Collection> {
+    let (id,): (i32,) = sqlx::query_as(
+        r#"INSERT INTO collections (uid, name, description, visibility, share_token, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6) RETURNING id"#,
+    )
+    .bind(uid)
+    .bind(&collection.name)
+    .bind(&collection.description)
+    .bind(&collection.visibility)
+    .bind(Uuid::new_v4())
+    .bind(Utc::now())
+    .fetch_one(pool)
+    .await?;
+    let created = find_collection(pool, uid, id).await?;
+    created.ok_or_else(|| anyhow::anyhow!("the created collection {} is missing", id))
+}
+
+pub async fn find_collections(pool: &PgPool, uid: &Uuid) -> Result<Vec<Collection>> {
+    let sql = format!(
+        "{} WHERE c.uid = $1 GROUP BY c.id ORDER BY c.id",
+        SELECT_COLLECTIONS
+    );
+    let collections = sqlx::query_as::<_, Collection>(&sql)
+        .bind(uid)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(collections)
+}
+
+pub async fn find_public_collections(pool: &PgPool, uid: &Uuid) -> Result<Vec<Collection>> {
+    let sql = format!(
+        "{} WHERE c.uid = $1 AND c.visibility = 'public' GROUP BY c.id ORDER BY c.id",
+        SELECT_COLLECTIONS
+    );
+    let collections = sqlx::query_as::<_, Collection>(&sql)
+        .bind(uid)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(collections)
+}
+
+pub async fn find_collection(pool: &PgPool, uid: &Uuid, id: i32) -> Result<Option<Collection>> {
+    let sql = format!(
+        "{} WHERE c.uid = $1 AND c.id = $2 GROUP BY c.id",
+        SELECT_COLLECTIONS
+    );
+    let collection = sqlx::query_as::<_, Collection>(&sql)
+        .bind(uid)
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(collection)
+}
+
+// Returns the id of the collection of the user with the name, if any.
+pub async fn find_collection_by_name(pool: &PgPool, uid: &Uuid, name: &str) -> Result<Option<i32>> {
+    let id: Option<(i32,)> =
+        sqlx::query_as("SELECT id FROM collections WHERE uid = $1 AND name = $2")
+            .bind(uid)
+            .bind(name)
+            .fetch_optional(pool)
+            .await?;
+
+    Ok(id.map(|(id,)| id))
+}
+
+pub async fn update_collection(
+    pool: &PgPool,
+    uid: &Uuid,
+    id: i32,
+    collection: &NewCollection,
+) -> Result<bool> {
+    let result = sqlx::query(
+        r#"UPDATE collections SET name = $1, description = $2, visibility = $3, updated_at = $4
+        WHERE uid = $5 AND id = $6"#,
+    )
+    .bind(&collection.name)
+    .bind(&collection.description)
+    .bind(&collection.visibility)
+    .bind(Utc::now())
+    .bind(uid)
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// The reviews stay, only the entries go with the collection.
+pub async fn delete_collection(pool: &PgPool, uid: &Uuid, id: i32) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM collections WHERE uid = $1 AND id = $2")
+        .bind(uid)
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// The entries in the order they were added.
+pub async fn find_entries(pool: &PgPool, collection_id: i32) -> Result<Vec<Entry>> {
+    let entries = sqlx::query_as::<_, Entry>(
+        r#"SELECT e.id, e.review_id, r.problem_name, r.url, r.platform, r.difficulty,
+        p.difficulty AS platform_difficulty,
+        COALESCE(ARRAY_AGG(t.name ORDER BY t.name) FILTER (WHERE t.name IS NOT NULL), '{}') AS tags,
+        e.note, e.added_at
+        FROM collection_entries e
+        JOIN reviews r ON r.id = e.review_id
+        LEFT JOIN problems p ON p.id = r.problem_id
+        LEFT JOIN review_tags rt ON rt.review_id = r.id
+        LEFT JOIN tags t ON t.id = rt.tag_id
+        WHERE e.collection_id = $1
+        GROUP BY e.id, r.id, p.id
+        ORDER BY e.id"#,
+    )
+    .bind(collection_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(entries)
+}
+
+// Add a review of the owner of the collection, absent when the review is in it already.
+pub async fn add_entry(
+    pool: &PgPool,
+    collection_id: i32,
+    review_id: i32,
+    note: &Option<String>,
+) -> Result<Option<Entry>> {
+    let now = Utc::now();
+    let mut tx = pool.begin().await?;
+    let id: Option<(i32,)> = sqlx::query_as(
+        r#"INSERT INTO collection_entries (collection_id, review_id, note, added_at) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (collection_id, review_id) DO NOTHING RETURNING id"#,
+    )
+    .bind(collection_id)
+    .bind(review_id)
+    .bind(note)
+    .bind(now)
+    .fetch_optional(&mut tx)
+    .await?;
+    let id = match id {
+        Some((id,)) => id,
+        None => return Ok(None),
+    };
+    sqlx::query("UPDATE collections SET updated_at = $1 WHERE id = $2")
+        .bind(now)
+        .bind(collection_id)
+        .execute(&mut tx)
+        .await?;
+    tx.commit().await?;
+    let entry = find_entries(pool, collection_id)
+        .await?
+        .into_iter()
+        .find(|e| e.id == id);
+
+    Ok(entry)
+}
+
+pub async fn remove_entry(pool: &PgPool, collection_id: i32, id: i32) -> Result<bool> {
+    let mut tx = pool.begin().await?;
+    let result = sqlx::query("DELETE FROM collection_entries WHERE collection_id = $1 AND id = $2")
+        .bind(collection_id)
+        .bind(id)
+        .execute(&mut tx)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Ok(false);
+    }
+    sqlx::query("UPDATE collections SET updated_at = $1 WHERE id = $2")
+        .bind(Utc::now())
+        .bind(collection_id)
+        .execute(&mut tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(true)
+}
+
+// the id, name, description, owner and update time of a shared collection
+type SharedRow = (i32, String, Option<String>, String, Option<DateTime<Utc>>);
+
+// The collection behind a link, unless it is private or its owner is deleting the account.
+pub async fn find_shared_collection(
+    pool: &PgPool,
+    token: &Uuid,
+) -> Result<Option<SharedCollection>> {
+    let row: Option<SharedRow> = sqlx::query_as(
+        r#"SELECT c.id, c.name, c.description, u.user_name, c.updated_at
+        FROM collections c
+        JOIN users u ON u.uid = c.uid
+        WHERE c.share_token = $1 AND c.visibility <> 'private'
+        AND NOT EXISTS (SELECT 1 FROM account_deletions a WHERE a.uid = c.uid AND a.confirmed_at IS NOT NULL)"#,
+    )
+    .bind(token)
+    .fetch_optional(pool)
+    .await?;
+    let (id, name, description, owner, updated_at) = match row {
+        Some(row) => row,
+        None => return Ok(None),
+    };
+    let entries = find_entries(pool, id).await?;
+
+    Ok(Some(SharedCollection {
+        name,
+        description,
+        owner,
+        updated_at,
+        entries,
+    }))
+}
+
+// Whether the entry is in the collection behind a link, reachable the way the collection is.
+pub async fn find_shared_entry(pool: &PgPool, token: &Uuid, entry_id: i32) -> Result<bool> {
+    let (shared,): (bool,) = sqlx::query_as(
+        r#"SELECT EXISTS (SELECT 1 FROM collection_entries e
+        JOIN collections c ON c.id = e.collection_id
+        WHERE c.share_token = $1 AND e.id = $2 AND c.visibility <> 'private'
+        AND NOT EXISTS (SELECT 1 FROM account_deletions a WHERE a.uid = c.uid AND a.confirmed_at IS NOT NULL))"#,
+    )
+    .bind(token)
+    .bind(entry_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(shared)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collections::model::VISIBILITIES;
+    use crate::{config, utils};
+
+    fn new_collection(name: &str, visibility: &str) -> NewCollection {
+        NewCollection {
+            name: name.to_string(),
+            description: Some("problems to redo".to_string()),
+            visibility: visibility.to_string(),
+        }
+    }
+
+    async fn insert_user(pool: &PgPool, uid: &Uuid) {
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'test_user', 'password', 'test@gmail.com', $1)"#)
+			.bind(uid)
+			.execute(pool)
+			.await
+			.unwrap();
+        sqlx::query(r#"INSERT INTO reviews (id, problem_name, url, memo, uid, platform, created_at) VALUES (0, 'test_prob_name', 'test_url', 'secret memo', $1, 1, now()), (1, 'other_prob_name', 'test_url', 'secret memo', $1, 1, now())"#)
+			.bind(uid)
+			.execute(pool)
+			.await
+			.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn collection_entries() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        insert_user(&pool, &uid).await;
+
+        let collection = create_collection(&pool, &uid, &new_collection("redo", "private"))
+            .await
+            .unwrap();
+        assert_eq!(0, collection.entries);
+        assert_eq!(
+            Some(collection.id),
+            find_collection_by_name(&pool, &uid, "redo").await.unwrap()
+        );
+        let entry = add_entry(
+            &pool,
+            collection.id,
+            1,
+            &Some("use a segment tree".to_string()),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!("other_prob_name", entry.problem_name);
+        assert_eq!(
+            None,
+            add_entry(&pool, collection.id, 1, &None).await.unwrap()
+        );
+        add_entry(&pool, collection.id, 0, &None).await.unwrap();
+
+        let collections = find_collections(&pool, &uid).await.unwrap();
+        assert_eq!(2, collections[0].entries);
+        assert!(collections[0].updated_at.is_some());
+        let entries = find_entries(&pool, collection.id).await.unwrap();
+        assert_eq!(
+            vec![1, 0],
+            entries.iter().map(|e| e.review_id).collect::<Vec<i32>>()
+        );
+
+        assert!(remove_entry(&pool, collection.id, entry.id).await.unwrap());
+        assert!(!remove_entry(&pool, collection.id, entry.id).await.unwrap());
+        // deleting the review takes it out of the collection
+        sqlx::query("DELETE FROM reviews WHERE id = 0")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(find_entries(&pool, collection.id).await.unwrap().is_empty());
+
+        assert!(delete_collection(&pool, &uid, collection.id).await.unwrap());
+        assert!(!delete_collection(&pool, &uid, collection.id).await.unwrap());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn shared_by_visibility() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        insert_user(&pool, &uid).await;
+        let mut tokens = Vec::new();
+        for visibility in VISIBILITIES.iter() {
+            let collection =
+                create_collection(&pool, &uid, &new_collection(visibility, visibility))
+                    .await
+                    .unwrap();
+            add_entry(&pool, collection.id, 0, &None).await.unwrap();
+            tokens.push(collection.share_token);
+        }
+
+        assert_eq!(
+            None,
+            find_shared_collection(&pool, &tokens[0]).await.unwrap()
+        );
+        let unlisted = find_shared_collection(&pool, &tokens[1])
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!("test_user", unlisted.owner);
+        assert_eq!(1, unlisted.entries.len());
+        assert!(find_shared_collection(&pool, &tokens[2])
+            .await
+            .unwrap()
+            .is_some());
+        let public = find_public_collections(&pool, &uid).await.unwrap();
+        assert_eq!(
+            vec!["public"],
+            public
+                .iter()
+                .map(|c| c.name.as_str())
+                .collect::<Vec<&str>>()
+        );
+
+        // hidden while the account is being deleted
+        sqlx::query(r#"INSERT INTO account_deletions (uid, token, confirmed_at, created_at) VALUES ($1, $2, now(), now())"#)
+			.bind(uid)
+			.bind(Uuid::new_v4())
+			.execute(&pool)
+			.await
+			.unwrap();
+        assert_eq!(
+            None,
+            find_shared_collection(&pool, &tokens[2]).await.unwrap()
+        );
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+}
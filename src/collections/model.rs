@@ -0,0 +1,91 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::{Validate, ValidationError};
+
+pub const VISIBILITIES: [&str; 3] = ["private", "unlisted", "public"];
+
+#[derive(Debug, Serialize, Deserialize, Validate, PartialEq)]
+pub struct NewCollection {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    #[validate(length(max = 1000))]
+    pub description: Option<String>,
+    #[validate(custom = "validate_visibility")]
+    pub visibility: String,
+}
+
+fn validate_visibility(visibility: &str) -> Result<(), ValidationError> {
+    if VISIBILITIES.contains(&visibility) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("invalid_visibility"))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, PartialEq)]
+pub struct Collection {
+    pub id: i32,
+    pub name: String,
+    pub description: Option<String>,
+    pub visibility: String,
+    // the link is `/shared/collections/{share_token}`, which works unless the collection is private
+    pub share_token: Uuid,
+    pub entries: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, PartialEq)]
+pub struct NewEntry {
+    pub review_id: i32,
+    #[validate(length(max = 500))]
+    pub note: Option<String>,
+}
+
+// A review in a collection. It is shown to anyone the collection is shared with,
+// so it carries the problem but never the memo of the review.
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, PartialEq)]
+pub struct Entry {
+    pub id: i32,
+    pub review_id: i32,
+    pub problem_name: String,
+    pub url: String,
+    pub platform: i16,
+    pub difficulty: Option<i16>,
+    pub platform_difficulty: Option<i32>,
+    pub tags: Vec<String>,
+    pub note: Option<String>,
+    pub added_at: DateTime<Utc>,
+}
+
+// A collection as the ones it is shared with see it.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct SharedCollection {
+    pub name: String,
+    pub description: Option<String>,
+    // the user name of the owner
+    pub owner: String,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub entries: Vec<Entry>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_collection() {
+        let mut collection = NewCollection {
+            name: "redo".to_string(),
+            description: None,
+            visibility: "unlisted".to_string(),
+        };
+        assert!(collection.validate().is_ok());
+        collection.visibility = "friends".to_string();
+        assert!(collection.validate().is_err());
+        collection.visibility = "public".to_string();
+        collection.name = "".to_string();
+        assert!(collection.validate().is_err());
+    }
+}
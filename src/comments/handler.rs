@@ -0,0 +1,383 @@
+use super::infrastructures;
+use super::model::{EditComment, NewComment, Target};
+use crate::auth::AuthenticatedUser;
+use crate::error::ApiError;
+use crate::{collections, problems};
+use actix_web::{delete, get, post, put, web, HttpResponse};
+use anyhow::Result;
+use sqlx::PgPool;
+use uuid::Uuid;
+use validator::Validate;
+
+async fn check_problem(pool: &PgPool, id: i32) -> Result<(), ApiError> {
+    match problems::infrastructures::find_problem(pool, id).await {
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+// The threads on an entry are read by the ones the collection is shared with.
+async fn check_shared_entry(pool: &PgPool, token: &Uuid, entry_id: i32) -> Result<(), ApiError> {
+    match collections::infrastructures::find_shared_entry(pool, token, entry_id).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+async fn create_comment(
+    pool: &PgPool,
+    uid: &Uuid,
+    target: &Target,
+    form: &NewComment,
+    path: &str,
+) -> Result<HttpResponse, ApiError> {
+    match infrastructures::create_comment(pool, uid, target, form, path).await {
+        Ok(Some(comment)) => Ok(HttpResponse::Created().json(comment)),
+        // the comment replied to is not in the thread
+        Ok(None) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[get("/problems/{id:\\d+}/comments")]
+pub async fn list_problem_comments(
+    pool: web::Data<PgPool>,
+    _: AuthenticatedUser,
+    web::Path(id): web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    check_problem(pool.get_ref(), id).await?;
+
+    match infrastructures::find_comments(pool.get_ref(), &Target::Problem(id)).await {
+        Ok(comments) => Ok(HttpResponse::Ok().json(comments)),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[post("/problems/{id:\\d+}/comments")]
+pub async fn comment_problem(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path(id): web::Path<i32>,
+    form: web::Form<NewComment>,
+) -> Result<HttpResponse, ApiError> {
+    match form.validate() {
+        Ok(_) => (),
+        Err(e) => return Err(e.into()),
+    }
+    check_problem(pool.get_ref(), id).await?;
+
+    let path = format!("problems/{}/comments", id);
+    create_comment(
+        pool.get_ref(),
+        &user.uid,
+        &Target::Problem(id),
+        &form,
+        &path,
+    )
+    .await
+}
+
+// Anyone with the link reads the threads, without signing in, like the collection itself.
+#[get("/shared/collections/{token}/entries/{entry_id:\\d+}/comments")]
+pub async fn list_entry_comments(
+    pool: web::Data<PgPool>,
+    web::Path((token, entry_id)): web::Path<(Uuid, i32)>,
+) -> Result<HttpResponse, ApiError> {
+    check_shared_entry(pool.get_ref(), &token, entry_id).await?;
+
+    match infrastructures::find_comments(pool.get_ref(), &Target::Entry(entry_id)).await {
+        Ok(comments) => Ok(HttpResponse::Ok().json(comments)),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[post("/shared/collections/{token}/entries/{entry_id:\\d+}/comments")]
+pub async fn comment_entry(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path((token, entry_id)): web::Path<(Uuid, i32)>,
+    form: web::Form<NewComment>,
+) -> Result<HttpResponse, ApiError> {
+    match form.validate() {
+        Ok(_) => (),
+        Err(e) => return Err(e.into()),
+    }
+    check_shared_entry(pool.get_ref(), &token, entry_id).await?;
+
+    let path = format!("shared/collections/{}/entries/{}/comments", token, entry_id);
+    create_comment(
+        pool.get_ref(),
+        &user.uid,
+        &Target::Entry(entry_id),
+        &form,
+        &path,
+    )
+    .await
+}
+
+// Only the author edits a comment.
+#[put("/comments/{id:\\d+}")]
+pub async fn update_comment(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path(id): web::Path<i32>,
+    form: web::Form<EditComment>,
+) -> Result<HttpResponse, ApiError> {
+    match form.validate() {
+        Ok(_) => (),
+        Err(e) => return Err(e.into()),
+    }
+    let comment = match infrastructures::find_comment(pool.get_ref(), id).await {
+        Ok(Some(comment)) if !comment.deleted => comment,
+        Ok(_) => return Err(ApiError::NotFound),
+        Err(_) => return Err(ApiError::InternalError),
+    };
+    if comment.uid != user.uid {
+        return Err(ApiError::Forbidden);
+    }
+
+    match infrastructures::update_comment(pool.get_ref(), &comment, &form.body).await {
+        Ok(true) => Ok(HttpResponse::Ok().json("")),
+        Ok(false) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+// The author deletes a comment, and so does the owner of a group the author and the owner of
+// the entry commented on are members of.
+#[delete("/comments/{id:\\d+}")]
+pub async fn delete_comment(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path(id): web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    let comment = match infrastructures::find_comment(pool.get_ref(), id).await {
+        Ok(Some(comment)) if !comment.deleted => comment,
+        Ok(_) => return Err(ApiError::NotFound),
+        Err(_) => return Err(ApiError::InternalError),
+    };
+    if comment.uid != user.uid {
+        match infrastructures::is_moderator(pool.get_ref(), &user.uid, id).await {
+            Ok(true) => (),
+            Ok(false) => return Err(ApiError::Forbidden),
+            Err(_) => return Err(ApiError::InternalError),
+        }
+    }
+
+    match infrastructures::delete_comment(pool.get_ref(), id).await {
+        Ok(_) => Ok(HttpResponse::Ok().json("")),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collections::model::NewCollection;
+    use crate::comments::model::Comment;
+    use crate::{auth, config, utils};
+    use actix_web::{test, App};
+
+    #[actix_rt::test]
+    async fn comments_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .data(pool.clone())
+                .service(list_problem_comments)
+                .service(comment_problem)
+                .service(list_entry_comments)
+                .service(comment_entry)
+                .service(update_comment)
+                .service(delete_comment),
+        )
+        .await;
+        let (author, other) = (Uuid::new_v4(), Uuid::new_v4());
+        let author_token = auth::infrastructures::create_session(&pool, &author)
+            .await
+            .unwrap();
+        let other_token = auth::infrastructures::create_session(&pool, &other)
+            .await
+            .unwrap();
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'author', 'password', 'author@gmail.com', $1), (1, 'other', 'password', 'other@gmail.com', $2)"#)
+			.bind(author)
+			.bind(other)
+			.execute(&pool)
+			.await
+			.unwrap();
+        sqlx::query(r#"INSERT INTO problems (id, platform, external_id, title, url) VALUES (0, 4, '1', 'No.1', 'https://yukicoder.me/problems/no/1')"#)
+			.execute(&pool)
+			.await
+			.unwrap();
+
+        // the owner is a member of the group as well
+        let (group_id,): (i32,) = sqlx::query_as("INSERT INTO study_groups (uid, name, created_at) VALUES ($1, 'ICPC team', now()) RETURNING id")
+            .bind(other)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO group_members (group_id, uid, joined_at) VALUES ($1, $2, now()), ($1, $3, now())")
+            .bind(group_id)
+            .bind(other)
+            .bind(author)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let req = test::TestRequest::post()
+            .uri("/problems/0/comments")
+            .header("Authorization", format!("Bearer {}", author_token))
+            .set_form(&NewComment {
+                body: "@other a greedy works, $O(N)$".to_string(),
+                parent_id: None,
+            })
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(201, resp.status());
+        let comment: Comment = test::read_body_json(resp).await;
+        let (body,): (String,) =
+            sqlx::query_as("SELECT body FROM email_outbox WHERE recipient = 'other@gmail.com'")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert!(body.starts_with("Hi other! author mentioned you in a comment:"));
+        assert!(body.ends_with("https://problems/0/comments"));
+
+        let req = test::TestRequest::post()
+            .uri("/problems/0/comments")
+            .header("Authorization", format!("Bearer {}", other_token))
+            .set_form(&NewComment {
+                body: "thanks".to_string(),
+                parent_id: Some(comment.id),
+            })
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(201, resp.status());
+        let req = test::TestRequest::get()
+            .uri("/problems/0/comments")
+            .header("Authorization", format!("Bearer {}", other_token))
+            .to_request();
+        let threads: Vec<Comment> = test::read_response_json(&mut app, req).await;
+        assert_eq!(1, threads.len());
+        assert_eq!(Some("other".to_string()), threads[0].replies[0].author);
+
+        // only the author edits, and the mentioned users are mailed once
+        let req = test::TestRequest::put()
+            .uri(&format!("/comments/{}", comment.id))
+            .header("Authorization", format!("Bearer {}", other_token))
+            .set_form(&EditComment {
+                body: "edited".to_string(),
+            })
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(403, resp.status());
+        let req = test::TestRequest::put()
+            .uri(&format!("/comments/{}", comment.id))
+            .header("Authorization", format!("Bearer {}", author_token))
+            .set_form(&EditComment {
+                body: "@other a greedy works, $O(N \\log N)$".to_string(),
+            })
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let (mails,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM email_outbox")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(1, mails);
+
+        // the catalog belongs to no group, so the comments on the problems are left to their authors
+        let req = test::TestRequest::delete()
+            .uri(&format!("/comments/{}", comment.id))
+            .header("Authorization", format!("Bearer {}", other_token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(403, resp.status());
+        let req = test::TestRequest::delete()
+            .uri(&format!("/comments/{}", comment.id))
+            .header("Authorization", format!("Bearer {}", author_token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+
+        let mentions: Vec<String> = (0..11).map(|i| format!("@user{}", i)).collect();
+        let req = test::TestRequest::post()
+            .uri("/problems/0/comments")
+            .header("Authorization", format!("Bearer {}", author_token))
+            .set_form(&NewComment {
+                body: mentions.join(" "),
+                parent_id: None,
+            })
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(400, resp.status());
+
+        // the threads on an entry follow the visibility of the collection
+        sqlx::query(r#"INSERT INTO reviews (id, problem_name, url, uid, platform, created_at) VALUES (0, 'No.1', 'https://yukicoder.me/problems/no/1', $1, 4, now())"#)
+			.bind(author)
+			.execute(&pool)
+			.await
+			.unwrap();
+        let collection = collections::infrastructures::create_collection(
+            &pool,
+            &author,
+            &NewCollection {
+                name: "redo".to_string(),
+                description: None,
+                visibility: "private".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+        let entry = collections::infrastructures::add_entry(&pool, collection.id, 0, &None)
+            .await
+            .unwrap()
+            .unwrap();
+        let thread = format!(
+            "/shared/collections/{}/entries/{}/comments",
+            collection.share_token, entry.id
+        );
+        let req = test::TestRequest::get().uri(&thread).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(404, resp.status());
+        sqlx::query("UPDATE collections SET visibility = 'unlisted'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let req = test::TestRequest::post()
+            .uri(&thread)
+            .header("Authorization", format!("Bearer {}", other_token))
+            .set_form(&NewComment {
+                body: "nice pick".to_string(),
+                parent_id: None,
+            })
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(201, resp.status());
+        let req = test::TestRequest::get().uri(&thread).to_request();
+        let threads: Vec<Comment> = test::read_response_json(&mut app, req).await;
+        assert_eq!(Some("nice pick".to_string()), threads[0].body);
+
+        // the owner of a group moderates the comments of its members on the entries they share
+        let req = test::TestRequest::post()
+            .uri(&thread)
+            .header("Authorization", format!("Bearer {}", author_token))
+            .set_form(&NewComment {
+                body: "thanks".to_string(),
+                parent_id: None,
+            })
+            .to_request();
+        let reply: Comment = test::read_response_json(&mut app, req).await;
+        let req = test::TestRequest::delete()
+            .uri(&format!("/comments/{}", reply.id))
+            .header("Authorization", format!("Bearer {}", other_token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+}
@@ -0,0 +1,502 @@
+use super::model::{mentions, Comment, CommentRow, NewComment, Recipient, StoredComment, Target};
+use crate::{mail, markdown};
+use anyhow::Result;
+use chrono::Utc;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+const SELECT_COMMENTS: &str = r#"SELECT c.id, c.parent_id, u.user_name AS author, c.body, c.body_html,
+    c.created_at, c.updated_at, c.deleted_at IS NOT NULL AS deleted
+    FROM comments c
+    LEFT JOIN users u ON u.uid = c.uid"#;
+
+// The threads on the target, oldest first.
+pub async fn find_comments(pool: &PgPool, target: &Target) -> Result<Vec<Comment>> {
+    let sql = format!(
+        "{} WHERE c.{} = $1 ORDER BY c.id",
+        SELECT_COMMENTS,
+        target.column()
+    );
+    let rows = sqlx::query_as::<_, CommentRow>(&sql)
+        .bind(target.id())
+        .fetch_all(pool)
+        .await?;
+
+    Ok(Comment::threads(rows))
+}
+
+pub async fn find_comment(pool: &PgPool, id: i32) -> Result<Option<StoredComment>> {
+    let comment = sqlx::query_as::<_, StoredComment>(
+        r#"SELECT c.id, c.uid, c.problem_id, c.entry_id, l.share_token, c.body, c.deleted_at IS NOT NULL AS deleted
+        FROM comments c
+        LEFT JOIN collection_entries e ON e.id = c.entry_id
+        LEFT JOIN collections l ON l.id = e.collection_id
+        WHERE c.id = $1"#,
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(comment)
+}
+
+// Absent when the comment replied to is not one of the target or is deleted.
+// The users mentioned are mailed along with the comment.
+pub async fn create_comment(
+    pool: &PgPool,
+    uid: &Uuid,
+    target: &Target,
+    comment: &NewComment,
+    path: &str,
+) -> Result<Option<Comment>> {
+    let (problem_id, entry_id) = match *target {
+        Target::Problem(id) => (Some(id), None),
+        Target::Entry(id) => (None, Some(id)),
+    };
+    let mut tx = pool.begin().await?;
+    let sql = format!(
+        r#"INSERT INTO comments (uid, problem_id, entry_id, parent_id, body, body_html, created_at)
+        SELECT $1, $2, $3, $4, $5, $6, $7
+        WHERE $4::INTEGER IS NULL
+        OR EXISTS (SELECT 1 FROM comments p WHERE p.id = $4 AND p.{} = $8 AND p.deleted_at IS NULL)
+        RETURNING id"#,
+        target.column()
+    );
+    let id: Option<(i32,)> = sqlx::query_as(&sql)
+        .bind(uid)
+        .bind(problem_id)
+        .bind(entry_id)
+        .bind(comment.parent_id)
+        .bind(&comment.body)
+        .bind(markdown::render(&comment.body))
+        .bind(Utc::now())
+        .bind(target.id())
+        .fetch_optional(&mut tx)
+        .await?;
+    let id = match id {
+        Some((id,)) => id,
+        None => return Ok(None),
+    };
+    enqueue_mentions(&mut tx, uid, target, &comment.body, "", path).await?;
+    let sql = format!("{} WHERE c.id = $1", SELECT_COMMENTS);
+    let row = sqlx::query_as::<_, CommentRow>(&sql)
+        .bind(id)
+        .fetch_one(&mut tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(Some(Comment::from(row)))
+}
+
+// The users newly mentioned by the edit are mailed along with it.
+pub async fn update_comment(pool: &PgPool, comment: &StoredComment, body: &str) -> Result<bool> {
+    let mut tx = pool.begin().await?;
+    // the body replaced is read under the lock, so that a mention is mailed once
+    let before: Option<(Option<String>,)> =
+        sqlx::query_as("SELECT body FROM comments WHERE id = $1 AND deleted_at IS NULL FOR UPDATE")
+            .bind(comment.id)
+            .fetch_optional(&mut tx)
+            .await?;
+    let before = match before {
+        Some((before,)) => before.unwrap_or_default(),
+        None => return Ok(false),
+    };
+    sqlx::query(r#"UPDATE comments SET body = $1, body_html = $2, updated_at = $3 WHERE id = $4"#)
+        .bind(body)
+        .bind(markdown::render(body))
+        .bind(Utc::now())
+        .bind(comment.id)
+        .execute(&mut tx)
+        .await?;
+    enqueue_mentions(
+        &mut tx,
+        &comment.uid,
+        &comment.target(),
+        body,
+        &before,
+        &comment.path(),
+    )
+    .await?;
+    tx.commit().await?;
+
+    Ok(true)
+}
+
+// Mail the users mentioned in the body and not already in the body before.
+async fn enqueue_mentions(
+    tx: &mut Transaction<'_, Postgres>,
+    author: &Uuid,
+    target: &Target,
+    body: &str,
+    before: &str,
+    path: &str,
+) -> Result<()> {
+    let mentioned_before = mentions(before);
+    let names: Vec<String> = mentions(body)
+        .into_iter()
+        .filter(|name| !mentioned_before.contains(name))
+        .collect();
+    if names.is_empty() {
+        return Ok(());
+    }
+    let (author_name,): (String,) = sqlx::query_as("SELECT user_name FROM users WHERE uid = $1")
+        .bind(author)
+        .fetch_one(&mut *tx)
+        .await?;
+    for recipient in find_recipients(&mut *tx, author, target, &names).await? {
+        let mention_mail = mail::templates::mention(
+            &recipient.user_name,
+            &recipient.email,
+            &author_name,
+            body,
+            path,
+        );
+        mail::infrastructures::enqueue(&mut *tx, &mention_mail).await?;
+    }
+
+    Ok(())
+}
+
+// A comment without replies goes; one with replies loses its body but keeps its place in the thread.
+pub async fn delete_comment(pool: &PgPool, id: i32) -> Result<()> {
+    let mut tx = pool.begin().await?;
+    let result = sqlx::query(
+        "DELETE FROM comments WHERE id = $1 AND NOT EXISTS (SELECT 1 FROM comments r WHERE r.parent_id = $1)",
+    )
+    .bind(id)
+    .execute(&mut tx)
+    .await?;
+    if result.rows_affected() == 0 {
+        sqlx::query(
+            r#"UPDATE comments SET body = NULL, body_html = NULL, deleted_at = $1
+            WHERE id = $2 AND deleted_at IS NULL"#,
+        )
+        .bind(Utc::now())
+        .bind(id)
+        .execute(&mut tx)
+        .await?;
+    }
+    tx.commit().await?;
+
+    Ok(())
+}
+
+// The owner of a group moderates the comments its members write on the entries its members share.
+// The catalog belongs to no group, so the comments on the problems are left to their authors.
+pub async fn is_moderator(pool: &PgPool, uid: &Uuid, comment_id: i32) -> Result<bool> {
+    let (moderator,): (bool,) = sqlx::query_as(
+        r#"SELECT EXISTS (SELECT 1 FROM comments c
+        JOIN collection_entries e ON e.id = c.entry_id
+        JOIN collections l ON l.id = e.collection_id
+        JOIN study_groups g ON g.uid = $1
+        JOIN group_members a ON a.group_id = g.id AND a.uid = c.uid
+        JOIN group_members o ON o.group_id = g.id AND o.uid = l.uid
+        WHERE c.id = $2)"#,
+    )
+    .bind(uid)
+    .bind(comment_id)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(moderator)
+}
+
+// The users with the names who take part in the thread, leaving out the author and the accounts
+// being deleted. The thread is in the groups of the owner of the collection for an entry, and in
+// the groups of the author for a problem, so that the users outside are not mailed.
+pub async fn find_recipients<'e, E>(
+    executor: E,
+    author: &Uuid,
+    target: &Target,
+    names: &[String],
+) -> Result<Vec<Recipient>>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    let owner = match target {
+        Target::Problem(_) => "$2",
+        Target::Entry(_) => {
+            "(SELECT l.uid FROM collection_entries e JOIN collections l ON l.id = e.collection_id WHERE e.id = $3)"
+        }
+    };
+    let sql = format!(
+        r#"SELECT u.user_name, u.email FROM users u
+        WHERE u.user_name = ANY($1) AND u.uid <> $2
+        AND NOT EXISTS (SELECT 1 FROM account_deletions a WHERE a.uid = u.uid AND a.confirmed_at IS NOT NULL)
+        AND (u.uid = {0} OR EXISTS (SELECT 1 FROM group_members o JOIN group_members m ON m.group_id = o.group_id
+            WHERE o.uid = {0} AND m.uid = u.uid))
+        ORDER BY u.user_name"#,
+        owner
+    );
+    let recipients = sqlx::query_as::<_, Recipient>(&sql)
+        .bind(names)
+        .bind(author)
+        .bind(target.id())
+        .fetch_all(executor)
+        .await?;
+
+    Ok(recipients)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collections;
+    use crate::collections::model::NewCollection;
+    use crate::{config, utils};
+
+    const PATH: &str = "problems/0/comments";
+
+    async fn insert_users(pool: &PgPool, author: &Uuid, other: &Uuid) {
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'author', 'password', 'author@gmail.com', $1), (1, 'other', 'password', 'other@gmail.com', $2)"#)
+			.bind(author)
+			.bind(other)
+			.execute(pool)
+			.await
+			.unwrap();
+        sqlx::query(r#"INSERT INTO problems (id, platform, external_id, title, url) VALUES (0, 4, '1', 'No.1', 'https://yukicoder.me/problems/no/1'), (1, 4, '2', 'No.2', 'https://yukicoder.me/problems/no/2')"#)
+			.execute(pool)
+			.await
+			.unwrap();
+    }
+
+    fn new_comment(body: &str, parent_id: Option<i32>) -> NewComment {
+        NewComment {
+            body: body.to_string(),
+            parent_id,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn threads_and_deletion() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let (author, other) = (Uuid::new_v4(), Uuid::new_v4());
+        insert_users(&pool, &author, &other).await;
+        let target = Target::Problem(0);
+
+        let root = create_comment(
+            &pool,
+            &author,
+            &target,
+            &new_comment("**DP** works", None),
+            PATH,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(Some("author".to_string()), root.author);
+        assert_eq!(
+            Some("<p><strong>DP</strong> works</p>\n".to_string()),
+            root.body_html
+        );
+        let reply = create_comment(
+            &pool,
+            &other,
+            &target,
+            &new_comment("why?", Some(root.id)),
+            PATH,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        // the comment replied to is on another problem
+        assert_eq!(
+            None,
+            create_comment(
+                &pool,
+                &other,
+                &Target::Problem(1),
+                &new_comment("no", Some(root.id)),
+                PATH
+            )
+            .await
+            .unwrap()
+        );
+
+        let stored = find_comment(&pool, root.id).await.unwrap().unwrap();
+        assert!(update_comment(&pool, &stored, "a DP works").await.unwrap());
+        assert_eq!(author, stored.uid);
+        assert_eq!(Target::Problem(0), stored.target());
+        assert_eq!(PATH, stored.path());
+
+        // the comment with a reply is kept without its body
+        delete_comment(&pool, root.id).await.unwrap();
+        let threads = find_comments(&pool, &target).await.unwrap();
+        assert_eq!(1, threads.len());
+        assert!(threads[0].deleted);
+        assert_eq!(None, threads[0].body);
+        assert_eq!(reply.id, threads[0].replies[0].id);
+        assert!(!update_comment(&pool, &stored, "edited").await.unwrap());
+        assert_eq!(
+            None,
+            create_comment(
+                &pool,
+                &other,
+                &target,
+                &new_comment("and?", Some(root.id)),
+                PATH
+            )
+            .await
+            .unwrap()
+        );
+
+        delete_comment(&pool, reply.id).await.unwrap();
+        assert_eq!(None, find_comment(&pool, reply.id).await.unwrap());
+        assert!(find_comments(&pool, &Target::Problem(1))
+            .await
+            .unwrap()
+            .is_empty());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    async fn insert_group(pool: &PgPool, owner: &Uuid, members: &[Uuid]) {
+        let (group_id,): (i32,) = sqlx::query_as("INSERT INTO study_groups (uid, name, created_at) VALUES ($1, 'ICPC team', now()) RETURNING id")
+            .bind(owner)
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        for member in members {
+            sqlx::query(
+                "INSERT INTO group_members (group_id, uid, joined_at) VALUES ($1, $2, now())",
+            )
+            .bind(group_id)
+            .bind(member)
+            .execute(pool)
+            .await
+            .unwrap();
+        }
+    }
+
+    // An entry shared by the owner, with a comment of the author on it.
+    async fn comment_entry(pool: &PgPool, owner: &Uuid, author: &Uuid) -> (Target, Comment) {
+        sqlx::query(r#"INSERT INTO reviews (id, problem_name, url, uid, platform, created_at) VALUES (0, 'No.1', 'https://yukicoder.me/problems/no/1', $1, 4, now())"#)
+			.bind(owner)
+			.execute(pool)
+			.await
+			.unwrap();
+        let collection = collections::infrastructures::create_collection(
+            pool,
+            owner,
+            &NewCollection {
+                name: "redo".to_string(),
+                description: None,
+                visibility: "unlisted".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+        let entry = collections::infrastructures::add_entry(pool, collection.id, 0, &None)
+            .await
+            .unwrap()
+            .unwrap();
+        let target = Target::Entry(entry.id);
+        let comment = create_comment(pool, author, &target, &new_comment("nice pick", None), PATH)
+            .await
+            .unwrap()
+            .unwrap();
+        (target, comment)
+    }
+
+    #[actix_rt::test]
+    async fn moderators() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let (author, other, stranger) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        insert_users(&pool, &author, &other).await;
+        let on_problem = create_comment(
+            &pool,
+            &author,
+            &Target::Problem(0),
+            &new_comment("DP", None),
+            PATH,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        let (_, on_entry) = comment_entry(&pool, &other, &author).await;
+        assert!(!is_moderator(&pool, &other, on_entry.id).await.unwrap());
+
+        // the owner of an unrelated group of the author does not moderate the thread
+        insert_group(&pool, &stranger, &[stranger, author]).await;
+        assert!(!is_moderator(&pool, &stranger, on_entry.id).await.unwrap());
+        insert_group(&pool, &other, &[other, author]).await;
+        assert!(is_moderator(&pool, &other, on_entry.id).await.unwrap());
+        assert!(!is_moderator(&pool, &author, on_entry.id).await.unwrap());
+        assert!(!is_moderator(&pool, &other, on_problem.id).await.unwrap());
+        assert!(!is_moderator(&pool, &stranger, on_problem.id).await.unwrap());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn mention_recipients() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let (author, other, third) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        insert_users(&pool, &author, &other).await;
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (2, 'third', 'password', 'third@gmail.com', $1)"#)
+			.bind(third)
+			.execute(&pool)
+			.await
+			.unwrap();
+        let names = vec![
+            "other".to_string(),
+            "third".to_string(),
+            "author".to_string(),
+            "nobody".to_string(),
+        ];
+        let problem = Target::Problem(0);
+        assert!(find_recipients(&pool, &author, &problem, &names)
+            .await
+            .unwrap()
+            .is_empty());
+
+        // the users in a group with the author read the threads on the problems
+        insert_group(&pool, &other, &[other, author]).await;
+        let recipients = find_recipients(&pool, &author, &problem, &names)
+            .await
+            .unwrap();
+        assert_eq!(
+            vec![Recipient {
+                user_name: "other".to_string(),
+                email: "other@gmail.com".to_string(),
+            }],
+            recipients
+        );
+
+        // and the owner of the entry and the users in a group with the owner read the threads on it
+        let (entry, _) = comment_entry(&pool, &third, &author).await;
+        let recipients = find_recipients(&pool, &author, &entry, &names)
+            .await
+            .unwrap();
+        assert_eq!(
+            vec!["third"],
+            recipients
+                .iter()
+                .map(|r| r.user_name.as_str())
+                .collect::<Vec<&str>>()
+        );
+
+        // the mails are queued along with the comment
+        create_comment(
+            &pool,
+            &author,
+            &entry,
+            &new_comment("@other @third see the editorial", None),
+            PATH,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        let mails: Vec<(String,)> =
+            sqlx::query_as("SELECT recipient FROM email_outbox ORDER BY recipient")
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert_eq!(vec![("third@gmail.com".to_string(),)], mails);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+}
@@ -0,0 +1,251 @@
+use chrono::{DateTime, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+use validator::{Validate, ValidationError};
+
+// the users mentioned in a comment are mailed, so that many are not mailed at once
+pub const MAX_MENTIONS: usize = 10;
+
+lazy_static! {
+    // `@user_name` not preceded by a word, so that addresses are not taken for mentions;
+    // the names with symbols other than `-`, `_` and inner dots cannot be mentioned
+    static ref RE_MENTION: Regex =
+        Regex::new(r"(?:^|[^\w@])@([A-Za-z0-9_-]+(?:\.[A-Za-z0-9_-]+)*)").unwrap();
+}
+
+// What a thread is about.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Target {
+    Problem(i32),
+    // an entry of a collection shared by link or on the profile
+    Entry(i32),
+}
+
+impl Target {
+    pub fn column(&self) -> &'static str {
+        match self {
+            Target::Problem(_) => "problem_id",
+            Target::Entry(_) => "entry_id",
+        }
+    }
+
+    pub fn id(&self) -> i32 {
+        match *self {
+            Target::Problem(id) | Target::Entry(id) => id,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, PartialEq)]
+pub struct NewComment {
+    // Markdown with `$...$` math
+    #[validate(length(min = 1, max = 10000), custom = "validate_mentions")]
+    pub body: String,
+    // the comment replied to, absent to start a thread
+    pub parent_id: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, PartialEq)]
+pub struct EditComment {
+    #[validate(length(min = 1, max = 10000), custom = "validate_mentions")]
+    pub body: String,
+}
+
+fn validate_mentions(body: &str) -> Result<(), ValidationError> {
+    if mentions(body).len() > MAX_MENTIONS {
+        return Err(ValidationError::new("too_many_mentions"));
+    }
+
+    Ok(())
+}
+
+// A comment as the checks on editing and deleting it need it.
+#[derive(Debug, sqlx::FromRow, PartialEq)]
+pub struct StoredComment {
+    pub id: i32,
+    pub uid: Uuid,
+    pub problem_id: Option<i32>,
+    pub entry_id: Option<i32>,
+    // the link of the collection of the entry
+    pub share_token: Option<Uuid>,
+    pub body: Option<String>,
+    pub deleted: bool,
+}
+
+impl StoredComment {
+    pub fn target(&self) -> Target {
+        match (self.problem_id, self.entry_id) {
+            (Some(problem_id), _) => Target::Problem(problem_id),
+            // a comment is on either a problem or an entry
+            (_, entry_id) => Target::Entry(entry_id.unwrap_or_default()),
+        }
+    }
+
+    // where the thread of the comment is read
+    pub fn path(&self) -> String {
+        match (self.problem_id, self.entry_id, self.share_token) {
+            (Some(problem_id), _, _) => format!("problems/{}/comments", problem_id),
+            (_, Some(entry_id), Some(token)) => {
+                format!("shared/collections/{}/entries/{}/comments", token, entry_id)
+            }
+            _ => "comments".to_string(),
+        }
+    }
+}
+
+// A user mentioned in a comment, who is told about it by mail.
+#[derive(Debug, sqlx::FromRow, PartialEq)]
+pub struct Recipient {
+    pub user_name: String,
+    pub email: String,
+}
+
+#[derive(Debug, sqlx::FromRow, PartialEq)]
+pub struct CommentRow {
+    pub id: i32,
+    pub parent_id: Option<i32>,
+    // absent once the account of the author is deleted
+    pub author: Option<String>,
+    pub body: Option<String>,
+    pub body_html: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub deleted: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Comment {
+    pub id: i32,
+    pub parent_id: Option<i32>,
+    pub author: Option<String>,
+    pub body: Option<String>,
+    // the body rendered into sanitized HTML
+    pub body_html: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub deleted: bool,
+    // the replies in the order they were written
+    pub replies: Vec<Comment>,
+}
+
+impl From<CommentRow> for Comment {
+    fn from(row: CommentRow) -> Comment {
+        Comment {
+            id: row.id,
+            parent_id: row.parent_id,
+            author: row.author,
+            body: row.body,
+            body_html: row.body_html,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+            deleted: row.deleted,
+            replies: Vec::new(),
+        }
+    }
+}
+
+impl Comment {
+    // Nest the comments of a target, given in the order they were written, into threads.
+    pub fn threads(rows: Vec<CommentRow>) -> Vec<Comment> {
+        let mut replies: HashMap<i32, Vec<Comment>> = HashMap::new();
+        let mut roots: Vec<Comment> = Vec::new();
+        // the replies are written after what they reply to, so the deepest are nested first
+        for row in rows.into_iter().rev() {
+            let mut comment = Comment::from(row);
+            if let Some(mut nested) = replies.remove(&comment.id) {
+                nested.reverse();
+                comment.replies = nested;
+            }
+            match comment.parent_id {
+                Some(parent_id) => replies.entry(parent_id).or_default().push(comment),
+                None => roots.push(comment),
+            }
+        }
+        roots.reverse();
+
+        roots
+    }
+}
+
+// The user names mentioned in a body, each once, in the order they first appear.
+pub fn mentions(body: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for caps in RE_MENTION.captures_iter(body) {
+        let name = caps[1].to_string();
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn mentions_test() {
+        assert_eq!(
+            vec!["alice", "bob.s", "carol_1"],
+            mentions("@alice, see the editorial. cc @bob.s.\n@carol_1 @alice")
+        );
+        assert!(mentions("mail test@gmail.com or @@alice").is_empty());
+    }
+
+    #[test]
+    fn validate_mentions_test() {
+        let names: Vec<String> = (0..MAX_MENTIONS).map(|i| format!("@user{}", i)).collect();
+        let mut comment = NewComment {
+            // mentioning the same user again does not count
+            body: format!("{} @user0", names.join(" ")),
+            parent_id: None,
+        };
+        assert!(comment.validate().is_ok());
+        comment.body.push_str(" @one_more");
+        assert!(comment.validate().is_err());
+        let edit = EditComment {
+            body: comment.body.clone(),
+        };
+        assert!(edit.validate().is_err());
+    }
+
+    #[test]
+    fn threads_test() {
+        let row = |id: i32, parent_id: Option<i32>| CommentRow {
+            id,
+            parent_id,
+            author: Some("test_user".to_string()),
+            body: Some(format!("comment {}", id)),
+            body_html: Some(format!("<p>comment {}</p>\n", id)),
+            created_at: Utc.ymd(2021, 5, 20).and_hms(8, 40, id as u32),
+            updated_at: None,
+            deleted: false,
+        };
+        let rows = vec![
+            row(1, None),
+            row(2, Some(1)),
+            row(3, None),
+            row(4, Some(2)),
+            row(5, Some(1)),
+        ];
+        let threads = Comment::threads(rows);
+        assert_eq!(
+            vec![1, 3],
+            threads.iter().map(|c| c.id).collect::<Vec<i32>>()
+        );
+        assert_eq!(
+            vec![2, 5],
+            threads[0]
+                .replies
+                .iter()
+                .map(|c| c.id)
+                .collect::<Vec<i32>>()
+        );
+        assert_eq!(4, threads[0].replies[0].replies[0].id);
+        assert!(threads[1].replies.is_empty());
+    }
+}
@@ -1,3 +1,4 @@
+use bcrypt::DEFAULT_COST;
 use std::env;
 
 #[derive(Debug, PartialEq, Eq)]
@@ -6,19 +7,45 @@ pub struct Config {
     pub smtp_username: String,
     pub smtp_password: String,
     pub mailer: String,
+    pub password_min_length: usize,
+    pub password_min_entropy_bits: u32,
+    pub bcrypt_cost: u32,
+    pub admin_token: Option<String>,
 }
 
+const DEFAULT_PASSWORD_MIN_LENGTH: usize = 8;
+const DEFAULT_PASSWORD_MIN_ENTROPY_BITS: u32 = 40;
+
 impl Config {
     pub fn new() -> Config {
         let database_url = env::var("DATABASE_URL").unwrap();
         let smtp_username = env::var("SMTP_USERNAME").unwrap();
         let smtp_password = env::var("SMTP_PASSWORD").unwrap();
         let mailer = env::var("MAILER").unwrap();
+        // optional settings fall back to the defaults
+        let password_min_length = env::var("PASSWORD_MIN_LENGTH")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_PASSWORD_MIN_LENGTH);
+        let password_min_entropy_bits = env::var("PASSWORD_MIN_ENTROPY_BITS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_PASSWORD_MIN_ENTROPY_BITS);
+        let bcrypt_cost = env::var("BCRYPT_COST")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_COST);
+        // the admin endpoints are disabled unless a token is set
+        let admin_token = env::var("ADMIN_TOKEN").ok();
         Config {
             database_url,
             smtp_username,
             smtp_password,
             mailer,
+            password_min_length,
+            password_min_entropy_bits,
+            bcrypt_cost,
+            admin_token,
         }
     }
 }
@@ -34,6 +61,10 @@ mod tests {
             smtp_username: "dummy_username".to_string(),
             smtp_password: "dummy_password".to_string(),
             mailer: "dummy_mailer".to_string(),
+            password_min_length: 8,
+            password_min_entropy_bits: 40,
+            bcrypt_cost: 12,
+            admin_token: Some("dummy_admin_token".to_string()),
         };
         let actual = Config::new();
         assert_eq!(expected, actual);
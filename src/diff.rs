@@ -0,0 +1,27 @@
+use similar::TextDiff;
+
+// the lines of context around each change
+const CONTEXT_LINES: usize = 3;
+
+// A unified diff between two texts, empty when they are the same.
+pub fn unified(old: &str, new: &str, old_label: &str, new_label: &str) -> String {
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(CONTEXT_LINES)
+        .header(old_label, new_label)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unified_test() {
+        assert_eq!(
+            "--- old\n+++ new\n@@ -1,2 +1,2 @@\n a\n-b\n+c\n",
+            unified("a\nb\n", "a\nc\n", "old", "new")
+        );
+        assert_eq!("", unified("a\n", "a\n", "old", "new"));
+    }
+}
@@ -0,0 +1,116 @@
+use super::infrastructures;
+use crate::error::ApiError;
+use actix_web::{get, post, web, HttpResponse};
+use anyhow::Result;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// Opened from the link in the digest, so the token is the only credential.
+// Only a page confirming the choice is shown, as mail scanners and previews follow links.
+#[get("/unsubscribe-digest/{token}")]
+pub async fn confirm_unsubscribe(
+    pool: web::Data<PgPool>,
+    web::Path(token): web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    match infrastructures::is_known_token(pool.get_ref(), &token).await {
+        Ok(true) => Ok(HttpResponse::Ok()
+            .content_type("text/html; charset=utf-8")
+            .body(format!(
+                r#"<!DOCTYPE html>
+<html>
+<head><meta charset="utf-8"><title>Unsubscribe</title></head>
+<body>
+<p>Stop the digests of the reviews due?</p>
+<form method="post" action="/unsubscribe-digest/{}"><button type="submit">Unsubscribe</button></form>
+</body>
+</html>
+"#,
+                token
+            ))),
+        Ok(false) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+// Posted by the confirmation page.
+#[post("/unsubscribe-digest/{token}")]
+pub async fn unsubscribe(
+    pool: web::Data<PgPool>,
+    web::Path(token): web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    match infrastructures::unsubscribe(pool.get_ref(), &token).await {
+        Ok(true) => Ok(HttpResponse::Ok().json("")),
+        Ok(false) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config, users, utils};
+    use actix_web::{http::Method, test, App};
+
+    #[actix_rt::test]
+    async fn unsubscribe_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .data(pool.clone())
+                .service(confirm_unsubscribe)
+                .service(unsubscribe),
+        )
+        .await;
+        let uid = Uuid::new_v4();
+        let token = Uuid::new_v4();
+        sqlx::query(r#"INSERT INTO preferences (uid, email_digest) VALUES ($1, 'daily')"#)
+            .bind(uid)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query(r#"INSERT INTO email_digests (uid, token) VALUES ($1, $2)"#)
+            .bind(uid)
+            .bind(token)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        // following the link only shows the confirmation
+        let req = test::TestRequest::get()
+            .uri(&format!("/unsubscribe-digest/{}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let body = test::read_body(resp).await;
+        assert!(String::from_utf8(body.to_vec()).unwrap().contains(&format!(
+            r#"<form method="post" action="/unsubscribe-digest/{}">"#,
+            token
+        )));
+        let preferences = users::infrastructures::find_preferences(&pool, &uid)
+            .await
+            .unwrap();
+        assert_eq!("daily", preferences.email_digest);
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/unsubscribe-digest/{}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let preferences = users::infrastructures::find_preferences(&pool, &uid)
+            .await
+            .unwrap();
+        assert_eq!("off", preferences.email_digest);
+
+        for method in &[Method::GET, Method::POST] {
+            let req = test::TestRequest::default()
+                .method(method.clone())
+                .uri(&format!("/unsubscribe-digest/{}", Uuid::new_v4()))
+                .to_request();
+            let resp = test::call_service(&mut app, req).await;
+            assert_eq!(404, resp.status());
+        }
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+}
@@ -0,0 +1,298 @@
+use super::model::{DigestReview, Period, Subscriber};
+use crate::mail;
+use anyhow::Result;
+use chrono::Utc;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// The users who opted in to the digest, unless their account is being deleted
+// or their last failure is to be retried later.
+pub async fn find_subscribers(pool: &PgPool) -> Result<Vec<Subscriber>> {
+    let subscribers = sqlx::query_as::<_, Subscriber>(
+        r#"SELECT u.uid, u.user_name, u.email, p.email_digest AS frequency, p.timezone, p.digest_hour,
+        (NOW() AT TIME ZONE p.timezone) AS local_now, d.last_sent_on, COALESCE(d.attempts, 0) AS attempts
+        FROM preferences p
+        JOIN users u ON u.uid = p.uid
+        LEFT JOIN email_digests d ON d.uid = p.uid
+        WHERE p.email_digest <> 'off'
+        AND (d.next_attempt_at IS NULL OR d.next_attempt_at <= NOW())
+        AND NOT EXISTS (SELECT 1 FROM account_deletions a WHERE a.uid = p.uid AND a.confirmed_at IS NOT NULL)
+        ORDER BY u.id"#,
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(subscribers)
+}
+
+// The reviews due before the end of the period, the overdue ones included.
+pub async fn find_digest_reviews(
+    pool: &PgPool,
+    uid: &Uuid,
+    tz: &str,
+    until: chrono::NaiveDate,
+) -> Result<Vec<DigestReview>> {
+    let reviews = sqlx::query_as::<_, DigestReview>(
+        r#"SELECT r.id, r.problem_name, r.url, (s.due_at AT TIME ZONE $2)::DATE AS due_on
+        FROM reviews r
+        JOIN schedules s ON s.review_id = r.id
+        WHERE r.uid = $1 AND s.due_at < ($3::TIMESTAMP AT TIME ZONE $2)
+        ORDER BY s.due_at, r.id"#,
+    )
+    .bind(uid)
+    .bind(tz)
+    .bind(until)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(reviews)
+}
+
+// Record the digest of the period as sent and queue it in a single transaction.
+// Returns whether it was queued: not if it had been sent already, nor when nothing is due.
+pub async fn send_digest(pool: &PgPool, subscriber: &Subscriber, period: &Period) -> Result<bool> {
+    let reviews =
+        find_digest_reviews(pool, &subscriber.uid, &subscriber.timezone, period.until).await?;
+    let mut tx = pool.begin().await?;
+    // the condition makes a concurrent or repeated run find nothing left to send
+    let token: Option<(Uuid,)> = sqlx::query_as(
+        r#"INSERT INTO email_digests (uid, token, last_sent_on) VALUES ($1, $2, $3)
+        ON CONFLICT (uid) DO UPDATE SET last_sent_on = $3, attempts = 0, next_attempt_at = NULL, last_error = NULL
+        WHERE email_digests.last_sent_on IS NULL OR email_digests.last_sent_on < $3
+        RETURNING token"#,
+    )
+    .bind(subscriber.uid)
+    .bind(Uuid::new_v4())
+    .bind(period.start)
+    .fetch_optional(&mut tx)
+    .await?;
+    let token = match token {
+        Some((token,)) => token,
+        None => return Ok(false),
+    };
+    if reviews.is_empty() {
+        tx.commit().await?;
+        return Ok(false);
+    }
+    let digest = mail::templates::digest(
+        &subscriber.user_name,
+        &subscriber.email,
+        &subscriber.frequency,
+        period,
+        &reviews,
+        &token,
+    );
+    mail::infrastructures::enqueue(&mut tx, &digest).await?;
+    tx.commit().await?;
+
+    Ok(true)
+}
+
+// Queue the digests which are due and return how many of them were queued.
+pub async fn send_due_digests(pool: &PgPool) -> Result<usize> {
+    let mut sent = 0;
+    for subscriber in find_subscribers(pool).await? {
+        if let Some(period) = subscriber.due_period() {
+            // a failed digest is retried later and must not hold up the others
+            match send_digest(pool, &subscriber, &period).await {
+                Ok(true) => sent += 1,
+                Ok(false) => (),
+                Err(e) => mark_failed(pool, &subscriber, &e.to_string()).await?,
+            }
+        }
+    }
+
+    Ok(sent)
+}
+
+// Schedule the next attempt with the backoff of the mails. The digest is retried until it is sent,
+// as the backoff is capped.
+pub async fn mark_failed(pool: &PgPool, subscriber: &Subscriber, error: &str) -> Result<()> {
+    let attempts = subscriber.attempts + 1;
+    let next_attempt_at = Utc::now() + mail::infrastructures::backoff(attempts);
+    sqlx::query(
+        r#"INSERT INTO email_digests (uid, token, attempts, next_attempt_at, last_error) VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (uid) DO UPDATE SET attempts = $3, next_attempt_at = $4, last_error = $5"#,
+    )
+    .bind(subscriber.uid)
+    .bind(Uuid::new_v4())
+    .bind(attempts)
+    .bind(next_attempt_at)
+    .bind(error)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn is_known_token(pool: &PgPool, token: &Uuid) -> Result<bool> {
+    let (known,): (bool,) =
+        sqlx::query_as("SELECT EXISTS (SELECT 1 FROM email_digests WHERE token = $1)")
+            .bind(token)
+            .fetch_one(pool)
+            .await?;
+
+    Ok(known)
+}
+
+// Turn the digest off for the owner of the token. Returns whether the token is known.
+pub async fn unsubscribe(pool: &PgPool, token: &Uuid) -> Result<bool> {
+    let result = sqlx::query(
+        r#"UPDATE preferences SET email_digest = 'off'
+        WHERE uid = (SELECT uid FROM email_digests WHERE token = $1)"#,
+    )
+    .bind(token)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config, utils};
+    use chrono::{Duration, Utc};
+
+    async fn subscribe(pool: &PgPool, id: i32, uid: &Uuid, frequency: &str, digest_hour: i16) {
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES ($1, $2, 'password', $3, $4)"#)
+			.bind(id)
+			.bind(format!("test_user{}", id))
+			.bind(format!("test{}@gmail.com", id))
+			.bind(uid)
+			.execute(pool)
+			.await
+			.unwrap();
+        sqlx::query(
+            r#"INSERT INTO preferences (uid, email_digest, digest_hour) VALUES ($1, $2, $3)"#,
+        )
+        .bind(uid)
+        .bind(frequency)
+        .bind(digest_hour)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn send_due_digests_once() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        subscribe(&pool, 0, &uid, "daily", 0).await;
+        // a user who is past the hour but has nothing due
+        subscribe(&pool, 1, &Uuid::new_v4(), "daily", 0).await;
+        // and one whose hour never comes
+        subscribe(&pool, 2, &Uuid::new_v4(), "daily", 24).await;
+        let now = Utc::now();
+        for (id, name, due_at) in [
+            (0, "overdue_prob", now - Duration::days(2)),
+            (1, "later_prob", now + Duration::days(3)),
+        ]
+        .iter()
+        {
+            sqlx::query(r#"INSERT INTO reviews (id, problem_name, url, memo, uid, platform, created_at) VALUES ($1, $2, 'test_url', 'test_memo', $3, 1, now())"#)
+				.bind(id)
+				.bind(name)
+				.bind(uid)
+				.execute(&pool)
+				.await
+				.unwrap();
+            sqlx::query(r#"INSERT INTO schedules (review_id, due_at, interval_days, ease, repetitions) VALUES ($1, $2, 1, 2.5, 0)"#)
+				.bind(id)
+				.bind(due_at)
+				.execute(&pool)
+				.await
+				.unwrap();
+        }
+
+        assert_eq!(1, send_due_digests(&pool).await.unwrap());
+        // a restart runs the job again within the same day
+        assert_eq!(0, send_due_digests(&pool).await.unwrap());
+
+        let bodies: Vec<(String,)> = sqlx::query_as("SELECT body FROM email_outbox")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(1, bodies.len());
+        assert!(bodies[0].0.contains("overdue_prob"));
+        assert!(!bodies[0].0.contains("later_prob"));
+        let marked: Vec<(Uuid,)> =
+            sqlx::query_as("SELECT uid FROM email_digests WHERE last_sent_on IS NOT NULL")
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert_eq!(2, marked.len());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn retry_failed_digest() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        subscribe(&pool, 0, &uid, "daily", 0).await;
+        let subscriber = find_subscribers(&pool).await.unwrap().remove(0);
+        assert_eq!(0, subscriber.attempts);
+
+        mark_failed(&pool, &subscriber, "connection reset")
+            .await
+            .unwrap();
+
+        // the subscriber is left out until the next attempt is due
+        assert!(find_subscribers(&pool).await.unwrap().is_empty());
+        let (attempts, last_error): (i32, Option<String>) =
+            sqlx::query_as("SELECT attempts, last_error FROM email_digests WHERE uid = $1")
+                .bind(uid)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(1, attempts);
+        assert_eq!(Some("connection reset".to_string()), last_error);
+
+        // once due again, the digest sent clears the failures
+        sqlx::query("UPDATE email_digests SET next_attempt_at = NOW() - INTERVAL '1 second'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let subscriber = find_subscribers(&pool).await.unwrap().remove(0);
+        assert_eq!(1, subscriber.attempts);
+        let period = subscriber.due_period().unwrap();
+        send_digest(&pool, &subscriber, &period).await.unwrap();
+        let (attempts, last_error): (i32, Option<String>) =
+            sqlx::query_as("SELECT attempts, last_error FROM email_digests WHERE uid = $1")
+                .bind(uid)
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(0, attempts);
+        assert_eq!(None, last_error);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn unsubscribe_test() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        let token = Uuid::new_v4();
+        subscribe(&pool, 0, &uid, "weekly", 8).await;
+        sqlx::query(r#"INSERT INTO email_digests (uid, token) VALUES ($1, $2)"#)
+            .bind(uid)
+            .bind(token)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert!(!unsubscribe(&pool, &Uuid::new_v4()).await.unwrap());
+        assert!(unsubscribe(&pool, &token).await.unwrap());
+        let preferences = crate::users::infrastructures::find_preferences(&pool, &uid)
+            .await
+            .unwrap();
+        assert_eq!("off", preferences.email_digest);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+}
@@ -0,0 +1,148 @@
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Timelike};
+
+// A user who opted in to the digest, with the current time in the time zone of the user.
+#[derive(Debug, sqlx::FromRow, PartialEq)]
+pub struct Subscriber {
+    pub uid: uuid::Uuid,
+    pub user_name: String,
+    pub email: String,
+    // daily or weekly
+    pub frequency: String,
+    pub timezone: String,
+    pub digest_hour: i16,
+    pub local_now: NaiveDateTime,
+    pub last_sent_on: Option<NaiveDate>,
+    // the failures since the last digest sent
+    pub attempts: i32,
+}
+
+// The days a digest covers, from the day it is sent to the day before `until`.
+#[derive(Debug, PartialEq)]
+pub struct Period {
+    pub start: NaiveDate,
+    pub until: NaiveDate,
+}
+
+impl Subscriber {
+    // The period the digest is due for, unless it has been sent for it already.
+    // A daily digest covers the day and a weekly one the week from Monday.
+    // The digest missed while the server was down is sent late rather than skipped.
+    pub fn due_period(&self) -> Option<Period> {
+        let today = self.local_now.date();
+        let period = match self.frequency.as_str() {
+            "daily" => Period {
+                start: today,
+                until: today + Duration::days(1),
+            },
+            "weekly" => {
+                let start = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+                Period {
+                    start,
+                    until: start + Duration::days(7),
+                }
+            }
+            _ => return None,
+        };
+        if matches!(self.last_sent_on, Some(sent) if sent >= period.start) {
+            return None;
+        }
+        if today == period.start && (self.local_now.hour() as i16) < self.digest_hour {
+            return None;
+        }
+
+        Some(period)
+    }
+}
+
+#[derive(Debug, sqlx::FromRow, PartialEq)]
+pub struct DigestReview {
+    pub id: i32,
+    pub problem_name: String,
+    pub url: String,
+    // the local date it is due on
+    pub due_on: NaiveDate,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subscriber(
+        frequency: &str,
+        local_now: NaiveDateTime,
+        last: Option<NaiveDate>,
+    ) -> Subscriber {
+        Subscriber {
+            uid: uuid::Uuid::nil(),
+            user_name: "test_user".to_string(),
+            email: "test@gmail.com".to_string(),
+            frequency: frequency.to_string(),
+            timezone: "UTC".to_string(),
+            digest_hour: 8,
+            local_now,
+            last_sent_on: last,
+            attempts: 0,
+        }
+    }
+
+    #[test]
+    fn daily_period() {
+        // 2021-05-19 is a Wednesday
+        let day = NaiveDate::from_ymd(2021, 5, 19);
+        let period = Period {
+            start: day,
+            until: NaiveDate::from_ymd(2021, 5, 20),
+        };
+        assert_eq!(
+            None,
+            subscriber("daily", day.and_hms(7, 59, 0), None).due_period()
+        );
+        assert_eq!(
+            Some(period),
+            subscriber("daily", day.and_hms(8, 0, 0), Some(day.pred())).due_period()
+        );
+        assert_eq!(
+            None,
+            subscriber("daily", day.and_hms(9, 0, 0), Some(day)).due_period()
+        );
+        assert_eq!(
+            None,
+            subscriber("off", day.and_hms(9, 0, 0), None).due_period()
+        );
+    }
+
+    #[test]
+    fn weekly_period() {
+        let monday = NaiveDate::from_ymd(2021, 5, 17);
+        let period = Period {
+            start: monday,
+            until: NaiveDate::from_ymd(2021, 5, 24),
+        };
+        assert_eq!(
+            None,
+            subscriber("weekly", monday.and_hms(7, 0, 0), None).due_period()
+        );
+        assert_eq!(
+            Some(Period {
+                start: monday,
+                until: NaiveDate::from_ymd(2021, 5, 24),
+            }),
+            subscriber("weekly", monday.and_hms(8, 0, 0), None).due_period()
+        );
+        // sent late on Wednesday when the digest of Monday was missed, whatever the hour
+        let wednesday = NaiveDate::from_ymd(2021, 5, 19);
+        assert_eq!(
+            Some(period),
+            subscriber(
+                "weekly",
+                wednesday.and_hms(1, 0, 0),
+                Some(monday - Duration::days(7))
+            )
+            .due_period()
+        );
+        assert_eq!(
+            None,
+            subscriber("weekly", wednesday.and_hms(9, 0, 0), Some(monday)).due_period()
+        );
+    }
+}
@@ -1,12 +1,14 @@
 use actix_web::{error, http::StatusCode, HttpResponse};
 use derive_more::{Display, Error};
 use serde::Serialize;
-use validator::ValidationErrors;
+use validator::{ValidationErrors, ValidationErrorsKind};
 
 #[derive(Serialize)]
 struct ErrorResponse {
     code: u16,
     message: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    reasons: Vec<String>,
 }
 
 // TODO remove the attribute
@@ -19,19 +21,39 @@ pub enum ApiError {
     #[display(fmt = "bad request")]
     BadRequest,
 
+    #[display(fmt = "unauthorized")]
+    Unauthorized,
+
+    #[display(fmt = "forbidden")]
+    Forbidden,
+
+    #[display(fmt = "not found")]
+    NotFound,
+
+    #[display(fmt = "conflict")]
+    Conflict,
+
     #[display(fmt = "timeout")]
     Timeout,
 
     #[display(fmt = "validation error on field: {:?}", fields)]
-    ValidationError { fields: Vec<String> },
+    ValidationError {
+        fields: Vec<String>,
+        reasons: Vec<String>,
+    },
 }
 
 impl error::ResponseError for ApiError {
     fn error_response(&self) -> HttpResponse {
         let status_code = self.status_code();
+        let reasons = match self {
+            ApiError::ValidationError { reasons, .. } => reasons.clone(),
+            _ => Vec::new(),
+        };
         let error_response = ErrorResponse {
             code: status_code.as_u16(),
             message: self.to_string(),
+            reasons,
         };
         HttpResponse::build(self.status_code()).json(error_response)
     }
@@ -40,12 +62,26 @@ impl error::ResponseError for ApiError {
         match *self {
             ApiError::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
             ApiError::BadRequest => StatusCode::BAD_REQUEST,
+            ApiError::Unauthorized => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden => StatusCode::FORBIDDEN,
+            ApiError::NotFound => StatusCode::NOT_FOUND,
+            ApiError::Conflict => StatusCode::CONFLICT,
             ApiError::Timeout => StatusCode::GATEWAY_TIMEOUT,
             ApiError::ValidationError { .. } => StatusCode::BAD_REQUEST,
         }
     }
 }
 
+impl From<ValidationErrors> for ApiError {
+    fn from(err: ValidationErrors) -> ApiError {
+        let reasons = extract_reasons(&err);
+        ApiError::ValidationError {
+            fields: extract_field(err),
+            reasons,
+        }
+    }
+}
+
 pub fn extract_field(err: ValidationErrors) -> Vec<String> {
     let mut fields: Vec<String> = Vec::new();
     for key in err.into_errors().keys() {
@@ -54,3 +90,19 @@ pub fn extract_field(err: ValidationErrors) -> Vec<String> {
 
     fields
 }
+
+// Collect the failure reasons attached by custom validations, e.g. the password policy.
+pub fn extract_reasons(err: &ValidationErrors) -> Vec<String> {
+    let mut reasons: Vec<String> = Vec::new();
+    for kind in err.errors().values() {
+        if let ValidationErrorsKind::Field(errors) = kind {
+            for error in errors {
+                if let Some(serde_json::Value::Array(values)) = error.params.get("reasons") {
+                    reasons.extend(values.iter().filter_map(|v| v.as_str().map(String::from)));
+                }
+            }
+        }
+    }
+
+    reasons
+}
@@ -0,0 +1,537 @@
+use super::infrastructures;
+use super::model::{NewGroup, NewInvitation, NewProblemSet, NewSetProblem};
+use crate::auth::AuthenticatedUser;
+use crate::error::ApiError;
+use crate::mail::templates;
+use crate::problems::model::ProblemKey;
+use crate::users;
+use actix_web::{delete, get, post, web, HttpResponse};
+use anyhow::Result;
+use sqlx::PgPool;
+use uuid::Uuid;
+use validator::Validate;
+
+// Only the members see a group; returns whether the user owns it.
+async fn check_member(pool: &PgPool, uid: &Uuid, group_id: i32) -> Result<bool, ApiError> {
+    match infrastructures::find_membership(pool, uid, group_id).await {
+        Ok(Some(owner)) => Ok(owner),
+        Ok(None) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+// Only the owner manages the members and the problem sets of a group.
+async fn check_owner(pool: &PgPool, uid: &Uuid, group_id: i32) -> Result<(), ApiError> {
+    match check_member(pool, uid, group_id).await? {
+        true => Ok(()),
+        false => Err(ApiError::Forbidden),
+    }
+}
+
+async fn check_problem_set(pool: &PgPool, group_id: i32, id: i32) -> Result<(), ApiError> {
+    match infrastructures::find_problem_set(pool, group_id, id).await {
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[post("/groups")]
+pub async fn create_group(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    form: web::Form<NewGroup>,
+) -> Result<HttpResponse, ApiError> {
+    match form.validate() {
+        Ok(_) => (),
+        Err(e) => return Err(e.into()),
+    }
+
+    match infrastructures::create_group(pool.get_ref(), &user.uid, &form).await {
+        Ok(group) => Ok(HttpResponse::Created().json(group)),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[get("/groups")]
+pub async fn list_groups(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, ApiError> {
+    match infrastructures::find_groups(pool.get_ref(), &user.uid).await {
+        Ok(groups) => Ok(HttpResponse::Ok().json(groups)),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[get("/groups/{id:\\d+}")]
+pub async fn get_group(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path(id): web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    check_member(pool.get_ref(), &user.uid, id).await?;
+
+    match infrastructures::find_group(pool.get_ref(), id).await {
+        Ok(Some(group)) => Ok(HttpResponse::Ok().json(group)),
+        Ok(None) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[delete("/groups/{id:\\d+}")]
+pub async fn delete_group(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path(id): web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    check_owner(pool.get_ref(), &user.uid, id).await?;
+
+    match infrastructures::delete_group(pool.get_ref(), &user.uid, id).await {
+        Ok(true) => Ok(HttpResponse::Ok().json("")),
+        Ok(false) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[get("/groups/{id:\\d+}/members")]
+pub async fn list_members(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path(id): web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    check_member(pool.get_ref(), &user.uid, id).await?;
+
+    match infrastructures::find_members(pool.get_ref(), id).await {
+        Ok(members) => Ok(HttpResponse::Ok().json(members)),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+// The owner removes a member, a member leaves the group. The owner deletes the group instead.
+#[delete("/groups/{id:\\d+}/members/{user_name}")]
+pub async fn remove_member(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path((id, user_name)): web::Path<(i32, String)>,
+) -> Result<HttpResponse, ApiError> {
+    let owner = check_member(pool.get_ref(), &user.uid, id).await?;
+    let uid = match users::infrastructures::find_uid_by_name(pool.get_ref(), &user_name).await {
+        Ok(Some(uid)) => uid,
+        Ok(None) => return Err(ApiError::NotFound),
+        Err(_) => return Err(ApiError::InternalError),
+    };
+    match (owner, uid == user.uid) {
+        (true, true) => return Err(ApiError::BadRequest),
+        (false, false) => return Err(ApiError::Forbidden),
+        _ => (),
+    }
+
+    match infrastructures::remove_member(pool.get_ref(), id, &uid).await {
+        Ok(true) => Ok(HttpResponse::Ok().json("")),
+        Ok(false) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[post("/groups/{id:\\d+}/invitations")]
+pub async fn invite_member(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path(id): web::Path<i32>,
+    form: web::Form<NewInvitation>,
+) -> Result<HttpResponse, ApiError> {
+    match form.validate() {
+        Ok(_) => (),
+        Err(e) => return Err(e.into()),
+    }
+    check_owner(pool.get_ref(), &user.uid, id).await?;
+    let group = match infrastructures::find_group(pool.get_ref(), id).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return Err(ApiError::NotFound),
+        Err(_) => return Err(ApiError::InternalError),
+    };
+
+    // the mail is sent by the background worker
+    let token = Uuid::new_v4();
+    let invitation_mail =
+        templates::group_invitation(&group.owner, &group.name, &form.email, &token);
+    let invitation = match infrastructures::create_invitation(
+        pool.get_ref(),
+        id,
+        &form.email,
+        &token,
+        &invitation_mail,
+    )
+    .await
+    {
+        Ok(Some(invitation)) => invitation,
+        Ok(None) => return Err(ApiError::Conflict),
+        Err(_) => return Err(ApiError::InternalError),
+    };
+
+    Ok(HttpResponse::Created().json(invitation))
+}
+
+#[get("/groups/{id:\\d+}/invitations")]
+pub async fn list_invitations(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path(id): web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    check_owner(pool.get_ref(), &user.uid, id).await?;
+
+    match infrastructures::find_invitations(pool.get_ref(), id).await {
+        Ok(invitations) => Ok(HttpResponse::Ok().json(invitations)),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+// The link of the invitation mail, followed once signed in with the address invited.
+#[post("/join-group/{token}")]
+pub async fn join_group(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path(token): web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let id = match infrastructures::accept_invitation(pool.get_ref(), &user.uid, &token).await {
+        Ok(Some(id)) => id,
+        Ok(None) => return Err(ApiError::NotFound),
+        Err(_) => return Err(ApiError::InternalError),
+    };
+
+    match infrastructures::find_group(pool.get_ref(), id).await {
+        Ok(Some(group)) => Ok(HttpResponse::Ok().json(group)),
+        Ok(None) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[post("/groups/{id:\\d+}/problem-sets")]
+pub async fn create_problem_set(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path(id): web::Path<i32>,
+    form: web::Form<NewProblemSet>,
+) -> Result<HttpResponse, ApiError> {
+    match form.validate() {
+        Ok(_) => (),
+        Err(e) => return Err(e.into()),
+    }
+    check_owner(pool.get_ref(), &user.uid, id).await?;
+
+    match infrastructures::create_problem_set(pool.get_ref(), id, &form).await {
+        Ok(problem_set) => Ok(HttpResponse::Created().json(problem_set)),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[get("/groups/{id:\\d+}/problem-sets")]
+pub async fn list_problem_sets(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path(id): web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    check_member(pool.get_ref(), &user.uid, id).await?;
+
+    match infrastructures::find_problem_sets(pool.get_ref(), id).await {
+        Ok(problem_sets) => Ok(HttpResponse::Ok().json(problem_sets)),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[get("/groups/{id:\\d+}/problem-sets/{set_id:\\d+}")]
+pub async fn get_problem_set(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path((id, set_id)): web::Path<(i32, i32)>,
+) -> Result<HttpResponse, ApiError> {
+    check_member(pool.get_ref(), &user.uid, id).await?;
+
+    match infrastructures::find_problem_set(pool.get_ref(), id, set_id).await {
+        Ok(Some(problem_set)) => Ok(HttpResponse::Ok().json(problem_set)),
+        Ok(None) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+// The reviews imported from the set stay with the members.
+#[delete("/groups/{id:\\d+}/problem-sets/{set_id:\\d+}")]
+pub async fn delete_problem_set(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path((id, set_id)): web::Path<(i32, i32)>,
+) -> Result<HttpResponse, ApiError> {
+    check_owner(pool.get_ref(), &user.uid, id).await?;
+
+    match infrastructures::delete_problem_set(pool.get_ref(), id, set_id).await {
+        Ok(true) => Ok(HttpResponse::Ok().json("")),
+        Ok(false) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[get("/groups/{id:\\d+}/problem-sets/{set_id:\\d+}/problems")]
+pub async fn list_set_problems(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path((id, set_id)): web::Path<(i32, i32)>,
+) -> Result<HttpResponse, ApiError> {
+    check_member(pool.get_ref(), &user.uid, id).await?;
+    check_problem_set(pool.get_ref(), id, set_id).await?;
+
+    match infrastructures::find_set_problems(pool.get_ref(), set_id).await {
+        Ok(problems) => Ok(HttpResponse::Ok().json(problems)),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[post("/groups/{id:\\d+}/problem-sets/{set_id:\\d+}/problems")]
+pub async fn add_set_problem(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path((id, set_id)): web::Path<(i32, i32)>,
+    form: web::Form<NewSetProblem>,
+) -> Result<HttpResponse, ApiError> {
+    match form.validate() {
+        Ok(_) => (),
+        Err(e) => return Err(e.into()),
+    }
+    check_owner(pool.get_ref(), &user.uid, id).await?;
+    check_problem_set(pool.get_ref(), id, set_id).await?;
+
+    match infrastructures::add_set_problem(pool.get_ref(), set_id, &form).await {
+        Ok(Some(problem)) => Ok(HttpResponse::Created().json(problem)),
+        Ok(None) => Err(ApiError::Conflict),
+        // the problem is refused by its URL, should it pass the validation
+        Err(_) if ProblemKey::from_url(&form.url).is_none() => Err(ApiError::BadRequest),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[delete("/groups/{id:\\d+}/problem-sets/{set_id:\\d+}/problems/{problem_id:\\d+}")]
+pub async fn remove_set_problem(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path((id, set_id, problem_id)): web::Path<(i32, i32, i32)>,
+) -> Result<HttpResponse, ApiError> {
+    check_owner(pool.get_ref(), &user.uid, id).await?;
+    check_problem_set(pool.get_ref(), id, set_id).await?;
+
+    match infrastructures::remove_set_problem(pool.get_ref(), set_id, problem_id).await {
+        Ok(true) => Ok(HttpResponse::Ok().json("")),
+        Ok(false) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+// Every member imports the set into their own reviews, the owner included.
+#[post("/groups/{id:\\d+}/problem-sets/{set_id:\\d+}/import")]
+pub async fn import_problem_set(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path((id, set_id)): web::Path<(i32, i32)>,
+) -> Result<HttpResponse, ApiError> {
+    check_member(pool.get_ref(), &user.uid, id).await?;
+    check_problem_set(pool.get_ref(), id, set_id).await?;
+
+    match infrastructures::import_problem_set(pool.get_ref(), &user.uid, set_id).await {
+        Ok(import) => Ok(HttpResponse::Ok().json(import)),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[get("/groups/{id:\\d+}/problem-sets/{set_id:\\d+}/progress")]
+pub async fn get_progress(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path((id, set_id)): web::Path<(i32, i32)>,
+) -> Result<HttpResponse, ApiError> {
+    check_member(pool.get_ref(), &user.uid, id).await?;
+    check_problem_set(pool.get_ref(), id, set_id).await?;
+
+    match infrastructures::find_progress(pool.get_ref(), id, set_id).await {
+        Ok(progress) => Ok(HttpResponse::Ok().json(progress)),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::groups::model::{Group, Invitation, ProblemSet, SetImport, SetProgress};
+    use crate::{auth, config, utils};
+    use actix_web::{test, App};
+
+    #[actix_rt::test]
+    async fn groups_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .data(pool.clone())
+                .service(create_group)
+                .service(list_groups)
+                .service(get_group)
+                .service(delete_group)
+                .service(list_members)
+                .service(remove_member)
+                .service(invite_member)
+                .service(list_invitations)
+                .service(join_group)
+                .service(create_problem_set)
+                .service(list_problem_sets)
+                .service(get_problem_set)
+                .service(delete_problem_set)
+                .service(list_set_problems)
+                .service(add_set_problem)
+                .service(remove_set_problem)
+                .service(import_problem_set)
+                .service(get_progress),
+        )
+        .await;
+        let (owner, member) = (Uuid::new_v4(), Uuid::new_v4());
+        let owner_token = auth::infrastructures::create_session(&pool, &owner)
+            .await
+            .unwrap();
+        let member_token = auth::infrastructures::create_session(&pool, &member)
+            .await
+            .unwrap();
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'owner', 'password', 'owner@gmail.com', $1), (1, 'member', 'password', 'member@gmail.com', $2)"#)
+			.bind(owner)
+			.bind(member)
+			.execute(&pool)
+			.await
+			.unwrap();
+
+        let req = test::TestRequest::post()
+            .uri("/groups")
+            .header("Authorization", format!("Bearer {}", owner_token))
+            .set_form(&NewGroup {
+                name: "ICPC team".to_string(),
+                description: None,
+            })
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(201, resp.status());
+        let group: Group = test::read_body_json(resp).await;
+        // the group is hidden from the ones who are not members
+        let req = test::TestRequest::get()
+            .uri(&format!("/groups/{}", group.id))
+            .header("Authorization", format!("Bearer {}", member_token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(404, resp.status());
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/groups/{}/invitations", group.id))
+            .header("Authorization", format!("Bearer {}", owner_token))
+            .set_form(&NewInvitation {
+                email: "member@gmail.com".to_string(),
+            })
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(201, resp.status());
+        let _: Invitation = test::read_body_json(resp).await;
+        let (body,): (String,) =
+            sqlx::query_as("SELECT body FROM email_outbox WHERE recipient = 'member@gmail.com'")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        let link = body.rsplit("https://").next().unwrap();
+        let req = test::TestRequest::post()
+            .uri(&format!("/{}", link))
+            .header("Authorization", format!("Bearer {}", member_token))
+            .to_request();
+        let joined: Group = test::read_response_json(&mut app, req).await;
+        assert_eq!(2, joined.members);
+
+        // only the owner manages the problem sets
+        let new_problem_set = NewProblemSet {
+            name: "week 1".to_string(),
+            description: None,
+        };
+        let req = test::TestRequest::post()
+            .uri(&format!("/groups/{}/problem-sets", group.id))
+            .header("Authorization", format!("Bearer {}", member_token))
+            .set_form(&new_problem_set)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(403, resp.status());
+        let req = test::TestRequest::post()
+            .uri(&format!("/groups/{}/problem-sets", group.id))
+            .header("Authorization", format!("Bearer {}", owner_token))
+            .set_form(&new_problem_set)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(201, resp.status());
+        let problem_set: ProblemSet = test::read_body_json(resp).await;
+        let problems = format!(
+            "/groups/{}/problem-sets/{}/problems",
+            group.id, problem_set.id
+        );
+        let req = test::TestRequest::post()
+            .uri(&problems)
+            .header("Authorization", format!("Bearer {}", owner_token))
+            .set_form(&NewSetProblem {
+                url: "https://atcoder.jp/contests/abc200/tasks/abc200_a".to_string(),
+                title: "A - Century".to_string(),
+            })
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(201, resp.status());
+
+        let req = test::TestRequest::post()
+            .uri(&format!(
+                "/groups/{}/problem-sets/{}/import",
+                group.id, problem_set.id
+            ))
+            .header("Authorization", format!("Bearer {}", member_token))
+            .to_request();
+        let import: SetImport = test::read_response_json(&mut app, req).await;
+        assert_eq!(1, import.created.len());
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/groups/{}/problem-sets/{}/progress",
+                group.id, problem_set.id
+            ))
+            .header("Authorization", format!("Bearer {}", member_token))
+            .to_request();
+        let progress: SetProgress = test::read_response_json(&mut app, req).await;
+        assert_eq!(1, progress.problems.len());
+        assert_eq!(2, progress.members.len());
+        assert_eq!(
+            Some(import.created[0]),
+            progress.members[1].problems[0].review_id
+        );
+
+        // a member cannot remove another member, but can leave
+        let req = test::TestRequest::delete()
+            .uri(&format!("/groups/{}/members/owner", group.id))
+            .header("Authorization", format!("Bearer {}", member_token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(403, resp.status());
+        let req = test::TestRequest::delete()
+            .uri(&format!("/groups/{}/members/member", group.id))
+            .header("Authorization", format!("Bearer {}", member_token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let req = test::TestRequest::get()
+            .uri("/groups")
+            .header("Authorization", format!("Bearer {}", member_token))
+            .to_request();
+        let groups: Vec<Group> = test::read_response_json(&mut app, req).await;
+        assert!(groups.is_empty());
+
+        let req = test::TestRequest::delete()
+            .uri(&format!("/groups/{}", group.id))
+            .header("Authorization", format!("Bearer {}", owner_token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+}
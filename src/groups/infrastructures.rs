@@ -0,0 +1,678 @@
+use super::model::{
+    Group, Invitation, Member, NewGroup, NewProblemSet, NewSetProblem, ProblemSet, ProgressRow,
+    SetImport, SetProblem, SetProgress,
+};
+use crate::mail::{self, model::NewMail};
+use crate::problems::{self, model::ProblemKey};
+use crate::reviews::{self, model::NewReview};
+use crate::webhooks::model::EVENT_CREATED;
+use anyhow::Result;
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// how long an invitation can be accepted for
+const INVITATION_EXPIRY_DAYS: i64 = 7;
+
+// a group together with the user name of the owner and the number of members
+const SELECT_GROUPS: &str = r#"SELECT g.id, g.name, g.description, u.user_name AS owner,
+    (SELECT COUNT(*) FROM group_members m WHERE m.group_id = g.id) AS members, g.created_at
+    FROM study_groups g
+    JOIN users u ON u.uid = g.uid"#;
+
+// a problem set together with the number of its problems
+const SELECT_PROBLEM_SETS: &str = r#"SELECT s.id, s.name, s.description, COUNT(i.problem_id) AS problems, s.created_at
+    FROM problem_sets s
+    LEFT JOIN problem_set_items i ON i.problem_set_id = s.id"#;
+
+// The owner is the first member of the group.
+pub async fn create_group(pool: &PgPool, uid: &Uuid, group: &NewGroup) -> Result<Group> {
+    let now = Utc::now();
+    let mut tx = pool.begin().await?;
+    let (id,): (i32,) = sqlx::query_as(
+        r#"INSERT INTO study_groups (uid, name, description, created_at) VALUES ($1, $2, $3, $4) RETURNING id"#,
+    )
+    .bind(uid)
+    .bind(&group.name)
+    .bind(&group.description)
+    .bind(now)
+    .fetch_one(&mut tx)
+    .await?;
+    sqlx::query("INSERT INTO group_members (group_id, uid, joined_at) VALUES ($1, $2, $3)")
+        .bind(id)
+        .bind(uid)
+        .bind(now)
+        .execute(&mut tx)
+        .await?;
+    tx.commit().await?;
+    let created = find_group(pool, id).await?;
+    created.ok_or_else(|| anyhow::anyhow!("the created group {} is missing", id))
+}
+
+// The groups the user is a member of, the ones the user owns included.
+pub async fn find_groups(pool: &PgPool, uid: &Uuid) -> Result<Vec<Group>> {
+    let sql = format!(
+        "{} WHERE EXISTS (SELECT 1 FROM group_members m WHERE m.group_id = g.id AND m.uid = $1) ORDER BY g.id",
+        SELECT_GROUPS
+    );
+    let groups = sqlx::query_as::<_, Group>(&sql)
+        .bind(uid)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(groups)
+}
+
+pub async fn find_group(pool: &PgPool, id: i32) -> Result<Option<Group>> {
+    let sql = format!("{} WHERE g.id = $1", SELECT_GROUPS);
+    let group = sqlx::query_as::<_, Group>(&sql)
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(group)
+}
+
+// Whether the user owns the group, absent when the user is not a member.
+pub async fn find_membership(pool: &PgPool, uid: &Uuid, group_id: i32) -> Result<Option<bool>> {
+    let owner: Option<(bool,)> = sqlx::query_as(
+        r#"SELECT g.uid = m.uid FROM group_members m JOIN study_groups g ON g.id = m.group_id
+        WHERE m.group_id = $1 AND m.uid = $2"#,
+    )
+    .bind(group_id)
+    .bind(uid)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(owner.map(|(owner,)| owner))
+}
+
+// The members, the invitations and the problem sets go with the group; the reviews imported stay.
+pub async fn delete_group(pool: &PgPool, uid: &Uuid, id: i32) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM study_groups WHERE uid = $1 AND id = $2")
+        .bind(uid)
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// The members in the order they joined.
+pub async fn find_members(pool: &PgPool, group_id: i32) -> Result<Vec<Member>> {
+    let members = sqlx::query_as::<_, Member>(
+        r#"SELECT u.user_name, g.uid = m.uid AS owner, m.joined_at
+        FROM group_members m
+        JOIN study_groups g ON g.id = m.group_id
+        JOIN users u ON u.uid = m.uid
+        WHERE m.group_id = $1
+        ORDER BY m.joined_at, u.user_name"#,
+    )
+    .bind(group_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(members)
+}
+
+pub async fn remove_member(pool: &PgPool, group_id: i32, uid: &Uuid) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM group_members WHERE group_id = $1 AND uid = $2")
+        .bind(group_id)
+        .bind(uid)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// Register an invitation of the address together with its mail, absent when the address
+// is invited already or is the one of a member.
+pub async fn create_invitation(
+    pool: &PgPool,
+    group_id: i32,
+    email: &str,
+    token: &Uuid,
+    invitation_mail: &NewMail,
+) -> Result<Option<Invitation>> {
+    let now = Utc::now();
+    let mut tx = pool.begin().await?;
+    let invitation = sqlx::query_as::<_, Invitation>(
+        r#"INSERT INTO group_invitations (group_id, email, token, created_at)
+        SELECT $1, $2, $3, $4
+        WHERE NOT EXISTS (SELECT 1 FROM group_invitations i WHERE i.group_id = $1 AND LOWER(i.email) = LOWER($2)
+            AND i.accepted_at IS NULL AND i.created_at > $5)
+        AND NOT EXISTS (SELECT 1 FROM group_members m JOIN users u ON u.uid = m.uid
+            WHERE m.group_id = $1 AND LOWER(u.email) = LOWER($2))
+        RETURNING id, email, created_at, accepted_at"#,
+    )
+    .bind(group_id)
+    .bind(email)
+    .bind(token)
+    .bind(now)
+    .bind(now - Duration::days(INVITATION_EXPIRY_DAYS))
+    .fetch_optional(&mut tx)
+    .await?;
+    if invitation.is_some() {
+        mail::infrastructures::enqueue(&mut tx, invitation_mail).await?;
+    }
+    tx.commit().await?;
+
+    Ok(invitation)
+}
+
+pub async fn find_invitations(pool: &PgPool, group_id: i32) -> Result<Vec<Invitation>> {
+    let invitations = sqlx::query_as::<_, Invitation>(
+        r#"SELECT id, email, created_at, accepted_at FROM group_invitations
+        WHERE group_id = $1 ORDER BY id DESC"#,
+    )
+    .bind(group_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(invitations)
+}
+
+// Join the group of the invitation and return its id. Only the user of the address invited
+// can accept it, once and before it expires.
+pub async fn accept_invitation(pool: &PgPool, uid: &Uuid, token: &Uuid) -> Result<Option<i32>> {
+    let now = Utc::now();
+    let mut tx = pool.begin().await?;
+    let group_id: Option<(i32,)> = sqlx::query_as(
+        r#"UPDATE group_invitations i SET accepted_at = $1 FROM users u
+        WHERE i.token = $2 AND i.accepted_at IS NULL AND i.created_at > $3
+        AND u.uid = $4 AND LOWER(u.email) = LOWER(i.email)
+        RETURNING i.group_id"#,
+    )
+    .bind(now)
+    .bind(token)
+    .bind(now - Duration::days(INVITATION_EXPIRY_DAYS))
+    .bind(uid)
+    .fetch_optional(&mut tx)
+    .await?;
+    let group_id = match group_id {
+        Some((group_id,)) => group_id,
+        None => return Ok(None),
+    };
+    sqlx::query(
+        r#"INSERT INTO group_members (group_id, uid, joined_at) VALUES ($1, $2, $3)
+        ON CONFLICT (group_id, uid) DO NOTHING"#,
+    )
+    .bind(group_id)
+    .bind(uid)
+    .bind(now)
+    .execute(&mut tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(Some(group_id))
+}
+
+pub async fn create_problem_set(
+    pool: &PgPool,
+    group_id: i32,
+    problem_set: &NewProblemSet,
+) -> Result<ProblemSet> {
+    let (id,): (i32,) = sqlx::query_as(
+        r#"INSERT INTO problem_sets (group_id, name, description, created_at) VALUES ($1, $2, $3, $4) RETURNING id"#,
+    )
+    .bind(group_id)
+    .bind(&problem_set.name)
+    .bind(&problem_set.description)
+    .bind(Utc::now())
+    .fetch_one(pool)
+    .await?;
+    let created = find_problem_set(pool, group_id, id).await?;
+    created.ok_or_else(|| anyhow::anyhow!("the created problem set {} is missing", id))
+}
+
+pub async fn find_problem_sets(pool: &PgPool, group_id: i32) -> Result<Vec<ProblemSet>> {
+    let sql = format!(
+        "{} WHERE s.group_id = $1 GROUP BY s.id ORDER BY s.id",
+        SELECT_PROBLEM_SETS
+    );
+    let problem_sets = sqlx::query_as::<_, ProblemSet>(&sql)
+        .bind(group_id)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(problem_sets)
+}
+
+pub async fn find_problem_set(pool: &PgPool, group_id: i32, id: i32) -> Result<Option<ProblemSet>> {
+    let sql = format!(
+        "{} WHERE s.group_id = $1 AND s.id = $2 GROUP BY s.id",
+        SELECT_PROBLEM_SETS
+    );
+    let problem_set = sqlx::query_as::<_, ProblemSet>(&sql)
+        .bind(group_id)
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(problem_set)
+}
+
+pub async fn delete_problem_set(pool: &PgPool, group_id: i32, id: i32) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM problem_sets WHERE group_id = $1 AND id = $2")
+        .bind(group_id)
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// Add the problem to the set through the catalog, absent when the set has it already.
+// The title is the one of the set, the catalog names the problem on its own.
+pub async fn add_set_problem(
+    pool: &PgPool,
+    problem_set_id: i32,
+    problem: &NewSetProblem,
+) -> Result<Option<SetProblem>> {
+    // validated to be a problem of a known platform
+    let key = ProblemKey::from_url(&problem.url)
+        .ok_or_else(|| anyhow::anyhow!("{} is not a problem of a known platform", problem.url))?;
+    let mut tx = pool.begin().await?;
+    let problem_id = problems::infrastructures::register_problem(&mut tx, &key).await?;
+    let added = sqlx::query(
+        r#"INSERT INTO problem_set_items (problem_set_id, problem_id, title, added_at) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (problem_set_id, problem_id) DO NOTHING"#,
+    )
+    .bind(problem_set_id)
+    .bind(problem_id)
+    .bind(&problem.title)
+    .bind(Utc::now())
+    .execute(&mut tx)
+    .await?;
+    if added.rows_affected() == 0 {
+        return Ok(None);
+    }
+    tx.commit().await?;
+    let problem = find_set_problems(pool, problem_set_id)
+        .await?
+        .into_iter()
+        .find(|p| p.problem_id == problem_id);
+
+    Ok(problem)
+}
+
+// The problems in the order they were added.
+pub async fn find_set_problems(pool: &PgPool, problem_set_id: i32) -> Result<Vec<SetProblem>> {
+    let problems = sqlx::query_as::<_, SetProblem>(
+        r#"SELECT p.id AS problem_id, i.title, p.url, p.platform, p.difficulty, i.added_at
+        FROM problem_set_items i
+        JOIN problems p ON p.id = i.problem_id
+        WHERE i.problem_set_id = $1
+        ORDER BY i.added_at, p.id"#,
+    )
+    .bind(problem_set_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(problems)
+}
+
+pub async fn remove_set_problem(
+    pool: &PgPool,
+    problem_set_id: i32,
+    problem_id: i32,
+) -> Result<bool> {
+    let result =
+        sqlx::query("DELETE FROM problem_set_items WHERE problem_set_id = $1 AND problem_id = $2")
+            .bind(problem_set_id)
+            .bind(problem_id)
+            .execute(pool)
+            .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// Create a review of each problem of the set the user does not review yet,
+// so that importing the set again only brings the problems added since.
+pub async fn import_problem_set(
+    pool: &PgPool,
+    uid: &Uuid,
+    problem_set_id: i32,
+) -> Result<SetImport> {
+    let mut import = SetImport {
+        created: Vec::new(),
+        skipped: Vec::new(),
+    };
+    let problems = find_set_problems(pool, problem_set_id).await?;
+    let mut tx = pool.begin().await?;
+    for problem in problems {
+        let (reviewed,): (bool,) = sqlx::query_as(
+            "SELECT EXISTS (SELECT 1 FROM reviews WHERE uid = $1 AND problem_id = $2)",
+        )
+        .bind(uid)
+        .bind(problem.problem_id)
+        .fetch_one(&mut tx)
+        .await?;
+        if reviewed {
+            import.skipped.push(problem.problem_id);
+            continue;
+        }
+        let review = NewReview {
+            problem_name: problem.title,
+            url: problem.url,
+            memo: None,
+            platform: problem.platform,
+            difficulty: None,
+            solved_without_hints: false,
+        };
+        let id = reviews::infrastructures::insert_review(&mut tx, uid, &review).await?;
+        reviews::infrastructures::dispatch_review(&mut tx, uid, id, EVENT_CREATED).await?;
+        import.created.push(id);
+    }
+    tx.commit().await?;
+
+    Ok(import)
+}
+
+// Where every member stands on every problem of the set.
+pub async fn find_progress(
+    pool: &PgPool,
+    group_id: i32,
+    problem_set_id: i32,
+) -> Result<SetProgress> {
+    let problems = find_set_problems(pool, problem_set_id).await?;
+    let rows = sqlx::query_as::<_, ProgressRow>(
+        r#"SELECT u.user_name, i.problem_id, r.review_id, COALESCE(r.solved, FALSE) AS solved,
+        g.reviews, g.last_reviewed_at
+        FROM group_members m
+        JOIN users u ON u.uid = m.uid
+        CROSS JOIN problem_set_items i
+        LEFT JOIN LATERAL (
+            SELECT MIN(r.id) AS review_id,
+            BOOL_OR(r.solved_without_hints OR EXISTS (SELECT 1 FROM submissions s WHERE s.review_id = r.id AND s.verdict = 'AC')) AS solved
+            FROM reviews r WHERE r.uid = m.uid AND r.problem_id = i.problem_id
+        ) r ON TRUE
+        LEFT JOIN LATERAL (
+            SELECT COUNT(*) AS reviews, MAX(si.graded_at) AS last_reviewed_at
+            FROM session_items si JOIN reviews r ON r.id = si.review_id
+            WHERE r.uid = m.uid AND r.problem_id = i.problem_id AND si.graded_at IS NOT NULL
+        ) g ON TRUE
+        WHERE m.group_id = $1 AND i.problem_set_id = $2
+        ORDER BY m.joined_at, u.user_name, i.added_at, i.problem_id"#,
+    )
+    .bind(group_id)
+    .bind(problem_set_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(SetProgress::new(problems, rows))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config, utils};
+
+    async fn insert_user(pool: &PgPool, id: i32, user_name: &str, uid: &Uuid) {
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES ($1, $2, 'password', $3, $4)"#)
+			.bind(id)
+			.bind(user_name)
+			.bind(format!("{}@gmail.com", user_name))
+			.bind(uid)
+			.execute(pool)
+			.await
+			.unwrap();
+    }
+
+    fn new_group() -> NewGroup {
+        NewGroup {
+            name: "ICPC team".to_string(),
+            description: None,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn invitations() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let (owner, member) = (Uuid::new_v4(), Uuid::new_v4());
+        insert_user(&pool, 0, "owner", &owner).await;
+        insert_user(&pool, 1, "member", &member).await;
+        let group = create_group(&pool, &owner, &new_group()).await.unwrap();
+        assert_eq!("owner", group.owner);
+        assert_eq!(1, group.members);
+        assert_eq!(
+            Some(true),
+            find_membership(&pool, &owner, group.id).await.unwrap()
+        );
+        assert_eq!(
+            None,
+            find_membership(&pool, &member, group.id).await.unwrap()
+        );
+
+        // the owner is a member already
+        let token = Uuid::new_v4();
+        let invitation_mail =
+            mail::templates::group_invitation("owner", "ICPC team", "member@gmail.com", &token);
+        assert_eq!(
+            None,
+            create_invitation(&pool, group.id, "OWNER@gmail.com", &token, &invitation_mail)
+                .await
+                .unwrap()
+        );
+        assert!(create_invitation(
+            &pool,
+            group.id,
+            "member@gmail.com",
+            &token,
+            &invitation_mail
+        )
+        .await
+        .unwrap()
+        .is_some());
+        assert_eq!(
+            None,
+            create_invitation(
+                &pool,
+                group.id,
+                "member@gmail.com",
+                &Uuid::new_v4(),
+                &invitation_mail
+            )
+            .await
+            .unwrap()
+        );
+        // the mail is queued only with the invitation registered
+        let (mails,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM email_outbox")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(1, mails);
+
+        // only the user of the address invited can accept it
+        assert_eq!(
+            None,
+            accept_invitation(&pool, &owner, &token).await.unwrap()
+        );
+        assert_eq!(
+            Some(group.id),
+            accept_invitation(&pool, &member, &token).await.unwrap()
+        );
+        assert_eq!(
+            None,
+            accept_invitation(&pool, &member, &token).await.unwrap()
+        );
+        assert_eq!(
+            Some(false),
+            find_membership(&pool, &member, group.id).await.unwrap()
+        );
+        let members = find_members(&pool, group.id).await.unwrap();
+        assert_eq!(
+            vec![("owner", true), ("member", false)],
+            members
+                .iter()
+                .map(|m| (m.user_name.as_str(), m.owner))
+                .collect::<Vec<(&str, bool)>>()
+        );
+        assert_eq!(1, find_groups(&pool, &member).await.unwrap().len());
+
+        // an expired invitation cannot be accepted
+        let token = Uuid::new_v4();
+        sqlx::query("INSERT INTO group_invitations (group_id, email, token, created_at) VALUES ($1, 'late@gmail.com', $2, $3)")
+            .bind(group.id)
+            .bind(token)
+            .bind(Utc::now() - Duration::days(INVITATION_EXPIRY_DAYS + 1))
+            .execute(&pool)
+            .await
+            .unwrap();
+        let late = Uuid::new_v4();
+        insert_user(&pool, 2, "late", &late).await;
+        assert_eq!(None, accept_invitation(&pool, &late, &token).await.unwrap());
+        assert_eq!(2, find_invitations(&pool, group.id).await.unwrap().len());
+
+        assert!(remove_member(&pool, group.id, &member).await.unwrap());
+        assert!(!delete_group(&pool, &member, group.id).await.unwrap());
+        assert!(delete_group(&pool, &owner, group.id).await.unwrap());
+        assert!(find_invitations(&pool, group.id).await.unwrap().is_empty());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn import_and_progress() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let (owner, member) = (Uuid::new_v4(), Uuid::new_v4());
+        insert_user(&pool, 0, "owner", &owner).await;
+        insert_user(&pool, 1, "member", &member).await;
+        let group = create_group(&pool, &owner, &new_group()).await.unwrap();
+        sqlx::query("INSERT INTO group_members (group_id, uid, joined_at) VALUES ($1, $2, $3)")
+            .bind(group.id)
+            .bind(member)
+            .bind(Utc::now())
+            .execute(&pool)
+            .await
+            .unwrap();
+        let problem_set = create_problem_set(
+            &pool,
+            group.id,
+            &NewProblemSet {
+                name: "week 1".to_string(),
+                description: None,
+            },
+        )
+        .await
+        .unwrap();
+        for (url, title) in [
+            (
+                "https://atcoder.jp/contests/abc200/tasks/abc200_a",
+                "A - Century",
+            ),
+            (
+                "https://yukicoder.me/problems/no/1",
+                "No.1 道のショートカット",
+            ),
+        ]
+        .iter()
+        {
+            let problem = NewSetProblem {
+                url: url.to_string(),
+                title: title.to_string(),
+            };
+            assert!(add_set_problem(&pool, problem_set.id, &problem)
+                .await
+                .unwrap()
+                .is_some());
+            assert_eq!(
+                None,
+                add_set_problem(&pool, problem_set.id, &problem)
+                    .await
+                    .unwrap()
+            );
+        }
+        // a URL which is not of a known platform is an error, not a panic
+        let problem = NewSetProblem {
+            url: "https://example.com/problems/1".to_string(),
+            title: "Unknown".to_string(),
+        };
+        assert!(add_set_problem(&pool, problem_set.id, &problem)
+            .await
+            .is_err());
+        let problems = find_set_problems(&pool, problem_set.id).await.unwrap();
+        assert_eq!(2, problems.len());
+        // the set keeps its titles, the catalog names the problem by its ID
+        assert_eq!("No.1 道のショートカット".to_string(), problems[1].title);
+        let problem = problems::infrastructures::find_problem(&pool, problems[1].problem_id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!("No.1".to_string(), problem.title);
+        assert_eq!(
+            2,
+            find_problem_set(&pool, group.id, problem_set.id)
+                .await
+                .unwrap()
+                .unwrap()
+                .problems
+        );
+
+        // the owner reviews the first problem already, solved with an accepted submission
+        let reviewed = reviews::infrastructures::create_review(
+            &pool,
+            &owner,
+            &NewReview {
+                problem_name: "A - Century".to_string(),
+                url: "https://atcoder.jp/contests/abc200/tasks/abc200_a".to_string(),
+                memo: None,
+                platform: 1,
+                difficulty: None,
+                solved_without_hints: false,
+            },
+        )
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO submissions (review_id, source_code, language, verdict, without_editorial, created_at) VALUES ($1, 'main', 'Rust', 'AC', FALSE, now())")
+            .bind(reviewed.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let import = import_problem_set(&pool, &owner, problem_set.id)
+            .await
+            .unwrap();
+        assert_eq!(vec![problems[0].problem_id], import.skipped);
+        assert_eq!(1, import.created.len());
+        let import = import_problem_set(&pool, &member, problem_set.id)
+            .await
+            .unwrap();
+        assert_eq!(2, import.created.len());
+        let imported = reviews::infrastructures::find_review(&pool, &member, import.created[1])
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!("No.1 道のショートカット", imported.problem_name);
+        assert_eq!(Some(problems[1].problem_id), imported.problem_id);
+
+        let progress = find_progress(&pool, group.id, problem_set.id)
+            .await
+            .unwrap();
+        assert_eq!(
+            vec!["owner", "member"],
+            progress
+                .members
+                .iter()
+                .map(|m| m.user_name.as_str())
+                .collect::<Vec<&str>>()
+        );
+        assert_eq!(1, progress.members[0].solved);
+        assert_eq!(Some(reviewed.id), progress.members[0].problems[0].review_id);
+        assert_eq!(0, progress.members[1].solved);
+        assert_eq!(0, progress.members[1].reviewed);
+
+        assert!(
+            remove_set_problem(&pool, problem_set.id, problems[0].problem_id)
+                .await
+                .unwrap()
+        );
+        assert!(delete_problem_set(&pool, group.id, problem_set.id)
+            .await
+            .unwrap());
+        assert!(find_problem_sets(&pool, group.id).await.unwrap().is_empty());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+}
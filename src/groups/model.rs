@@ -0,0 +1,232 @@
+use crate::problems::model::ProblemKey;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use validator::{Validate, ValidationError};
+
+#[derive(Debug, Serialize, Deserialize, Validate, PartialEq)]
+pub struct NewGroup {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    #[validate(length(max = 1000))]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, PartialEq)]
+pub struct Group {
+    pub id: i32,
+    pub name: String,
+    pub description: Option<String>,
+    // the user name of the owner
+    pub owner: String,
+    pub members: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, PartialEq)]
+pub struct Member {
+    pub user_name: String,
+    pub owner: bool,
+    pub joined_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, PartialEq)]
+pub struct NewInvitation {
+    #[validate(email)]
+    pub email: String,
+}
+
+// The token is only sent to the address invited.
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, PartialEq)]
+pub struct Invitation {
+    pub id: i32,
+    pub email: String,
+    pub created_at: DateTime<Utc>,
+    pub accepted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, PartialEq)]
+pub struct NewProblemSet {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+    #[validate(length(max = 1000))]
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, PartialEq)]
+pub struct ProblemSet {
+    pub id: i32,
+    pub name: String,
+    pub description: Option<String>,
+    pub problems: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+// A problem is added by its URL; the title names it in the set, the catalog names it by its ID.
+#[derive(Debug, Serialize, Deserialize, Validate, PartialEq)]
+pub struct NewSetProblem {
+    #[validate(length(max = 255), custom = "validate_problem_url")]
+    pub url: String,
+    #[validate(length(min = 1, max = 255))]
+    pub title: String,
+}
+
+fn validate_problem_url(url: &str) -> Result<(), ValidationError> {
+    match ProblemKey::from_url(url) {
+        Some(_) => Ok(()),
+        None => Err(ValidationError::new("unknown_problem_url")),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow, PartialEq)]
+pub struct SetProblem {
+    pub problem_id: i32,
+    pub title: String,
+    pub url: String,
+    pub platform: i16,
+    // the difficulty given by the platform
+    pub difficulty: Option<i32>,
+    pub added_at: DateTime<Utc>,
+}
+
+// The problems of the set which the member did not review yet are imported as new reviews.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct SetImport {
+    // the ids of the reviews created
+    pub created: Vec<i32>,
+    // the ids of the problems the member already reviews
+    pub skipped: Vec<i32>,
+}
+
+// Where a member stands on a problem of a set.
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, PartialEq)]
+pub struct ProgressRow {
+    pub user_name: String,
+    pub problem_id: i32,
+    // the review of the problem by the member, absent until it is imported or reviewed
+    pub review_id: Option<i32>,
+    // an accepted submission is recorded or the review is solved without hints
+    pub solved: bool,
+    // the times the review has been graded in review sessions
+    pub reviews: i64,
+    pub last_reviewed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ProblemProgress {
+    pub problem_id: i32,
+    pub review_id: Option<i32>,
+    pub solved: bool,
+    pub reviews: i64,
+    pub last_reviewed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct MemberProgress {
+    pub user_name: String,
+    // the number of problems of the set solved, and reviewed at least once
+    pub solved: i64,
+    pub reviewed: i64,
+    // in the order of the problems of the set
+    pub problems: Vec<ProblemProgress>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct SetProgress {
+    pub problems: Vec<SetProblem>,
+    pub members: Vec<MemberProgress>,
+}
+
+impl SetProgress {
+    // The rows come ordered by member, then in the order of the problems of the set.
+    pub fn new(problems: Vec<SetProblem>, rows: Vec<ProgressRow>) -> SetProgress {
+        let mut members: Vec<MemberProgress> = Vec::new();
+        for row in rows {
+            if members.last().map(|m| &m.user_name) != Some(&row.user_name) {
+                members.push(MemberProgress {
+                    user_name: row.user_name.clone(),
+                    solved: 0,
+                    reviewed: 0,
+                    problems: Vec::new(),
+                });
+            }
+            let member = members.last_mut().unwrap();
+            if row.solved {
+                member.solved += 1;
+            }
+            if row.reviews > 0 {
+                member.reviewed += 1;
+            }
+            member.problems.push(ProblemProgress {
+                problem_id: row.problem_id,
+                review_id: row.review_id,
+                solved: row.solved,
+                reviews: row.reviews,
+                last_reviewed_at: row.last_reviewed_at,
+            });
+        }
+
+        SetProgress { problems, members }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn validate_set_problem() {
+        let mut problem = NewSetProblem {
+            url: "https://atcoder.jp/contests/abc200/tasks/abc200_a".to_string(),
+            title: "A - Century".to_string(),
+        };
+        assert!(problem.validate().is_ok());
+        problem.url = "https://example.com/problems/1".to_string();
+        assert!(problem.validate().is_err());
+    }
+
+    #[test]
+    fn set_progress() {
+        let added_at = Utc.ymd(2021, 5, 20).and_hms(8, 40, 0);
+        let problems: Vec<SetProblem> = (1..=2)
+            .map(|id| SetProblem {
+                problem_id: id,
+                title: format!("prob_{}", id),
+                url: format!("https://yukicoder.me/problems/no/{}", id),
+                platform: 4,
+                difficulty: None,
+                added_at,
+            })
+            .collect();
+        let row = |user_name: &str, problem_id: i32, solved: bool, reviews: i64| ProgressRow {
+            user_name: user_name.to_string(),
+            problem_id,
+            review_id: if solved { Some(problem_id) } else { None },
+            solved,
+            reviews,
+            last_reviewed_at: None,
+        };
+        let rows = vec![
+            row("alice", 1, true, 2),
+            row("alice", 2, true, 0),
+            row("bob", 1, false, 0),
+            row("bob", 2, false, 0),
+        ];
+        let progress = SetProgress::new(problems.clone(), rows);
+        assert_eq!(problems, progress.problems);
+        assert_eq!(2, progress.members.len());
+        assert_eq!("alice", progress.members[0].user_name);
+        assert_eq!(2, progress.members[0].solved);
+        assert_eq!(1, progress.members[0].reviewed);
+        assert_eq!("bob", progress.members[1].user_name);
+        assert_eq!(0, progress.members[1].solved);
+        assert_eq!(
+            vec![1, 2],
+            progress.members[1]
+                .problems
+                .iter()
+                .map(|p| p.problem_id)
+                .collect::<Vec<i32>>()
+        );
+    }
+}
@@ -0,0 +1,115 @@
+use super::infrastructures;
+use super::model::{History, ImportQuery, Source};
+use crate::auth::AuthenticatedUser;
+use crate::error::ApiError;
+use actix_web::{post, web, HttpResponse};
+use anyhow::Result;
+use sqlx::PgPool;
+
+// The body is the file of the history as downloaded from the API of the platform.
+#[post("/imports/{source}")]
+pub async fn import_history(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path(source): web::Path<String>,
+    query: web::Query<ImportQuery>,
+    body: web::Bytes,
+) -> Result<HttpResponse, ApiError> {
+    let source = match Source::from_name(&source) {
+        Some(source) => source,
+        None => return Err(ApiError::NotFound),
+    };
+    let history = match History::parse(&source, &body) {
+        Ok(history) => history,
+        Err(_) => return Err(ApiError::BadRequest),
+    };
+    let dry_run = query.dry_run.unwrap_or(false);
+
+    match infrastructures::import_history(pool.get_ref(), &user.uid, &history, dry_run).await {
+        Ok(report) if dry_run => Ok(HttpResponse::Ok().json(report)),
+        Ok(report) => Ok(HttpResponse::Created().json(report)),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imports::model::ImportReport;
+    use crate::{auth, config, utils};
+    use actix_web::{test, App};
+    use uuid::Uuid;
+
+    const HISTORY: &str = r#"[
+        {"id": 2, "epoch_second": 1620000100, "problem_id": "abc200_b", "contest_id": "abc200", "result": "AC"},
+        {"id": 1, "epoch_second": 1620000000, "problem_id": "abc200_a", "contest_id": "abc200", "result": "AC"},
+        {"id": 0, "epoch_second": 1620000000, "problem_id": "abc200_c"}
+    ]"#;
+
+    #[actix_rt::test]
+    async fn import_history_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app =
+            test::init_service(App::new().data(pool.clone()).service(import_history)).await;
+        let uid = Uuid::new_v4();
+        let token = auth::infrastructures::create_session(&pool, &uid)
+            .await
+            .unwrap();
+
+        let req = test::TestRequest::post()
+            .uri("/imports/atcoder?dry_run=true")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_payload(HISTORY)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let report: ImportReport = test::read_body_json(resp).await;
+        assert!(report.dry_run);
+        assert_eq!(2, report.created.len());
+        assert_eq!("abc200_a".to_string(), report.created[0].external_id);
+        assert_eq!(1, report.errors.len());
+
+        let req = test::TestRequest::post()
+            .uri("/imports/atcoder")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_payload(HISTORY)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(201, resp.status());
+        let report: ImportReport = test::read_body_json(resp).await;
+        assert!(!report.dry_run);
+        assert!(report.created.iter().all(|item| item.review_id.is_some()));
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn import_history_invalid() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app =
+            test::init_service(App::new().data(pool.clone()).service(import_history)).await;
+        let token = auth::infrastructures::create_session(&pool, &Uuid::new_v4())
+            .await
+            .unwrap();
+
+        let req = test::TestRequest::post()
+            .uri("/imports/codeforces")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_payload(HISTORY)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(400, resp.status());
+
+        let req = test::TestRequest::post()
+            .uri("/imports/topcoder")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_payload(HISTORY)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(404, resp.status());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+}
@@ -0,0 +1,189 @@
+use super::model::{History, ImportReport, Solve};
+use crate::problems::model::ProblemKey;
+use crate::reviews::model::NewReview;
+use crate::webhooks::model::EVENT_CREATED;
+use crate::{reviews, tags};
+use anyhow::Result;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+// the review of the user on the problem, if any
+async fn find_reviewed<'e, E>(executor: E, uid: &Uuid, key: &ProblemKey) -> Result<Option<i32>>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    let id: Option<(i32,)> = sqlx::query_as(
+        r#"SELECT r.id FROM reviews r JOIN problems p ON p.id = r.problem_id
+        WHERE r.uid = $1 AND p.platform = $2 AND p.external_id = $3 ORDER BY r.id LIMIT 1"#,
+    )
+    .bind(uid)
+    .bind(key.platform)
+    .bind(&key.external_id)
+    .fetch_optional(executor)
+    .await?;
+
+    Ok(id.map(|(id,)| id))
+}
+
+// Keep what the platform tells about the problem with the new review, not in the catalog,
+// as the histories are uploaded by the users.
+async fn keep_metadata(
+    tx: &mut Transaction<'_, Postgres>,
+    uid: &Uuid,
+    id: i32,
+    solve: &Solve,
+) -> Result<()> {
+    sqlx::query("UPDATE reviews SET imported_difficulty = $1 WHERE id = $2")
+        .bind(solve.difficulty)
+        .bind(id)
+        .execute(&mut *tx)
+        .await?;
+    // the tags not defined yet are defined as the tags of the user
+    for name in solve.tag_names() {
+        let tag = match tags::infrastructures::find_tag(&mut *tx, uid, &name).await? {
+            Some(tag) => tag,
+            None => tags::infrastructures::create_tag(&mut *tx, uid, &name).await?,
+        };
+        tags::infrastructures::attach_tag(&mut *tx, id, tag.id).await?;
+    }
+
+    Ok(())
+}
+
+// Create a review of each problem solved that the user is not reviewing yet, all or none of them.
+// On a dry run nothing is written and the report tells what would have been.
+pub async fn import_history(
+    pool: &PgPool,
+    uid: &Uuid,
+    history: &History,
+    dry_run: bool,
+) -> Result<ImportReport> {
+    let mut report = ImportReport {
+        dry_run,
+        created: vec![],
+        merged: vec![],
+        duplicates: history.duplicates.iter().map(|s| s.item(None)).collect(),
+        errors: history.errors.clone(),
+    };
+    let mut created = Vec::new();
+    let mut tx = pool.begin().await?;
+    for solve in &history.solves {
+        if let Some(id) = find_reviewed(&mut tx, uid, &solve.key).await? {
+            report.merged.push(solve.item(Some(id)));
+        } else if dry_run {
+            report.created.push(solve.item(None));
+        } else {
+            let review = NewReview {
+                problem_name: solve.title.clone(),
+                url: solve.key.url(),
+                memo: None,
+                platform: solve.key.platform,
+                difficulty: None,
+                solved_without_hints: false,
+            };
+            let id = reviews::infrastructures::insert_review(&mut tx, uid, &review).await?;
+            keep_metadata(&mut tx, uid, id, solve).await?;
+            created.push(id);
+            report.created.push(solve.item(Some(id)));
+        }
+    }
+    // the events carry the metadata kept
+    for id in created {
+        reviews::infrastructures::dispatch_review(&mut tx, uid, id, EVENT_CREATED).await?;
+    }
+    tx.commit().await?;
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::imports::model::Source;
+    use crate::{config, problems, utils};
+
+    const HISTORY: &str = r#"{"status": "OK", "result": [
+        {"id": 3, "creationTimeSeconds": 1620000200, "problem": {"contestId": 1520, "index": "B", "name": "Ordinary Numbers", "rating": 800, "tags": ["math"]}, "verdict": "OK"},
+        {"id": 2, "creationTimeSeconds": 1620000100, "problem": {"contestId": 1520, "index": "A", "name": "Do Not Be Distracted!", "rating": 800, "tags": []}, "verdict": "OK"},
+        {"id": 1, "creationTimeSeconds": 1620000000, "problem": {"contestId": 1520, "index": "A", "name": "Do Not Be Distracted!", "rating": 800, "tags": []}, "verdict": "OK"}
+    ]}"#;
+
+    #[actix_rt::test]
+    async fn import_history_test() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        let reviewed = NewReview {
+            problem_name: "A - my review".to_string(),
+            url: "https://codeforces.com/contest/1520/problem/A".to_string(),
+            memo: Some("count the blocks".to_string()),
+            platform: 2,
+            difficulty: Some(1),
+            solved_without_hints: false,
+        };
+        let reviewed = reviews::infrastructures::create_review(&pool, &uid, &reviewed)
+            .await
+            .unwrap();
+        let history = History::parse(&Source::Codeforces, HISTORY.as_bytes()).unwrap();
+
+        let preview = import_history(&pool, &uid, &history, true).await.unwrap();
+        assert_eq!(1, preview.created.len());
+        assert_eq!(None, preview.created[0].review_id);
+        assert_eq!(Some(reviewed.id), preview.merged[0].review_id);
+        assert_eq!("1520A".to_string(), preview.duplicates[0].external_id);
+        let key = ProblemKey::from_url("https://codeforces.com/contest/1520/problem/B").unwrap();
+        assert!(find_reviewed(&pool, &uid, &key).await.unwrap().is_none());
+
+        let report = import_history(&pool, &uid, &history, false).await.unwrap();
+        let id = find_reviewed(&pool, &uid, &key).await.unwrap();
+        assert!(id.is_some());
+        assert_eq!(id, report.created[0].review_id);
+        let review = reviews::infrastructures::find_review(&pool, &uid, id.unwrap())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!("B. Ordinary Numbers".to_string(), review.problem_name);
+        // what the platform tells is kept with the review of the user, not in the catalog
+        assert_eq!(Some(800), review.platform_difficulty);
+        assert_eq!(vec!["math".to_string()], review.tags);
+        let problem = problems::infrastructures::find_problem_by_key(&pool, &key)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(None, problem.difficulty);
+        assert!(problem.tags.is_empty());
+        // the review merged into is left as it is
+        let merged = reviews::infrastructures::find_review(&pool, &uid, reviewed.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(reviewed, merged);
+
+        // importing again creates nothing
+        let report = import_history(&pool, &uid, &history, false).await.unwrap();
+        assert!(report.created.is_empty());
+        assert_eq!(2, report.merged.len());
+
+        // another upload does not change the catalog nor the reviews merged into
+        let upload = r#"{"status": "OK", "result": [
+            {"id": 4, "creationTimeSeconds": 1620000300, "problem": {"contestId": 1520, "index": "B", "name": "Ordinary Numbers", "rating": 3500, "tags": ["dp"]}, "verdict": "OK"}
+        ]}"#;
+        let history = History::parse(&Source::Codeforces, upload.as_bytes()).unwrap();
+        import_history(&pool, &uid, &history, false).await.unwrap();
+        let problem = problems::infrastructures::find_problem_by_key(&pool, &key)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(None, problem.difficulty);
+        assert!(problem.tags.is_empty());
+        assert_eq!(
+            review,
+            reviews::infrastructures::find_review(&pool, &uid, review.id)
+                .await
+                .unwrap()
+                .unwrap()
+        );
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+}
@@ -0,0 +1,343 @@
+use crate::problems::model::ProblemKey;
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+
+// The submission histories as returned by the public APIs of the platforms.
+#[derive(Debug, PartialEq)]
+pub enum Source {
+    // the submission list of AtCoder Problems
+    AtCoder,
+    // the `user.status` response of Codeforces
+    Codeforces,
+}
+
+impl Source {
+    pub fn from_name(name: &str) -> Option<Source> {
+        match name {
+            "atcoder" => Some(Source::AtCoder),
+            "codeforces" => Some(Source::Codeforces),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct AtCoderSubmission {
+    epoch_second: i64,
+    problem_id: String,
+    contest_id: String,
+    result: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct CodeforcesStatus {
+    status: String,
+    // absent when the status is FAILED
+    #[serde(default)]
+    result: Vec<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CodeforcesSubmission {
+    creation_time_seconds: i64,
+    problem: CodeforcesProblem,
+    // absent while the submission is being judged
+    verdict: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CodeforcesProblem {
+    // absent for the problems of the problemsets outside of contests
+    contest_id: Option<i64>,
+    index: String,
+    name: String,
+    rating: Option<i32>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportQuery {
+    // report what would be imported without importing it
+    pub dry_run: Option<bool>,
+}
+
+// A problem solved according to the history.
+#[derive(Debug, PartialEq)]
+pub struct Solve {
+    pub key: ProblemKey,
+    pub title: String,
+    pub solved_at: DateTime<Utc>,
+    // the difficulty and the tags given by the platform, if any
+    pub difficulty: Option<i32>,
+    pub tags: Vec<String>,
+}
+
+impl Solve {
+    pub fn item(&self, review_id: Option<i32>) -> ImportItem {
+        ImportItem {
+            platform: self.key.platform,
+            external_id: self.key.external_id.clone(),
+            title: self.title.clone(),
+            url: self.key.url(),
+            review_id,
+        }
+    }
+
+    // The tags of the platform as names of tags of the user, e.g. `dfs-and-similar`.
+    pub fn tag_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .tags
+            .iter()
+            .map(|tag| {
+                tag.to_lowercase()
+                    .split(|c: char| !c.is_ascii_alphanumeric())
+                    .filter(|word| !word.is_empty())
+                    .collect::<Vec<&str>>()
+                    .join("-")
+            })
+            .filter(|name| !name.is_empty() && name.len() <= 50)
+            .collect();
+        names.sort();
+        names.dedup();
+
+        names
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ImportItem {
+    pub platform: i16,
+    pub external_id: String,
+    pub title: String,
+    pub url: String,
+    // the review created or merged into, absent on a dry run of a new review
+    pub review_id: Option<i32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ImportError {
+    // the position of the submission in the history
+    pub index: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct History {
+    // the first accepted submission of each problem, oldest first
+    pub solves: Vec<Solve>,
+    // the accepted submissions of a problem already solved before
+    pub duplicates: Vec<Solve>,
+    pub errors: Vec<ImportError>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    // the problems reviewed for the first time
+    pub created: Vec<ImportItem>,
+    // the problems the user was already reviewing, whose reviews are kept as they are
+    pub merged: Vec<ImportItem>,
+    // the accepted submissions of a problem solved earlier in the history
+    pub duplicates: Vec<ImportItem>,
+    pub errors: Vec<ImportError>,
+}
+
+impl History {
+    // Read the accepted submissions out of the history. Only a file which is not a history
+    // at all is an error; a malformed submission is reported and the others are read.
+    pub fn parse(source: &Source, body: &[u8]) -> Result<History, String> {
+        let submissions: Vec<Value> = match source {
+            Source::AtCoder => serde_json::from_slice(body).map_err(|e| e.to_string())?,
+            Source::Codeforces => {
+                let status: CodeforcesStatus =
+                    serde_json::from_slice(body).map_err(|e| e.to_string())?;
+                if status.status != "OK" {
+                    return Err(format!("the status of the response is {}", status.status));
+                }
+                status.result
+            }
+        };
+
+        let mut history = History::default();
+        let mut solves: Vec<Solve> = Vec::new();
+        for (index, submission) in submissions.into_iter().enumerate() {
+            let solve = match source {
+                Source::AtCoder => atcoder_solve(submission),
+                Source::Codeforces => codeforces_solve(submission),
+            };
+            match solve {
+                Ok(Some(solve)) => solves.push(solve),
+                Ok(None) => (),
+                Err(message) => history.errors.push(ImportError { index, message }),
+            }
+        }
+
+        // the histories are listed newest first
+        solves.sort_by_key(|solve| solve.solved_at);
+        let mut seen: HashSet<(i16, String)> = HashSet::new();
+        for solve in solves {
+            if seen.insert((solve.key.platform, solve.key.external_id.clone())) {
+                history.solves.push(solve);
+            } else {
+                history.duplicates.push(solve);
+            }
+        }
+
+        Ok(history)
+    }
+}
+
+// the solve of an accepted submission, None for the other verdicts
+fn atcoder_solve(submission: Value) -> Result<Option<Solve>, String> {
+    let submission: AtCoderSubmission =
+        serde_json::from_value(submission).map_err(|e| e.to_string())?;
+    if submission.result != "AC" {
+        return Ok(None);
+    }
+
+    Ok(Some(Solve {
+        key: ProblemKey {
+            platform: 1,
+            external_id: submission.problem_id.clone(),
+            contest: Some(submission.contest_id),
+        },
+        // the submissions do not carry the title of the problem
+        title: submission.problem_id,
+        solved_at: Utc.timestamp(submission.epoch_second, 0),
+        difficulty: None,
+        tags: vec![],
+    }))
+}
+
+fn codeforces_solve(submission: Value) -> Result<Option<Solve>, String> {
+    let submission: CodeforcesSubmission =
+        serde_json::from_value(submission).map_err(|e| e.to_string())?;
+    if submission.verdict.as_deref() != Some("OK") {
+        return Ok(None);
+    }
+    let problem = submission.problem;
+    let contest = match problem.contest_id {
+        Some(contest) => contest.to_string(),
+        None => return Err(format!("{} is not of a contest", problem.name)),
+    };
+
+    Ok(Some(Solve {
+        key: ProblemKey {
+            platform: 2,
+            external_id: format!("{}{}", contest, problem.index),
+            contest: Some(contest),
+        },
+        title: format!("{}. {}", problem.index, problem.name),
+        solved_at: Utc.timestamp(submission.creation_time_seconds, 0),
+        difficulty: problem.rating,
+        tags: problem.tags,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_atcoder() {
+        let body = r#"[
+            {"id": 3, "epoch_second": 1620000200, "problem_id": "abc200_a", "contest_id": "abc200", "user_id": "test_user", "language": "Rust (1.42.0)", "point": 100.0, "length": 200, "result": "AC", "execution_time": 6},
+            {"id": 2, "epoch_second": 1620000100, "problem_id": "abc200_b", "contest_id": "abc200", "user_id": "test_user", "language": "Rust (1.42.0)", "point": 0.0, "length": 200, "result": "WA", "execution_time": 6},
+            {"id": 1, "epoch_second": 1620000000, "problem_id": "abc200_a", "contest_id": "abc200", "user_id": "test_user", "language": "Rust (1.42.0)", "point": 100.0, "length": 200, "result": "AC", "execution_time": 6},
+            {"id": 0, "problem_id": "abc200_c"}
+        ]"#;
+
+        let history = History::parse(&Source::AtCoder, body.as_bytes()).unwrap();
+        assert_eq!(
+            vec![Solve {
+                key: ProblemKey {
+                    platform: 1,
+                    external_id: "abc200_a".to_string(),
+                    contest: Some("abc200".to_string()),
+                },
+                title: "abc200_a".to_string(),
+                solved_at: Utc.ymd(2021, 5, 3).and_hms(0, 0, 0),
+                difficulty: None,
+                tags: vec![],
+            }],
+            history.solves
+        );
+        assert_eq!(1, history.duplicates.len());
+        assert_eq!(
+            Utc.ymd(2021, 5, 3).and_hms(0, 3, 20),
+            history.duplicates[0].solved_at
+        );
+        assert_eq!(1, history.errors.len());
+        assert_eq!(3, history.errors[0].index);
+    }
+
+    #[test]
+    fn parse_codeforces() {
+        let body = r#"{"status": "OK", "result": [
+            {"id": 2, "contestId": 1520, "creationTimeSeconds": 1620000100, "problem": {"contestId": 1520, "index": "B", "name": "Ordinary Numbers", "type": "PROGRAMMING", "rating": 800, "tags": ["brute force", "math"]}, "programmingLanguage": "Rust", "verdict": "OK"},
+            {"id": 1, "contestId": 1520, "creationTimeSeconds": 1620000000, "problem": {"contestId": 1520, "index": "A", "name": "Do Not Be Distracted!", "type": "PROGRAMMING"}, "programmingLanguage": "Rust", "verdict": "WRONG_ANSWER"},
+            {"id": 0, "creationTimeSeconds": 1620000000, "problem": {"problemsetName": "acmsguru", "index": "100", "name": "A+B", "type": "PROGRAMMING"}, "verdict": "OK"}
+        ]}"#;
+
+        let history = History::parse(&Source::Codeforces, body.as_bytes()).unwrap();
+        assert_eq!(
+            vec![Solve {
+                key: ProblemKey {
+                    platform: 2,
+                    external_id: "1520B".to_string(),
+                    contest: Some("1520".to_string()),
+                },
+                title: "B. Ordinary Numbers".to_string(),
+                solved_at: Utc.ymd(2021, 5, 3).and_hms(0, 1, 40),
+                difficulty: Some(800),
+                tags: vec!["brute force".to_string(), "math".to_string()],
+            }],
+            history.solves
+        );
+        assert!(history.duplicates.is_empty());
+        assert_eq!(
+            vec![ImportError {
+                index: 2,
+                message: "A+B is not of a contest".to_string()
+            }],
+            history.errors
+        );
+    }
+
+    #[test]
+    fn tag_names() {
+        let solve = Solve {
+            key: ProblemKey::from_url("https://codeforces.com/contest/1520/problem/A").unwrap(),
+            title: "A. Do Not Be Distracted!".to_string(),
+            solved_at: Utc.ymd(2021, 5, 3).and_hms(0, 0, 0),
+            difficulty: Some(800),
+            tags: vec![
+                "dfs and similar".to_string(),
+                "*special".to_string(),
+                "2-sat".to_string(),
+                "Math".to_string(),
+                "math".to_string(),
+                "***".to_string(),
+            ],
+        };
+        assert_eq!(
+            vec!["2-sat", "dfs-and-similar", "math", "special"],
+            solve.tag_names()
+        );
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(History::parse(&Source::AtCoder, b"{}").is_err());
+        assert!(History::parse(
+            &Source::Codeforces,
+            br#"{"status": "FAILED", "comment": "handle: User not found"}"#
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,66 @@
+use crate::mail::{self, SmtpMailer};
+use crate::webhooks::{self, HttpSender};
+use crate::{config, digests, users};
+use actix_web::rt;
+use sqlx::PgPool;
+use std::time::Duration;
+
+const ACCOUNT_PURGE_INTERVAL_SECS: u64 = 60 * 60;
+const MAIL_DELIVERY_INTERVAL_SECS: u64 = 10;
+// the digests go out within this long after the hour of the user
+const DIGEST_INTERVAL_SECS: u64 = 5 * 60;
+const WEBHOOK_DELIVERY_INTERVAL_SECS: u64 = 10;
+
+// Delete the accounts whose deletion grace period has passed, once an hour.
+pub fn spawn_account_purge(pool: PgPool) {
+    rt::spawn(async move {
+        loop {
+            // a failed run is simply retried on the next tick
+            let _ = users::infrastructures::purge_deleted_accounts(&pool).await;
+            rt::time::delay_for(Duration::from_secs(ACCOUNT_PURGE_INTERVAL_SECS)).await;
+        }
+    });
+}
+
+// Send the queued mails. Failed ones are retried by the outbox with a backoff.
+pub fn spawn_mail_delivery(pool: PgPool) {
+    let mailer = SmtpMailer::new(&config::Config::new());
+    rt::spawn(async move {
+        loop {
+            // keep going while there are more due mails than a single batch
+            while let Ok(sent) = mail::infrastructures::deliver_due(&pool, &mailer).await {
+                if sent == 0 {
+                    break;
+                }
+            }
+            rt::time::delay_for(Duration::from_secs(MAIL_DELIVERY_INTERVAL_SECS)).await;
+        }
+    });
+}
+
+// Queue the digests of the users whose hour has come. The mails are sent by the outbox.
+pub fn spawn_digest_delivery(pool: PgPool) {
+    rt::spawn(async move {
+        loop {
+            // the digests already queued are marked, so a failed run is simply retried
+            let _ = digests::infrastructures::send_due_digests(&pool).await;
+            rt::time::delay_for(Duration::from_secs(DIGEST_INTERVAL_SECS)).await;
+        }
+    });
+}
+
+// Post the queued webhook events. Failed ones are retried with a backoff.
+pub fn spawn_webhook_delivery(pool: PgPool) {
+    let sender = HttpSender::new();
+    rt::spawn(async move {
+        loop {
+            // keep going while there are more due deliveries than a single batch
+            while let Ok(attempted) = webhooks::infrastructures::deliver_due(&pool, &sender).await {
+                if attempted == 0 {
+                    break;
+                }
+            }
+            rt::time::delay_for(Duration::from_secs(WEBHOOK_DELIVERY_INTERVAL_SECS)).await;
+        }
+    });
+}
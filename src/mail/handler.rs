@@ -0,0 +1,124 @@
+use super::infrastructures;
+use crate::auth::Admin;
+use crate::error::ApiError;
+use actix_web::{get, post, web, HttpResponse};
+use anyhow::Result;
+use sqlx::PgPool;
+
+#[get("/mail/dead-letters")]
+pub async fn dead_letters(pool: web::Data<PgPool>, _: Admin) -> Result<HttpResponse, ApiError> {
+    match infrastructures::find_dead_letters(pool.get_ref()).await {
+        Ok(letters) => Ok(HttpResponse::Ok().json(letters)),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[post("/mail/dead-letters/{id}/retry")]
+pub async fn retry_dead_letter(
+    pool: web::Data<PgPool>,
+    _: Admin,
+    web::Path(id): web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    match infrastructures::retry_dead_letter(pool.get_ref(), id).await {
+        Ok(true) => Ok(HttpResponse::Ok().json("")),
+        Ok(false) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mail::model::{DeadLetter, NewMail, STATUS_DEAD};
+    use crate::{config, utils};
+    use actix_web::{test, App};
+
+    async fn insert_dead_letter(pool: &PgPool) -> i32 {
+        let mail = NewMail {
+            recipient: "dead@gmail.com".to_string(),
+            subject: "subject".to_string(),
+            body: "body".to_string(),
+        };
+        infrastructures::enqueue(pool, &mail).await.unwrap();
+        sqlx::query(
+            "UPDATE email_outbox SET status = $1, attempts = $2, last_error = 'connection refused'",
+        )
+        .bind(STATUS_DEAD)
+        .bind(infrastructures::MAX_ATTEMPTS)
+        .execute(pool)
+        .await
+        .unwrap();
+        infrastructures::find_dead_letters(pool).await.unwrap()[0].id
+    }
+
+    #[actix_rt::test]
+    async fn dead_letters_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(App::new().data(pool.clone()).service(dead_letters)).await;
+        let id = insert_dead_letter(&pool).await;
+
+        let req = test::TestRequest::get()
+            .uri("/mail/dead-letters")
+            .header("Authorization", "Bearer dummy_admin_token")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let letters: Vec<DeadLetter> = test::read_body_json(resp).await;
+        assert_eq!(1, letters.len());
+        assert_eq!(id, letters[0].id);
+        assert_eq!("dead@gmail.com".to_string(), letters[0].recipient);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn dead_letters_unauthorized() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(App::new().data(pool.clone()).service(dead_letters)).await;
+
+        let req = test::TestRequest::get()
+            .uri("/mail/dead-letters")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(401, resp.status());
+    }
+
+    #[actix_rt::test]
+    async fn retry_dead_letter_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app =
+            test::init_service(App::new().data(pool.clone()).service(retry_dead_letter)).await;
+        let id = insert_dead_letter(&pool).await;
+
+        let uri = format!("/mail/dead-letters/{}/retry", id);
+        let req = test::TestRequest::post()
+            .uri(&uri)
+            .header("Authorization", "Bearer dummy_admin_token")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+
+        let letters = infrastructures::find_dead_letters(&pool).await.unwrap();
+        assert_eq!(0, letters.len());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn retry_dead_letter_not_exist() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app =
+            test::init_service(App::new().data(pool.clone()).service(retry_dead_letter)).await;
+
+        let req = test::TestRequest::post()
+            .uri("/mail/dead-letters/0/retry")
+            .header("Authorization", "Bearer dummy_admin_token")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(404, resp.status());
+    }
+}
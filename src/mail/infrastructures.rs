@@ -0,0 +1,375 @@
+use super::model::{DeadLetter, Mail, NewMail, STATUS_DEAD, STATUS_PENDING, STATUS_SENT};
+use super::Mailer;
+use actix_web::web;
+use anyhow::Result;
+use chrono::{Duration, Utc};
+use sqlx::{Executor, PgPool, Postgres};
+
+// a mail is given up and moved to the dead letters after this many failures
+pub const MAX_ATTEMPTS: i32 = 8;
+const BASE_BACKOFF_SECS: i64 = 30;
+const MAX_BACKOFF_SECS: i64 = 6 * 60 * 60;
+// claimed mails are hidden from other workers for this long
+const CLAIM_LEASE_SECS: i64 = 5 * 60;
+const BATCH_SIZE: i64 = 20;
+
+// Takes a transaction as well, so that the mail is only queued if the rest is committed.
+pub async fn enqueue<'e, E>(executor: E, mail: &NewMail) -> Result<()>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    let now = Utc::now();
+    sqlx::query(r#"INSERT INTO email_outbox (recipient, subject, body, status, attempts, next_attempt_at, created_at) VALUES ($1, $2, $3, $4, 0, $5, $5)"#)
+        .bind(&mail.recipient)
+        .bind(&mail.subject)
+        .bind(&mail.body)
+        .bind(STATUS_PENDING)
+        .bind(now)
+        .execute(executor)
+        .await?;
+
+    Ok(())
+}
+
+// Take the pending mails which are due.
+// Their next attempt is pushed back by a lease, so that no other worker sends them concurrently.
+pub async fn claim_due(pool: &PgPool, limit: i64) -> Result<Vec<Mail>> {
+    let now = Utc::now();
+    let lease = now + Duration::seconds(CLAIM_LEASE_SECS);
+    let mut mails = sqlx::query_as::<_, Mail>(
+        r#"UPDATE email_outbox SET next_attempt_at = $1
+        WHERE id IN (
+            SELECT id FROM email_outbox WHERE status = $2 AND next_attempt_at <= $3
+            ORDER BY id LIMIT $4 FOR UPDATE SKIP LOCKED
+        )
+        RETURNING id, recipient, subject, body, attempts"#,
+    )
+    .bind(lease)
+    .bind(STATUS_PENDING)
+    .bind(now)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+    // RETURNING does not keep the order of the subquery
+    mails.sort_by_key(|m| m.id);
+
+    Ok(mails)
+}
+
+pub async fn mark_sent(pool: &PgPool, id: i32) -> Result<()> {
+    let now = Utc::now();
+    sqlx::query("UPDATE email_outbox SET status = $1, attempts = attempts + 1, sent_at = $2, last_error = NULL WHERE id = $3")
+        .bind(STATUS_SENT)
+        .bind(now)
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+// Schedule the next attempt with an exponential backoff, or give up after `MAX_ATTEMPTS`.
+pub async fn mark_failed(pool: &PgPool, mail: &Mail, error: &str) -> Result<()> {
+    let attempts = mail.attempts + 1;
+    let status = if attempts >= MAX_ATTEMPTS {
+        STATUS_DEAD
+    } else {
+        STATUS_PENDING
+    };
+    let next_attempt_at = Utc::now() + backoff(attempts);
+    sqlx::query("UPDATE email_outbox SET status = $1, attempts = $2, next_attempt_at = $3, last_error = $4 WHERE id = $5")
+        .bind(status)
+        .bind(attempts)
+        .bind(next_attempt_at)
+        .bind(error)
+        .bind(mail.id)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+// 30s, 1m, 2m, 4m, ... capped at 6h
+pub fn backoff(attempts: i32) -> Duration {
+    let exponent = (attempts - 1).clamp(0, 30) as u32;
+    let secs = BASE_BACKOFF_SECS.saturating_mul(2_i64.saturating_pow(exponent));
+    Duration::seconds(secs.min(MAX_BACKOFF_SECS))
+}
+
+// Send a batch of due mails and return how many of them were sent.
+pub async fn deliver_due<M: Mailer>(pool: &PgPool, mailer: &M) -> Result<usize> {
+    let mails = claim_due(pool, BATCH_SIZE).await?;
+    let mut sent = 0;
+    for mail in mails {
+        let m = mailer.clone();
+        let outgoing = mail.clone();
+        // SMTP is synchronous, so it must not block the worker
+        match web::block(move || m.send(&outgoing)).await {
+            Ok(_) => {
+                mark_sent(pool, mail.id).await?;
+                sent += 1;
+            }
+            Err(e) => mark_failed(pool, &mail, &e.to_string()).await?,
+        }
+    }
+
+    Ok(sent)
+}
+
+pub async fn find_dead_letters(pool: &PgPool) -> Result<Vec<DeadLetter>> {
+    let letters = sqlx::query_as::<_, DeadLetter>(
+        "SELECT id, recipient, subject, attempts, last_error, created_at FROM email_outbox WHERE status = $1 ORDER BY id",
+    )
+    .bind(STATUS_DEAD)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(letters)
+}
+
+// Put a dead letter back to the queue. Returns whether there was such a dead letter.
+pub async fn retry_dead_letter(pool: &PgPool, id: i32) -> Result<bool> {
+    let now = Utc::now();
+    let result = sqlx::query("UPDATE email_outbox SET status = $1, attempts = 0, next_attempt_at = $2 WHERE id = $3 AND status = $4")
+        .bind(STATUS_PENDING)
+        .bind(now)
+        .bind(id)
+        .bind(STATUS_DEAD)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config, utils};
+    use anyhow::anyhow;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct FakeMailer {
+        fail: bool,
+        sent: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Mailer for FakeMailer {
+        fn send(&self, mail: &Mail) -> Result<()> {
+            if self.fail {
+                return Err(anyhow!("connection refused"));
+            }
+            self.sent.lock().unwrap().push(mail.recipient.clone());
+            Ok(())
+        }
+    }
+
+    fn new_mail(recipient: &str) -> NewMail {
+        NewMail {
+            recipient: recipient.to_string(),
+            subject: "subject".to_string(),
+            body: "body".to_string(),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn enqueue_test() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+
+        enqueue(&pool, &new_mail("test@gmail.com")).await.unwrap();
+
+        let mails = sqlx::query!("SELECT * FROM email_outbox")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(1, mails.len());
+        assert_eq!("test@gmail.com".to_string(), mails[0].recipient);
+        assert_eq!(STATUS_PENDING, mails[0].status);
+        assert_eq!(0, mails[0].attempts);
+        assert!(mails[0].sent_at.is_none());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn claim_due_test() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let later = Utc::now() + Duration::hours(1);
+        enqueue(&pool, &new_mail("due@gmail.com")).await.unwrap();
+        enqueue(&pool, &new_mail("later@gmail.com")).await.unwrap();
+        sqlx::query(
+            "UPDATE email_outbox SET next_attempt_at = $1 WHERE recipient = 'later@gmail.com'",
+        )
+        .bind(later)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let claimed = claim_due(&pool, 10).await.unwrap();
+        assert_eq!(1, claimed.len());
+        assert_eq!("due@gmail.com".to_string(), claimed[0].recipient);
+
+        // check a claimed mail is not handed out twice
+        let claimed = claim_due(&pool, 10).await.unwrap();
+        assert_eq!(0, claimed.len());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn mark_failed_retry() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        enqueue(&pool, &new_mail("test@gmail.com")).await.unwrap();
+        let mail = claim_due(&pool, 1).await.unwrap().remove(0);
+
+        mark_failed(&pool, &mail, "connection refused")
+            .await
+            .unwrap();
+
+        let row = sqlx::query!("SELECT * FROM email_outbox")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(STATUS_PENDING, row.status);
+        assert_eq!(1, row.attempts);
+        assert_eq!(Some("connection refused".to_string()), row.last_error);
+        assert!(row.next_attempt_at > Utc::now());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn mark_failed_dead() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        enqueue(&pool, &new_mail("test@gmail.com")).await.unwrap();
+        let mut mail = claim_due(&pool, 1).await.unwrap().remove(0);
+        mail.attempts = MAX_ATTEMPTS - 1;
+
+        mark_failed(&pool, &mail, "connection refused")
+            .await
+            .unwrap();
+
+        let row = sqlx::query!("SELECT * FROM email_outbox")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(STATUS_DEAD, row.status);
+        assert_eq!(MAX_ATTEMPTS, row.attempts);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[test]
+    fn backoff_test() {
+        assert_eq!(Duration::seconds(30), backoff(1));
+        assert_eq!(Duration::seconds(60), backoff(2));
+        assert_eq!(Duration::seconds(120), backoff(3));
+        assert_eq!(Duration::hours(6), backoff(20));
+        assert_eq!(Duration::hours(6), backoff(i32::MAX));
+    }
+
+    #[actix_rt::test]
+    async fn deliver_due_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mailer = FakeMailer::default();
+        enqueue(&pool, &new_mail("first@gmail.com")).await.unwrap();
+        enqueue(&pool, &new_mail("second@gmail.com")).await.unwrap();
+
+        let actual = deliver_due(&pool, &mailer).await.unwrap();
+        assert_eq!(2, actual);
+        assert_eq!(
+            vec![
+                "first@gmail.com".to_string(),
+                "second@gmail.com".to_string()
+            ],
+            *mailer.sent.lock().unwrap()
+        );
+
+        let rows = sqlx::query!("SELECT * FROM email_outbox")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert!(rows
+            .iter()
+            .all(|r| r.status == STATUS_SENT && r.sent_at.is_some()));
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn deliver_due_failed() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mailer = FakeMailer {
+            fail: true,
+            ..FakeMailer::default()
+        };
+        enqueue(&pool, &new_mail("test@gmail.com")).await.unwrap();
+
+        let actual = deliver_due(&pool, &mailer).await.unwrap();
+        assert_eq!(0, actual);
+
+        let row = sqlx::query!("SELECT * FROM email_outbox")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(STATUS_PENDING, row.status);
+        assert_eq!(1, row.attempts);
+        assert_eq!(Some("connection refused".to_string()), row.last_error);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn find_dead_letters_test() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        enqueue(&pool, &new_mail("dead@gmail.com")).await.unwrap();
+        enqueue(&pool, &new_mail("pending@gmail.com"))
+            .await
+            .unwrap();
+        sqlx::query("UPDATE email_outbox SET status = $1, attempts = $2, last_error = 'connection refused' WHERE recipient = 'dead@gmail.com'")
+            .bind(STATUS_DEAD)
+            .bind(MAX_ATTEMPTS)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let actual = find_dead_letters(&pool).await.unwrap();
+        assert_eq!(1, actual.len());
+        assert_eq!("dead@gmail.com".to_string(), actual[0].recipient);
+        assert_eq!(MAX_ATTEMPTS, actual[0].attempts);
+        assert_eq!(Some("connection refused".to_string()), actual[0].last_error);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn retry_dead_letter_test() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        enqueue(&pool, &new_mail("dead@gmail.com")).await.unwrap();
+        sqlx::query("UPDATE email_outbox SET status = $1, attempts = $2")
+            .bind(STATUS_DEAD)
+            .bind(MAX_ATTEMPTS)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let id = find_dead_letters(&pool).await.unwrap()[0].id;
+
+        assert!(retry_dead_letter(&pool, id).await.unwrap());
+        // check it is not a dead letter any more
+        assert!(!retry_dead_letter(&pool, id).await.unwrap());
+
+        let claimed = claim_due(&pool, 10).await.unwrap();
+        assert_eq!(1, claimed.len());
+        assert_eq!(0, claimed[0].attempts);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+}
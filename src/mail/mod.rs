@@ -0,0 +1,52 @@
+pub mod handler;
+pub mod infrastructures;
+pub mod model;
+pub mod templates;
+
+use crate::config;
+use anyhow::Result;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+use model::Mail;
+
+const SENDER: &str = "Competitive Programming Review Admin <info@granddaifuku.com>";
+
+// Sends a single mail. Implementations are called from the blocking thread pool.
+pub trait Mailer: Clone + Send + 'static {
+    fn send(&self, mail: &Mail) -> Result<()>;
+}
+
+#[derive(Debug, Clone)]
+pub struct SmtpMailer {
+    username: String,
+    password: String,
+    relay: String,
+}
+
+impl SmtpMailer {
+    pub fn new(config: &config::Config) -> SmtpMailer {
+        SmtpMailer {
+            username: config.smtp_username.clone(),
+            password: config.smtp_password.clone(),
+            relay: config.mailer.clone(),
+        }
+    }
+}
+
+impl Mailer for SmtpMailer {
+    fn send(&self, mail: &Mail) -> Result<()> {
+        let email = Message::builder()
+            .from(SENDER.parse()?)
+            .to(mail.recipient.parse()?)
+            .subject(&mail.subject)
+            .body(mail.body.clone())?;
+        let creds = Credentials::new(self.username.clone(), self.password.clone());
+
+        let mailer = SmtpTransport::starttls_relay(&self.relay)?
+            .credentials(creds)
+            .build();
+        mailer.send(&email)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,33 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+// the delivery status stored in `email_outbox.status`
+pub const STATUS_PENDING: i16 = 0;
+pub const STATUS_SENT: i16 = 1;
+pub const STATUS_DEAD: i16 = 2;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewMail {
+    pub recipient: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow, PartialEq)]
+pub struct Mail {
+    pub id: i32,
+    pub recipient: String,
+    pub subject: String,
+    pub body: String,
+    pub attempts: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, PartialEq)]
+pub struct DeadLetter {
+    pub id: i32,
+    pub recipient: String,
+    pub subject: String,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
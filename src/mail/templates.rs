@@ -0,0 +1,240 @@
+use super::model::NewMail;
+use crate::digests::model::{DigestReview, Period};
+use uuid::Uuid;
+
+// the reviews listed in a digest at most, the rest only being counted
+const DIGEST_MAX_REVIEWS: usize = 30;
+// the characters of a comment quoted in a mention mail at most
+const MENTION_MAX_CHARS: usize = 200;
+
+pub fn sign_up(user_name: &str, mail_address: &str, uid: &Uuid) -> NewMail {
+    NewMail {
+        recipient: mail_address.to_string(),
+        subject: "[DO NOT REPLY] SIGN-UP".to_string(),
+        body: format!(
+            "Hi {}! Verify your account by clicking on https://verify/{}",
+            user_name, uid
+        ),
+    }
+}
+
+pub fn email_change(user_name: &str, mail_address: &str, token: &Uuid) -> NewMail {
+    NewMail {
+        recipient: mail_address.to_string(),
+        subject: "[DO NOT REPLY] EMAIL CHANGE".to_string(),
+        body: format!(
+            "Hi {}! Confirm your new email address by clicking on https://verify-email/{}",
+            user_name, token
+        ),
+    }
+}
+
+pub fn account_deletion(user_name: &str, mail_address: &str, token: &Uuid) -> NewMail {
+    NewMail {
+        recipient: mail_address.to_string(),
+        subject: "[DO NOT REPLY] ACCOUNT DELETION".to_string(),
+        body: format!(
+            "Hi {}! Confirm the deletion of your account by clicking on https://confirm-deletion/{}",
+            user_name, token
+        ),
+    }
+}
+
+pub fn group_invitation(
+    inviter_name: &str,
+    group_name: &str,
+    mail_address: &str,
+    token: &Uuid,
+) -> NewMail {
+    NewMail {
+        recipient: mail_address.to_string(),
+        subject: "[DO NOT REPLY] GROUP INVITATION".to_string(),
+        body: format!(
+            "Hi! {} invites you to the study group {}. Sign in with this address and join it by clicking on https://join-group/{}",
+            inviter_name, group_name, token
+        ),
+    }
+}
+
+// The comment is quoted as it was written, cut when it is long.
+pub fn mention(
+    user_name: &str,
+    mail_address: &str,
+    author: &str,
+    body: &str,
+    path: &str,
+) -> NewMail {
+    let mut quote: String = body.chars().take(MENTION_MAX_CHARS).collect();
+    if body.chars().count() > MENTION_MAX_CHARS {
+        quote.push_str("...");
+    }
+    NewMail {
+        recipient: mail_address.to_string(),
+        subject: "[DO NOT REPLY] MENTION".to_string(),
+        body: format!(
+            "Hi {}! {} mentioned you in a comment:\n\n{}\n\nRead the thread on https://{}",
+            user_name, author, quote, path
+        ),
+    }
+}
+
+// The reviews due in the period, the overdue ones included, ordered by when they are due.
+pub fn digest(
+    user_name: &str,
+    mail_address: &str,
+    frequency: &str,
+    period: &Period,
+    reviews: &[DigestReview],
+    token: &Uuid,
+) -> NewMail {
+    let (subject, when) = match frequency {
+        "weekly" => ("[DO NOT REPLY] WEEKLY REVIEW DIGEST", "this week"),
+        _ => ("[DO NOT REPLY] DAILY REVIEW DIGEST", "today"),
+    };
+    let mut lines = vec![match reviews.len() {
+        1 => format!("Hi {}! 1 review is due {}.", user_name, when),
+        n => format!("Hi {}! {} reviews are due {}.", user_name, n, when),
+    }];
+    for review in reviews.iter().take(DIGEST_MAX_REVIEWS) {
+        lines.push(if review.due_on < period.start {
+            format!(
+                "- {} {} (overdue since {})",
+                review.problem_name, review.url, review.due_on
+            )
+        } else {
+            format!(
+                "- {} {} (due {})",
+                review.problem_name, review.url, review.due_on
+            )
+        });
+    }
+    if reviews.len() > DIGEST_MAX_REVIEWS {
+        lines.push(format!("and {} more", reviews.len() - DIGEST_MAX_REVIEWS));
+    }
+    lines.push(format!(
+        "Stop these digests by clicking on https://unsubscribe-digest/{}",
+        token
+    ));
+
+    NewMail {
+        recipient: mail_address.to_string(),
+        subject: subject.to_string(),
+        body: lines.join("\n"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn sign_up_test() {
+        let uid = Uuid::new_v4();
+        let expected = NewMail {
+            recipient: "test@gmail.com".to_string(),
+            subject: "[DO NOT REPLY] SIGN-UP".to_string(),
+            body: format!(
+                "Hi test_user! Verify your account by clicking on https://verify/{}",
+                uid
+            ),
+        };
+        let actual = sign_up("test_user", "test@gmail.com", &uid);
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn email_change_test() {
+        let token = Uuid::new_v4();
+        let actual = email_change("test_user", "new@gmail.com", &token);
+        assert_eq!("new@gmail.com".to_string(), actual.recipient);
+        assert!(actual
+            .body
+            .ends_with(&format!("https://verify-email/{}", token)));
+    }
+
+    #[test]
+    fn account_deletion_test() {
+        let token = Uuid::new_v4();
+        let actual = account_deletion("test_user", "test@gmail.com", &token);
+        assert_eq!("test@gmail.com".to_string(), actual.recipient);
+        assert!(actual
+            .body
+            .ends_with(&format!("https://confirm-deletion/{}", token)));
+    }
+
+    #[test]
+    fn group_invitation_test() {
+        let token = Uuid::new_v4();
+        let actual = group_invitation("test_user", "ICPC team", "member@gmail.com", &token);
+        assert_eq!("member@gmail.com".to_string(), actual.recipient);
+        assert!(actual
+            .body
+            .contains("test_user invites you to the study group ICPC team."));
+        assert!(actual
+            .body
+            .ends_with(&format!("https://join-group/{}", token)));
+    }
+
+    #[test]
+    fn mention_test() {
+        let actual = mention(
+            "other",
+            "other@gmail.com",
+            "author",
+            "@other see the editorial",
+            "problems/1/comments",
+        );
+        assert_eq!("other@gmail.com".to_string(), actual.recipient);
+        assert_eq!(
+            "Hi other! author mentioned you in a comment:\n\n@other see the editorial\n\nRead the thread on https://problems/1/comments",
+            actual.body
+        );
+        let actual = mention("other", "other@gmail.com", "author", &"a".repeat(201), "");
+        assert!(actual.body.contains(&format!("{}...", "a".repeat(200))));
+    }
+
+    #[test]
+    fn digest_test() {
+        let token = Uuid::new_v4();
+        let period = Period {
+            start: NaiveDate::from_ymd(2021, 5, 17),
+            until: NaiveDate::from_ymd(2021, 5, 24),
+        };
+        let reviews: Vec<DigestReview> = (0..32)
+            .map(|id| DigestReview {
+                id,
+                problem_name: format!("prob_{}", id),
+                url: format!("https://yukicoder.me/problems/no/{}", id),
+                due_on: NaiveDate::from_ymd(2021, 5, 16 + (id as u32).min(1)),
+            })
+            .collect();
+        let actual = digest(
+            "test_user",
+            "test@gmail.com",
+            "weekly",
+            &period,
+            &reviews,
+            &token,
+        );
+        assert_eq!("test@gmail.com".to_string(), actual.recipient);
+        assert_eq!(
+            "[DO NOT REPLY] WEEKLY REVIEW DIGEST".to_string(),
+            actual.subject
+        );
+        let lines: Vec<&str> = actual.body.lines().collect();
+        assert_eq!("Hi test_user! 32 reviews are due this week.", lines[0]);
+        assert_eq!(
+            "- prob_0 https://yukicoder.me/problems/no/0 (overdue since 2021-05-16)",
+            lines[1]
+        );
+        assert_eq!(
+            "- prob_1 https://yukicoder.me/problems/no/1 (due 2021-05-17)",
+            lines[2]
+        );
+        assert_eq!("and 2 more", lines[31]);
+        assert!(actual
+            .body
+            .ends_with(&format!("https://unsubscribe-digest/{}", token)));
+    }
+}
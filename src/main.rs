@@ -1,12 +1,33 @@
+mod attempts;
+mod auth;
+mod bulk;
+mod calendar;
+mod collections;
+mod comments;
 mod config;
+mod diff;
+mod digests;
 mod error;
+mod groups;
+mod imports;
+mod jobs;
+mod mail;
+mod markdown;
+mod password;
+mod problems;
+mod review_sessions;
+mod reviews;
+mod stats;
+mod submissions;
+mod tags;
 mod users;
 mod utils;
+mod webhooks;
 
 #[macro_use]
 extern crate lazy_static;
 
-use actix_web::{App, HttpServer};
+use actix_web::{web, App, HttpServer};
 use anyhow::Result;
 use sqlx::postgres::PgPool;
 
@@ -15,11 +36,115 @@ use sqlx::postgres::PgPool;
 async fn main() -> Result<()> {
     let config = config::Config::new();
     let pool = PgPool::connect(&config.database_url).await?;
+    jobs::spawn_account_purge(pool.clone());
+    jobs::spawn_mail_delivery(pool.clone());
+    jobs::spawn_digest_delivery(pool.clone());
+    jobs::spawn_webhook_delivery(pool.clone());
     HttpServer::new(move || {
         App::new()
             .data(pool.clone())
+            // memos are unbounded Markdown, well beyond the default 16KB of a form
+            .app_data(web::FormConfig::default().limit(1024 * 1024))
+            // years of submission history are well beyond the default 256KB of a payload
+            .app_data(web::PayloadConfig::new(32 * 1024 * 1024))
             .service(users::handler::sign_up)
             .service(users::handler::verify_user)
+            .service(users::handler::change_password)
+            .service(users::handler::change_user_name)
+            .service(users::handler::get_profile)
+            .service(users::handler::get_preferences)
+            .service(users::handler::update_preferences)
+            .service(users::handler::change_email)
+            .service(users::handler::verify_email)
+            .service(users::handler::export_account)
+            .service(users::handler::delete_account)
+            .service(users::handler::request_account_deletion)
+            .service(users::handler::confirm_account_deletion)
+            .service(users::handler::restore_account)
+            .service(attempts::handler::start_attempt)
+            .service(attempts::handler::stop_attempt)
+            .service(attempts::handler::list_attempts)
+            .service(auth::handler::sign_in)
+            .service(auth::handler::sign_out)
+            .service(collections::handler::create_collection)
+            .service(collections::handler::list_collections)
+            .service(collections::handler::get_collection)
+            .service(collections::handler::update_collection)
+            .service(collections::handler::delete_collection)
+            .service(collections::handler::list_entries)
+            .service(collections::handler::add_entry)
+            .service(collections::handler::remove_entry)
+            .service(collections::handler::get_shared_collection)
+            .service(groups::handler::create_group)
+            .service(groups::handler::list_groups)
+            .service(groups::handler::get_group)
+            .service(groups::handler::delete_group)
+            .service(groups::handler::list_members)
+            .service(groups::handler::remove_member)
+            .service(groups::handler::invite_member)
+            .service(groups::handler::list_invitations)
+            .service(groups::handler::join_group)
+            .service(groups::handler::create_problem_set)
+            .service(groups::handler::list_problem_sets)
+            .service(groups::handler::get_problem_set)
+            .service(groups::handler::delete_problem_set)
+            .service(groups::handler::list_set_problems)
+            .service(groups::handler::add_set_problem)
+            .service(groups::handler::remove_set_problem)
+            .service(groups::handler::import_problem_set)
+            .service(groups::handler::get_progress)
+            .service(comments::handler::list_problem_comments)
+            .service(comments::handler::comment_problem)
+            .service(comments::handler::list_entry_comments)
+            .service(comments::handler::comment_entry)
+            .service(comments::handler::update_comment)
+            .service(comments::handler::delete_comment)
+            .service(calendar::handler::regenerate_feed)
+            .service(calendar::handler::revoke_feed)
+            .service(calendar::handler::get_feed)
+            .service(digests::handler::confirm_unsubscribe)
+            .service(digests::handler::unsubscribe)
+            .service(mail::handler::dead_letters)
+            .service(mail::handler::retry_dead_letter)
+            .service(bulk::handler::import_reviews)
+            .service(bulk::handler::export_reviews)
+            .service(imports::handler::import_history)
+            .service(problems::handler::find_problem)
+            .service(problems::handler::get_problem)
+            .service(problems::handler::list_challenges)
+            .service(problems::handler::update_atcoder_difficulties)
+            .service(review_sessions::handler::start_session)
+            .service(review_sessions::handler::get_current_session)
+            .service(review_sessions::handler::next_item)
+            .service(review_sessions::handler::grade_item)
+            .service(review_sessions::handler::finish_session)
+            .service(review_sessions::handler::get_summary)
+            .service(reviews::handler::create_review)
+            .service(reviews::handler::list_reviews)
+            .service(reviews::handler::search_reviews)
+            .service(reviews::handler::get_review)
+            .service(reviews::handler::update_review)
+            .service(reviews::handler::delete_review)
+            .service(reviews::handler::list_revisions)
+            .service(reviews::handler::diff_revisions)
+            .service(reviews::handler::restore_revision)
+            .service(stats::handler::get_dashboard)
+            .service(stats::handler::time_stats)
+            .service(stats::handler::time_trend)
+            .service(submissions::handler::create_submission)
+            .service(submissions::handler::list_submissions)
+            .service(submissions::handler::get_submission)
+            .service(submissions::handler::diff_submissions)
+            .service(tags::handler::list_tags)
+            .service(tags::handler::create_tag)
+            .service(tags::handler::delete_tag)
+            .service(tags::handler::attach_tag)
+            .service(tags::handler::detach_tag)
+            .service(webhooks::handler::create_webhook)
+            .service(webhooks::handler::list_webhooks)
+            .service(webhooks::handler::delete_webhook)
+            .service(webhooks::handler::send_test_event)
+            .service(webhooks::handler::list_deliveries)
     })
     .bind("127.0.0.1:8000")?
     .run()
@@ -0,0 +1,301 @@
+use pulldown_cmark::{
+    escape::escape_html, html, CodeBlockKind, CowStr, Event, Options, Parser, Tag,
+};
+
+// the math spans are taken out of the Markdown as these placeholders,
+// so that `_` and `*` in TeX are not read as emphasis
+const PLACEHOLDER_START: char = '\u{e000}';
+const PLACEHOLDER_STOP: char = '\u{e001}';
+
+lazy_static! {
+    static ref SANITIZER: ammonia::Builder<'static> = {
+        let mut builder = ammonia::Builder::default();
+        builder.add_allowed_classes("span", &["math", "math-inline", "math-display"]);
+        builder
+    };
+}
+
+#[derive(Debug, PartialEq)]
+struct Math {
+    tex: String,
+    display: bool,
+    // the span as written, put back where math is not rendered, e.g. in code
+    source: String,
+}
+
+impl Math {
+    // The TeX is left to KaTeX or MathJax on the client.
+    fn to_html(&self) -> String {
+        let mut html = String::new();
+        if self.display {
+            html.push_str(r#"<span class="math math-display">"#);
+        } else {
+            html.push_str(r#"<span class="math math-inline">"#);
+        }
+        escape_html(&mut html, &self.tex).unwrap();
+        html.push_str("</span>");
+        html
+    }
+}
+
+// Render Markdown with `$...$` and `$$...$$` math into sanitized HTML.
+pub fn render(source: &str) -> String {
+    let (text, maths) = extract_math(source);
+    let mut in_code_block = false;
+    let mut events: Vec<Event> = Vec::new();
+    for event in Parser::new_ext(
+        &text,
+        Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH,
+    ) {
+        match event {
+            Event::Text(text) if !in_code_block => events.extend(replace_math(&text, &maths)),
+            Event::Text(text) => events.push(Event::Text(restore(text, &maths))),
+            Event::Code(code) => events.push(Event::Code(restore(code, &maths))),
+            Event::Html(raw) => events.push(Event::Html(restore(raw, &maths))),
+            Event::Start(tag) => {
+                if let Tag::CodeBlock(_) = tag {
+                    in_code_block = true;
+                }
+                events.push(Event::Start(restore_tag(tag, &maths)));
+            }
+            Event::End(tag) => {
+                if let Tag::CodeBlock(_) = tag {
+                    in_code_block = false;
+                }
+                events.push(Event::End(restore_tag(tag, &maths)));
+            }
+            event => events.push(event),
+        }
+    }
+
+    let mut unsafe_html = String::new();
+    html::push_html(&mut unsafe_html, events.into_iter());
+    SANITIZER.clean(&unsafe_html).to_string()
+}
+
+// Replace the math spans by placeholders. The rules follow pandoc: the opening `$` must be
+// followed by a non-space, the closing one preceded by a non-space and not followed by a digit.
+fn extract_math(source: &str) -> (String, Vec<Math>) {
+    let chars: Vec<char> = source.chars().collect();
+    let mut text = String::with_capacity(source.len());
+    let mut maths: Vec<Math> = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if i + 1 < chars.len() => {
+                text.push(chars[i]);
+                text.push(chars[i + 1]);
+                i += 2;
+            }
+            // code spans and fences are copied as they are
+            '`' => {
+                let run = count(&chars, i, '`');
+                let end = find_backticks(&chars, i + run, run).unwrap_or(i + run);
+                text.extend(&chars[i..end]);
+                i = end;
+            }
+            '$' => match find_math(&chars, i) {
+                Some((end, math)) => {
+                    text.push(PLACEHOLDER_START);
+                    text.push_str(&maths.len().to_string());
+                    text.push(PLACEHOLDER_STOP);
+                    maths.push(math);
+                    i = end;
+                }
+                None => {
+                    text.push('$');
+                    i += 1;
+                }
+            },
+            c => {
+                text.push(c);
+                i += 1;
+            }
+        }
+    }
+
+    (text, maths)
+}
+
+fn count(chars: &[char], from: usize, c: char) -> usize {
+    chars[from..].iter().take_while(|&&x| x == c).count()
+}
+
+// the end of the run of exactly `run` backticks closing a code span
+fn find_backticks(chars: &[char], from: usize, run: usize) -> Option<usize> {
+    let mut i = from;
+    while i < chars.len() {
+        if chars[i] == '`' {
+            let n = count(chars, i, '`');
+            if n == run {
+                return Some(i + n);
+            }
+            i += n;
+        } else {
+            i += 1;
+        }
+    }
+    None
+}
+
+// the end of the math span opened at `start` and the span itself
+fn find_math(chars: &[char], start: usize) -> Option<(usize, Math)> {
+    let display = chars.get(start + 1) == Some(&'$');
+    let delimiter = if display { 2 } else { 1 };
+    let from = start + delimiter;
+    // display math may start on the next line
+    let opened = match chars.get(from) {
+        Some('$') | None => false,
+        Some(c) => display || !c.is_whitespace(),
+    };
+    if !opened {
+        return None;
+    }
+
+    let mut i = from;
+    while i < chars.len() {
+        match chars[i] {
+            // a math span never crosses a paragraph nor a code span
+            '\n' if chars.get(i + 1) == Some(&'\n') => return None,
+            '`' => return None,
+            '\\' => i += 2,
+            '$' => {
+                let closes = if display {
+                    chars.get(i + 1) == Some(&'$')
+                } else {
+                    !chars[i - 1].is_whitespace()
+                        && !chars.get(i + 1).is_some_and(|c| c.is_ascii_digit())
+                };
+                if !closes {
+                    i += 1;
+                    continue;
+                }
+                let end = i + delimiter;
+                let math = Math {
+                    tex: chars[from..i].iter().collect::<String>().trim().to_string(),
+                    display,
+                    source: chars[start..end].iter().collect(),
+                };
+                return Some((end, math));
+            }
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+// Split a text at its placeholders into the text and the rendered math.
+fn replace_math<'a>(text: &str, maths: &[Math]) -> Vec<Event<'a>> {
+    let mut events: Vec<Event> = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(PLACEHOLDER_START) {
+        let stop = match rest[start..].find(PLACEHOLDER_STOP) {
+            Some(stop) => start + stop,
+            None => break,
+        };
+        let index = &rest[start + PLACEHOLDER_START.len_utf8()..stop];
+        let math = match index.parse::<usize>().ok().and_then(|i| maths.get(i)) {
+            Some(math) => math,
+            None => break,
+        };
+        if start > 0 {
+            events.push(Event::Text(CowStr::from(rest[..start].to_string())));
+        }
+        events.push(Event::Html(CowStr::from(math.to_html())));
+        rest = &rest[stop + PLACEHOLDER_STOP.len_utf8()..];
+    }
+    if !rest.is_empty() {
+        events.push(Event::Text(CowStr::from(rest.to_string())));
+    }
+    events
+}
+
+// Put the math spans back as they were written.
+fn restore<'a>(text: CowStr<'a>, maths: &[Math]) -> CowStr<'a> {
+    if !text.contains(PLACEHOLDER_START) {
+        return text;
+    }
+    let mut restored = text.to_string();
+    for (i, math) in maths.iter().enumerate() {
+        let placeholder = format!("{}{}{}", PLACEHOLDER_START, i, PLACEHOLDER_STOP);
+        restored = restored.replace(&placeholder, &math.source);
+    }
+    CowStr::from(restored)
+}
+
+fn restore_tag<'a>(tag: Tag<'a>, maths: &[Math]) -> Tag<'a> {
+    match tag {
+        Tag::CodeBlock(CodeBlockKind::Fenced(info)) => {
+            Tag::CodeBlock(CodeBlockKind::Fenced(restore(info, maths)))
+        }
+        Tag::Link(kind, url, title) => Tag::Link(kind, restore(url, maths), restore(title, maths)),
+        Tag::Image(kind, url, title) => {
+            Tag::Image(kind, restore(url, maths), restore(title, maths))
+        }
+        tag => tag,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_markdown() {
+        assert_eq!(
+            "<h2>Idea</h2>\n<p>use <strong>binary search</strong> on <code>ans</code></p>\n",
+            render("## Idea\nuse **binary search** on `ans`")
+        );
+    }
+
+    #[test]
+    fn render_math() {
+        assert_eq!(
+            "<p>sum <span class=\"math math-inline\">a_i * b_i</span> in <span class=\"math math-inline\">O(n \\log n)</span></p>\n",
+            render("sum $a_i * b_i$ in $O(n \\log n)$")
+        );
+        assert_eq!(
+            "<p><span class=\"math math-display\">\\sum_{i=1}^n i &lt; 2^n</span></p>\n",
+            render("$$\n\\sum_{i=1}^n i < 2^n\n$$")
+        );
+    }
+
+    #[test]
+    fn render_dollars_as_text() {
+        // prices, escaped dollars and dollars in code are not math
+        assert_eq!(
+            "<p>$5 and $10, $x$ costs <code>$y$</code></p>\n",
+            render("$5 and $10, \\$x\\$ costs `$y$`")
+        );
+        assert_eq!(
+            "<pre><code>echo $a$\n</code></pre>\n",
+            render("~~~\necho $a$\n~~~")
+        );
+    }
+
+    #[test]
+    fn render_sanitized() {
+        assert_eq!(
+            "hi\n<p><a href=\"https://atcoder.jp\" rel=\"noopener noreferrer\">x</a></p>\n",
+            render("<script>alert(1)</script>hi\n\n<a href=\"https://atcoder.jp\" onclick=\"alert(1)\">x</a>")
+        );
+        assert_eq!(
+            "<p><a rel=\"noopener noreferrer\">x</a> <span class=\"\"><span class=\"math math-inline\">a</span></span></p>\n",
+            render("[x](javascript:alert(1)) <span class=\"evil\">$a$</span>")
+        );
+    }
+
+    #[test]
+    fn extract_math_spans() {
+        let (text, maths) = extract_math("a $x^2$ b");
+        assert_eq!("a \u{e000}0\u{e001} b".to_string(), text);
+        assert_eq!(
+            vec![Math {
+                tex: "x^2".to_string(),
+                display: false,
+                source: "$x^2$".to_string(),
+            }],
+            maths
+        );
+    }
+}
@@ -0,0 +1,288 @@
+use crate::config;
+use actix_web::web;
+use anyhow::Result;
+use derive_more::Display;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use validator::ValidationError;
+
+// bcrypt ignores everything after the first 72 bytes
+const MAX_PASSWORD_BYTES: usize = 72;
+
+// character pool sizes used to estimate the entropy
+const LOWERCASE_POOL: u32 = 26;
+const UPPERCASE_POOL: u32 = 26;
+const DIGIT_POOL: u32 = 10;
+const SYMBOL_POOL: u32 = 33;
+const NON_ASCII_POOL: u32 = 100;
+
+lazy_static! {
+    pub static ref PASSWORD_POLICY: PasswordPolicy =
+        PasswordPolicy::from_config(&config::Config::new());
+    static ref BCRYPT_COST: u32 = config::Config::new().bcrypt_cost;
+    // verified against when there is no hash to verify, so that it takes as long as with one
+    pub static ref DUMMY_HASH: String =
+        bcrypt::hash("dummy password never matched", *BCRYPT_COST).unwrap();
+    static ref COMMON_PASSWORDS: HashSet<&'static str> = include_str!("common_passwords.txt")
+        .lines()
+        .filter(|line| !line.is_empty())
+        .collect();
+}
+
+#[derive(Debug, Display, PartialEq)]
+pub enum PolicyViolation {
+    #[display(fmt = "password must be at least {} characters long", _0)]
+    Short(usize),
+
+    #[display(fmt = "password must be at most {} bytes long", _0)]
+    Long(usize),
+
+    #[display(
+        fmt = "password is too easy to guess (estimated {} bits, {} required)",
+        _0,
+        _1
+    )]
+    Weak(u32, u32),
+
+    #[display(fmt = "password is one of the most commonly used passwords")]
+    Common,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct PasswordPolicy {
+    pub min_length: usize,
+    pub min_entropy_bits: u32,
+}
+
+impl PasswordPolicy {
+    pub fn from_config(config: &config::Config) -> PasswordPolicy {
+        PasswordPolicy {
+            min_length: config.password_min_length,
+            min_entropy_bits: config.password_min_entropy_bits,
+        }
+    }
+
+    // Every rule is checked so that the user can fix all of them at once.
+    pub fn check(&self, password: &str) -> Vec<PolicyViolation> {
+        let mut violations = Vec::new();
+        if password.chars().count() < self.min_length {
+            violations.push(PolicyViolation::Short(self.min_length));
+        }
+        if password.len() > MAX_PASSWORD_BYTES {
+            violations.push(PolicyViolation::Long(MAX_PASSWORD_BYTES));
+        }
+        let entropy = estimate_entropy(password) as u32;
+        if entropy < self.min_entropy_bits {
+            violations.push(PolicyViolation::Weak(entropy, self.min_entropy_bits));
+        }
+        if COMMON_PASSWORDS.contains(password.to_lowercase().as_str()) {
+            violations.push(PolicyViolation::Common);
+        }
+
+        violations
+    }
+}
+
+// Estimate the entropy in bits from the character classes in use.
+// Repeated and sequential characters ("aaa", "abc", "321") add only a single bit each.
+pub fn estimate_entropy(password: &str) -> f64 {
+    let chars: Vec<char> = password.chars().collect();
+    let mut pool = 0;
+    if chars.iter().any(|c| c.is_ascii_lowercase()) {
+        pool += LOWERCASE_POOL;
+    }
+    if chars.iter().any(|c| c.is_ascii_uppercase()) {
+        pool += UPPERCASE_POOL;
+    }
+    if chars.iter().any(|c| c.is_ascii_digit()) {
+        pool += DIGIT_POOL;
+    }
+    if chars.iter().any(|c| c.is_ascii_punctuation() || *c == ' ') {
+        pool += SYMBOL_POOL;
+    }
+    if chars.iter().any(|c| !c.is_ascii()) {
+        pool += NON_ASCII_POOL;
+    }
+    if pool == 0 {
+        return 0.0;
+    }
+
+    let bits_per_char = (pool as f64).log2();
+    let mut bits = 0.0;
+    for (i, c) in chars.iter().enumerate() {
+        let predictable = i > 0 && (*c as i64 - chars[i - 1] as i64).abs() <= 1;
+        bits += if predictable { 1.0 } else { bits_per_char };
+    }
+
+    bits
+}
+
+// Custom validation for the `validator` derive.
+// The failure reasons are passed on as the `reasons` parameter.
+pub fn validate_password(password: &str) -> Result<(), ValidationError> {
+    let violations = PASSWORD_POLICY.check(password);
+    if violations.is_empty() {
+        return Ok(());
+    }
+
+    let reasons: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+    let mut error = ValidationError::new("password_policy");
+    error.add_param(Cow::from("reasons"), &reasons);
+    Err(error)
+}
+
+// bcrypt takes hundreds of milliseconds by design,
+// so hashing and verification run on the blocking thread pool instead of an actix worker.
+pub async fn hash(password: &str) -> Result<String> {
+    let password = password.to_string();
+    let cost = *BCRYPT_COST;
+    let hashed = web::block(move || bcrypt::hash(password, cost)).await?;
+
+    Ok(hashed)
+}
+
+pub async fn verify(password: &str, hashed: &str) -> Result<bool> {
+    let password = password.to_string();
+    let hashed = hashed.to_string();
+    let valid = web::block(move || bcrypt::verify(password, &hashed)).await?;
+
+    Ok(valid)
+}
+
+// Whether the hash was made with another cost than the configured one, e.g. before it was raised.
+pub fn needs_rehash(hashed: &str) -> bool {
+    // a bcrypt hash looks like `$2b$12$<salt and hash>`
+    match hashed.split('$').nth(2).map(|cost| cost.parse::<u32>()) {
+        Some(Ok(cost)) => cost != *BCRYPT_COST,
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy() -> PasswordPolicy {
+        PasswordPolicy {
+            min_length: 8,
+            min_entropy_bits: 40,
+        }
+    }
+
+    #[test]
+    fn check_ok() {
+        let actual = policy().check("correct-horse-battery-staple");
+        assert!(actual.is_empty());
+    }
+
+    #[test]
+    fn check_non_ascii_passphrase() {
+        let actual = policy().check("競プロの復習は大事です");
+        assert!(actual.is_empty());
+    }
+
+    #[test]
+    fn check_short() {
+        let actual = policy().check("x7#Kp");
+        assert!(actual.contains(&PolicyViolation::Short(8)));
+    }
+
+    #[test]
+    fn check_long() {
+        let password = "aB3$".repeat(20);
+        let expected = vec![PolicyViolation::Long(MAX_PASSWORD_BYTES)];
+        let actual = policy().check(&password);
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn check_weak() {
+        let actual = policy().check("aaaaaaaaaaaa");
+        assert_eq!(1, actual.len());
+        assert!(matches!(actual[0], PolicyViolation::Weak(_, 40)));
+    }
+
+    #[test]
+    fn check_common() {
+        let actual = policy().check("Password123");
+        assert!(actual.contains(&PolicyViolation::Common));
+    }
+
+    #[test]
+    fn check_collects_every_violation() {
+        let expected = vec![
+            PolicyViolation::Short(8),
+            PolicyViolation::Weak(14, 40),
+            PolicyViolation::Common,
+        ];
+        let actual = policy().check("abc123");
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn estimate_entropy_empty() {
+        assert_eq!(0.0, estimate_entropy(""));
+    }
+
+    #[test]
+    fn estimate_entropy_sequences() {
+        // "abcdef" and "aaaaaa" are much weaker than six random lowercase letters
+        let random = estimate_entropy("qmzrxk");
+        assert!(estimate_entropy("abcdef") < random);
+        assert!(estimate_entropy("aaaaaa") < random);
+        assert!(estimate_entropy("fedcba") < random);
+    }
+
+    #[test]
+    fn estimate_entropy_character_classes() {
+        assert!(estimate_entropy("qmzrxk") < estimate_entropy("qMzRxK"));
+        assert!(estimate_entropy("qMzRxK") < estimate_entropy("q#z7xK"));
+    }
+
+    #[actix_rt::test]
+    async fn hash_and_verify() {
+        let hashed = hash("correct-horse-battery-staple").await.unwrap();
+        assert!(verify("correct-horse-battery-staple", &hashed)
+            .await
+            .unwrap());
+        assert!(!verify("wrong-horse-battery-staple", &hashed).await.unwrap());
+    }
+
+    #[actix_rt::test]
+    async fn hash_uses_configured_cost() {
+        let hashed = hash("correct-horse-battery-staple").await.unwrap();
+        assert!(hashed.starts_with(&format!("$2b${}$", *BCRYPT_COST)));
+        assert!(!needs_rehash(&hashed));
+    }
+
+    #[test]
+    fn dummy_hash_uses_configured_cost() {
+        // an unknown user name costs as much to check as a known one
+        assert!(!needs_rehash(&DUMMY_HASH));
+    }
+
+    #[test]
+    fn needs_rehash_outdated_cost() {
+        let hashed = bcrypt::hash("correct-horse-battery-staple", 4).unwrap();
+        assert!(needs_rehash(&hashed));
+    }
+
+    #[test]
+    fn needs_rehash_not_bcrypt() {
+        assert!(needs_rehash("password"));
+    }
+
+    #[test]
+    fn validate_password_reasons() {
+        let error = validate_password("").unwrap_err();
+        assert_eq!("password_policy", error.code);
+        let reasons = error.params.get("reasons").unwrap();
+        assert_eq!(
+            &serde_json::json!([
+                "password must be at least 8 characters long",
+                "password is too easy to guess (estimated 0 bits, 40 required)"
+            ]),
+            reasons
+        );
+    }
+}
@@ -0,0 +1,247 @@
+use super::infrastructures;
+use super::model::{atcoder_difficulties, ChallengeQuery, Challenges, ProblemKey, ProblemQuery};
+use crate::auth::{Admin, AuthenticatedUser};
+use crate::error::ApiError;
+use crate::users;
+use actix_web::{get, post, web, HttpResponse};
+use anyhow::Result;
+use serde_json::json;
+use sqlx::PgPool;
+
+// Look the problem up by any of its URLs, e.g. before reviewing it.
+#[get("/problems")]
+pub async fn find_problem(
+    pool: web::Data<PgPool>,
+    _: AuthenticatedUser,
+    query: web::Query<ProblemQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let key = match ProblemKey::from_url(&query.url) {
+        Some(key) => key,
+        None => return Err(ApiError::BadRequest),
+    };
+
+    match infrastructures::find_problem_by_key(pool.get_ref(), &key).await {
+        Ok(Some(problem)) => Ok(HttpResponse::Ok().json(problem)),
+        Ok(None) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[get("/problems/{id:\\d+}")]
+pub async fn get_problem(
+    pool: web::Data<PgPool>,
+    _: AuthenticatedUser,
+    web::Path(id): web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    match infrastructures::find_problem(pool.get_ref(), id).await {
+        Ok(Some(problem)) => Ok(HttpResponse::Ok().json(problem)),
+        Ok(None) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[get("/problems/challenges")]
+pub async fn list_challenges(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    query: web::Query<ChallengeQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let preferences =
+        match users::infrastructures::find_preferences(pool.get_ref(), &user.uid).await {
+            Ok(preferences) => preferences,
+            Err(_) => return Err(ApiError::InternalError),
+        };
+    let (platform, limit) = match query.parse(preferences.default_platform) {
+        Ok(q) => q,
+        Err(e) => return Err(e.into()),
+    };
+
+    let comfort_max =
+        match infrastructures::find_comfort_max(pool.get_ref(), &user.uid, platform).await {
+            Ok(max) => max,
+            Err(_) => return Err(ApiError::InternalError),
+        };
+    let problems = match comfort_max {
+        Some(max) => {
+            match infrastructures::find_challenges(pool.get_ref(), &user.uid, platform, max, limit)
+                .await
+            {
+                Ok(problems) => problems,
+                Err(_) => return Err(ApiError::InternalError),
+            }
+        }
+        None => vec![],
+    };
+
+    Ok(HttpResponse::Ok().json(Challenges {
+        platform,
+        comfort_max,
+        problems,
+    }))
+}
+
+// The body is the `problem-models.json` of AtCoder Problems as it is.
+#[post("/problems/difficulties/atcoder")]
+pub async fn update_atcoder_difficulties(
+    pool: web::Data<PgPool>,
+    _: Admin,
+    body: web::Bytes,
+) -> Result<HttpResponse, ApiError> {
+    let difficulties = match atcoder_difficulties(&body) {
+        Ok(difficulties) => difficulties,
+        Err(_) => return Err(ApiError::BadRequest),
+    };
+
+    match infrastructures::update_difficulties(pool.get_ref(), 1, &difficulties).await {
+        Ok(updated) => Ok(HttpResponse::Ok().json(json!({ "updated": updated }))),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::problems::model::Problem;
+    use crate::reviews::model::NewReview;
+    use crate::{auth, config, reviews, utils};
+    use actix_web::body::Body;
+    use actix_web::{test, App};
+    use uuid::Uuid;
+
+    #[actix_rt::test]
+    async fn find_problem_by_url() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .data(pool.clone())
+                .service(find_problem)
+                .service(get_problem),
+        )
+        .await;
+        let uid = Uuid::new_v4();
+        let token = auth::infrastructures::create_session(&pool, &uid)
+            .await
+            .unwrap();
+        let review = NewReview {
+            problem_name: "A - Review".to_string(),
+            url: "https://codeforces.com/contest/1520/problem/A".to_string(),
+            memo: None,
+            platform: 2,
+            difficulty: None,
+            solved_without_hints: false,
+        };
+        let review = reviews::infrastructures::create_review(&pool, &uid, &review)
+            .await
+            .unwrap();
+
+        let req = test::TestRequest::get()
+            .uri("/problems?url=https%3A%2F%2Fcodeforces.com%2Fproblemset%2Fproblem%2F1520%2FA")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let problem: Problem = test::read_body_json(resp).await;
+        assert_eq!(review.problem_id, Some(problem.id));
+        assert_eq!("1520A".to_string(), problem.external_id);
+        assert_eq!(1, problem.reviewers);
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/problems/{}", problem.id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+
+        let req = test::TestRequest::get()
+            .uri("/problems?url=https%3A%2F%2Fcodeforces.com%2Fcontest%2F1520%2Fproblem%2FB")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(404, resp.status());
+
+        let req = test::TestRequest::get()
+            .uri("/problems?url=https%3A%2F%2Fexample.com")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(400, resp.status());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn list_challenges_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .data(pool.clone())
+                .service(list_challenges)
+                .service(update_atcoder_difficulties),
+        )
+        .await;
+        let uid = Uuid::new_v4();
+        let token = auth::infrastructures::create_session(&pool, &uid)
+            .await
+            .unwrap();
+        for (task, solved_without_hints) in [("abc200_a", true), ("abc200_b", false)].iter() {
+            let review = NewReview {
+                problem_name: task.to_string(),
+                url: format!("https://atcoder.jp/contests/abc200/tasks/{}", task),
+                memo: None,
+                platform: 1,
+                difficulty: None,
+                solved_without_hints: *solved_without_hints,
+            };
+            reviews::infrastructures::create_review(&pool, &uid, &review)
+                .await
+                .unwrap();
+        }
+
+        let difficulties = r#"{"abc200_a": {"difficulty": 800}, "abc200_b": {"difficulty": 950}, "abc200_c": {"difficulty": 1000}}"#;
+        // the catalog is only updated by the operator
+        let req = test::TestRequest::post()
+            .uri("/problems/difficulties/atcoder")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_payload(difficulties)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(403, resp.status());
+        let resp_body = test::read_body(resp).await;
+        assert_eq!(
+            json!({"code": 403, "message": "forbidden"}).to_string(),
+            resp_body
+        );
+        let req = test::TestRequest::post()
+            .uri("/problems/difficulties/atcoder")
+            .header("Authorization", "Bearer dummy_admin_token")
+            .set_payload(difficulties)
+            .to_request();
+        let mut resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let resp_body = resp.take_body();
+        let resp_body = resp_body.as_ref().unwrap();
+        assert_eq!(&Body::from(json!({"updated": 2})), resp_body);
+
+        let req = test::TestRequest::get()
+            .uri("/problems/challenges?platform=1")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let challenges: Challenges = test::read_body_json(resp).await;
+        assert_eq!(Some(800), challenges.comfort_max);
+        assert_eq!(1, challenges.problems.len());
+        assert_eq!(Some(950), challenges.problems[0].difficulty);
+
+        let req = test::TestRequest::get()
+            .uri("/problems/challenges?platform=3")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(400, resp.status());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+}
@@ -0,0 +1,249 @@
+use super::model::{Problem, ProblemKey};
+use anyhow::Result;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+// the share of the comfortable solves below the top of the comfort band
+const COMFORT_PERCENTILE: f64 = 0.8;
+// how far above the comfort band the challenges reach, in rating points
+const STRETCH: i32 = 200;
+
+const SELECT_PROBLEMS: &str = r#"SELECT p.id, p.platform, p.external_id, p.title, p.url, p.contest, p.difficulty, p.tags,
+    COUNT(DISTINCT r.uid) AS reviewers
+    FROM problems p
+    LEFT JOIN reviews r ON r.problem_id = p.id"#;
+
+// Add the problem to the catalog unless it is there, and return its id.
+// Everything written is derived from the key, as the users are not trusted with the catalog.
+pub async fn register_problem(tx: &mut Transaction<'_, Postgres>, key: &ProblemKey) -> Result<i32> {
+    // the no-op update makes the id returned on conflict as well
+    let (id,): (i32,) = sqlx::query_as(
+        r#"INSERT INTO problems (platform, external_id, title, url, contest) VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (platform, external_id) DO UPDATE SET platform = EXCLUDED.platform RETURNING id"#,
+    )
+    .bind(key.platform)
+    .bind(&key.external_id)
+    .bind(key.title())
+    .bind(key.url())
+    .bind(&key.contest)
+    .fetch_one(tx)
+    .await?;
+
+    Ok(id)
+}
+
+// Set the difficulties of the problems in the catalog, given by their IDs on the platform.
+// The problems nobody has reviewed yet are not in the catalog and are left out.
+pub async fn update_difficulties(
+    pool: &PgPool,
+    platform: i16,
+    difficulties: &[(String, i32)],
+) -> Result<u64> {
+    let (ids, values): (Vec<String>, Vec<i32>) = difficulties.iter().cloned().unzip();
+    let result = sqlx::query(
+        r#"UPDATE problems p SET difficulty = d.difficulty
+        FROM UNNEST($1::VARCHAR[], $2::INTEGER[]) AS d (external_id, difficulty)
+        WHERE p.platform = $3 AND p.external_id = d.external_id"#,
+    )
+    .bind(&ids)
+    .bind(&values)
+    .bind(platform)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+// The top of the band of difficulties the user is comfortable with on the platform, judged from
+// the problems solved without hints and not rated harder than 3.
+pub async fn find_comfort_max(pool: &PgPool, uid: &Uuid, platform: i16) -> Result<Option<i32>> {
+    let (comfort_max,): (Option<f64>,) = sqlx::query_as(
+        r#"SELECT PERCENTILE_CONT($1) WITHIN GROUP (ORDER BY p.difficulty)
+        FROM reviews r JOIN problems p ON p.id = r.problem_id
+        WHERE r.uid = $2 AND p.platform = $3 AND p.difficulty IS NOT NULL
+        AND r.solved_without_hints AND COALESCE(r.difficulty, 3) <= 3"#,
+    )
+    .bind(COMFORT_PERCENTILE)
+    .bind(uid)
+    .bind(platform)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(comfort_max.map(|max| max.round() as i32))
+}
+
+// The problems of the catalog just above the comfort band, easiest first,
+// leaving out the ones the user has already solved without hints.
+pub async fn find_challenges(
+    pool: &PgPool,
+    uid: &Uuid,
+    platform: i16,
+    comfort_max: i32,
+    limit: i64,
+) -> Result<Vec<Problem>> {
+    let sql = format!(
+        r#"{} WHERE p.platform = $1 AND p.difficulty > $2 AND p.difficulty <= $2 + $3
+        AND NOT EXISTS (SELECT 1 FROM reviews m WHERE m.problem_id = p.id AND m.uid = $4 AND m.solved_without_hints)
+        GROUP BY p.id ORDER BY p.difficulty, p.id LIMIT $5"#,
+        SELECT_PROBLEMS
+    );
+    let problems = sqlx::query_as::<_, Problem>(&sql)
+        .bind(platform)
+        .bind(comfort_max)
+        .bind(STRETCH)
+        .bind(uid)
+        .bind(limit)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(problems)
+}
+
+pub async fn find_problem(pool: &PgPool, id: i32) -> Result<Option<Problem>> {
+    let sql = format!("{} WHERE p.id = $1 GROUP BY p.id", SELECT_PROBLEMS);
+    let problem = sqlx::query_as::<_, Problem>(&sql)
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(problem)
+}
+
+pub async fn find_problem_by_key(pool: &PgPool, key: &ProblemKey) -> Result<Option<Problem>> {
+    let sql = format!(
+        "{} WHERE p.platform = $1 AND p.external_id = $2 GROUP BY p.id",
+        SELECT_PROBLEMS
+    );
+    let problem = sqlx::query_as::<_, Problem>(&sql)
+        .bind(key.platform)
+        .bind(&key.external_id)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(problem)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reviews::infrastructures::create_review;
+    use crate::reviews::model::NewReview;
+    use crate::{config, utils};
+
+    #[actix_rt::test]
+    async fn problems_deduplicated() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        let another_uid = Uuid::new_v4();
+        let url = "https://atcoder.jp/contests/abc200/tasks/abc200_a";
+
+        let first = create_review(
+            &pool,
+            &uid,
+            &NewReview {
+                url: url.to_string(),
+                ..utils::new_review("test_prob_name")
+            },
+        )
+        .await
+        .unwrap();
+        let second = create_review(
+            &pool,
+            &uid,
+            &NewReview {
+                url: format!("{}/", url),
+                ..utils::new_review("test_prob_name")
+            },
+        )
+        .await
+        .unwrap();
+        let another = create_review(
+            &pool,
+            &another_uid,
+            &NewReview {
+                url: url.to_string(),
+                ..utils::new_review("test_prob_name")
+            },
+        )
+        .await
+        .unwrap();
+        let other = create_review(
+            &pool,
+            &uid,
+            &NewReview {
+                url: "https://example.com/a".to_string(),
+                ..utils::new_review("test_prob_name")
+            },
+        )
+        .await
+        .unwrap();
+
+        let id = first.problem_id.unwrap();
+        assert_eq!(Some(id), second.problem_id);
+        assert_eq!(Some(id), another.problem_id);
+        assert_eq!(None, other.problem_id);
+
+        let expected = Some(Problem {
+            id,
+            platform: 1,
+            external_id: "abc200_a".to_string(),
+            title: "abc200_a".to_string(),
+            url: url.to_string(),
+            contest: Some("abc200".to_string()),
+            difficulty: None,
+            tags: vec![],
+            reviewers: 2,
+        });
+        assert_eq!(expected, find_problem(&pool, id).await.unwrap());
+        let key = ProblemKey::from_url(url).unwrap();
+        assert_eq!(expected, find_problem_by_key(&pool, &key).await.unwrap());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn find_challenges_test() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        let mut ids = Vec::new();
+        for (i, solved_without_hints) in [true, true, false, false, false].iter().enumerate() {
+            let mut review = NewReview {
+                url: format!("https://atcoder.jp/contests/abc200/tasks/abc200_{}", i),
+                ..utils::new_review("test_prob_name")
+            };
+            review.solved_without_hints = *solved_without_hints;
+            ids.push(create_review(&pool, &uid, &review).await.unwrap().id);
+        }
+        // another user is reviewing a harder one
+        create_review(
+            &pool,
+            &Uuid::new_v4(),
+            &NewReview {
+                url: "https://atcoder.jp/contests/abc200/tasks/abc200_5".to_string(),
+                ..utils::new_review("test_prob_name")
+            },
+        )
+        .await
+        .unwrap();
+        let difficulties: Vec<(String, i32)> = vec![1000, 1200, 1300, 1400, 1500, 1350]
+            .into_iter()
+            .enumerate()
+            .map(|(i, d)| (format!("abc200_{}", i), d))
+            .collect();
+        let updated = update_difficulties(&pool, 1, &difficulties).await.unwrap();
+        assert_eq!(6, updated);
+
+        // 0.8 of the way from 1000 to 1200
+        let comfort_max = find_comfort_max(&pool, &uid, 1).await.unwrap();
+        assert_eq!(Some(1160), comfort_max);
+        assert_eq!(None, find_comfort_max(&pool, &uid, 2).await.unwrap());
+
+        let challenges = find_challenges(&pool, &uid, 1, 1160, 10).await.unwrap();
+        let names: Vec<&str> = challenges.iter().map(|p| p.external_id.as_str()).collect();
+        assert_eq!(vec!["abc200_2", "abc200_5"], names);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+}
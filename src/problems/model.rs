@@ -0,0 +1,322 @@
+use crate::reviews::model::invalid;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use validator::ValidationErrors;
+
+// the number of challenges listed when no limit is given
+const DEFAULT_CHALLENGE_LIMIT: i64 = 20;
+const MAX_CHALLENGE_LIMIT: i64 = 100;
+
+lazy_static! {
+    static ref RE_ATCODER: Regex =
+        Regex::new(r"^https?://atcoder\.jp/contests/([\w-]+)/tasks/([\w-]+)/?$").unwrap();
+    static ref RE_CODEFORCES: Regex = Regex::new(
+        r"^https?://(?:www\.)?codeforces\.com/(?:contest/(\d+)/problem|problemset/problem/(\d+))/([A-Za-z]\d?)/?$"
+    )
+    .unwrap();
+    static ref RE_AOJ: Regex = Regex::new(
+        r"^https?://(?:onlinejudge\.u-aizu\.ac\.jp/(?:problems|courses/.+)/|judge\.u-aizu\.ac\.jp/onlinejudge/description\.jsp\?id=)(\w+)/?$"
+    )
+    .unwrap();
+    static ref RE_YUKICODER: Regex =
+        Regex::new(r"^https?://yukicoder\.me/problems/no/(\d+)/?$").unwrap();
+}
+
+// What identifies a problem across the URLs pointing to it.
+#[derive(Debug, PartialEq)]
+pub struct ProblemKey {
+    pub platform: i16,
+    pub external_id: String,
+    pub contest: Option<String>,
+}
+
+impl ProblemKey {
+    // None when the URL is not of a problem of a known platform
+    pub fn from_url(url: &str) -> Option<ProblemKey> {
+        if let Some(caps) = RE_ATCODER.captures(url) {
+            return Some(ProblemKey {
+                platform: 1,
+                external_id: caps[2].to_string(),
+                contest: Some(caps[1].to_string()),
+            });
+        }
+        if let Some(caps) = RE_CODEFORCES.captures(url) {
+            let contest = caps.get(1).or_else(|| caps.get(2)).unwrap().as_str();
+            return Some(ProblemKey {
+                platform: 2,
+                external_id: format!("{}{}", contest, caps[3].to_uppercase()),
+                contest: Some(contest.to_string()),
+            });
+        }
+        if let Some(caps) = RE_AOJ.captures(url) {
+            return Some(ProblemKey {
+                platform: 3,
+                external_id: caps[1].to_string(),
+                contest: None,
+            });
+        }
+        if let Some(caps) = RE_YUKICODER.captures(url) {
+            return Some(ProblemKey {
+                platform: 4,
+                external_id: caps[1].to_string(),
+                contest: None,
+            });
+        }
+        None
+    }
+
+    // The title of the problem in the catalog. The names given by the users are kept with
+    // their reviews, so the catalog names a problem by its ID on the platform.
+    pub fn title(&self) -> String {
+        match self.platform {
+            4 => format!("No.{}", self.external_id),
+            _ => self.external_id.clone(),
+        }
+    }
+
+    // the URL the problem is listed with, whichever URL it was found by
+    pub fn url(&self) -> String {
+        match (self.platform, &self.contest) {
+            (1, Some(contest)) => format!(
+                "https://atcoder.jp/contests/{}/tasks/{}",
+                contest, self.external_id
+            ),
+            (2, Some(contest)) => format!(
+                "https://codeforces.com/contest/{}/problem/{}",
+                contest,
+                &self.external_id[contest.len()..]
+            ),
+            (3, _) => format!(
+                "https://onlinejudge.u-aizu.ac.jp/problems/{}",
+                self.external_id
+            ),
+            _ => format!("https://yukicoder.me/problems/no/{}", self.external_id),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ProblemQuery {
+    pub url: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, PartialEq)]
+pub struct Problem {
+    pub id: i32,
+    pub platform: i16,
+    pub external_id: String,
+    pub title: String,
+    pub url: String,
+    pub contest: Option<String>,
+    // the difficulty given by the platform, e.g. the Codeforces rating
+    pub difficulty: Option<i32>,
+    pub tags: Vec<String>,
+    // the number of users reviewing the problem
+    pub reviewers: i64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ChallengeQuery {
+    // 1: AtCoder, 2: Codeforces, the platforms giving difficulties;
+    // the default platform of the user when absent
+    pub platform: Option<String>,
+    pub limit: Option<String>,
+}
+
+impl ChallengeQuery {
+    pub fn parse(&self, default_platform: Option<i16>) -> Result<(i16, i64), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        let platform = match self.platform.as_deref().map(|p| p.parse::<i16>()) {
+            None => default_platform.map(Ok),
+            given => given,
+        };
+        let platform = match platform {
+            Some(Ok(platform)) if platform == 1 || platform == 2 => platform,
+            _ => {
+                invalid(&mut errors, "platform", "platform must be 1 or 2");
+                0
+            }
+        };
+        let limit = match self.limit.as_deref().map(|l| l.parse::<i64>()) {
+            None => DEFAULT_CHALLENGE_LIMIT,
+            Some(Ok(limit)) if (1..=MAX_CHALLENGE_LIMIT).contains(&limit) => limit,
+            Some(_) => {
+                invalid(
+                    &mut errors,
+                    "limit",
+                    &format!("limit must be a number from 1 to {}", MAX_CHALLENGE_LIMIT),
+                );
+                0
+            }
+        };
+
+        if errors.is_empty() {
+            Ok((platform, limit))
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+// The problems a little harder than the ones the user solves comfortably.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Challenges {
+    pub platform: i16,
+    // the top of the comfort band, absent until the user has solved a problem
+    // of a known difficulty without hints
+    pub comfort_max: Option<i32>,
+    pub problems: Vec<Problem>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AtCoderModel {
+    // absent for the problems too few users took
+    difficulty: Option<f64>,
+}
+
+// Read the difficulties out of the `problem-models.json` of AtCoder Problems.
+// The difficulties below 400 are clipped the way AtCoder Problems shows them, so that they stay positive.
+pub fn atcoder_difficulties(body: &[u8]) -> Result<Vec<(String, i32)>, String> {
+    let models: HashMap<String, AtCoderModel> =
+        serde_json::from_slice(body).map_err(|e| e.to_string())?;
+    let mut difficulties: Vec<(String, i32)> = models
+        .into_iter()
+        .filter_map(|(id, model)| {
+            model.difficulty.map(|d| {
+                let clipped = if d >= 400.0 {
+                    d
+                } else {
+                    400.0 / ((400.0 - d) / 400.0).exp()
+                };
+                (id, clipped.round() as i32)
+            })
+        })
+        .collect();
+    difficulties.sort();
+
+    Ok(difficulties)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(platform: i16, external_id: &str, contest: Option<&str>) -> Option<ProblemKey> {
+        Some(ProblemKey {
+            platform,
+            external_id: external_id.to_string(),
+            contest: contest.map(|c| c.to_string()),
+        })
+    }
+
+    #[test]
+    fn from_url() {
+        assert_eq!(
+            key(1, "abc200_a", Some("abc200")),
+            ProblemKey::from_url("https://atcoder.jp/contests/abc200/tasks/abc200_a")
+        );
+        assert_eq!(
+            key(2, "1520A", Some("1520")),
+            ProblemKey::from_url("https://codeforces.com/contest/1520/problem/A")
+        );
+        assert_eq!(
+            key(2, "1520F2", Some("1520")),
+            ProblemKey::from_url("https://codeforces.com/problemset/problem/1520/f2")
+        );
+        assert_eq!(
+            key(3, "ALDS1_1_A", None),
+            ProblemKey::from_url(
+                "https://onlinejudge.u-aizu.ac.jp/courses/lesson/1/ALDS1/1/ALDS1_1_A"
+            )
+        );
+        assert_eq!(
+            key(3, "0001", None),
+            ProblemKey::from_url("http://judge.u-aizu.ac.jp/onlinejudge/description.jsp?id=0001")
+        );
+        assert_eq!(
+            key(4, "1000", None),
+            ProblemKey::from_url("https://yukicoder.me/problems/no/1000/")
+        );
+        assert_eq!(
+            None,
+            ProblemKey::from_url("https://atcoder.jp/contests/abc200/submissions/1")
+        );
+        assert_eq!(None, ProblemKey::from_url("https://example.com/problems/1"));
+    }
+
+    #[test]
+    fn parse_challenge_query() {
+        let query = ChallengeQuery {
+            platform: Some("2".to_string()),
+            limit: None,
+        };
+        assert_eq!((2, DEFAULT_CHALLENGE_LIMIT), query.parse(Some(1)).unwrap());
+        assert_eq!(
+            (1, DEFAULT_CHALLENGE_LIMIT),
+            ChallengeQuery::default().parse(Some(1)).unwrap()
+        );
+        assert!(ChallengeQuery::default().parse(Some(3)).is_err());
+        assert!(ChallengeQuery::default().parse(None).is_err());
+        let query = ChallengeQuery {
+            platform: Some("4".to_string()),
+            limit: Some("0".to_string()),
+        };
+        assert_eq!(2, query.parse(None).unwrap_err().errors().len());
+    }
+
+    #[test]
+    fn read_atcoder_difficulties() {
+        let body = r#"{
+            "abc200_a": {"slope": -0.0006, "intercept": 7.5, "variance": 0.2, "difficulty": -1107, "discrimination": 0.004, "irt_loglikelihood": -0.5, "irt_users": 8000, "is_experimental": false},
+            "abc200_f": {"slope": -0.0004, "intercept": 9.8, "variance": 0.3, "difficulty": 2051.3, "discrimination": 0.004, "irt_loglikelihood": -0.5, "irt_users": 8000, "is_experimental": false},
+            "practice_1": {"is_experimental": true}
+        }"#;
+        assert_eq!(
+            vec![("abc200_a".to_string(), 9), ("abc200_f".to_string(), 2051)],
+            atcoder_difficulties(body.as_bytes()).unwrap()
+        );
+        assert!(atcoder_difficulties(b"[]").is_err());
+    }
+
+    #[test]
+    fn canonical_url() {
+        let urls = vec![
+            "https://atcoder.jp/contests/abc200/tasks/abc200_a",
+            "https://codeforces.com/contest/1520/problem/F2",
+            "https://onlinejudge.u-aizu.ac.jp/problems/ALDS1_1_A",
+            "https://yukicoder.me/problems/no/1000",
+        ];
+        for url in urls {
+            assert_eq!(url, ProblemKey::from_url(url).unwrap().url());
+        }
+        assert_eq!(
+            "https://codeforces.com/contest/1520/problem/A",
+            ProblemKey::from_url("https://www.codeforces.com/problemset/problem/1520/a")
+                .unwrap()
+                .url()
+        );
+    }
+
+    #[test]
+    fn catalog_title() {
+        let titles = vec![
+            (
+                "https://atcoder.jp/contests/abc200/tasks/abc200_a",
+                "abc200_a",
+            ),
+            (
+                "https://codeforces.com/problemset/problem/1520/f2",
+                "1520F2",
+            ),
+            (
+                "https://onlinejudge.u-aizu.ac.jp/problems/ALDS1_1_A",
+                "ALDS1_1_A",
+            ),
+            ("https://yukicoder.me/problems/no/1000", "No.1000"),
+        ];
+        for (url, title) in titles {
+            assert_eq!(title, ProblemKey::from_url(url).unwrap().title());
+        }
+    }
+}
@@ -0,0 +1,297 @@
+use super::infrastructures;
+use super::model::{GradeOutcome, NewGrade, NewSession, StartOutcome};
+use crate::auth::AuthenticatedUser;
+use crate::error::ApiError;
+use actix_web::{get, post, web, HttpResponse};
+use anyhow::Result;
+use sqlx::PgPool;
+use validator::Validate;
+
+// The session left unfinished is resumed rather than another one started.
+#[post("/review-sessions")]
+pub async fn start_session(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    form: web::Form<NewSession>,
+) -> Result<HttpResponse, ApiError> {
+    match form.validate() {
+        Ok(_) => (),
+        Err(e) => return Err(e.into()),
+    }
+
+    match infrastructures::find_current_session(pool.get_ref(), &user.uid).await {
+        Ok(Some(session)) => return Ok(HttpResponse::Ok().json(session)),
+        Ok(None) => (),
+        Err(_) => return Err(ApiError::InternalError),
+    }
+    match infrastructures::start_session(pool.get_ref(), &user.uid, form.size).await {
+        Ok(StartOutcome::Started(session)) => Ok(HttpResponse::Created().json(session)),
+        Ok(StartOutcome::NothingDue) => Err(ApiError::NotFound),
+        Ok(StartOutcome::AlreadyStarted) => Err(ApiError::Conflict),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[get("/review-sessions/current")]
+pub async fn get_current_session(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, ApiError> {
+    match infrastructures::find_current_session(pool.get_ref(), &user.uid).await {
+        Ok(Some(session)) => Ok(HttpResponse::Ok().json(session)),
+        Ok(None) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[get("/review-sessions/{id:\\d+}/next")]
+pub async fn next_item(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path(id): web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    match infrastructures::next_item(pool.get_ref(), &user.uid, id).await {
+        Ok(Some(item)) => Ok(HttpResponse::Ok().json(item)),
+        Ok(None) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[post("/review-sessions/{id:\\d+}/items/{position:\\d+}/grade")]
+pub async fn grade_item(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path((id, position)): web::Path<(i32, i16)>,
+    form: web::Form<NewGrade>,
+) -> Result<HttpResponse, ApiError> {
+    match form.validate() {
+        Ok(_) => (),
+        Err(e) => return Err(e.into()),
+    }
+
+    match infrastructures::grade_item(
+        pool.get_ref(),
+        &user.uid,
+        id,
+        position,
+        form.grade,
+        form.seconds,
+    )
+    .await
+    {
+        Ok(GradeOutcome::Recorded) => (),
+        Ok(GradeOutcome::NotFound) => return Err(ApiError::NotFound),
+        Ok(GradeOutcome::AlreadyGraded) => return Err(ApiError::Conflict),
+        Err(_) => return Err(ApiError::InternalError),
+    }
+    match infrastructures::find_session(pool.get_ref(), &user.uid, id).await {
+        Ok(Some(session)) => Ok(HttpResponse::Ok().json(session)),
+        Ok(None) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[post("/review-sessions/{id:\\d+}/finish")]
+pub async fn finish_session(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path(id): web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    match infrastructures::finish_session(pool.get_ref(), &user.uid, id).await {
+        Ok(true) => (),
+        Ok(false) => return Err(ApiError::NotFound),
+        Err(_) => return Err(ApiError::InternalError),
+    }
+    match infrastructures::find_summary(pool.get_ref(), &user.uid, id).await {
+        Ok(Some(summary)) => Ok(HttpResponse::Ok().json(summary)),
+        Ok(None) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[get("/review-sessions/{id:\\d+}/summary")]
+pub async fn get_summary(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path(id): web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    match infrastructures::find_summary(pool.get_ref(), &user.uid, id).await {
+        Ok(Some(summary)) => Ok(HttpResponse::Ok().json(summary)),
+        Ok(None) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::review_sessions::model::{Session, SessionItem, SessionSummary};
+    use crate::reviews::model::NewReview;
+    use crate::{auth, config, reviews, utils};
+    use actix_web::{test, App};
+    use chrono::{Duration, Utc};
+    use uuid::Uuid;
+
+    #[actix_rt::test]
+    async fn review_session_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .data(pool.clone())
+                .service(start_session)
+                .service(get_current_session)
+                .service(next_item)
+                .service(grade_item)
+                .service(get_summary),
+        )
+        .await;
+        let uid = Uuid::new_v4();
+        let token = auth::infrastructures::create_session(&pool, &uid)
+            .await
+            .unwrap();
+        let review = NewReview {
+            problem_name: "A - Session".to_string(),
+            url: "https://yukicoder.me/problems/no/1".to_string(),
+            memo: None,
+            platform: 4,
+            difficulty: None,
+            solved_without_hints: false,
+        };
+        let review = reviews::infrastructures::create_review(&pool, &uid, &review)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE schedules SET due_at = $1 WHERE review_id = $2")
+            .bind(Utc::now() - Duration::days(1))
+            .bind(review.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let req = test::TestRequest::post()
+            .uri("/review-sessions")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&NewSession::default())
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(201, resp.status());
+        let session: Session = test::read_body_json(resp).await;
+        assert_eq!(1, session.items);
+
+        // resumed rather than started again
+        let req = test::TestRequest::post()
+            .uri("/review-sessions")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&NewSession::default())
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let resumed: Session = test::read_body_json(resp).await;
+        assert_eq!(session.id, resumed.id);
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/review-sessions/{}/next", session.id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let item: SessionItem = test::read_body_json(resp).await;
+        assert_eq!(review.id, item.review.id);
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/review-sessions/{}/items/1/grade", session.id))
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&NewGrade {
+                grade: 6,
+                seconds: None,
+            })
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(400, resp.status());
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/review-sessions/{}/items/1/grade", session.id))
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&NewGrade {
+                grade: 4,
+                seconds: Some(90),
+            })
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let graded: Session = test::read_body_json(resp).await;
+        assert!(graded.finished_at.is_some());
+
+        let req = test::TestRequest::get()
+            .uri("/review-sessions/current")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(404, resp.status());
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/review-sessions/{}/summary", session.id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let summary: SessionSummary = test::read_body_json(resp).await;
+        assert_eq!(90, summary.total_seconds);
+        assert_eq!(Some(4.0), summary.average_grade);
+
+        // nothing is due any more
+        let req = test::TestRequest::post()
+            .uri("/review-sessions")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&NewSession::default())
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(404, resp.status());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn grade_item_of_other_user() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .data(pool.clone())
+                .service(grade_item)
+                .service(finish_session),
+        )
+        .await;
+        let token = auth::infrastructures::create_session(&pool, &Uuid::new_v4())
+            .await
+            .unwrap();
+        let (id,): (i32,) = sqlx::query_as(
+            "INSERT INTO review_sessions (uid, started_at) VALUES ($1, $2) RETURNING id",
+        )
+        .bind(Uuid::new_v4())
+        .bind(Utc::now())
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/review-sessions/{}/items/1/grade", id))
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&NewGrade {
+                grade: 3,
+                seconds: None,
+            })
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(404, resp.status());
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/review-sessions/{}/finish", id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(404, resp.status());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+}
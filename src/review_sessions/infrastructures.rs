@@ -0,0 +1,465 @@
+use super::model::{GradeOutcome, Schedule, Session, SessionItem, SessionSummary, StartOutcome};
+use crate::webhooks::model::EVENT_GRADED;
+use crate::{reviews, users, webhooks};
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use serde_json::json;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// the time measured from serving an item is capped, e.g. when it was left open overnight
+const MAX_MEASURED_SECONDS: i64 = 3600;
+
+// a session together with the number of its items drawn and graded
+const SELECT_SESSIONS: &str = r#"SELECT rs.id, rs.started_at, rs.finished_at,
+    COUNT(si.position) AS items, COUNT(si.grade) AS answered
+    FROM review_sessions rs
+    LEFT JOIN session_items si ON si.session_id = rs.id"#;
+
+// The session of the user left unfinished, to be resumed.
+pub async fn find_current_session(pool: &PgPool, uid: &Uuid) -> Result<Option<Session>> {
+    let sql = format!(
+        "{} WHERE rs.uid = $1 AND rs.finished_at IS NULL GROUP BY rs.id ORDER BY rs.id DESC LIMIT 1",
+        SELECT_SESSIONS
+    );
+    let session = sqlx::query_as::<_, Session>(&sql)
+        .bind(uid)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(session)
+}
+
+pub async fn find_session(pool: &PgPool, uid: &Uuid, id: i32) -> Result<Option<Session>> {
+    let sql = format!(
+        "{} WHERE rs.uid = $1 AND rs.id = $2 GROUP BY rs.id",
+        SELECT_SESSIONS
+    );
+    let session = sqlx::query_as::<_, Session>(&sql)
+        .bind(uid)
+        .bind(id)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(session)
+}
+
+// Draw the reviews due the soonest, as many as the size and what is left of the daily limit allow.
+// None when no review is due or the daily limit is used up.
+pub async fn start_session(pool: &PgPool, uid: &Uuid, size: Option<i16>) -> Result<StartOutcome> {
+    let now = Utc::now();
+    let preferences = users::infrastructures::find_preferences(pool, uid).await?;
+    let mut tx = pool.begin().await?;
+    // the items graded since midnight in the time zone of the user
+    let (graded_today,): (i64,) = sqlx::query_as(
+        r#"SELECT COUNT(*) FROM session_items si
+        JOIN review_sessions rs ON rs.id = si.session_id
+        WHERE rs.uid = $1 AND si.graded_at >= (DATE_TRUNC('day', $2 AT TIME ZONE $3) AT TIME ZONE $3)"#,
+    )
+    .bind(uid)
+    .bind(now)
+    .bind(&preferences.timezone)
+    .fetch_one(&mut tx)
+    .await?;
+    let mut limit = preferences.daily_review_limit as i64 - graded_today;
+    if let Some(size) = size {
+        limit = limit.min(size as i64);
+    }
+    if limit <= 0 {
+        return Ok(StartOutcome::NothingDue);
+    }
+
+    let due: Vec<(i32,)> = sqlx::query_as(
+        r#"SELECT r.id FROM reviews r
+        JOIN schedules s ON s.review_id = r.id
+        WHERE r.uid = $1 AND s.due_at <= $2
+        ORDER BY s.due_at, r.id LIMIT $3"#,
+    )
+    .bind(uid)
+    .bind(now)
+    .bind(limit)
+    .fetch_all(&mut tx)
+    .await?;
+    if due.is_empty() {
+        return Ok(StartOutcome::NothingDue);
+    }
+
+    let id: Option<(i32,)> = sqlx::query_as(
+        r#"INSERT INTO review_sessions (uid, started_at) VALUES ($1, $2)
+        ON CONFLICT (uid) WHERE finished_at IS NULL DO NOTHING RETURNING id"#,
+    )
+    .bind(uid)
+    .bind(now)
+    .fetch_optional(&mut tx)
+    .await?;
+    let id = match id {
+        Some((id,)) => id,
+        None => return Ok(StartOutcome::AlreadyStarted),
+    };
+    for (position, (review_id,)) in due.iter().enumerate() {
+        sqlx::query(
+            "INSERT INTO session_items (session_id, position, review_id) VALUES ($1, $2, $3)",
+        )
+        .bind(id)
+        .bind(position as i16 + 1)
+        .bind(review_id)
+        .execute(&mut tx)
+        .await?;
+    }
+    tx.commit().await?;
+
+    let session = find_session(pool, uid, id).await?;
+    match session {
+        Some(session) => Ok(StartOutcome::Started(session)),
+        None => Err(anyhow::anyhow!("the started session {} is missing", id)),
+    }
+}
+
+// Serve the first item not graded yet, again when the session is resumed.
+// None when every item is graded or the review was deleted meanwhile.
+pub async fn next_item(pool: &PgPool, uid: &Uuid, id: i32) -> Result<Option<SessionItem>> {
+    let item: Option<(i16, i32)> = sqlx::query_as(
+        r#"UPDATE session_items SET served_at = $1
+        WHERE (session_id, position) = (
+            SELECT si.session_id, si.position FROM session_items si
+            JOIN review_sessions rs ON rs.id = si.session_id
+            WHERE rs.uid = $2 AND rs.id = $3 AND rs.finished_at IS NULL AND si.grade IS NULL
+            ORDER BY si.position LIMIT 1
+        )
+        RETURNING position, review_id"#,
+    )
+    .bind(Utc::now())
+    .bind(uid)
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+    let (position, review_id) = match item {
+        Some(item) => item,
+        None => return Ok(None),
+    };
+
+    let review = reviews::infrastructures::find_review(pool, uid, review_id).await?;
+    Ok(review.map(|review| SessionItem {
+        session_id: id,
+        position,
+        review,
+    }))
+}
+
+// Record the grade and the time spent, reschedule the review by SM-2,
+// and finish the session once its last item is graded.
+pub async fn grade_item(
+    pool: &PgPool,
+    uid: &Uuid,
+    id: i32,
+    position: i16,
+    grade: i16,
+    seconds: Option<i32>,
+) -> Result<GradeOutcome> {
+    let now = Utc::now();
+    let mut tx = pool.begin().await?;
+    let item: Option<(i32, Option<DateTime<Utc>>, Option<i16>)> = sqlx::query_as(
+        r#"SELECT si.review_id, si.served_at, si.grade FROM session_items si
+        JOIN review_sessions rs ON rs.id = si.session_id
+        WHERE rs.uid = $1 AND si.session_id = $2 AND si.position = $3 AND rs.finished_at IS NULL
+        FOR UPDATE OF si"#,
+    )
+    .bind(uid)
+    .bind(id)
+    .bind(position)
+    .fetch_optional(&mut tx)
+    .await?;
+    let (review_id, served_at) = match item {
+        Some((_, _, Some(_))) => return Ok(GradeOutcome::AlreadyGraded),
+        Some((review_id, served_at, None)) => (review_id, served_at),
+        None => return Ok(GradeOutcome::NotFound),
+    };
+    let seconds = seconds.unwrap_or_else(|| {
+        served_at.map_or(0, |served_at| {
+            (now - served_at)
+                .num_seconds()
+                .clamp(0, MAX_MEASURED_SECONDS) as i32
+        })
+    });
+
+    sqlx::query(
+        "UPDATE session_items SET grade = $1, seconds = $2, graded_at = $3 WHERE session_id = $4 AND position = $5",
+    )
+    .bind(grade)
+    .bind(seconds)
+    .bind(now)
+    .bind(id)
+    .bind(position)
+    .execute(&mut tx)
+    .await?;
+    let schedule: Option<Schedule> = sqlx::query_as(
+        "SELECT interval_days, ease, repetitions FROM schedules WHERE review_id = $1",
+    )
+    .bind(review_id)
+    .fetch_optional(&mut tx)
+    .await?;
+    if let Some(schedule) = schedule {
+        let schedule = schedule.graded(grade);
+        sqlx::query(
+            "UPDATE schedules SET due_at = $1, interval_days = $2, ease = $3, repetitions = $4 WHERE review_id = $5",
+        )
+        .bind(now + Duration::days(schedule.interval_days as i64))
+        .bind(schedule.interval_days)
+        .bind(schedule.ease)
+        .bind(schedule.repetitions)
+        .bind(review_id)
+        .execute(&mut tx)
+        .await?;
+    }
+
+    let (left,): (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM session_items WHERE session_id = $1 AND grade IS NULL",
+    )
+    .bind(id)
+    .fetch_one(&mut tx)
+    .await?;
+    let finished = left == 0;
+    if finished {
+        sqlx::query("UPDATE review_sessions SET finished_at = $1 WHERE id = $2")
+            .bind(now)
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+    }
+    if let Some(review) = reviews::infrastructures::find_review(&mut tx, uid, review_id).await? {
+        let graded = json!({
+            "session_id": id,
+            "position": position,
+            "grade": grade,
+            "review": review,
+        });
+        webhooks::infrastructures::dispatch(&mut tx, uid, EVENT_GRADED, &graded).await?;
+    }
+    tx.commit().await?;
+
+    Ok(GradeOutcome::Recorded)
+}
+
+// Finish the session before every item is graded; the items left stay due.
+pub async fn finish_session(pool: &PgPool, uid: &Uuid, id: i32) -> Result<bool> {
+    let result = sqlx::query(
+        "UPDATE review_sessions SET finished_at = $1 WHERE uid = $2 AND id = $3 AND finished_at IS NULL",
+    )
+    .bind(Utc::now())
+    .bind(uid)
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn find_summary(pool: &PgPool, uid: &Uuid, id: i32) -> Result<Option<SessionSummary>> {
+    let summary = sqlx::query_as::<_, SessionSummary>(
+        r#"SELECT rs.id, rs.started_at, rs.finished_at,
+        COUNT(si.position) AS items, COUNT(si.grade) AS answered,
+        COALESCE(SUM(si.seconds), 0)::BIGINT AS total_seconds,
+        AVG(si.grade)::FLOAT8 AS average_grade,
+        COUNT(si.grade) FILTER (WHERE si.grade < 3) AS lapses
+        FROM review_sessions rs
+        LEFT JOIN session_items si ON si.session_id = rs.id
+        WHERE rs.uid = $1 AND rs.id = $2
+        GROUP BY rs.id"#,
+    )
+    .bind(uid)
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reviews::model::NewReview;
+    use crate::users::model::Preferences;
+    use crate::{config, utils};
+
+    fn started(outcome: StartOutcome) -> Session {
+        match outcome {
+            StartOutcome::Started(session) => session,
+            _ => panic!("no session is started"),
+        }
+    }
+
+    async fn create_due_review(
+        pool: &PgPool,
+        uid: &Uuid,
+        problem_name: &str,
+        days_ago: i64,
+    ) -> i32 {
+        let review = NewReview {
+            problem_name: problem_name.to_string(),
+            url: "https://example.com".to_string(),
+            memo: None,
+            platform: 1,
+            difficulty: None,
+            solved_without_hints: false,
+        };
+        let review = reviews::infrastructures::create_review(pool, uid, &review)
+            .await
+            .unwrap();
+        sqlx::query("UPDATE schedules SET due_at = $1 WHERE review_id = $2")
+            .bind(Utc::now() - Duration::days(days_ago))
+            .bind(review.id)
+            .execute(pool)
+            .await
+            .unwrap();
+        review.id
+    }
+
+    #[actix_rt::test]
+    async fn review_in_session() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        let newer = create_due_review(&pool, &uid, "newer", 1).await;
+        let older = create_due_review(&pool, &uid, "older", 3).await;
+        create_due_review(&pool, &uid, "oldest", 5).await;
+        // not due yet
+        reviews::infrastructures::create_review(
+            &pool,
+            &uid,
+            &NewReview {
+                problem_name: "upcoming".to_string(),
+                url: "https://example.com".to_string(),
+                memo: None,
+                platform: 1,
+                difficulty: None,
+                solved_without_hints: false,
+            },
+        )
+        .await
+        .unwrap();
+
+        let session = started(start_session(&pool, &uid, Some(2)).await.unwrap());
+        assert_eq!(2, session.items);
+        assert_eq!(
+            Some(session.id),
+            find_current_session(&pool, &uid)
+                .await
+                .unwrap()
+                .map(|s| s.id)
+        );
+        let first = next_item(&pool, &uid, session.id).await.unwrap().unwrap();
+        assert_eq!(1, first.position);
+        assert_eq!("oldest".to_string(), first.review.problem_name);
+        // served again until it is graded
+        let resumed = next_item(&pool, &uid, session.id).await.unwrap().unwrap();
+        assert_eq!(1, resumed.position);
+        let webhook =
+            webhooks::infrastructures::create_webhook(&pool, &uid, "https://203.0.113.7/hook")
+                .await
+                .unwrap();
+
+        let graded = grade_item(&pool, &uid, session.id, 1, 5, Some(30))
+            .await
+            .unwrap();
+        assert!(matches!(graded, GradeOutcome::Recorded));
+        assert!(matches!(
+            grade_item(&pool, &uid, session.id, 1, 5, None)
+                .await
+                .unwrap(),
+            GradeOutcome::AlreadyGraded
+        ));
+        let second = next_item(&pool, &uid, session.id).await.unwrap().unwrap();
+        assert_eq!(older, second.review.id);
+        let graded = grade_item(&pool, &uid, session.id, 2, 1, None)
+            .await
+            .unwrap();
+        assert!(matches!(graded, GradeOutcome::Recorded));
+        let review = reviews::infrastructures::find_review(&pool, &uid, older)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(review.due_at.unwrap() > Utc::now());
+        // the events are queued along with the grades, carrying the review as rescheduled
+        let deliveries = webhooks::infrastructures::find_deliveries(&pool, &uid, webhook.id)
+            .await
+            .unwrap();
+        assert_eq!(2, deliveries.len());
+        assert_eq!(EVENT_GRADED, deliveries[0].event);
+        let payload: serde_json::Value = serde_json::from_str(&deliveries[0].payload).unwrap();
+        assert_eq!(json!(older), payload["data"]["review"]["id"]);
+        assert_eq!(json!(review.due_at), payload["data"]["review"]["due_at"]);
+        assert_eq!(None, next_item(&pool, &uid, session.id).await.unwrap());
+        assert_eq!(None, find_current_session(&pool, &uid).await.unwrap());
+
+        let summary = find_summary(&pool, &uid, session.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(2, summary.answered);
+        assert_eq!(Some(3.0), summary.average_grade);
+        assert_eq!(1, summary.lapses);
+        assert!(summary.total_seconds >= 30);
+
+        // two of the daily limit of 20 are used up, and the only review still due is drawn
+        let session = started(start_session(&pool, &uid, None).await.unwrap());
+        assert_eq!(1, session.items);
+        let item = next_item(&pool, &uid, session.id).await.unwrap().unwrap();
+        assert_eq!(newer, item.review.id);
+        assert!(finish_session(&pool, &uid, session.id).await.unwrap());
+        assert!(!finish_session(&pool, &uid, session.id).await.unwrap());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn start_session_within_daily_limit() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        let preferences = Preferences {
+            daily_review_limit: 1,
+            ..Preferences::default()
+        };
+        users::infrastructures::update_preferences(&pool, &uid, &preferences)
+            .await
+            .unwrap();
+        create_due_review(&pool, &uid, "first", 2).await;
+        create_due_review(&pool, &uid, "second", 1).await;
+
+        let session = started(start_session(&pool, &uid, Some(10)).await.unwrap());
+        assert_eq!(1, session.items);
+        grade_item(&pool, &uid, session.id, 1, 4, None)
+            .await
+            .unwrap();
+        assert!(matches!(
+            start_session(&pool, &uid, None).await.unwrap(),
+            StartOutcome::NothingDue
+        ));
+        assert!(matches!(
+            start_session(&pool, &Uuid::new_v4(), None).await.unwrap(),
+            StartOutcome::NothingDue
+        ));
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn start_session_once_at_a_time() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        create_due_review(&pool, &uid, "first", 2).await;
+        create_due_review(&pool, &uid, "second", 1).await;
+
+        // as when two requests race past the check for the current session
+        let session = started(start_session(&pool, &uid, Some(1)).await.unwrap());
+        assert!(matches!(
+            start_session(&pool, &uid, Some(1)).await.unwrap(),
+            StartOutcome::AlreadyStarted
+        ));
+        assert!(finish_session(&pool, &uid, session.id).await.unwrap());
+        started(start_session(&pool, &uid, Some(1)).await.unwrap());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+}
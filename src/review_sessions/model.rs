@@ -0,0 +1,142 @@
+use crate::reviews::model::Review;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+// SM-2 never lets the ease fall below this
+const MIN_EASE: f32 = 1.3;
+// the grades from this one on mean the problem was recalled
+const PASSING_GRADE: i16 = 3;
+
+#[derive(Debug, Default, Serialize, Deserialize, Validate, PartialEq)]
+pub struct NewSession {
+    // the number of reviews to draw, at most what is left of the daily limit
+    #[validate(range(min = 1, max = 500))]
+    pub size: Option<i16>,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, PartialEq)]
+pub struct Session {
+    pub id: i32,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    // the number of reviews drawn and graded
+    pub items: i64,
+    pub answered: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct SessionItem {
+    pub session_id: i32,
+    pub position: i16,
+    pub review: Review,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, PartialEq)]
+pub struct NewGrade {
+    // 0: blackout, 1: wrong but familiar, 2: wrong but easy once seen,
+    // 3: right with effort, 4: right after hesitation, 5: perfect
+    #[validate(range(min = 0, max = 5))]
+    pub grade: i16,
+    // the time spent as measured by the client, otherwise measured from when the item was served
+    #[validate(range(min = 0, max = 86400))]
+    pub seconds: Option<i32>,
+}
+
+pub enum StartOutcome {
+    Started(Session),
+    // no review is due, or the daily limit is reached
+    NothingDue,
+    // another session was started meanwhile and is left unfinished
+    AlreadyStarted,
+}
+
+pub enum GradeOutcome {
+    // the session is finished along with its last item
+    Recorded,
+    NotFound,
+    AlreadyGraded,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, PartialEq)]
+pub struct SessionSummary {
+    pub id: i32,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub items: i64,
+    pub answered: i64,
+    pub total_seconds: i64,
+    pub average_grade: Option<f64>,
+    // the reviews graded below 3, which start over from the first interval
+    pub lapses: i64,
+}
+
+#[derive(Debug, sqlx::FromRow, PartialEq, Clone, Copy)]
+pub struct Schedule {
+    pub interval_days: i32,
+    pub ease: f32,
+    pub repetitions: i32,
+}
+
+impl Schedule {
+    // The schedule after a review graded by SM-2.
+    pub fn graded(&self, grade: i16) -> Schedule {
+        let q = (5 - grade) as f32;
+        let ease = (self.ease + 0.1 - q * (0.08 + q * 0.02)).max(MIN_EASE);
+        if grade < PASSING_GRADE {
+            return Schedule {
+                interval_days: 1,
+                ease,
+                repetitions: 0,
+            };
+        }
+
+        let repetitions = self.repetitions + 1;
+        let interval_days = match repetitions {
+            1 => 1,
+            2 => 6,
+            _ => (self.interval_days as f32 * self.ease).round() as i32,
+        };
+        Schedule {
+            interval_days,
+            ease,
+            repetitions,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn graded_schedule() {
+        let schedule = Schedule {
+            interval_days: 1,
+            ease: 2.5,
+            repetitions: 0,
+        };
+        let first = schedule.graded(5);
+        assert_eq!(1, first.interval_days);
+        assert_eq!(1, first.repetitions);
+        assert!((first.ease - 2.6).abs() < 1e-6);
+        let second = first.graded(4);
+        assert_eq!(6, second.interval_days);
+        assert!((second.ease - 2.6).abs() < 1e-6);
+        let third = second.graded(3);
+        // the interval grows by the ease before the grade
+        assert_eq!(16, third.interval_days);
+        assert!((third.ease - 2.46).abs() < 1e-6);
+
+        let lapsed = third.graded(1);
+        assert_eq!(1, lapsed.interval_days);
+        assert_eq!(0, lapsed.repetitions);
+        assert!((lapsed.ease - 1.92).abs() < 1e-6);
+        let floor = Schedule {
+            interval_days: 1,
+            ease: 1.3,
+            repetitions: 0,
+        };
+        assert!((floor.graded(0).ease - MIN_EASE).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,539 @@
+use super::infrastructures;
+use super::model::{NewReview, ReviewPage, ReviewQuery, RevisionDiff, SearchQuery, SearchResult};
+use crate::auth::AuthenticatedUser;
+use crate::error::ApiError;
+use crate::users;
+use actix_web::{delete, get, post, put, web, HttpResponse};
+use anyhow::Result;
+use sqlx::PgPool;
+use validator::Validate;
+
+#[post("/reviews")]
+pub async fn create_review(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    form: web::Form<NewReview>,
+) -> Result<HttpResponse, ApiError> {
+    match form.validate() {
+        Ok(_) => (),
+        Err(e) => return Err(e.into()),
+    }
+
+    match infrastructures::create_review(pool.get_ref(), &user.uid, &form).await {
+        Ok(review) => Ok(HttpResponse::Created().json(review)),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[get("/reviews")]
+pub async fn list_reviews(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    query: web::Query<ReviewQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let mut filter = match query.parse() {
+        Ok(f) => f,
+        Err(e) => return Err(e.into()),
+    };
+    filter.timezone =
+        match users::infrastructures::find_preferences(pool.get_ref(), &user.uid).await {
+            Ok(preferences) => preferences.timezone,
+            Err(_) => return Err(ApiError::InternalError),
+        };
+
+    match infrastructures::find_reviews(pool.get_ref(), &user.uid, &filter).await {
+        Ok(reviews) => Ok(HttpResponse::Ok().json(ReviewPage::new(reviews, &filter))),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[get("/reviews/search")]
+pub async fn search_reviews(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    query: web::Query<SearchQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let search = match query.parse() {
+        Ok(s) => s,
+        Err(e) => return Err(e.into()),
+    };
+
+    match infrastructures::search_reviews(pool.get_ref(), &user.uid, &search).await {
+        Ok(rows) => {
+            let results: Vec<SearchResult> = rows
+                .into_iter()
+                .map(|row| SearchResult::new(row, &search))
+                .collect();
+            Ok(HttpResponse::Ok().json(results))
+        }
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[get("/reviews/{id:\\d+}")]
+pub async fn get_review(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path(id): web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    match infrastructures::find_review(pool.get_ref(), &user.uid, id).await {
+        Ok(Some(review)) => Ok(HttpResponse::Ok().json(review)),
+        Ok(None) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[put("/reviews/{id:\\d+}")]
+pub async fn update_review(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path(id): web::Path<i32>,
+    form: web::Form<NewReview>,
+) -> Result<HttpResponse, ApiError> {
+    match form.validate() {
+        Ok(_) => (),
+        Err(e) => return Err(e.into()),
+    }
+
+    match infrastructures::update_review(pool.get_ref(), &user.uid, id, &form).await {
+        Ok(true) => (),
+        Ok(false) => return Err(ApiError::NotFound),
+        Err(_) => return Err(ApiError::InternalError),
+    }
+    match infrastructures::find_review(pool.get_ref(), &user.uid, id).await {
+        Ok(Some(review)) => Ok(HttpResponse::Ok().json(review)),
+        Ok(None) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[delete("/reviews/{id:\\d+}")]
+pub async fn delete_review(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path(id): web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    match infrastructures::delete_review(pool.get_ref(), &user.uid, id).await {
+        Ok(true) => Ok(HttpResponse::Ok().json("")),
+        Ok(false) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[get("/reviews/{id:\\d+}/revisions")]
+pub async fn list_revisions(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path(id): web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    match infrastructures::find_review(pool.get_ref(), &user.uid, id).await {
+        Ok(Some(_)) => (),
+        Ok(None) => return Err(ApiError::NotFound),
+        Err(_) => return Err(ApiError::InternalError),
+    }
+
+    match infrastructures::find_revisions(pool.get_ref(), &user.uid, id).await {
+        Ok(revisions) => Ok(HttpResponse::Ok().json(revisions)),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[get("/reviews/{id:\\d+}/revisions/{from:\\d+}/diff/{to:\\d+}")]
+pub async fn diff_revisions(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path((id, from, to)): web::Path<(i32, i32, i32)>,
+) -> Result<HttpResponse, ApiError> {
+    let from = match infrastructures::find_revision(pool.get_ref(), &user.uid, id, from).await {
+        Ok(Some(r)) => r,
+        Ok(None) => return Err(ApiError::NotFound),
+        Err(_) => return Err(ApiError::InternalError),
+    };
+    let to = match infrastructures::find_revision(pool.get_ref(), &user.uid, id, to).await {
+        Ok(Some(r)) => r,
+        Ok(None) => return Err(ApiError::NotFound),
+        Err(_) => return Err(ApiError::InternalError),
+    };
+
+    Ok(HttpResponse::Ok().json(RevisionDiff::new(&from, &to)))
+}
+
+#[post("/reviews/{id:\\d+}/revisions/{revision_id:\\d+}/restore")]
+pub async fn restore_revision(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path((id, revision_id)): web::Path<(i32, i32)>,
+) -> Result<HttpResponse, ApiError> {
+    match infrastructures::restore_revision(pool.get_ref(), &user.uid, id, revision_id).await {
+        Ok(true) => (),
+        Ok(false) => return Err(ApiError::NotFound),
+        Err(_) => return Err(ApiError::InternalError),
+    }
+    match infrastructures::find_review(pool.get_ref(), &user.uid, id).await {
+        Ok(Some(review)) => Ok(HttpResponse::Ok().json(review)),
+        Ok(None) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reviews::model::{Review, Revision};
+    use crate::webhooks::model::{EVENT_CREATED, EVENT_DELETED, EVENT_UPDATED};
+    use crate::{config, utils, webhooks};
+    use actix_web::{body::Body, test, App};
+    use serde_json::json;
+    use uuid::Uuid;
+
+    #[actix_rt::test]
+    async fn create_review_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app =
+            test::init_service(App::new().data(pool.clone()).service(create_review)).await;
+        let (uid, token) = utils::sign_in(&pool).await;
+
+        let req = test::TestRequest::post()
+            .uri("/reviews")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&utils::new_review("test_prob_name"))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(201, resp.status());
+        let review: Review = test::read_body_json(resp).await;
+        assert_eq!("test_prob_name".to_string(), review.problem_name);
+        let stored = infrastructures::find_review(&pool, &uid, review.id)
+            .await
+            .unwrap();
+        assert_eq!(Some(review), stored);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn review_events_queued() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .data(pool.clone())
+                .service(create_review)
+                .service(update_review)
+                .service(delete_review),
+        )
+        .await;
+        let (uid, token) = utils::sign_in(&pool).await;
+        let webhook =
+            webhooks::infrastructures::create_webhook(&pool, &uid, "http://127.0.0.1:1/hook")
+                .await
+                .unwrap();
+
+        let req = test::TestRequest::post()
+            .uri("/reviews")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&utils::new_review("test_prob_name"))
+            .to_request();
+        let review: Review = test::read_response_json(&mut app, req).await;
+        let req = test::TestRequest::put()
+            .uri(&format!("/reviews/{}", review.id))
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&utils::new_review("renamed_prob_name"))
+            .to_request();
+        test::call_service(&mut app, req).await;
+        let req = test::TestRequest::delete()
+            .uri(&format!("/reviews/{}", review.id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        test::call_service(&mut app, req).await;
+
+        let deliveries = webhooks::infrastructures::find_deliveries(&pool, &uid, webhook.id)
+            .await
+            .unwrap();
+        assert_eq!(
+            vec![EVENT_DELETED, EVENT_UPDATED, EVENT_CREATED],
+            deliveries
+                .iter()
+                .map(|d| d.event.as_str())
+                .collect::<Vec<&str>>()
+        );
+        let updated: serde_json::Value = serde_json::from_str(&deliveries[1].payload).unwrap();
+        assert_eq!(json!("renamed_prob_name"), updated["data"]["problem_name"]);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn create_review_invalid_url() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app =
+            test::init_service(App::new().data(pool.clone()).service(create_review)).await;
+        let (_, token) = utils::sign_in(&pool).await;
+
+        let mut review = utils::new_review("test_prob_name");
+        review.url = "not a url".to_string();
+        let req = test::TestRequest::post()
+            .uri("/reviews")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&review)
+            .to_request();
+        let mut resp = test::call_service(&mut app, req).await;
+        assert_eq!(400, resp.status());
+        let resp_body = resp.take_body();
+        let resp_body = resp_body.as_ref().unwrap();
+        assert_eq!(
+            &Body::from(json!({"code": 400, "message": "validation error on field: [\"url\"]"})),
+            resp_body
+        );
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn list_reviews_filtered_by_tags() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(App::new().data(pool.clone()).service(list_reviews)).await;
+        let (uid, token) = utils::sign_in(&pool).await;
+        let tagged = infrastructures::create_review(&pool, &uid, &utils::new_review("tagged"))
+            .await
+            .unwrap();
+        infrastructures::create_review(&pool, &uid, &utils::new_review("untagged"))
+            .await
+            .unwrap();
+        sqlx::query(r#"INSERT INTO review_tags (review_id, tag_id) SELECT $1, id FROM tags WHERE name = 'dp' AND uid IS NULL"#)
+			.bind(tagged.id)
+			.execute(&pool)
+			.await
+			.unwrap();
+
+        let req = test::TestRequest::get()
+            .uri("/reviews?tags=dp,graph&tag_mode=any")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let page: ReviewPage = test::read_body_json(resp).await;
+        assert_eq!(1, page.reviews.len());
+        assert_eq!(tagged.id, page.reviews[0].id);
+        assert_eq!(vec!["dp".to_string()], page.reviews[0].tags);
+        assert_eq!(None, page.next_cursor);
+
+        let req = test::TestRequest::get()
+            .uri("/reviews?tags=dp,graph&tag_mode=all")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        let page: ReviewPage = test::read_body_json(resp).await;
+        assert_eq!(0, page.reviews.len());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn list_reviews_invalid_query() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(App::new().data(pool.clone()).service(list_reviews)).await;
+        let (_, token) = utils::sign_in(&pool).await;
+
+        let req = test::TestRequest::get()
+            .uri("/reviews?due=tomorrow")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let mut resp = test::call_service(&mut app, req).await;
+        assert_eq!(400, resp.status());
+        let resp_body = resp.take_body();
+        let resp_body = resp_body.as_ref().unwrap();
+        assert_eq!(
+            &Body::from(json!({
+                "code": 400,
+                "message": "validation error on field: [\"due\"]",
+                "reasons": ["due must be overdue, due or upcoming"]
+            })),
+            resp_body
+        );
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn search_reviews_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .data(pool.clone())
+                .service(search_reviews)
+                .service(get_review),
+        )
+        .await;
+        let (uid, token) = utils::sign_in(&pool).await;
+        let mut review = utils::new_review("ABC 200 F");
+        review.memo = Some("with a stack, the convex hull 凸包".to_string());
+        let review = infrastructures::create_review(&pool, &uid, &review)
+            .await
+            .unwrap();
+
+        let req = test::TestRequest::get()
+            .uri("/reviews/search?q=hull%20%E5%87%B8%E5%8C%85")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let results: Vec<SearchResult> = test::read_body_json(resp).await;
+        assert_eq!(1, results.len());
+        assert_eq!(review.id, results[0].id);
+        assert_eq!("ABC 200 F".to_string(), results[0].problem_name);
+        let snippet = results[0].snippet.as_ref().unwrap();
+        assert!(snippet.contains("<mark>hull</mark> <mark>凸包</mark>"));
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn update_review_not_found() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app =
+            test::init_service(App::new().data(pool.clone()).service(update_review)).await;
+        let (_, token) = utils::sign_in(&pool).await;
+        // the review belongs to another user
+        let review =
+            infrastructures::create_review(&pool, &Uuid::new_v4(), &utils::new_review("other"))
+                .await
+                .unwrap();
+
+        let req = test::TestRequest::put()
+            .uri(&format!("/reviews/{}", review.id))
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&utils::new_review("changed"))
+            .to_request();
+        let mut resp = test::call_service(&mut app, req).await;
+        assert_eq!(404, resp.status());
+        let resp_body = resp.take_body();
+        let resp_body = resp_body.as_ref().unwrap();
+        assert_eq!(
+            &Body::from(json!({"code": 404, "message": "not found"})),
+            resp_body
+        );
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn delete_review_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app =
+            test::init_service(App::new().data(pool.clone()).service(delete_review)).await;
+        let (uid, token) = utils::sign_in(&pool).await;
+        let review =
+            infrastructures::create_review(&pool, &uid, &utils::new_review("test_prob_name"))
+                .await
+                .unwrap();
+
+        let req = test::TestRequest::delete()
+            .uri(&format!("/reviews/{}", review.id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let stored = infrastructures::find_review(&pool, &uid, review.id)
+            .await
+            .unwrap();
+        assert_eq!(None, stored);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn revisions_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .data(pool.clone())
+                .service(list_revisions)
+                .service(diff_revisions)
+                .service(restore_revision),
+        )
+        .await;
+        let (uid, token) = utils::sign_in(&pool).await;
+        let mut review = utils::new_review("test_prob_name");
+        review.memo = Some("use dp\n".to_string());
+        let review = infrastructures::create_review(&pool, &uid, &review)
+            .await
+            .unwrap();
+        let mut edited = utils::new_review("test_prob_name");
+        edited.memo = Some("use greedy\n".to_string());
+        infrastructures::update_review(&pool, &uid, review.id, &edited)
+            .await
+            .unwrap();
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/reviews/{}/revisions", review.id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let revisions: Vec<Revision> = test::read_body_json(resp).await;
+        assert_eq!(2, revisions.len());
+
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/reviews/{}/revisions/{}/diff/{}",
+                review.id, revisions[0].id, revisions[1].id
+            ))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let diff: RevisionDiff = test::read_body_json(resp).await;
+        assert_eq!("", diff.problem_name);
+        assert!(diff.memo.contains("-use dp\n+use greedy\n"));
+
+        let req = test::TestRequest::post()
+            .uri(&format!(
+                "/reviews/{}/revisions/{}/restore",
+                review.id, revisions[0].id
+            ))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let restored: Review = test::read_body_json(resp).await;
+        assert_eq!(Some("use dp\n".to_string()), restored.memo);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn restore_revision_of_other_user() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app =
+            test::init_service(App::new().data(pool.clone()).service(restore_revision)).await;
+        let (_, token) = utils::sign_in(&pool).await;
+        let other = Uuid::new_v4();
+        let review = infrastructures::create_review(&pool, &other, &utils::new_review("other"))
+            .await
+            .unwrap();
+        let revisions = infrastructures::find_revisions(&pool, &other, review.id)
+            .await
+            .unwrap();
+
+        let req = test::TestRequest::post()
+            .uri(&format!(
+                "/reviews/{}/revisions/{}/restore",
+                review.id, revisions[0].id
+            ))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(404, resp.status());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+}
@@ -0,0 +1,956 @@
+use super::model::{
+    DueStatus, NewReview, Review, ReviewFilter, Revision, Search, SearchRow, SortValue, TagMode,
+    HIGHLIGHT_START, HIGHLIGHT_STOP,
+};
+use crate::markdown;
+use crate::problems;
+use crate::problems::model::ProblemKey;
+use crate::webhooks;
+use crate::webhooks::model::{EVENT_CREATED, EVENT_DELETED, EVENT_UPDATED};
+use anyhow::Result;
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use serde_json::json;
+use sqlx::{Executor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+// a new review is first due the next day with the initial ease of SM-2
+const FIRST_INTERVAL_DAYS: i32 = 1;
+const INITIAL_EASE: f32 = 2.5;
+
+// a review together with its due date and the names of its tags
+const SELECT_REVIEWS: &str = r#"SELECT r.id, r.problem_name, r.url, r.memo, r.memo_html, r.platform, r.problem_id, r.difficulty,
+    COALESCE(p.difficulty, r.imported_difficulty) AS platform_difficulty, r.solved_without_hints, r.created_at, r.updated_at, s.due_at,
+    COALESCE(ARRAY_AGG(t.name ORDER BY t.name) FILTER (WHERE t.name IS NOT NULL), '{}') AS tags
+    FROM reviews r
+    LEFT JOIN schedules s ON s.review_id = r.id
+    LEFT JOIN problems p ON p.id = r.problem_id
+    LEFT JOIN review_tags rt ON rt.review_id = r.id
+    LEFT JOIN tags t ON t.id = rt.tag_id"#;
+
+const GROUP_REVIEWS: &str = "GROUP BY r.id, s.review_id, p.id";
+
+// the values bound to a dynamically built query
+enum Bind {
+    Uuid(Uuid),
+    BigInt(i64),
+    SmallInt(Option<i16>),
+    SmallInts(Vec<i16>),
+    Text(String),
+    Texts(Vec<String>),
+    Time(Option<NaiveDateTime>),
+    Sort(SortValue),
+}
+
+impl Bind {
+    // the placeholder of the value, cast since the same one may be compared several times
+    fn placeholder(&self, n: usize) -> String {
+        let sql_type = match self {
+            Bind::Uuid(_) => "UUID",
+            Bind::BigInt(_) | Bind::Sort(SortValue::Int(_)) => "BIGINT",
+            Bind::SmallInt(_) => "SMALLINT",
+            Bind::SmallInts(_) => "SMALLINT[]",
+            Bind::Text(_) | Bind::Sort(SortValue::Text(_)) => "VARCHAR",
+            Bind::Texts(_) => "VARCHAR[]",
+            // a wall clock time, read in the time zone of the user
+            Bind::Time(_) => "TIMESTAMP",
+            Bind::Sort(SortValue::Time(_)) => "TIMESTAMPTZ",
+        };
+        format!("${}::{}", n, sql_type)
+    }
+}
+
+// Collects the conditions of a query along with the values bound to them.
+struct Conditions {
+    clauses: Vec<String>,
+    binds: Vec<Bind>,
+    // the placeholder of the time zone once bound
+    timezone: Option<String>,
+}
+
+impl Conditions {
+    fn bind(&mut self, value: Bind) -> String {
+        let placeholder = value.placeholder(self.binds.len() + 1);
+        self.binds.push(value);
+        placeholder
+    }
+
+    // The time zone is bound once however many times it is used.
+    fn timezone(&mut self, timezone: &str) -> String {
+        if let Some(placeholder) = &self.timezone {
+            return placeholder.clone();
+        }
+        let placeholder = self.bind(Bind::Text(timezone.to_string()));
+        self.timezone = Some(placeholder.clone());
+        placeholder
+    }
+}
+
+pub async fn create_review(pool: &PgPool, uid: &Uuid, review: &NewReview) -> Result<Review> {
+    let mut tx = pool.begin().await?;
+    let id = insert_review(&mut tx, uid, review).await?;
+    dispatch_review(&mut tx, uid, id, EVENT_CREATED).await?;
+    let review = find_review(&mut tx, uid, id).await?;
+    tx.commit().await?;
+
+    review.ok_or_else(|| anyhow::anyhow!("the created review {} is missing", id))
+}
+
+// Write a review along with its first schedule and revision, for the imports which write
+// many of them in a single transaction. Returns the id of the review.
+pub async fn insert_review(
+    tx: &mut Transaction<'_, Postgres>,
+    uid: &Uuid,
+    review: &NewReview,
+) -> Result<i32> {
+    let now = Utc::now();
+    let problem_id = catalog_problem(tx, review).await?;
+    let (id,): (i32,) = sqlx::query_as(
+        r#"INSERT INTO reviews (problem_name, url, memo, memo_html, uid, platform, problem_id, difficulty, solved_without_hints, created_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) RETURNING id"#,
+    )
+    .bind(&review.problem_name)
+    .bind(&review.url)
+    .bind(&review.memo)
+    .bind(review.memo.as_deref().map(markdown::render))
+    .bind(uid)
+    .bind(review.platform)
+    .bind(problem_id)
+    .bind(review.difficulty)
+    .bind(review.solved_without_hints)
+    .bind(now)
+    .fetch_one(&mut *tx)
+    .await?;
+    sqlx::query(r#"INSERT INTO schedules (review_id, due_at, interval_days, ease, repetitions) VALUES ($1, $2, $3, $4, 0)"#)
+        .bind(id)
+        .bind(now + Duration::days(FIRST_INTERVAL_DAYS as i64))
+        .bind(FIRST_INTERVAL_DAYS)
+        .bind(INITIAL_EASE)
+        .execute(&mut *tx)
+        .await?;
+    insert_revision(tx, id, &review.problem_name, &review.memo, now).await?;
+
+    Ok(id)
+}
+
+pub async fn find_review<'e, E>(executor: E, uid: &Uuid, id: i32) -> Result<Option<Review>>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    let sql = format!(
+        "{} WHERE r.uid = $1 AND r.id = $2 {}",
+        SELECT_REVIEWS, GROUP_REVIEWS
+    );
+    let review = sqlx::query_as::<_, Review>(&sql)
+        .bind(uid)
+        .bind(id)
+        .fetch_optional(executor)
+        .await?;
+
+    Ok(review)
+}
+
+// Queue the event of the review as it is in the transaction, so that the event is only sent
+// once the write is committed and the write is not committed without it.
+pub async fn dispatch_review(
+    tx: &mut Transaction<'_, Postgres>,
+    uid: &Uuid,
+    id: i32,
+    event: &str,
+) -> Result<()> {
+    let review = find_review(&mut *tx, uid, id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("the review {} is missing", id))?;
+    webhooks::infrastructures::dispatch(&mut *tx, uid, event, &review).await?;
+
+    Ok(())
+}
+
+// List a page of the reviews of the user matching the filter.
+// One review more than the limit is fetched to tell whether another page follows.
+pub async fn find_reviews(pool: &PgPool, uid: &Uuid, filter: &ReviewFilter) -> Result<Vec<Review>> {
+    let mut conditions = Conditions {
+        clauses: Vec::new(),
+        binds: Vec::new(),
+        timezone: None,
+    };
+    let p = conditions.bind(Bind::Uuid(*uid));
+    conditions.clauses.push(format!("r.uid = {}", p));
+
+    if !filter.tags.is_empty() {
+        // the number of the given tags a review must carry
+        let required = match filter.tag_mode {
+            TagMode::Any => 1,
+            TagMode::All => filter.tags.len() as i64,
+        };
+        let tags = conditions.bind(Bind::Texts(filter.tags.clone()));
+        let required = conditions.bind(Bind::BigInt(required));
+        conditions.clauses.push(format!(
+            r#"(SELECT COUNT(DISTINCT ft.name) FROM review_tags frt JOIN tags ft ON ft.id = frt.tag_id
+            WHERE frt.review_id = r.id AND ft.name = ANY({})) >= {}"#,
+            tags, required
+        ));
+    }
+    if !filter.platforms.is_empty() {
+        let p = conditions.bind(Bind::SmallInts(filter.platforms.clone()));
+        conditions.clauses.push(format!("r.platform = ANY({})", p));
+    }
+    let ranges = vec![
+        ("r.created_at >=", filter.created_from),
+        ("r.created_at <", filter.created_to),
+        ("r.updated_at >=", filter.updated_from),
+        ("r.updated_at <", filter.updated_to),
+    ];
+    for (condition, time) in ranges {
+        if time.is_some() {
+            let p = conditions.bind(Bind::Time(time));
+            let tz = conditions.timezone(&filter.timezone);
+            conditions
+                .clauses
+                .push(format!("{} ({} AT TIME ZONE {})", condition, p, tz));
+        }
+    }
+    if filter.difficulty_min.is_some() {
+        let p = conditions.bind(Bind::SmallInt(filter.difficulty_min));
+        conditions.clauses.push(format!("r.difficulty >= {}", p));
+    }
+    if filter.difficulty_max.is_some() {
+        let p = conditions.bind(Bind::SmallInt(filter.difficulty_max));
+        conditions.clauses.push(format!("r.difficulty <= {}", p));
+    }
+    if let Some(due) = filter.due {
+        let tz = conditions.timezone(&filter.timezone);
+        // the midnights starting today and tomorrow in the time zone of the user
+        let today = format!("DATE_TRUNC('day', NOW() AT TIME ZONE {})", tz);
+        let (condition, boundary) = match due {
+            DueStatus::Overdue => ("s.due_at <", today),
+            DueStatus::Due => ("s.due_at <", format!("{} + INTERVAL '1 day'", today)),
+            DueStatus::Upcoming => ("s.due_at >=", format!("{} + INTERVAL '1 day'", today)),
+        };
+        conditions.clauses.push(format!(
+            "{} (({}) AT TIME ZONE {})",
+            condition, boundary, tz
+        ));
+    }
+    if let Some(after) = &filter.after {
+        let clause = after_cursor(&mut conditions, filter, after);
+        conditions.clauses.push(clause);
+    }
+
+    let order: Vec<String> = filter
+        .sort
+        .iter()
+        .map(|key| {
+            // NULL sorts before every value in both directions of the cursor
+            if key.descending {
+                format!("{} DESC NULLS LAST", key.field.column())
+            } else {
+                format!("{} ASC NULLS FIRST", key.field.column())
+            }
+        })
+        .collect();
+    let limit = conditions.bind(Bind::BigInt(filter.limit + 1));
+    let sql = format!(
+        "{} WHERE {} {} ORDER BY {} LIMIT {}",
+        SELECT_REVIEWS,
+        conditions.clauses.join(" AND "),
+        GROUP_REVIEWS,
+        order.join(", "),
+        limit
+    );
+
+    let mut query = sqlx::query_as::<_, Review>(&sql);
+    for value in conditions.binds {
+        query = match value {
+            Bind::Uuid(v) => query.bind(v),
+            Bind::BigInt(v) => query.bind(v),
+            Bind::SmallInt(v) => query.bind(v),
+            Bind::SmallInts(v) => query.bind(v),
+            Bind::Text(v) => query.bind(v),
+            Bind::Texts(v) => query.bind(v),
+            Bind::Time(v) => query.bind(v),
+            Bind::Sort(SortValue::Int(v)) => query.bind(v),
+            Bind::Sort(SortValue::Time(v)) => query.bind(v),
+            Bind::Sort(SortValue::Text(v)) => query.bind(v),
+        };
+    }
+    let reviews = query.fetch_all(pool).await?;
+
+    Ok(reviews)
+}
+
+// The rows following the cursor in the order of the sort keys, that is
+// (k1 > v1) OR (k1 = v1 AND k2 > v2) OR ... with "greater" reversed for descending keys.
+fn after_cursor(conditions: &mut Conditions, filter: &ReviewFilter, after: &[SortValue]) -> String {
+    let mut equals: Vec<String> = Vec::new();
+    let mut alternatives: Vec<String> = Vec::new();
+    for (key, value) in filter.sort.iter().zip(after) {
+        let column = key.field.column();
+        let p = conditions.bind(Bind::Sort(value.clone()));
+        let follows = if key.descending {
+            format!(
+                "COALESCE({c} < {p}, {c} IS NULL AND {p} IS NOT NULL)",
+                c = column,
+                p = p
+            )
+        } else {
+            format!(
+                "COALESCE({c} > {p}, {c} IS NOT NULL AND {p} IS NULL)",
+                c = column,
+                p = p
+            )
+        };
+        let mut alternative = equals.clone();
+        alternative.push(follows);
+        alternatives.push(format!("({})", alternative.join(" AND ")));
+        equals.push(format!("{} IS NOT DISTINCT FROM {}", column, p));
+    }
+
+    format!("({})", alternatives.join(" OR "))
+}
+
+// Rank the reviews of the user matching the search, best first.
+pub async fn search_reviews(pool: &PgPool, uid: &Uuid, search: &Search) -> Result<Vec<SearchRow>> {
+    let english = "websearch_to_tsquery('english', $2)";
+    let cjk = "to_tsquery('simple', $3)";
+    let query = match (search.english.is_empty(), search.cjk_terms.is_empty()) {
+        (false, true) => english.to_string(),
+        (true, false) => cjk.to_string(),
+        _ => format!("{} && {}", english, cjk),
+    };
+    let options = format!(
+        "StartSel={}, StopSel={}, MaxFragments=2, MaxWords=15, MinWords=5",
+        HIGHLIGHT_START, HIGHLIGHT_STOP
+    );
+    let sql = format!(
+        r#"SELECT r.id, r.url, r.platform, ts_rank(r.search_vector, q.query) AS rank,
+        r.problem_name, ts_headline('english', r.problem_name, q.english, $4 || ', HighlightAll=true') AS problem_name_headline,
+        r.memo, ts_headline('english', r.memo, q.english, $4) AS memo_headline
+        FROM reviews r, (SELECT {} AS query, {} AS english, {} AS cjk) q
+        WHERE r.uid = $1 AND r.search_vector @@ q.query
+        ORDER BY rank DESC, r.id LIMIT $5"#,
+        query, english, cjk
+    );
+    let rows = sqlx::query_as::<_, SearchRow>(&sql)
+        .bind(uid)
+        .bind(&search.english)
+        .bind(search.cjk_tsquery())
+        .bind(options)
+        .bind(search.limit)
+        .fetch_all(pool)
+        .await?;
+
+    Ok(rows)
+}
+
+pub async fn update_review(pool: &PgPool, uid: &Uuid, id: i32, review: &NewReview) -> Result<bool> {
+    let now = Utc::now();
+    let mut tx = pool.begin().await?;
+    let current: Option<(String, Option<String>)> = sqlx::query_as(
+        "SELECT problem_name, memo FROM reviews WHERE uid = $1 AND id = $2 FOR UPDATE",
+    )
+    .bind(uid)
+    .bind(id)
+    .fetch_optional(&mut tx)
+    .await?;
+    let (problem_name, memo) = match current {
+        Some(current) => current,
+        None => return Ok(false),
+    };
+
+    // the review written before the revisions were kept has its content as of then recorded first
+    if problem_name != review.problem_name || memo != review.memo {
+        insert_baseline_revision(&mut tx, id).await?;
+    }
+    let problem_id = catalog_problem(&mut tx, review).await?;
+    sqlx::query(
+        r#"UPDATE reviews SET problem_name = $1, url = $2, memo = $3, memo_html = $4, platform = $5, problem_id = $6, difficulty = $7, solved_without_hints = $8, updated_at = $9 WHERE id = $10"#,
+    )
+    .bind(&review.problem_name)
+    .bind(&review.url)
+    .bind(&review.memo)
+    .bind(review.memo.as_deref().map(markdown::render))
+    .bind(review.platform)
+    .bind(problem_id)
+    .bind(review.difficulty)
+    .bind(review.solved_without_hints)
+    .bind(now)
+    .bind(id)
+    .execute(&mut tx)
+    .await?;
+    // only the edits of the problem name and the memo are kept as revisions
+    if problem_name != review.problem_name || memo != review.memo {
+        insert_revision(&mut tx, id, &review.problem_name, &review.memo, now).await?;
+    }
+    dispatch_review(&mut tx, uid, id, EVENT_UPDATED).await?;
+    tx.commit().await?;
+
+    Ok(true)
+}
+
+// the catalog entry of the problem reviewed, if its URL is of a known platform
+async fn catalog_problem(
+    tx: &mut Transaction<'_, Postgres>,
+    review: &NewReview,
+) -> Result<Option<i32>> {
+    match ProblemKey::from_url(&review.url) {
+        Some(key) => Ok(Some(
+            problems::infrastructures::register_problem(tx, &key).await?,
+        )),
+        None => Ok(None),
+    }
+}
+
+async fn insert_revision(
+    tx: &mut Transaction<'_, Postgres>,
+    review_id: i32,
+    problem_name: &str,
+    memo: &Option<String>,
+    updated_at: DateTime<Utc>,
+) -> Result<()> {
+    sqlx::query(r#"INSERT INTO review_revisions (review_id, problem_name, memo, updated_at) VALUES ($1, $2, $3, $4)"#)
+        .bind(review_id)
+        .bind(problem_name)
+        .bind(memo)
+        .bind(updated_at)
+        .execute(tx)
+        .await?;
+
+    Ok(())
+}
+
+// Record the content of the review as its first revision unless it has any.
+async fn insert_baseline_revision(
+    tx: &mut Transaction<'_, Postgres>,
+    review_id: i32,
+) -> Result<()> {
+    sqlx::query(
+        r#"INSERT INTO review_revisions (review_id, problem_name, memo, updated_at)
+        SELECT r.id, r.problem_name, r.memo, COALESCE(r.updated_at, r.created_at) FROM reviews r
+        WHERE r.id = $1 AND NOT EXISTS (SELECT 1 FROM review_revisions v WHERE v.review_id = r.id)"#,
+    )
+    .bind(review_id)
+    .execute(tx)
+    .await?;
+
+    Ok(())
+}
+
+// The revisions of a review of the user, oldest first.
+pub async fn find_revisions(pool: &PgPool, uid: &Uuid, review_id: i32) -> Result<Vec<Revision>> {
+    let revisions = sqlx::query_as::<_, Revision>(
+        r#"SELECT v.id, v.review_id, v.problem_name, v.memo, v.updated_at FROM review_revisions v
+        JOIN reviews r ON r.id = v.review_id WHERE r.uid = $1 AND v.review_id = $2 ORDER BY v.id"#,
+    )
+    .bind(uid)
+    .bind(review_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(revisions)
+}
+
+pub async fn find_revision<'e, E>(
+    executor: E,
+    uid: &Uuid,
+    review_id: i32,
+    id: i32,
+) -> Result<Option<Revision>>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    let revision = sqlx::query_as::<_, Revision>(
+        r#"SELECT v.id, v.review_id, v.problem_name, v.memo, v.updated_at FROM review_revisions v
+        JOIN reviews r ON r.id = v.review_id WHERE r.uid = $1 AND v.review_id = $2 AND v.id = $3"#,
+    )
+    .bind(uid)
+    .bind(review_id)
+    .bind(id)
+    .fetch_optional(executor)
+    .await?;
+
+    Ok(revision)
+}
+
+// Bring an old revision back as the head, which is itself recorded as a new revision.
+pub async fn restore_revision(pool: &PgPool, uid: &Uuid, review_id: i32, id: i32) -> Result<bool> {
+    let now = Utc::now();
+    let mut tx = pool.begin().await?;
+    // the review is locked so that a concurrent edit lands before or after the restore
+    sqlx::query("SELECT id FROM reviews WHERE uid = $1 AND id = $2 FOR UPDATE")
+        .bind(uid)
+        .bind(review_id)
+        .execute(&mut tx)
+        .await?;
+    let revision = match find_revision(&mut tx, uid, review_id, id).await? {
+        Some(revision) => revision,
+        None => return Ok(false),
+    };
+    sqlx::query(
+        r#"UPDATE reviews SET problem_name = $1, memo = $2, memo_html = $3, updated_at = $4 WHERE id = $5"#,
+    )
+    .bind(&revision.problem_name)
+    .bind(&revision.memo)
+    .bind(revision.memo.as_deref().map(markdown::render))
+    .bind(now)
+    .bind(review_id)
+    .execute(&mut tx)
+    .await?;
+    insert_revision(
+        &mut tx,
+        review_id,
+        &revision.problem_name,
+        &revision.memo,
+        now,
+    )
+    .await?;
+    dispatch_review(&mut tx, uid, review_id, EVENT_UPDATED).await?;
+    tx.commit().await?;
+
+    Ok(true)
+}
+
+pub async fn delete_review(pool: &PgPool, uid: &Uuid, id: i32) -> Result<bool> {
+    let mut tx = pool.begin().await?;
+    // the schedule and the links to its tags are removed by the foreign keys
+    let result = sqlx::query("DELETE FROM reviews WHERE uid = $1 AND id = $2")
+        .bind(uid)
+        .bind(id)
+        .execute(&mut tx)
+        .await?;
+    if result.rows_affected() == 0 {
+        return Ok(false);
+    }
+    webhooks::infrastructures::dispatch(&mut tx, uid, EVENT_DELETED, &json!({ "id": id })).await?;
+    tx.commit().await?;
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reviews::model::{ReviewPage, ReviewQuery, SearchQuery};
+    use crate::{config, utils};
+
+    async fn tag(pool: &PgPool, review_id: i32, name: &str) {
+        sqlx::query(r#"INSERT INTO review_tags (review_id, tag_id) SELECT $1, id FROM tags WHERE name = $2 AND uid IS NULL"#)
+			.bind(review_id)
+			.bind(name)
+			.execute(pool)
+			.await
+			.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn create_review_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+
+        let created = create_review(&pool, &uid, &utils::new_review("test_prob_name"))
+            .await
+            .unwrap();
+        assert_eq!("test_prob_name".to_string(), created.problem_name);
+        assert_eq!(Some("test_memo".to_string()), created.memo);
+        assert_eq!(Some("<p>test_memo</p>\n".to_string()), created.memo_html);
+        assert_eq!(None, created.updated_at);
+        assert!(created.tags.is_empty());
+
+        let found = find_review(&pool, &uid, created.id).await.unwrap();
+        assert_eq!(Some(created), found);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn find_review_of_other_user() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let created = create_review(&pool, &Uuid::new_v4(), &utils::new_review("test_prob_name"))
+            .await
+            .unwrap();
+
+        let found = find_review(&pool, &Uuid::new_v4(), created.id)
+            .await
+            .unwrap();
+        assert_eq!(None, found);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    fn ids(reviews: &[Review]) -> Vec<i32> {
+        reviews.iter().map(|r| r.id).collect()
+    }
+
+    #[actix_rt::test]
+    async fn create_review_schedule() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+
+        let created = create_review(&pool, &uid, &utils::new_review("test_prob_name"))
+            .await
+            .unwrap();
+        let due_at = created.due_at.unwrap();
+        assert_eq!(Duration::days(1), due_at - created.created_at);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn find_reviews_by_tags() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        let dp = create_review(&pool, &uid, &utils::new_review("dp_only"))
+            .await
+            .unwrap();
+        let both = create_review(&pool, &uid, &utils::new_review("dp_and_graph"))
+            .await
+            .unwrap();
+        let none = create_review(&pool, &uid, &utils::new_review("untagged"))
+            .await
+            .unwrap();
+        tag(&pool, dp.id, "dp").await;
+        tag(&pool, both.id, "dp").await;
+        tag(&pool, both.id, "graph").await;
+
+        let mut filter = ReviewFilter {
+            tags: vec!["dp".to_string(), "graph".to_string()],
+            ..Default::default()
+        };
+        let any = find_reviews(&pool, &uid, &filter).await.unwrap();
+        assert_eq!(vec![dp.id, both.id], ids(&any));
+        filter.tag_mode = TagMode::All;
+        let all = find_reviews(&pool, &uid, &filter).await.unwrap();
+        assert_eq!(vec![both.id], ids(&all));
+        let unfiltered = find_reviews(&pool, &uid, &ReviewFilter::default())
+            .await
+            .unwrap();
+        assert_eq!(vec![dp.id, both.id, none.id], ids(&unfiltered));
+        assert_eq!(
+            vec!["dp".to_string(), "graph".to_string()],
+            unfiltered[1].tags
+        );
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn find_reviews_by_fields() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        let mut hard = utils::new_review("hard");
+        hard.platform = 2;
+        hard.difficulty = Some(5);
+        let hard = create_review(&pool, &uid, &hard).await.unwrap();
+        let easy = create_review(&pool, &uid, &utils::new_review("easy"))
+            .await
+            .unwrap();
+        sqlx::query("UPDATE schedules SET due_at = $1 WHERE review_id = $2")
+            .bind(Utc::now() - Duration::days(3))
+            .bind(easy.id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let filter = ReviewFilter {
+            platforms: vec![2],
+            ..Default::default()
+        };
+        let reviews = find_reviews(&pool, &uid, &filter).await.unwrap();
+        assert_eq!(vec![hard.id], ids(&reviews));
+
+        let filter = ReviewFilter {
+            difficulty_min: Some(4),
+            ..Default::default()
+        };
+        let reviews = find_reviews(&pool, &uid, &filter).await.unwrap();
+        assert_eq!(vec![hard.id], ids(&reviews));
+
+        let filter = ReviewFilter {
+            due: Some(DueStatus::Overdue),
+            ..Default::default()
+        };
+        let reviews = find_reviews(&pool, &uid, &filter).await.unwrap();
+        assert_eq!(vec![easy.id], ids(&reviews));
+
+        let filter = ReviewFilter {
+            due: Some(DueStatus::Upcoming),
+            ..Default::default()
+        };
+        let reviews = find_reviews(&pool, &uid, &filter).await.unwrap();
+        assert_eq!(vec![hard.id], ids(&reviews));
+
+        let filter = ReviewFilter {
+            created_to: Some(easy.created_at.naive_utc() - Duration::days(1)),
+            ..Default::default()
+        };
+        let reviews = find_reviews(&pool, &uid, &filter).await.unwrap();
+        assert!(reviews.is_empty());
+        // 8 hours after the creation in UTC is still before it in JST
+        let filter = ReviewFilter {
+            created_from: Some(easy.created_at.naive_utc() + Duration::hours(8)),
+            timezone: "Asia/Tokyo".to_string(),
+            ..Default::default()
+        };
+        let reviews = find_reviews(&pool, &uid, &filter).await.unwrap();
+        assert_eq!(vec![hard.id, easy.id], ids(&reviews));
+        let filter = ReviewFilter {
+            timezone: "UTC".to_string(),
+            ..filter
+        };
+        assert!(find_reviews(&pool, &uid, &filter).await.unwrap().is_empty());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn find_reviews_paginated() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        // difficulties with ties and NULLs
+        let difficulties = vec![Some(3), None, Some(1), Some(3), None];
+        let mut created = Vec::new();
+        for difficulty in difficulties {
+            let mut review = utils::new_review("test_prob_name");
+            review.difficulty = difficulty;
+            created.push(create_review(&pool, &uid, &review).await.unwrap());
+        }
+
+        let query = ReviewQuery {
+            sort: Some("-difficulty".to_string()),
+            limit: Some("2".to_string()),
+            ..Default::default()
+        };
+        let mut filter = query.parse().unwrap();
+        let mut pages = Vec::new();
+        loop {
+            let reviews = find_reviews(&pool, &uid, &filter).await.unwrap();
+            let page = ReviewPage::new(reviews, &filter);
+            pages.push(ids(&page.reviews));
+            match page.next_cursor {
+                Some(cursor) => {
+                    // a review inserted meanwhile does not shift the following pages
+                    create_review(&pool, &uid, &utils::new_review("inserted"))
+                        .await
+                        .unwrap();
+                    let query = ReviewQuery {
+                        sort: Some("-difficulty".to_string()),
+                        limit: Some("2".to_string()),
+                        cursor: Some(cursor),
+                        ..Default::default()
+                    };
+                    filter = query.parse().unwrap();
+                }
+                None => break,
+            }
+        }
+        let id = |i: usize| created[i].id;
+        assert_eq!(vec![id(0), id(3)], pages[0]);
+        assert_eq!(vec![id(2), id(1)], pages[1]);
+        // the reviews inserted meanwhile have no difficulty and greater ids, thus come last
+        assert_eq!(id(4), pages[2][0]);
+        assert_eq!(
+            created.len() + pages.len() - 1,
+            pages.iter().map(|p| p.len()).sum::<usize>()
+        );
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn update_and_delete_review() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        let created = create_review(&pool, &uid, &utils::new_review("test_prob_name"))
+            .await
+            .unwrap();
+
+        // other users can neither update nor delete the review
+        let other = Uuid::new_v4();
+        assert!(
+            !update_review(&pool, &other, created.id, &utils::new_review("changed"))
+                .await
+                .unwrap()
+        );
+        assert!(!delete_review(&pool, &other, created.id).await.unwrap());
+
+        assert!(
+            update_review(&pool, &uid, created.id, &utils::new_review("changed"))
+                .await
+                .unwrap()
+        );
+        let updated = find_review(&pool, &uid, created.id).await.unwrap().unwrap();
+        assert_eq!("changed".to_string(), updated.problem_name);
+        assert!(updated.updated_at.is_some());
+
+        assert!(delete_review(&pool, &uid, created.id).await.unwrap());
+        assert_eq!(None, find_review(&pool, &uid, created.id).await.unwrap());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    async fn search(pool: &PgPool, uid: &Uuid, q: &str) -> Vec<SearchRow> {
+        let query = SearchQuery {
+            q: Some(q.to_string()),
+            ..Default::default()
+        };
+        search_reviews(pool, uid, &query.parse().unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[actix_rt::test]
+    async fn search_reviews_english() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        let mut in_memo = utils::new_review("ABC 200 F");
+        in_memo.memo = Some("that trick with the convex hull".to_string());
+        let in_memo = create_review(&pool, &uid, &in_memo).await.unwrap();
+        let in_name = create_review(&pool, &uid, &utils::new_review("Convex Hulls"))
+            .await
+            .unwrap();
+        create_review(&pool, &uid, &utils::new_review("unrelated"))
+            .await
+            .unwrap();
+        // the reviews of another user are never searched
+        create_review(&pool, &Uuid::new_v4(), &utils::new_review("Convex Hull"))
+            .await
+            .unwrap();
+
+        let rows = search(&pool, &uid, "convex hulls").await;
+        assert_eq!(
+            vec![in_name.id, in_memo.id],
+            rows.iter().map(|r| r.id).collect::<Vec<i32>>()
+        );
+        assert!(rows[0].rank > rows[1].rank);
+        assert!(rows[1]
+            .memo_headline
+            .as_ref()
+            .unwrap()
+            .contains("\u{2}hull\u{3}"));
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn search_reviews_japanese() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        let mut hull = utils::new_review("ABC 200 F");
+        hull.memo = Some("凸包のトリックを使う".to_string());
+        let hull = create_review(&pool, &uid, &hull).await.unwrap();
+        let mut search_memo = utils::new_review("ABC 201 D");
+        search_memo.memo = Some("二分探索で解ける".to_string());
+        let search_memo = create_review(&pool, &uid, &search_memo).await.unwrap();
+
+        let rows = search(&pool, &uid, "トリック").await;
+        assert_eq!(
+            vec![hull.id],
+            rows.iter().map(|r| r.id).collect::<Vec<i32>>()
+        );
+        // a single character matches as well
+        let rows = search(&pool, &uid, "探").await;
+        assert_eq!(
+            vec![search_memo.id],
+            rows.iter().map(|r| r.id).collect::<Vec<i32>>()
+        );
+        // the characters must be adjacent
+        let rows = search(&pool, &uid, "凸ト").await;
+        assert!(rows.is_empty());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn revisions_test() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        let created = create_review(&pool, &uid, &utils::new_review("first"))
+            .await
+            .unwrap();
+        let mut edited = utils::new_review("second");
+        edited.memo = Some("better idea".to_string());
+        update_review(&pool, &uid, created.id, &edited)
+            .await
+            .unwrap();
+        // editing other fields adds no revision
+        edited.difficulty = Some(3);
+        update_review(&pool, &uid, created.id, &edited)
+            .await
+            .unwrap();
+
+        let revisions = find_revisions(&pool, &uid, created.id).await.unwrap();
+        assert_eq!(
+            vec!["first", "second"],
+            revisions
+                .iter()
+                .map(|r| r.problem_name.as_str())
+                .collect::<Vec<&str>>()
+        );
+        assert_eq!(created.created_at, revisions[0].updated_at);
+        assert!(find_revisions(&pool, &Uuid::new_v4(), created.id)
+            .await
+            .unwrap()
+            .is_empty());
+
+        assert!(restore_revision(&pool, &uid, created.id, revisions[0].id)
+            .await
+            .unwrap());
+        let restored = find_review(&pool, &uid, created.id).await.unwrap().unwrap();
+        assert_eq!("first".to_string(), restored.problem_name);
+        assert_eq!(Some("test_memo".to_string()), restored.memo);
+        assert_eq!(Some(3), restored.difficulty);
+        let revisions = find_revisions(&pool, &uid, created.id).await.unwrap();
+        assert_eq!(3, revisions.len());
+        assert_eq!(restored.updated_at, Some(revisions[2].updated_at));
+
+        assert!(
+            !restore_revision(&pool, &Uuid::new_v4(), created.id, revisions[0].id)
+                .await
+                .unwrap()
+        );
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn revisions_of_review_written_before() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        let created = create_review(&pool, &uid, &utils::new_review("first"))
+            .await
+            .unwrap();
+        // as written before the revisions were kept
+        sqlx::query("DELETE FROM review_revisions")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        update_review(&pool, &uid, created.id, &utils::new_review("second"))
+            .await
+            .unwrap();
+        update_review(&pool, &uid, created.id, &utils::new_review("third"))
+            .await
+            .unwrap();
+        let revisions = find_revisions(&pool, &uid, created.id).await.unwrap();
+        assert_eq!(
+            vec!["first", "second", "third"],
+            revisions
+                .iter()
+                .map(|r| r.problem_name.as_str())
+                .collect::<Vec<&str>>()
+        );
+        assert_eq!(Some("test_memo".to_string()), revisions[0].memo);
+        assert_eq!(created.created_at, revisions[0].updated_at);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+}
@@ -0,0 +1,910 @@
+use crate::diff;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use validator::{Validate, ValidationError, ValidationErrors};
+
+// the number of reviews listed when no limit is given
+const DEFAULT_LIMIT: i64 = 50;
+const MAX_LIMIT: i64 = 100;
+// the number of search results returned when no limit is given
+const DEFAULT_SEARCH_LIMIT: i64 = 20;
+const MAX_SEARCH_LIMIT: i64 = 50;
+// snippets longer than this are cut
+const MAX_SNIPPET_CHARS: usize = 160;
+// the characters shown around a match found outside of the headline
+const SNIPPET_CONTEXT_CHARS: usize = 30;
+// the highlight markers of ts_headline, replaced after the snippet has been escaped
+pub const HIGHLIGHT_START: char = '\u{2}';
+pub const HIGHLIGHT_STOP: char = '\u{3}';
+
+#[derive(Debug, Serialize, Deserialize, Validate, PartialEq)]
+pub struct NewReview {
+    #[validate(length(min = 1, max = 255))]
+    pub problem_name: String,
+    #[validate(length(max = 255), url)]
+    pub url: String,
+    // Markdown with `$...$` math
+    pub memo: Option<String>,
+    // 0: other, 1: AtCoder, 2: Codeforces, 3: AOJ, 4: yukicoder
+    #[validate(range(min = 0, max = 4))]
+    pub platform: i16,
+    // the personal rating from 1 (easy) to 5 (hard)
+    #[validate(range(min = 1, max = 5))]
+    pub difficulty: Option<i16>,
+    // absent is taken as solved with the help of hints or the editorial
+    #[serde(default)]
+    pub solved_without_hints: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, PartialEq)]
+pub struct Review {
+    pub id: i32,
+    pub problem_name: String,
+    pub url: String,
+    pub memo: Option<String>,
+    // the memo rendered into sanitized HTML
+    pub memo_html: Option<String>,
+    pub platform: i16,
+    // the entry of the problem catalog, absent when the URL is not of a known platform
+    pub problem_id: Option<i32>,
+    pub difficulty: Option<i16>,
+    // the difficulty given by the platform to the problem in the catalog, or else in the import
+    pub platform_difficulty: Option<i32>,
+    pub solved_without_hints: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub due_at: Option<DateTime<Utc>>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ReviewPage {
+    pub reviews: Vec<Review>,
+    // pass it as `cursor` to get the next page, absent on the last page
+    pub next_cursor: Option<String>,
+}
+
+impl ReviewPage {
+    // The reviews are fetched with one extra row telling whether another page follows.
+    pub fn new(mut reviews: Vec<Review>, filter: &ReviewFilter) -> ReviewPage {
+        let next_cursor = if reviews.len() as i64 > filter.limit {
+            reviews.truncate(filter.limit as usize);
+            reviews.last().map(|last| filter.cursor_of(last))
+        } else {
+            None
+        };
+
+        ReviewPage {
+            reviews,
+            next_cursor,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TagMode {
+    // reviews carrying at least one of the tags
+    Any,
+    // reviews carrying every tag
+    All,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DueStatus {
+    // due before today
+    Overdue,
+    // due by the end of today, including the overdue ones
+    Due,
+    // due after today
+    Upcoming,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SortField {
+    Id,
+    CreatedAt,
+    UpdatedAt,
+    DueAt,
+    Difficulty,
+    Platform,
+    ProblemName,
+}
+
+impl SortField {
+    fn parse(name: &str) -> Option<SortField> {
+        match name {
+            "id" => Some(SortField::Id),
+            "created_at" => Some(SortField::CreatedAt),
+            "updated_at" => Some(SortField::UpdatedAt),
+            "due_at" => Some(SortField::DueAt),
+            "difficulty" => Some(SortField::Difficulty),
+            "platform" => Some(SortField::Platform),
+            "problem_name" => Some(SortField::ProblemName),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            SortField::Id => "id",
+            SortField::CreatedAt => "created_at",
+            SortField::UpdatedAt => "updated_at",
+            SortField::DueAt => "due_at",
+            SortField::Difficulty => "difficulty",
+            SortField::Platform => "platform",
+            SortField::ProblemName => "problem_name",
+        }
+    }
+
+    pub fn column(&self) -> &'static str {
+        match self {
+            SortField::DueAt => "s.due_at",
+            SortField::Id => "r.id",
+            SortField::CreatedAt => "r.created_at",
+            SortField::UpdatedAt => "r.updated_at",
+            SortField::Difficulty => "r.difficulty",
+            SortField::Platform => "r.platform",
+            SortField::ProblemName => "r.problem_name",
+        }
+    }
+
+    fn value_of(&self, review: &Review) -> SortValue {
+        match self {
+            SortField::Id => SortValue::Int(Some(review.id as i64)),
+            SortField::CreatedAt => SortValue::Time(Some(review.created_at)),
+            SortField::UpdatedAt => SortValue::Time(review.updated_at),
+            SortField::DueAt => SortValue::Time(review.due_at),
+            SortField::Difficulty => SortValue::Int(review.difficulty.map(i64::from)),
+            SortField::Platform => SortValue::Int(Some(review.platform as i64)),
+            SortField::ProblemName => SortValue::Text(Some(review.problem_name.clone())),
+        }
+    }
+
+    fn decode(&self, value: serde_json::Value) -> Option<SortValue> {
+        let value = match self {
+            SortField::Id | SortField::Difficulty | SortField::Platform => {
+                SortValue::Int(serde_json::from_value(value).ok()?)
+            }
+            SortField::CreatedAt | SortField::UpdatedAt | SortField::DueAt => {
+                SortValue::Time(serde_json::from_value(value).ok()?)
+            }
+            SortField::ProblemName => SortValue::Text(serde_json::from_value(value).ok()?),
+        };
+
+        Some(value)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SortKey {
+    pub field: SortField,
+    pub descending: bool,
+}
+
+// The value of a sort key of a review, NULL sorting before everything else.
+#[derive(Debug, Serialize, PartialEq, Clone)]
+#[serde(untagged)]
+pub enum SortValue {
+    Int(Option<i64>),
+    Time(Option<DateTime<Utc>>),
+    Text(Option<String>),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Cursor {
+    sort: String,
+    values: Vec<serde_json::Value>,
+}
+
+// The raw query of the review list. Every parameter is taken as a string
+// so that a malformed one is reported as a validation error on its field.
+#[derive(Debug, Deserialize, PartialEq, Default)]
+pub struct ReviewQuery {
+    // comma separated tag names
+    pub tags: Option<String>,
+    // any or all
+    pub tag_mode: Option<String>,
+    // comma separated platforms
+    pub platform: Option<String>,
+    // dates or date-times in the time zone of the user, the lower bounds are inclusive and the upper ones exclusive
+    pub created_from: Option<String>,
+    pub created_to: Option<String>,
+    pub updated_from: Option<String>,
+    pub updated_to: Option<String>,
+    pub difficulty_min: Option<String>,
+    pub difficulty_max: Option<String>,
+    // overdue, due or upcoming
+    pub due: Option<String>,
+    // comma separated fields, descending when prefixed by `-`
+    pub sort: Option<String>,
+    pub limit: Option<String>,
+    pub cursor: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ReviewFilter {
+    pub tags: Vec<String>,
+    pub tag_mode: TagMode,
+    pub platforms: Vec<i16>,
+    pub created_from: Option<NaiveDateTime>,
+    pub created_to: Option<NaiveDateTime>,
+    pub updated_from: Option<NaiveDateTime>,
+    pub updated_to: Option<NaiveDateTime>,
+    pub difficulty_min: Option<i16>,
+    pub difficulty_max: Option<i16>,
+    pub due: Option<DueStatus>,
+    // the time zone of the user, where the days start and the bounds above are read in
+    pub timezone: String,
+    // always ends with the id so that the order is total
+    pub sort: Vec<SortKey>,
+    pub limit: i64,
+    // the sort values of the last review of the previous page
+    pub after: Option<Vec<SortValue>>,
+}
+
+impl Default for ReviewFilter {
+    fn default() -> ReviewFilter {
+        ReviewFilter {
+            tags: Vec::new(),
+            tag_mode: TagMode::Any,
+            platforms: Vec::new(),
+            created_from: None,
+            created_to: None,
+            updated_from: None,
+            updated_to: None,
+            difficulty_min: None,
+            difficulty_max: None,
+            due: None,
+            timezone: "UTC".to_string(),
+            sort: vec![SortKey {
+                field: SortField::Id,
+                descending: false,
+            }],
+            limit: DEFAULT_LIMIT,
+            after: None,
+        }
+    }
+}
+
+impl ReviewFilter {
+    fn sort_spec(&self) -> String {
+        self.sort
+            .iter()
+            .map(|key| {
+                if key.descending {
+                    format!("-{}", key.field.name())
+                } else {
+                    key.field.name().to_string()
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+
+    pub fn cursor_of(&self, review: &Review) -> String {
+        let cursor = Cursor {
+            sort: self.sort_spec(),
+            values: self
+                .sort
+                .iter()
+                .map(|key| serde_json::to_value(key.field.value_of(review)).unwrap())
+                .collect(),
+        };
+        let json = serde_json::to_vec(&cursor).unwrap();
+        base64::encode_config(json, base64::URL_SAFE_NO_PAD)
+    }
+
+    fn decode_cursor(&self, cursor: &str) -> Option<Vec<SortValue>> {
+        let json = base64::decode_config(cursor, base64::URL_SAFE_NO_PAD).ok()?;
+        let cursor: Cursor = serde_json::from_slice(&json).ok()?;
+        // a cursor is only valid for the order it was issued for
+        if cursor.sort != self.sort_spec() || cursor.values.len() != self.sort.len() {
+            return None;
+        }
+        self.sort
+            .iter()
+            .zip(cursor.values)
+            .map(|(key, value)| key.field.decode(value))
+            .collect()
+    }
+}
+
+impl ReviewQuery {
+    pub fn parse(&self) -> Result<ReviewFilter, ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        let mut filter = ReviewFilter::default();
+
+        if let Some(tags) = &self.tags {
+            let mut names: Vec<String> = tags
+                .split(',')
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty())
+                .collect();
+            names.sort();
+            names.dedup();
+            filter.tags = names;
+        }
+        match self.tag_mode.as_deref() {
+            None | Some("any") => (),
+            Some("all") => filter.tag_mode = TagMode::All,
+            Some(_) => invalid(&mut errors, "tag_mode", "tag_mode must be any or all"),
+        }
+        if let Some(platform) = &self.platform {
+            let platforms: Option<Vec<i16>> = platform
+                .split(',')
+                .map(|p| p.trim().parse::<i16>().ok().filter(|p| (0..=4).contains(p)))
+                .collect();
+            match platforms {
+                Some(platforms) => filter.platforms = platforms,
+                None => invalid(
+                    &mut errors,
+                    "platform",
+                    "platform must be a comma separated list of platforms from 0 to 4",
+                ),
+            }
+        }
+
+        filter.created_from = parse_time(&mut errors, "created_from", &self.created_from);
+        filter.created_to = parse_time(&mut errors, "created_to", &self.created_to);
+        filter.updated_from = parse_time(&mut errors, "updated_from", &self.updated_from);
+        filter.updated_to = parse_time(&mut errors, "updated_to", &self.updated_to);
+        filter.difficulty_min =
+            parse_difficulty(&mut errors, "difficulty_min", &self.difficulty_min);
+        filter.difficulty_max =
+            parse_difficulty(&mut errors, "difficulty_max", &self.difficulty_max);
+
+        match self.due.as_deref() {
+            None => (),
+            Some("overdue") => filter.due = Some(DueStatus::Overdue),
+            Some("due") => filter.due = Some(DueStatus::Due),
+            Some("upcoming") => filter.due = Some(DueStatus::Upcoming),
+            Some(_) => invalid(&mut errors, "due", "due must be overdue, due or upcoming"),
+        }
+
+        if let Some(sort) = &self.sort {
+            match parse_sort(sort) {
+                Some(keys) => filter.sort = keys,
+                None => invalid(
+                    &mut errors,
+                    "sort",
+                    "sort must be a comma separated list of distinct fields among id, created_at, updated_at, due_at, difficulty, platform and problem_name",
+                ),
+            }
+        }
+        if let Some(limit) = &self.limit {
+            match limit.parse::<i64>() {
+                Ok(limit) if (1..=MAX_LIMIT).contains(&limit) => filter.limit = limit,
+                _ => invalid(
+                    &mut errors,
+                    "limit",
+                    &format!("limit must be a number from 1 to {}", MAX_LIMIT),
+                ),
+            }
+        }
+        // the cursor depends on the sort, so it is decoded last
+        if let Some(cursor) = &self.cursor {
+            match filter.decode_cursor(cursor) {
+                Some(values) => filter.after = Some(values),
+                None => invalid(
+                    &mut errors,
+                    "cursor",
+                    "cursor is malformed or was issued for another sort",
+                ),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(filter)
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+pub fn invalid(errors: &mut ValidationErrors, field: &'static str, reason: &str) {
+    let mut error = ValidationError::new("invalid_query");
+    error.add_param(Cow::from("reasons"), &vec![reason.to_string()]);
+    errors.add(field, error);
+}
+
+fn parse_time(
+    errors: &mut ValidationErrors,
+    field: &'static str,
+    value: &Option<String>,
+) -> Option<NaiveDateTime> {
+    let value = value.as_ref()?;
+    if let Ok(time) = NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S") {
+        return Some(time);
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Some(date.and_hms(0, 0, 0));
+    }
+    invalid(
+        errors,
+        field,
+        &format!(
+            "{} must be formatted as YYYY-MM-DD or YYYY-MM-DDTHH:MM:SS",
+            field
+        ),
+    );
+    None
+}
+
+fn parse_difficulty(
+    errors: &mut ValidationErrors,
+    field: &'static str,
+    value: &Option<String>,
+) -> Option<i16> {
+    let value = value.as_ref()?;
+    match value.parse::<i16>() {
+        Ok(difficulty) if (1..=5).contains(&difficulty) => Some(difficulty),
+        _ => {
+            invalid(
+                errors,
+                field,
+                &format!("{} must be a number from 1 to 5", field),
+            );
+            None
+        }
+    }
+}
+
+fn parse_sort(sort: &str) -> Option<Vec<SortKey>> {
+    let mut keys: Vec<SortKey> = Vec::new();
+    for name in sort.split(',').map(str::trim) {
+        let (descending, name) = match name.strip_prefix('-') {
+            Some(name) => (true, name),
+            None => (false, name),
+        };
+        let field = SortField::parse(name)?;
+        if keys.iter().any(|key| key.field == field) {
+            return None;
+        }
+        keys.push(SortKey { field, descending });
+    }
+    // the id breaks the ties of the other fields
+    if !keys.iter().any(|key| key.field == SortField::Id) {
+        keys.push(SortKey {
+            field: SortField::Id,
+            descending: false,
+        });
+    }
+
+    Some(keys)
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, PartialEq)]
+pub struct Revision {
+    pub id: i32,
+    pub review_id: i32,
+    pub problem_name: String,
+    pub memo: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Revision {
+    fn label(&self) -> String {
+        format!(
+            "revision {} ({})",
+            self.id,
+            self.updated_at.format("%Y-%m-%d %H:%M")
+        )
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct RevisionDiff {
+    pub from: i32,
+    pub to: i32,
+    // unified diffs, empty when unchanged
+    pub problem_name: String,
+    pub memo: String,
+}
+
+impl RevisionDiff {
+    pub fn new(from: &Revision, to: &Revision) -> RevisionDiff {
+        let (from_label, to_label) = (from.label(), to.label());
+        RevisionDiff {
+            from: from.id,
+            to: to.id,
+            problem_name: diff::unified(
+                &from.problem_name,
+                &to.problem_name,
+                &from_label,
+                &to_label,
+            ),
+            memo: diff::unified(
+                from.memo.as_deref().unwrap_or(""),
+                to.memo.as_deref().unwrap_or(""),
+                &from_label,
+                &to_label,
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, PartialEq, Default)]
+pub struct SearchQuery {
+    pub q: Option<String>,
+    pub limit: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Search {
+    // the text handed to websearch_to_tsquery, keeping its quotes, `or` and `-`
+    pub english: String,
+    // the runs of kana and kanji, matched by their bigrams
+    pub cjk_terms: Vec<String>,
+    pub limit: i64,
+}
+
+impl SearchQuery {
+    pub fn parse(&self) -> Result<Search, ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        let q = self.q.as_deref().unwrap_or("").trim();
+        let mut english = String::new();
+        let mut cjk_terms: Vec<String> = Vec::new();
+        let mut run = String::new();
+        for c in q.chars() {
+            if is_cjk(c) {
+                run.push(c);
+                continue;
+            }
+            if !run.is_empty() {
+                cjk_terms.push(std::mem::take(&mut run));
+                english.push(' ');
+            }
+            english.push(c);
+        }
+        if !run.is_empty() {
+            cjk_terms.push(run);
+        }
+        let english = english.split_whitespace().collect::<Vec<&str>>().join(" ");
+        if q.chars().count() > 200 {
+            invalid(&mut errors, "q", "q must be at most 200 characters long");
+        } else if english.chars().all(|c| !c.is_alphanumeric()) && cjk_terms.is_empty() {
+            invalid(&mut errors, "q", "q must contain a word to search for");
+        }
+
+        let mut limit = DEFAULT_SEARCH_LIMIT;
+        if let Some(l) = &self.limit {
+            match l.parse::<i64>() {
+                Ok(l) if (1..=MAX_SEARCH_LIMIT).contains(&l) => limit = l,
+                _ => invalid(
+                    &mut errors,
+                    "limit",
+                    &format!("limit must be a number from 1 to {}", MAX_SEARCH_LIMIT),
+                ),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(Search {
+                english,
+                cjk_terms,
+                limit,
+            })
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl Search {
+    // The tsquery matching the tokens of `cjk_bigrams` in init.sql:
+    // a run is a phrase of its bigrams, and a single character a prefix of one.
+    pub fn cjk_tsquery(&self) -> String {
+        self.cjk_terms
+            .iter()
+            .map(|term| {
+                let chars: Vec<String> = term.chars().map(|c| format!("{:x}", c as u32)).collect();
+                if chars.len() == 1 {
+                    return format!("(j{c}x:* | j{c})", c = chars[0]);
+                }
+                let bigrams: Vec<String> = chars
+                    .windows(2)
+                    .map(|pair| format!("j{}x{}", pair[0], pair[1]))
+                    .collect();
+                format!("({})", bigrams.join(" <-> "))
+            })
+            .collect::<Vec<String>>()
+            .join(" & ")
+    }
+
+    // Turn a headline of ts_headline into HTML, highlighting the Japanese terms as well.
+    // When only a Japanese term matches, the snippet is cut around it from the source.
+    pub fn highlight(&self, headline: &str, source: &str) -> String {
+        let mut snippet = headline.to_string();
+        if !headline.contains(HIGHLIGHT_START) {
+            let found = self
+                .cjk_terms
+                .iter()
+                .filter_map(|term| source.find(term.as_str()).map(|at| (at, term)))
+                .min();
+            if let Some((at, term)) = found {
+                let before: Vec<char> = source[..at].chars().collect();
+                let after: Vec<char> = source[at + term.len()..].chars().collect();
+                let start = before.len().saturating_sub(SNIPPET_CONTEXT_CHARS);
+                let end = after.len().min(SNIPPET_CONTEXT_CHARS);
+                snippet = format!(
+                    "{}{}{}{}{}",
+                    if start > 0 { "…" } else { "" },
+                    before[start..].iter().collect::<String>(),
+                    term,
+                    after[..end].iter().collect::<String>(),
+                    if end < after.len() { "…" } else { "" }
+                );
+            }
+        }
+        if snippet.chars().count() > MAX_SNIPPET_CHARS {
+            snippet = snippet.chars().take(MAX_SNIPPET_CHARS).collect::<String>() + "…";
+        }
+        // close a highlight the cut may have left open
+        if snippet.matches(HIGHLIGHT_START).count() > snippet.matches(HIGHLIGHT_STOP).count() {
+            snippet.push(HIGHLIGHT_STOP);
+        }
+
+        let mut html = escape_html(&snippet);
+        for term in &self.cjk_terms {
+            html = html.replace(
+                term.as_str(),
+                &format!("{}{}{}", HIGHLIGHT_START, term, HIGHLIGHT_STOP),
+            );
+        }
+        html.replace(HIGHLIGHT_START, "<mark>")
+            .replace(HIGHLIGHT_STOP, "</mark>")
+    }
+}
+
+#[derive(Debug, sqlx::FromRow, PartialEq)]
+pub struct SearchRow {
+    pub id: i32,
+    pub url: String,
+    pub platform: i16,
+    pub rank: f32,
+    pub problem_name: String,
+    pub problem_name_headline: String,
+    pub memo: Option<String>,
+    pub memo_headline: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct SearchResult {
+    pub id: i32,
+    pub url: String,
+    pub platform: i16,
+    pub rank: f32,
+    // escaped HTML with the matches wrapped in <mark>
+    pub problem_name: String,
+    pub snippet: Option<String>,
+}
+
+impl SearchResult {
+    pub fn new(row: SearchRow, search: &Search) -> SearchResult {
+        let snippet = match (&row.memo, &row.memo_headline) {
+            (Some(memo), Some(headline)) => Some(search.highlight(headline, memo)),
+            _ => None,
+        };
+
+        SearchResult {
+            id: row.id,
+            url: row.url,
+            platform: row.platform,
+            rank: row.rank,
+            problem_name: search.highlight(&row.problem_name_headline, &row.problem_name),
+            snippet,
+        }
+    }
+}
+
+// kana and kanji, the ranges of `cjk_bigrams` in init.sql
+fn is_cjk(c: char) -> bool {
+    matches!(c, '\u{3040}'..='\u{30ff}' | '\u{3400}'..='\u{9fff}' | '\u{f900}'..='\u{faff}' | '\u{ff66}'..='\u{ff9f}')
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ApiError;
+    use chrono::TimeZone;
+
+    fn review() -> Review {
+        Review {
+            id: 3,
+            problem_name: "test_prob_name".to_string(),
+            url: "https://atcoder.jp/contests/abc200/tasks/abc200_a".to_string(),
+            memo: None,
+            memo_html: None,
+            platform: 1,
+            problem_id: None,
+            difficulty: None,
+            platform_difficulty: None,
+            solved_without_hints: false,
+            created_at: Utc.ymd(2021, 5, 1).and_hms(12, 0, 0),
+            updated_at: None,
+            due_at: None,
+            tags: vec![],
+        }
+    }
+
+    #[test]
+    fn parse_default() {
+        let filter = ReviewQuery::default().parse().unwrap();
+        assert_eq!(ReviewFilter::default(), filter);
+    }
+
+    #[test]
+    fn parse_filters() {
+        let query = ReviewQuery {
+            tags: Some("graph, dp,dp".to_string()),
+            tag_mode: Some("all".to_string()),
+            platform: Some("1,2".to_string()),
+            created_from: Some("2021-05-01".to_string()),
+            updated_to: Some("2021-06-01T09:30:00".to_string()),
+            difficulty_min: Some("2".to_string()),
+            due: Some("overdue".to_string()),
+            sort: Some("-due_at,problem_name".to_string()),
+            limit: Some("10".to_string()),
+            ..Default::default()
+        };
+        let filter = query.parse().unwrap();
+        assert_eq!(vec!["dp".to_string(), "graph".to_string()], filter.tags);
+        assert_eq!(TagMode::All, filter.tag_mode);
+        assert_eq!(vec![1, 2], filter.platforms);
+        assert_eq!(
+            Some(NaiveDate::from_ymd(2021, 5, 1).and_hms(0, 0, 0)),
+            filter.created_from
+        );
+        assert_eq!(
+            Some(NaiveDate::from_ymd(2021, 6, 1).and_hms(9, 30, 0)),
+            filter.updated_to
+        );
+        assert_eq!(Some(2), filter.difficulty_min);
+        assert_eq!(Some(DueStatus::Overdue), filter.due);
+        assert_eq!(
+            vec![
+                SortKey {
+                    field: SortField::DueAt,
+                    descending: true
+                },
+                SortKey {
+                    field: SortField::ProblemName,
+                    descending: false
+                },
+                SortKey {
+                    field: SortField::Id,
+                    descending: false
+                },
+            ],
+            filter.sort
+        );
+        assert_eq!(10, filter.limit);
+    }
+
+    #[test]
+    fn parse_invalid() {
+        let query = ReviewQuery {
+            sort: Some("created_at,created_at".to_string()),
+            ..Default::default()
+        };
+        let error: ApiError = query.parse().unwrap_err().into();
+        assert_eq!(
+            ApiError::ValidationError {
+                fields: vec!["sort".to_string()],
+                reasons: vec!["sort must be a comma separated list of distinct fields among id, created_at, updated_at, due_at, difficulty, platform and problem_name".to_string()],
+            },
+            error
+        );
+
+        let query = ReviewQuery {
+            limit: Some("0".to_string()),
+            ..Default::default()
+        };
+        let error: ApiError = query.parse().unwrap_err().into();
+        assert_eq!(
+            ApiError::ValidationError {
+                fields: vec!["limit".to_string()],
+                reasons: vec!["limit must be a number from 1 to 100".to_string()],
+            },
+            error
+        );
+    }
+
+    #[test]
+    fn cursor_round_trip() {
+        let query = ReviewQuery {
+            sort: Some("-updated_at".to_string()),
+            ..Default::default()
+        };
+        let filter = query.parse().unwrap();
+        let cursor = filter.cursor_of(&review());
+
+        let query = ReviewQuery {
+            sort: Some("-updated_at".to_string()),
+            cursor: Some(cursor.clone()),
+            ..Default::default()
+        };
+        assert_eq!(
+            Some(vec![SortValue::Time(None), SortValue::Int(Some(3))]),
+            query.parse().unwrap().after
+        );
+
+        // the cursor cannot be reused with another order
+        let query = ReviewQuery {
+            sort: Some("updated_at".to_string()),
+            cursor: Some(cursor),
+            ..Default::default()
+        };
+        assert!(query.parse().is_err());
+    }
+
+    #[test]
+    fn parse_search() {
+        let query = SearchQuery {
+            q: Some("convex 凸包 hull の".to_string()),
+            ..Default::default()
+        };
+        let search = query.parse().unwrap();
+        assert_eq!("convex hull".to_string(), search.english);
+        assert_eq!(vec!["凸包".to_string(), "の".to_string()], search.cjk_terms);
+        assert_eq!(
+            "(j51f8x5305) & (j306ex:* | j306e)".to_string(),
+            search.cjk_tsquery()
+        );
+
+        let query = SearchQuery {
+            q: Some(" -- ".to_string()),
+            ..Default::default()
+        };
+        let error: ApiError = query.parse().unwrap_err().into();
+        assert_eq!(
+            ApiError::ValidationError {
+                fields: vec!["q".to_string()],
+                reasons: vec!["q must contain a word to search for".to_string()],
+            },
+            error
+        );
+    }
+
+    #[test]
+    fn highlight_escapes() {
+        let search = Search {
+            english: "convex".to_string(),
+            cjk_terms: vec![],
+            limit: 20,
+        };
+        let headline = "<script>\u{2}convex\u{3}</script>";
+        assert_eq!(
+            "&lt;script&gt;<mark>convex</mark>&lt;/script&gt;".to_string(),
+            search.highlight(headline, headline)
+        );
+    }
+
+    #[test]
+    fn highlight_cjk_window() {
+        let search = Search {
+            english: String::new(),
+            cjk_terms: vec!["凸包".to_string()],
+            limit: 20,
+        };
+        let source = format!("{}凸包のトリック", "あ".repeat(40));
+        let expected = format!("…{}<mark>凸包</mark>のトリック", "あ".repeat(30));
+        assert_eq!(expected, search.highlight(&source, &source));
+    }
+}
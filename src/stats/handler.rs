@@ -0,0 +1,186 @@
+use super::infrastructures;
+use super::model::{DashboardQuery, TimeStatsQuery, TimeTrend, TrendQuery};
+use crate::auth::AuthenticatedUser;
+use crate::error::ApiError;
+use crate::reviews::model::invalid;
+use crate::users;
+use actix_web::{get, web, HttpResponse};
+use anyhow::Result;
+use sqlx::PgPool;
+use validator::ValidationErrors;
+
+async fn user_timezone(pool: &PgPool, user: &AuthenticatedUser) -> Result<String, ApiError> {
+    match users::infrastructures::find_preferences(pool, &user.uid).await {
+        Ok(preferences) => Ok(preferences.timezone),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[get("/stats")]
+pub async fn get_dashboard(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    query: web::Query<DashboardQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let tz = match query.parse() {
+        Ok(Some(tz)) => tz,
+        Ok(None) => user_timezone(pool.get_ref(), &user).await?,
+        Err(e) => return Err(e.into()),
+    };
+    match users::infrastructures::timezone_exists(pool.get_ref(), &tz).await {
+        Ok(true) => (),
+        Ok(false) => {
+            let mut errors = ValidationErrors::new();
+            invalid(&mut errors, "tz", "tz must be a time zone name");
+            return Err(errors.into());
+        }
+        Err(_) => return Err(ApiError::InternalError),
+    }
+
+    match infrastructures::find_dashboard(pool.get_ref(), &user.uid, &tz).await {
+        Ok(dashboard) => Ok(HttpResponse::Ok().json(dashboard)),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[get("/stats/time")]
+pub async fn time_stats(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    query: web::Query<TimeStatsQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let group = match query.parse() {
+        Ok(group) => group,
+        Err(e) => return Err(e.into()),
+    };
+
+    match infrastructures::find_time_stats(pool.get_ref(), &user.uid, group).await {
+        Ok(stats) => Ok(HttpResponse::Ok().json(stats)),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[get("/stats/time/weekly")]
+pub async fn time_trend(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    query: web::Query<TrendQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let weeks = match query.parse() {
+        Ok(weeks) => weeks,
+        Err(e) => return Err(e.into()),
+    };
+
+    let tz = user_timezone(pool.get_ref(), &user).await?;
+
+    match infrastructures::find_weekly_time(pool.get_ref(), &user.uid, weeks, &tz).await {
+        Ok(weekly) => Ok(HttpResponse::Ok().json(TimeTrend::new(weekly))),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::model::{Dashboard, TimeStat};
+    use crate::{auth, config, utils};
+    use actix_web::{test, App};
+    use uuid::Uuid;
+
+    #[actix_rt::test]
+    async fn time_stats_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .data(pool.clone())
+                .service(time_stats)
+                .service(time_trend),
+        )
+        .await;
+        let uid = Uuid::new_v4();
+        let token = auth::infrastructures::create_session(&pool, &uid)
+            .await
+            .unwrap();
+        sqlx::query(r#"INSERT INTO reviews (id, problem_name, url, memo, uid, platform, created_at) VALUES (0, 'test_prob_name', 'test_url', 'test_memo', $1, 3, now())"#)
+			.bind(uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+        sqlx::query(r#"INSERT INTO review_attempts (review_id, started_at, stopped_at, seconds) VALUES (0, now() - INTERVAL '10 minutes', now(), 600)"#)
+			.execute(&pool)
+			.await
+			.unwrap();
+
+        let req = test::TestRequest::get()
+            .uri("/stats/time?by=platform")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let stats: Vec<TimeStat> = test::read_body_json(resp).await;
+        assert_eq!(Some("3".to_string()), stats[0].group);
+        assert_eq!(600, stats[0].total_seconds);
+
+        let req = test::TestRequest::get()
+            .uri("/stats/time/weekly?weeks=4")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let trend: TimeTrend = test::read_body_json(resp).await;
+        assert_eq!(4, trend.weeks.len());
+        assert_eq!(Some(600.0), trend.weeks[3].average_seconds);
+        assert_eq!(None, trend.seconds_per_week);
+
+        let req = test::TestRequest::get()
+            .uri("/stats/time?by=language")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(400, resp.status());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn dashboard_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app =
+            test::init_service(App::new().data(pool.clone()).service(get_dashboard)).await;
+        let uid = Uuid::new_v4();
+        let token = auth::infrastructures::create_session(&pool, &uid)
+            .await
+            .unwrap();
+        sqlx::query(r#"INSERT INTO reviews (id, problem_name, url, memo, uid, platform, created_at) VALUES (0, 'test_prob_name', 'test_url', 'test_memo', $1, 3, now())"#)
+			.bind(uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+        sqlx::query(r#"INSERT INTO review_attempts (review_id, started_at, stopped_at, seconds) VALUES (0, now() - INTERVAL '1 minute', now(), 60)"#)
+			.execute(&pool)
+			.await
+			.unwrap();
+
+        let req = test::TestRequest::get()
+            .uri("/stats?tz=Asia%2FTokyo")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let dashboard: Dashboard = test::read_body_json(resp).await;
+        assert_eq!("Asia/Tokyo".to_string(), dashboard.timezone);
+        assert_eq!(1, dashboard.current_streak);
+        assert_eq!(None, dashboard.retention);
+
+        let req = test::TestRequest::get()
+            .uri("/stats?tz=Mars%2FOlympus")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(400, resp.status());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+}
@@ -0,0 +1,300 @@
+use super::model::{
+    Dashboard, DayActivity, PlatformCount, TimeGroup, TimeStat, WeeklyTime, DIFFICULTY_BAND,
+    PASSING_GRADE,
+};
+use anyhow::Result;
+use chrono::NaiveDate;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+// The time spent on the stopped attempts of the user, by platform, tag or difficulty band.
+// A review with several tags counts toward each of them.
+pub async fn find_time_stats(pool: &PgPool, uid: &Uuid, group: TimeGroup) -> Result<Vec<TimeStat>> {
+    let (key, join) = match group {
+        TimeGroup::Platform => ("r.platform::TEXT", ""),
+        TimeGroup::Tag => (
+            "t.name::TEXT",
+            "LEFT JOIN review_tags rt ON rt.review_id = r.id LEFT JOIN tags t ON t.id = rt.tag_id",
+        ),
+        TimeGroup::Difficulty => (
+            "(FLOOR(p.difficulty::FLOAT8 / $2) * $2)::INTEGER::TEXT",
+            "LEFT JOIN problems p ON p.id = r.problem_id",
+        ),
+    };
+    let sql = format!(
+        r#"SELECT {} AS "group", COUNT(*) AS attempts,
+        COALESCE(SUM(a.seconds), 0)::BIGINT AS total_seconds, AVG(a.seconds)::FLOAT8 AS average_seconds
+        FROM review_attempts a
+        JOIN reviews r ON r.id = a.review_id
+        {}
+        WHERE r.uid = $1 AND a.stopped_at IS NOT NULL
+        GROUP BY 1 ORDER BY 1 NULLS LAST"#,
+        key, join
+    );
+    let mut query = sqlx::query_as::<_, TimeStat>(&sql).bind(uid);
+    if group == TimeGroup::Difficulty {
+        query = query.bind(DIFFICULTY_BAND);
+    }
+    let stats = query.fetch_all(pool).await?;
+
+    Ok(stats)
+}
+
+// The time spent on the attempts stopped in each of the last weeks, the current one included.
+// The weeks start on Monday in the time zone.
+pub async fn find_weekly_time(
+    pool: &PgPool,
+    uid: &Uuid,
+    weeks: i32,
+    tz: &str,
+) -> Result<Vec<WeeklyTime>> {
+    let weekly = sqlx::query_as::<_, WeeklyTime>(
+        r#"WITH weeks AS (
+            SELECT GENERATE_SERIES(DATE_TRUNC('week', NOW() AT TIME ZONE $2) - ($3 - 1) * INTERVAL '1 week', DATE_TRUNC('week', NOW() AT TIME ZONE $2), INTERVAL '1 week') AS week
+        )
+        SELECT w.week::DATE AS week, COUNT(a.id) AS attempts,
+        COALESCE(SUM(a.seconds), 0)::BIGINT AS total_seconds, AVG(a.seconds)::FLOAT8 AS average_seconds
+        FROM weeks w
+        LEFT JOIN (review_attempts a JOIN reviews r ON r.id = a.review_id AND r.uid = $1)
+        ON DATE_TRUNC('week', a.stopped_at AT TIME ZONE $2) = w.week
+        GROUP BY w.week ORDER BY w.week"#,
+    )
+    .bind(uid)
+    .bind(tz)
+    .bind(weeks)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(weekly)
+}
+
+// The streaks, the heatmap and the progress of the user, the days starting at midnight in the time zone.
+pub async fn find_dashboard(pool: &PgPool, uid: &Uuid, tz: &str) -> Result<Dashboard> {
+    let (today,): (NaiveDate,) = sqlx::query_as("SELECT (NOW() AT TIME ZONE $1)::DATE")
+        .bind(tz)
+        .fetch_one(pool)
+        .await?;
+    let activity = sqlx::query_as::<_, DayActivity>(
+        r#"WITH activity AS (
+            SELECT si.graded_at AS at FROM session_items si
+                JOIN review_sessions rs ON rs.id = si.session_id
+                WHERE rs.uid = $1 AND si.graded_at IS NOT NULL
+            UNION ALL SELECT a.stopped_at FROM review_attempts a
+                JOIN reviews r ON r.id = a.review_id
+                WHERE r.uid = $1 AND a.stopped_at IS NOT NULL
+        )
+        SELECT (at AT TIME ZONE $2)::DATE AS date, COUNT(*) AS count
+        FROM activity GROUP BY 1 ORDER BY 1"#,
+    )
+    .bind(uid)
+    .bind(tz)
+    .fetch_all(pool)
+    .await?;
+    let platforms = sqlx::query_as::<_, PlatformCount>(
+        "SELECT platform, COUNT(*) AS reviews FROM reviews WHERE uid = $1 GROUP BY platform ORDER BY platform",
+    )
+    .bind(uid)
+    .fetch_all(pool)
+    .await?;
+    let (recalls, successes): (i64, i64) = sqlx::query_as(
+        r#"SELECT COUNT(si.grade), COUNT(si.grade) FILTER (WHERE si.grade >= $2)
+        FROM session_items si
+        JOIN review_sessions rs ON rs.id = si.session_id
+        WHERE rs.uid = $1"#,
+    )
+    .bind(uid)
+    .bind(PASSING_GRADE)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(Dashboard::new(
+        tz.to_string(),
+        today,
+        &activity,
+        platforms,
+        recalls,
+        successes,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reviews::model::NewReview;
+    use crate::{config, reviews, tags, utils};
+    use chrono::{Datelike, Duration, Utc, Weekday};
+
+    async fn insert_attempt(pool: &PgPool, review_id: i32, seconds: i32, days_ago: i64) {
+        let stopped_at = Utc::now() - Duration::days(days_ago);
+        sqlx::query(r#"INSERT INTO review_attempts (review_id, started_at, stopped_at, seconds) VALUES ($1, $2, $3, $4)"#)
+			.bind(review_id)
+			.bind(stopped_at - Duration::seconds(seconds as i64))
+			.bind(stopped_at)
+			.bind(seconds)
+			.execute(pool)
+			.await
+			.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn time_stats() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        let mut ids = vec![];
+        for (task, platform) in [("abc200_a", 1), ("abc200_f", 1), ("1520A", 2)].iter() {
+            let url = if *platform == 1 {
+                format!("https://atcoder.jp/contests/abc200/tasks/{}", task)
+            } else {
+                "https://codeforces.com/contest/1520/problem/A".to_string()
+            };
+            let review = NewReview {
+                problem_name: task.to_string(),
+                url,
+                memo: None,
+                platform: *platform,
+                difficulty: None,
+                solved_without_hints: false,
+            };
+            let review = reviews::infrastructures::create_review(&pool, &uid, &review)
+                .await
+                .unwrap();
+            ids.push(review.id);
+        }
+        sqlx::query("UPDATE problems SET difficulty = 2051 WHERE external_id = 'abc200_f'")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let dp = tags::infrastructures::find_tag(&pool, &uid, "dp")
+            .await
+            .unwrap()
+            .unwrap();
+        tags::infrastructures::attach_tag(&pool, ids[1], dp.id)
+            .await
+            .unwrap();
+        insert_attempt(&pool, ids[0], 300, 0).await;
+        insert_attempt(&pool, ids[1], 1800, 0).await;
+        insert_attempt(&pool, ids[1], 1200, 14).await;
+        insert_attempt(&pool, ids[2], 600, 0).await;
+        // still running
+        sqlx::query("INSERT INTO review_attempts (review_id, started_at) VALUES ($1, now())")
+            .bind(ids[2])
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let stats = find_time_stats(&pool, &uid, TimeGroup::Platform)
+            .await
+            .unwrap();
+        assert_eq!(
+            vec![
+                TimeStat {
+                    group: Some("1".to_string()),
+                    attempts: 3,
+                    total_seconds: 3300,
+                    average_seconds: Some(1100.0),
+                },
+                TimeStat {
+                    group: Some("2".to_string()),
+                    attempts: 1,
+                    total_seconds: 600,
+                    average_seconds: Some(600.0),
+                },
+            ],
+            stats
+        );
+        let stats = find_time_stats(&pool, &uid, TimeGroup::Tag).await.unwrap();
+        assert_eq!(Some("dp".to_string()), stats[0].group);
+        assert_eq!(2, stats[0].attempts);
+        assert_eq!(None, stats[1].group);
+        let stats = find_time_stats(&pool, &uid, TimeGroup::Difficulty)
+            .await
+            .unwrap();
+        assert_eq!(Some("2000".to_string()), stats[0].group);
+        assert_eq!(Some(1500.0), stats[0].average_seconds);
+        assert_eq!(None, stats[1].group);
+
+        let weekly = find_weekly_time(&pool, &uid, 3, "UTC").await.unwrap();
+        assert_eq!(3, weekly.len());
+        assert!(weekly.iter().all(|w| w.week.weekday() == Weekday::Mon));
+        assert_eq!(3, weekly[2].attempts);
+        assert_eq!(2700, weekly[2].total_seconds);
+        assert_eq!(1, weekly[0].attempts);
+        assert_eq!(None, weekly[1].average_seconds);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn dashboard() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        // 23:30 UTC is already the next day in Tokyo
+        let yesterday = Utc::now().date().and_hms(23, 30, 0) - Duration::days(1);
+        for (id, platform, days_ago) in [(0, 1, 0), (1, 2, 3), (2, 2, 4)].iter() {
+            sqlx::query(r#"INSERT INTO reviews (id, problem_name, url, memo, uid, platform, created_at) VALUES ($1, 'test_prob_name', 'test_url', 'test_memo', $2, $3, $4)"#)
+				.bind(id)
+				.bind(uid)
+				.bind(*platform as i16)
+				.bind(yesterday - Duration::days(*days_ago))
+				.execute(&pool)
+				.await
+				.unwrap();
+        }
+        let (session_id,): (i32,) = sqlx::query_as(
+            "INSERT INTO review_sessions (uid, started_at) VALUES ($1, now()) RETURNING id",
+        )
+        .bind(uid)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        for (position, grade) in [(1, 5), (2, 1)].iter() {
+            sqlx::query("INSERT INTO session_items (session_id, position, review_id, grade, graded_at) VALUES ($1, $2, 0, $3, $4)")
+                .bind(session_id)
+                .bind(*position as i16)
+                .bind(*grade as i16)
+                .bind(yesterday - Duration::days(*position as i64))
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        for (review_id, days_ago) in [(0, 0), (1, 3)].iter() {
+            let stopped_at = yesterday - Duration::days(*days_ago);
+            sqlx::query(r#"INSERT INTO review_attempts (review_id, started_at, stopped_at, seconds) VALUES ($1, $2, $3, 60)"#)
+				.bind(review_id)
+				.bind(stopped_at - Duration::seconds(60))
+				.bind(stopped_at)
+				.execute(&pool)
+				.await
+				.unwrap();
+        }
+
+        let dashboard = find_dashboard(&pool, &uid, "UTC").await.unwrap();
+        assert_eq!(Utc::now().date().naive_utc(), dashboard.today);
+        // from 4 days ago to yesterday, by the attempts and the grades;
+        // writing a review is not reviewing it, so the one 5 days ago does not count
+        assert_eq!(4, dashboard.current_streak);
+        assert_eq!(4, dashboard.longest_streak);
+        assert_eq!(Some(0.5), dashboard.retention);
+        assert_eq!(
+            vec![
+                PlatformCount {
+                    platform: 1,
+                    reviews: 1
+                },
+                PlatformCount {
+                    platform: 2,
+                    reviews: 2
+                },
+            ],
+            dashboard.platforms
+        );
+        let tokyo = find_dashboard(&pool, &uid, "Asia/Tokyo").await.unwrap();
+        let last = tokyo.heatmap.iter().rev().find(|d| d.count > 0).unwrap();
+        assert_eq!(Utc::now().date().naive_utc(), last.date);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+}
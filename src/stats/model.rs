@@ -0,0 +1,317 @@
+use crate::reviews::model::invalid;
+use chrono::{Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use validator::ValidationErrors;
+
+// the width of the bands the platform difficulties are grouped by, the width of a color of AtCoder
+pub const DIFFICULTY_BAND: i32 = 400;
+// the number of weeks the trend covers when none is given
+const DEFAULT_WEEKS: i32 = 12;
+const MAX_WEEKS: i32 = 104;
+// the number of days the heatmap covers, today included
+pub const HEATMAP_DAYS: i64 = 365;
+// the grades from this one on count as successful recalls
+pub const PASSING_GRADE: i16 = 3;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TimeGroup {
+    Platform,
+    Tag,
+    // the band of the difficulty given by the platform
+    Difficulty,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TimeStatsQuery {
+    // platform, tag or difficulty
+    pub by: Option<String>,
+}
+
+impl TimeStatsQuery {
+    pub fn parse(&self) -> Result<TimeGroup, ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        let group = match self.by.as_deref() {
+            Some("platform") => Some(TimeGroup::Platform),
+            Some("tag") => Some(TimeGroup::Tag),
+            Some("difficulty") => Some(TimeGroup::Difficulty),
+            _ => {
+                invalid(&mut errors, "by", "by must be platform, tag or difficulty");
+                None
+            }
+        };
+
+        match group {
+            Some(group) => Ok(group),
+            None => Err(errors),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct TrendQuery {
+    pub weeks: Option<String>,
+}
+
+impl TrendQuery {
+    pub fn parse(&self) -> Result<i32, ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        match self.weeks.as_deref().map(|w| w.parse::<i32>()) {
+            None => Ok(DEFAULT_WEEKS),
+            Some(Ok(weeks)) if (1..=MAX_WEEKS).contains(&weeks) => Ok(weeks),
+            Some(_) => {
+                invalid(
+                    &mut errors,
+                    "weeks",
+                    &format!("weeks must be a number from 1 to {}", MAX_WEEKS),
+                );
+                Err(errors)
+            }
+        }
+    }
+}
+
+// The time spent on the stopped attempts of a group.
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, PartialEq)]
+pub struct TimeStat {
+    // the platform, the tag name or the lowest difficulty of the band,
+    // absent for the reviews without a tag or a known difficulty
+    pub group: Option<String>,
+    pub attempts: i64,
+    pub total_seconds: i64,
+    pub average_seconds: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, PartialEq)]
+pub struct WeeklyTime {
+    // the Monday starting the week
+    pub week: NaiveDate,
+    pub attempts: i64,
+    pub total_seconds: i64,
+    pub average_seconds: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct TimeTrend {
+    pub weeks: Vec<WeeklyTime>,
+    // the slope of the least squares line through the weekly averages, negative when getting faster;
+    // absent until two weeks have attempts
+    pub seconds_per_week: Option<f64>,
+}
+
+impl TimeTrend {
+    pub fn new(weeks: Vec<WeeklyTime>) -> TimeTrend {
+        let points: Vec<(f64, f64)> = weeks
+            .iter()
+            .enumerate()
+            .filter_map(|(i, week)| week.average_seconds.map(|a| (i as f64, a)))
+            .collect();
+        TimeTrend {
+            seconds_per_week: slope(&points),
+            weeks,
+        }
+    }
+}
+
+fn slope(points: &[(f64, f64)]) -> Option<f64> {
+    if points.len() < 2 {
+        return None;
+    }
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+    let covariance: f64 = points
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    let variance: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+
+    Some(covariance / variance)
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct DashboardQuery {
+    // an IANA time zone name such as Asia/Tokyo, the one of the user when absent
+    pub tz: Option<String>,
+}
+
+impl DashboardQuery {
+    pub fn parse(&self) -> Result<Option<String>, ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        match self.tz.as_deref() {
+            None => Ok(None),
+            Some(tz) if !tz.is_empty() && tz.len() <= 64 => Ok(Some(tz.to_string())),
+            Some(_) => {
+                invalid(&mut errors, "tz", "tz must be a time zone name");
+                Err(errors)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, PartialEq, Clone, Copy)]
+pub struct DayActivity {
+    pub date: NaiveDate,
+    // the items graded in sessions and the attempts stopped on the day
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, PartialEq)]
+pub struct PlatformCount {
+    pub platform: i16,
+    pub reviews: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Dashboard {
+    pub timezone: String,
+    pub today: NaiveDate,
+    // the days in a row with activity up to today, or up to yesterday while today has none yet
+    pub current_streak: i64,
+    pub longest_streak: i64,
+    // every day of the last year, oldest first
+    pub heatmap: Vec<DayActivity>,
+    pub platforms: Vec<PlatformCount>,
+    // the number of session items graded
+    pub recalls: i64,
+    // the share of the recalls graded as successful, absent until something is graded
+    pub retention: Option<f64>,
+}
+
+impl Dashboard {
+    // The days of activity are the ones with a count, oldest first.
+    pub fn new(
+        timezone: String,
+        today: NaiveDate,
+        activity: &[DayActivity],
+        platforms: Vec<PlatformCount>,
+        recalls: i64,
+        successes: i64,
+    ) -> Dashboard {
+        let (current_streak, longest_streak) = streaks(activity, today);
+        Dashboard {
+            timezone,
+            today,
+            current_streak,
+            longest_streak,
+            heatmap: heatmap(activity, today),
+            platforms,
+            recalls,
+            retention: if recalls > 0 {
+                Some(successes as f64 / recalls as f64)
+            } else {
+                None
+            },
+        }
+    }
+}
+
+fn streaks(activity: &[DayActivity], today: NaiveDate) -> (i64, i64) {
+    let mut longest = 0;
+    let mut run = 0;
+    let mut last: Option<NaiveDate> = None;
+    for day in activity.iter().filter(|d| d.count > 0 && d.date <= today) {
+        run = match last {
+            Some(last) if day.date - last == Duration::days(1) => run + 1,
+            _ => 1,
+        };
+        longest = longest.max(run);
+        last = Some(day.date);
+    }
+    let current = match last {
+        Some(last) if today - last <= Duration::days(1) => run,
+        _ => 0,
+    };
+
+    (current, longest)
+}
+
+fn heatmap(activity: &[DayActivity], today: NaiveDate) -> Vec<DayActivity> {
+    let counts: HashMap<NaiveDate, i64> = activity.iter().map(|d| (d.date, d.count)).collect();
+    (0..HEATMAP_DAYS)
+        .rev()
+        .map(|ago| {
+            let date = today - Duration::days(ago);
+            DayActivity {
+                date,
+                count: counts.get(&date).copied().unwrap_or(0),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn week(day: u32, average_seconds: Option<f64>) -> WeeklyTime {
+        WeeklyTime {
+            week: NaiveDate::from_ymd(2021, 5, day),
+            attempts: average_seconds.map_or(0, |_| 1),
+            total_seconds: average_seconds.map_or(0, |a| a as i64),
+            average_seconds,
+        }
+    }
+
+    #[test]
+    fn time_trend() {
+        let trend = TimeTrend::new(vec![
+            week(3, Some(1200.0)),
+            week(10, None),
+            week(17, Some(800.0)),
+            week(24, Some(600.0)),
+        ]);
+        // fitted through the weeks 0, 2 and 3
+        assert!((trend.seconds_per_week.unwrap() + 200.0).abs() < 1e-6);
+        assert_eq!(
+            None,
+            TimeTrend::new(vec![week(3, Some(1200.0))]).seconds_per_week
+        );
+    }
+
+    fn activity(days: &[(u32, i64)]) -> Vec<DayActivity> {
+        days.iter()
+            .map(|(day, count)| DayActivity {
+                date: NaiveDate::from_ymd(2021, 5, *day),
+                count: *count,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn dashboard() {
+        let today = NaiveDate::from_ymd(2021, 5, 20);
+        let days = activity(&[(1, 2), (2, 1), (3, 5), (4, 1), (10, 1), (18, 3), (19, 1)]);
+        let dashboard = Dashboard::new("UTC".to_string(), today, &days, vec![], 4, 3);
+        assert_eq!(2, dashboard.current_streak);
+        assert_eq!(4, dashboard.longest_streak);
+        assert_eq!(Some(0.75), dashboard.retention);
+        assert_eq!(HEATMAP_DAYS as usize, dashboard.heatmap.len());
+        assert_eq!(
+            activity(&[(18, 3), (19, 1), (20, 0)]),
+            dashboard.heatmap[HEATMAP_DAYS as usize - 3..].to_vec()
+        );
+        assert_eq!(14, dashboard.heatmap.iter().map(|d| d.count).sum::<i64>());
+
+        // the streak is broken once a whole day passes without activity
+        let today = NaiveDate::from_ymd(2021, 5, 21);
+        let dashboard = Dashboard::new("UTC".to_string(), today, &days, vec![], 0, 0);
+        assert_eq!(0, dashboard.current_streak);
+        assert_eq!(None, dashboard.retention);
+        assert_eq!((0, 0), streaks(&[], today));
+    }
+
+    #[test]
+    fn parse_queries() {
+        let query = TimeStatsQuery {
+            by: Some("tag".to_string()),
+        };
+        assert_eq!(TimeGroup::Tag, query.parse().unwrap());
+        assert!(TimeStatsQuery::default().parse().is_err());
+        assert_eq!(DEFAULT_WEEKS, TrendQuery::default().parse().unwrap());
+        let query = TrendQuery {
+            weeks: Some("105".to_string()),
+        };
+        assert!(query.parse().is_err());
+    }
+}
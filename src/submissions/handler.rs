@@ -0,0 +1,235 @@
+use super::infrastructures;
+use super::model::{NewSubmission, SubmissionDiff};
+use crate::auth::AuthenticatedUser;
+use crate::error::ApiError;
+use crate::reviews;
+use actix_web::{get, post, web, HttpResponse};
+use anyhow::Result;
+use sqlx::PgPool;
+use uuid::Uuid;
+use validator::Validate;
+
+// The submissions can only be reached through a review of the user.
+async fn check_review(pool: &PgPool, uid: &Uuid, review_id: i32) -> Result<(), ApiError> {
+    match reviews::infrastructures::find_review(pool, uid, review_id).await {
+        Ok(Some(_)) => Ok(()),
+        Ok(None) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[post("/reviews/{id:\\d+}/submissions")]
+pub async fn create_submission(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path(id): web::Path<i32>,
+    form: web::Form<NewSubmission>,
+) -> Result<HttpResponse, ApiError> {
+    match form.validate() {
+        Ok(_) => (),
+        Err(e) => return Err(e.into()),
+    }
+    check_review(pool.get_ref(), &user.uid, id).await?;
+
+    match infrastructures::create_submission(pool.get_ref(), id, &form).await {
+        Ok(submission) => Ok(HttpResponse::Created().json(submission)),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[get("/reviews/{id:\\d+}/submissions")]
+pub async fn list_submissions(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path(id): web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    check_review(pool.get_ref(), &user.uid, id).await?;
+
+    match infrastructures::find_submissions(pool.get_ref(), id).await {
+        Ok(submissions) => Ok(HttpResponse::Ok().json(submissions)),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[get("/reviews/{id:\\d+}/submissions/{submission_id:\\d+}")]
+pub async fn get_submission(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path((id, submission_id)): web::Path<(i32, i32)>,
+) -> Result<HttpResponse, ApiError> {
+    check_review(pool.get_ref(), &user.uid, id).await?;
+
+    match infrastructures::find_submission(pool.get_ref(), id, submission_id).await {
+        Ok(Some(submission)) => Ok(HttpResponse::Ok().json(submission)),
+        Ok(None) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[get("/reviews/{id:\\d+}/submissions/{from:\\d+}/diff/{to:\\d+}")]
+pub async fn diff_submissions(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path((id, from, to)): web::Path<(i32, i32, i32)>,
+) -> Result<HttpResponse, ApiError> {
+    check_review(pool.get_ref(), &user.uid, id).await?;
+
+    let from = match infrastructures::find_submission(pool.get_ref(), id, from).await {
+        Ok(Some(s)) => s,
+        Ok(None) => return Err(ApiError::NotFound),
+        Err(_) => return Err(ApiError::InternalError),
+    };
+    let to = match infrastructures::find_submission(pool.get_ref(), id, to).await {
+        Ok(Some(s)) => s,
+        Ok(None) => return Err(ApiError::NotFound),
+        Err(_) => return Err(ApiError::InternalError),
+    };
+
+    Ok(HttpResponse::Ok().json(SubmissionDiff::new(&from, &to)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::submissions::model::{Submission, SubmissionSummary};
+    use crate::{config, utils};
+    use actix_web::{body::Body, test, App};
+    use serde_json::json;
+
+    fn new_submission(source_code: &str, verdict: &str) -> NewSubmission {
+        NewSubmission {
+            source_code: source_code.to_string(),
+            language: "Rust".to_string(),
+            verdict: verdict.to_string(),
+            runtime_ms: None,
+            without_editorial: false,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn create_and_list_submissions() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .data(pool.clone())
+                .service(create_submission)
+                .service(list_submissions),
+        )
+        .await;
+        let (uid, token) = utils::sign_in(&pool).await;
+        utils::insert_review(&pool, 0, &uid).await;
+
+        let req = test::TestRequest::post()
+            .uri("/reviews/0/submissions")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&new_submission("fn main() {}\n", "AC"))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(201, resp.status());
+        let created: Submission = test::read_body_json(resp).await;
+        assert_eq!("fn main() {}\n".to_string(), created.source_code);
+
+        let req = test::TestRequest::get()
+            .uri("/reviews/0/submissions")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let submissions: Vec<SubmissionSummary> = test::read_body_json(resp).await;
+        assert_eq!(1, submissions.len());
+        assert_eq!(created.id, submissions[0].id);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn create_submission_invalid_verdict() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app =
+            test::init_service(App::new().data(pool.clone()).service(create_submission)).await;
+        let (uid, token) = utils::sign_in(&pool).await;
+        utils::insert_review(&pool, 0, &uid).await;
+
+        let req = test::TestRequest::post()
+            .uri("/reviews/0/submissions")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&new_submission("fn main() {}\n", "Accepted"))
+            .to_request();
+        let mut resp = test::call_service(&mut app, req).await;
+        assert_eq!(400, resp.status());
+        let resp_body = resp.take_body();
+        let resp_body = resp_body.as_ref().unwrap();
+        assert_eq!(
+            &Body::from(
+                json!({"code": 400, "message": "validation error on field: [\"verdict\"]"})
+            ),
+            resp_body
+        );
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn list_submissions_of_other_user() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app =
+            test::init_service(App::new().data(pool.clone()).service(list_submissions)).await;
+        let (_, token) = utils::sign_in(&pool).await;
+        utils::insert_review(&pool, 0, &Uuid::new_v4()).await;
+
+        let req = test::TestRequest::get()
+            .uri("/reviews/0/submissions")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(404, resp.status());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn diff_submissions_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app =
+            test::init_service(App::new().data(pool.clone()).service(diff_submissions)).await;
+        let (uid, token) = utils::sign_in(&pool).await;
+        utils::insert_review(&pool, 0, &uid).await;
+        let from = infrastructures::create_submission(&pool, 0, &new_submission("a\nb\n", "WA"))
+            .await
+            .unwrap();
+        let to = infrastructures::create_submission(&pool, 0, &new_submission("a\nc\n", "AC"))
+            .await
+            .unwrap();
+
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/reviews/0/submissions/{}/diff/{}",
+                from.id, to.id
+            ))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let diff: SubmissionDiff = test::read_body_json(resp).await;
+        assert_eq!(SubmissionDiff::new(&from, &to), diff);
+        assert!(diff.diff.contains("-b\n+c\n"));
+
+        // both submissions must belong to the review
+        let req = test::TestRequest::get()
+            .uri(&format!(
+                "/reviews/0/submissions/{}/diff/{}",
+                from.id,
+                to.id + 1
+            ))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(404, resp.status());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+}
@@ -0,0 +1,121 @@
+use super::model::{NewSubmission, Submission, SubmissionSummary};
+use anyhow::Result;
+use chrono::Utc;
+use sqlx::PgPool;
+
+pub async fn create_submission(
+    pool: &PgPool,
+    review_id: i32,
+    submission: &NewSubmission,
+) -> Result<Submission> {
+    let now = Utc::now();
+    let submission = sqlx::query_as::<_, Submission>(
+        r#"INSERT INTO submissions (review_id, source_code, language, verdict, runtime_ms, without_editorial, created_at) VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING id, review_id, source_code, language, verdict, runtime_ms, without_editorial, created_at"#,
+    )
+    .bind(review_id)
+    .bind(&submission.source_code)
+    .bind(&submission.language)
+    .bind(&submission.verdict)
+    .bind(submission.runtime_ms)
+    .bind(submission.without_editorial)
+    .bind(now)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(submission)
+}
+
+// The submissions of the review, oldest first.
+pub async fn find_submissions(pool: &PgPool, review_id: i32) -> Result<Vec<SubmissionSummary>> {
+    let submissions = sqlx::query_as::<_, SubmissionSummary>(
+        r#"SELECT id, language, verdict, runtime_ms, without_editorial, created_at FROM submissions WHERE review_id = $1 ORDER BY id"#,
+    )
+    .bind(review_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(submissions)
+}
+
+pub async fn find_submission(pool: &PgPool, review_id: i32, id: i32) -> Result<Option<Submission>> {
+    let submission = sqlx::query_as::<_, Submission>(
+        r#"SELECT id, review_id, source_code, language, verdict, runtime_ms, without_editorial, created_at FROM submissions WHERE review_id = $1 AND id = $2"#,
+    )
+    .bind(review_id)
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(submission)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config, utils};
+    use uuid::Uuid;
+
+    fn new_submission(verdict: &str) -> NewSubmission {
+        NewSubmission {
+            source_code: "int main() {}\n".to_string(),
+            language: "C++17".to_string(),
+            verdict: verdict.to_string(),
+            runtime_ms: Some(12),
+            without_editorial: true,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn create_and_find_submissions() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        utils::insert_review(&pool, 0, &Uuid::new_v4()).await;
+        utils::insert_review(&pool, 1, &Uuid::new_v4()).await;
+
+        let wa = create_submission(&pool, 0, &new_submission("WA"))
+            .await
+            .unwrap();
+        let ac = create_submission(&pool, 0, &new_submission("AC"))
+            .await
+            .unwrap();
+        create_submission(&pool, 1, &new_submission("AC"))
+            .await
+            .unwrap();
+
+        let summaries = find_submissions(&pool, 0).await.unwrap();
+        assert_eq!(
+            vec![(wa.id, "WA"), (ac.id, "AC")],
+            summaries
+                .iter()
+                .map(|s| (s.id, s.verdict.as_str()))
+                .collect::<Vec<(i32, &str)>>()
+        );
+        assert_eq!(
+            Some(ac),
+            find_submission(&pool, 0, summaries[1].id).await.unwrap()
+        );
+        // the submission must belong to the review
+        assert_eq!(None, find_submission(&pool, 1, wa.id).await.unwrap());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn submissions_deleted_with_review() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        utils::insert_review(&pool, 0, &Uuid::new_v4()).await;
+        create_submission(&pool, 0, &new_submission("AC"))
+            .await
+            .unwrap();
+
+        sqlx::query("DELETE FROM reviews WHERE id = 0")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(find_submissions(&pool, 0).await.unwrap().is_empty());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+}
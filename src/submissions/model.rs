@@ -0,0 +1,137 @@
+use crate::diff;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use validator::{Validate, ValidationError};
+
+pub const VERDICTS: [&str; 8] = ["AC", "WA", "TLE", "MLE", "RE", "CE", "OLE", "IE"];
+
+#[derive(Debug, Serialize, Deserialize, Validate, PartialEq)]
+pub struct NewSubmission {
+    #[validate(length(min = 1, max = 65536))]
+    pub source_code: String,
+    #[validate(length(min = 1, max = 50))]
+    pub language: String,
+    #[validate(custom = "validate_verdict")]
+    pub verdict: String,
+    #[validate(range(min = 0))]
+    pub runtime_ms: Option<i32>,
+    // solved without reading the editorial
+    #[serde(default)]
+    pub without_editorial: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, PartialEq)]
+pub struct Submission {
+    pub id: i32,
+    pub review_id: i32,
+    pub source_code: String,
+    pub language: String,
+    pub verdict: String,
+    pub runtime_ms: Option<i32>,
+    pub without_editorial: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+// a submission listed without its source code
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, PartialEq)]
+pub struct SubmissionSummary {
+    pub id: i32,
+    pub language: String,
+    pub verdict: String,
+    pub runtime_ms: Option<i32>,
+    pub without_editorial: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct SubmissionDiff {
+    pub from: i32,
+    pub to: i32,
+    // a unified diff, empty when the source codes are the same
+    pub diff: String,
+}
+
+impl SubmissionDiff {
+    pub fn new(from: &Submission, to: &Submission) -> SubmissionDiff {
+        SubmissionDiff {
+            from: from.id,
+            to: to.id,
+            diff: diff::unified(
+                &from.source_code,
+                &to.source_code,
+                &from.label(),
+                &to.label(),
+            ),
+        }
+    }
+}
+
+impl Submission {
+    fn label(&self) -> String {
+        format!(
+            "submission {} ({}, {}, {})",
+            self.id,
+            self.language,
+            self.verdict,
+            self.created_at.format("%Y-%m-%d %H:%M")
+        )
+    }
+}
+
+fn validate_verdict(verdict: &str) -> Result<(), ValidationError> {
+    if VERDICTS.contains(&verdict) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("verdict"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn submission(id: i32, source_code: &str) -> Submission {
+        Submission {
+            id,
+            review_id: 0,
+            source_code: source_code.to_string(),
+            language: "C++17".to_string(),
+            verdict: "WA".to_string(),
+            runtime_ms: Some(10),
+            without_editorial: false,
+            created_at: Utc.ymd(2021, 5, id as u32).and_hms(12, 0, 0),
+        }
+    }
+
+    #[test]
+    fn diff_test() {
+        let from = submission(1, "int main() {\n  int n;\n  return 0;\n}\n");
+        let to = submission(2, "int main() {\n  long long n;\n  return 0;\n}\n");
+        let diff = SubmissionDiff::new(&from, &to);
+        assert_eq!(
+            "--- submission 1 (C++17, WA, 2021-05-01 12:00)\n\
+             +++ submission 2 (C++17, WA, 2021-05-02 12:00)\n\
+             @@ -1,4 +1,4 @@\n \
+             int main() {\n\
+             -  int n;\n\
+             +  long long n;\n   \
+             return 0;\n \
+             }\n",
+            diff.diff
+        );
+    }
+
+    #[test]
+    fn diff_same() {
+        let from = submission(1, "int main() {}\n");
+        let to = submission(2, "int main() {}\n");
+        assert_eq!("", SubmissionDiff::new(&from, &to).diff);
+    }
+
+    #[test]
+    fn validate_verdict_test() {
+        assert!(validate_verdict("TLE").is_ok());
+        assert!(validate_verdict("tle").is_err());
+    }
+}
@@ -0,0 +1,257 @@
+use super::infrastructures;
+use super::model::NewTag;
+use crate::auth::AuthenticatedUser;
+use crate::error::ApiError;
+use crate::reviews;
+use actix_web::{delete, get, post, web, HttpResponse};
+use anyhow::Result;
+use sqlx::PgPool;
+use validator::Validate;
+
+#[get("/tags")]
+pub async fn list_tags(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, ApiError> {
+    match infrastructures::find_tags(pool.get_ref(), &user.uid).await {
+        Ok(tags) => Ok(HttpResponse::Ok().json(tags)),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[post("/tags")]
+pub async fn create_tag(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    form: web::Form<NewTag>,
+) -> Result<HttpResponse, ApiError> {
+    match form.validate() {
+        Ok(_) => (),
+        Err(e) => return Err(e.into()),
+    }
+
+    // the name must not shadow a curated tag nor another tag of the user
+    match infrastructures::find_tag(pool.get_ref(), &user.uid, &form.name).await {
+        Ok(Some(_)) => return Err(ApiError::Conflict),
+        Ok(None) => (),
+        Err(_) => return Err(ApiError::InternalError),
+    }
+
+    match infrastructures::create_tag(pool.get_ref(), &user.uid, &form.name).await {
+        Ok(tag) => Ok(HttpResponse::Created().json(tag)),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[delete("/tags/{name}")]
+pub async fn delete_tag(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path(name): web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    match infrastructures::delete_tag(pool.get_ref(), &user.uid, &name).await {
+        Ok(true) => Ok(HttpResponse::Ok().json("")),
+        Ok(false) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[post("/reviews/{id}/tags")]
+pub async fn attach_tag(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path(id): web::Path<i32>,
+    form: web::Form<NewTag>,
+) -> Result<HttpResponse, ApiError> {
+    match reviews::infrastructures::find_review(pool.get_ref(), &user.uid, id).await {
+        Ok(Some(_)) => (),
+        Ok(None) => return Err(ApiError::NotFound),
+        Err(_) => return Err(ApiError::InternalError),
+    }
+    let tag = match infrastructures::find_tag(pool.get_ref(), &user.uid, &form.name).await {
+        Ok(Some(tag)) => tag,
+        Ok(None) => return Err(ApiError::NotFound),
+        Err(_) => return Err(ApiError::InternalError),
+    };
+
+    match infrastructures::attach_tag(pool.get_ref(), id, tag.id).await {
+        Ok(_) => (),
+        Err(_) => return Err(ApiError::InternalError),
+    }
+    match reviews::infrastructures::find_review(pool.get_ref(), &user.uid, id).await {
+        Ok(Some(review)) => Ok(HttpResponse::Ok().json(review)),
+        Ok(None) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[delete("/reviews/{id}/tags/{name}")]
+pub async fn detach_tag(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path((id, name)): web::Path<(i32, String)>,
+) -> Result<HttpResponse, ApiError> {
+    match reviews::infrastructures::find_review(pool.get_ref(), &user.uid, id).await {
+        Ok(Some(_)) => (),
+        Ok(None) => return Err(ApiError::NotFound),
+        Err(_) => return Err(ApiError::InternalError),
+    }
+    let tag = match infrastructures::find_tag(pool.get_ref(), &user.uid, &name).await {
+        Ok(Some(tag)) => tag,
+        Ok(None) => return Err(ApiError::NotFound),
+        Err(_) => return Err(ApiError::InternalError),
+    };
+
+    match infrastructures::detach_tag(pool.get_ref(), id, tag.id).await {
+        Ok(true) => Ok(HttpResponse::Ok().json("")),
+        Ok(false) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reviews::model::Review;
+    use crate::tags::model::Tag;
+    use crate::{config, utils};
+    use actix_web::{body::Body, test, App};
+    use serde_json::json;
+
+    #[actix_rt::test]
+    async fn create_tag_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(App::new().data(pool.clone()).service(create_tag)).await;
+        let (_, token) = utils::sign_in(&pool).await;
+
+        let req = test::TestRequest::post()
+            .uri("/tags")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&NewTag {
+                name: "my-tag".to_string(),
+            })
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(201, resp.status());
+        let tag: Tag = test::read_body_json(resp).await;
+        assert_eq!("my-tag".to_string(), tag.name);
+        assert!(!tag.curated);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn create_tag_curated_name() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(App::new().data(pool.clone()).service(create_tag)).await;
+        let (_, token) = utils::sign_in(&pool).await;
+
+        let req = test::TestRequest::post()
+            .uri("/tags")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&NewTag {
+                name: "dp".to_string(),
+            })
+            .to_request();
+        let mut resp = test::call_service(&mut app, req).await;
+        assert_eq!(409, resp.status());
+        let resp_body = resp.take_body();
+        let resp_body = resp_body.as_ref().unwrap();
+        assert_eq!(
+            &Body::from(json!({"code": 409, "message": "conflict"})),
+            resp_body
+        );
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn create_tag_invalid_name() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(App::new().data(pool.clone()).service(create_tag)).await;
+        let (_, token) = utils::sign_in(&pool).await;
+
+        let req = test::TestRequest::post()
+            .uri("/tags")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&NewTag {
+                name: "Two Words".to_string(),
+            })
+            .to_request();
+        let mut resp = test::call_service(&mut app, req).await;
+        assert_eq!(400, resp.status());
+        let resp_body = resp.take_body();
+        let resp_body = resp_body.as_ref().unwrap();
+        assert_eq!(
+            &Body::from(json!({"code": 400, "message": "validation error on field: [\"name\"]"})),
+            resp_body
+        );
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn attach_and_detach_tag_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .data(pool.clone())
+                .service(attach_tag)
+                .service(detach_tag),
+        )
+        .await;
+        let (uid, token) = utils::sign_in(&pool).await;
+        utils::insert_review(&pool, 0, &uid).await;
+
+        let req = test::TestRequest::post()
+            .uri("/reviews/0/tags")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&NewTag {
+                name: "graph".to_string(),
+            })
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let review: Review = test::read_body_json(resp).await;
+        assert_eq!(vec!["graph".to_string()], review.tags);
+
+        let req = test::TestRequest::delete()
+            .uri("/reviews/0/tags/graph")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let review = reviews::infrastructures::find_review(&pool, &uid, 0)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(review.tags.is_empty());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn attach_tag_unknown() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(App::new().data(pool.clone()).service(attach_tag)).await;
+        let (uid, token) = utils::sign_in(&pool).await;
+        utils::insert_review(&pool, 0, &uid).await;
+
+        let req = test::TestRequest::post()
+            .uri("/reviews/0/tags")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&NewTag {
+                name: "no-such-tag".to_string(),
+            })
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(404, resp.status());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+}
@@ -0,0 +1,161 @@
+use super::model::Tag;
+use anyhow::Result;
+use sqlx::{Executor, PgPool, Postgres};
+use uuid::Uuid;
+
+// The curated tags followed by the ones defined by the user.
+pub async fn find_tags(pool: &PgPool, uid: &Uuid) -> Result<Vec<Tag>> {
+    let tags = sqlx::query_as::<_, Tag>(
+        r#"SELECT id, name, uid IS NULL AS curated FROM tags WHERE uid IS NULL OR uid = $1 ORDER BY uid NULLS FIRST, name"#,
+    )
+    .bind(uid)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(tags)
+}
+
+// Find a tag the user can attach, either curated or defined by the user.
+pub async fn find_tag<'e, E>(executor: E, uid: &Uuid, name: &str) -> Result<Option<Tag>>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    let tag = sqlx::query_as::<_, Tag>(
+        r#"SELECT id, name, uid IS NULL AS curated FROM tags WHERE name = $1 AND (uid IS NULL OR uid = $2)"#,
+    )
+    .bind(name)
+    .bind(uid)
+    .fetch_optional(executor)
+    .await?;
+
+    Ok(tag)
+}
+
+pub async fn create_tag<'e, E>(executor: E, uid: &Uuid, name: &str) -> Result<Tag>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    let tag = sqlx::query_as::<_, Tag>(
+        r#"INSERT INTO tags (name, uid) VALUES ($1, $2) RETURNING id, name, uid IS NULL AS curated"#,
+    )
+    .bind(name)
+    .bind(uid)
+    .fetch_one(executor)
+    .await?;
+
+    Ok(tag)
+}
+
+// Only the tags defined by the user can be deleted, which detaches them from every review.
+pub async fn delete_tag(pool: &PgPool, uid: &Uuid, name: &str) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM tags WHERE name = $1 AND uid = $2")
+        .bind(name)
+        .bind(uid)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn attach_tag<'e, E>(executor: E, review_id: i32, tag_id: i32) -> Result<()>
+where
+    E: Executor<'e, Database = Postgres>,
+{
+    sqlx::query(
+        r#"INSERT INTO review_tags (review_id, tag_id) VALUES ($1, $2) ON CONFLICT DO NOTHING"#,
+    )
+    .bind(review_id)
+    .bind(tag_id)
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn detach_tag(pool: &PgPool, review_id: i32, tag_id: i32) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM review_tags WHERE review_id = $1 AND tag_id = $2")
+        .bind(review_id)
+        .bind(tag_id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config, utils};
+
+    #[actix_rt::test]
+    async fn find_tags_curated_and_own() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        create_tag(&pool, &uid, "my-tag").await.unwrap();
+        create_tag(&pool, &Uuid::new_v4(), "other-tag")
+            .await
+            .unwrap();
+
+        let tags = find_tags(&pool, &uid).await.unwrap();
+        assert!(tags.iter().any(|t| t.name == "dp" && t.curated));
+        assert_eq!("my-tag".to_string(), tags.last().unwrap().name);
+        assert!(!tags.last().unwrap().curated);
+        assert!(!tags.iter().any(|t| t.name == "other-tag"));
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn find_tag_of_other_user() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        create_tag(&pool, &Uuid::new_v4(), "other-tag")
+            .await
+            .unwrap();
+
+        let tag = find_tag(&pool, &Uuid::new_v4(), "other-tag").await.unwrap();
+        assert_eq!(None, tag);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn delete_tag_curated() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+
+        assert!(!delete_tag(&pool, &uid, "dp").await.unwrap());
+        assert!(find_tag(&pool, &uid, "dp").await.unwrap().is_some());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn attach_and_detach_tag() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        sqlx::query(r#"INSERT INTO reviews (id, problem_name, url, memo, uid, platform, created_at) VALUES (0, 'test_prob_name', 'test_url', 'test_memo', $1, 1, now())"#)
+			.bind(uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+        let tag = find_tag(&pool, &uid, "dp").await.unwrap().unwrap();
+
+        // attaching twice is fine
+        attach_tag(&pool, 0, tag.id).await.unwrap();
+        attach_tag(&pool, 0, tag.id).await.unwrap();
+        let links: Vec<(i32,)> = sqlx::query_as("SELECT tag_id FROM review_tags")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(vec![(tag.id,)], links);
+
+        assert!(detach_tag(&pool, 0, tag.id).await.unwrap());
+        assert!(!detach_tag(&pool, 0, tag.id).await.unwrap());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+}
@@ -0,0 +1,17 @@
+use crate::utils::RE_TAG;
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+#[derive(Debug, Serialize, Deserialize, Validate, PartialEq)]
+pub struct NewTag {
+    #[validate(length(min = 1, max = 50), regex(path = "RE_TAG"))]
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, PartialEq)]
+pub struct Tag {
+    pub id: i32,
+    pub name: String,
+    // shared by every user rather than defined by the user
+    pub curated: bool,
+}
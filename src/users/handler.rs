@@ -1,11 +1,20 @@
 use super::infrastructures;
-use super::model::NewUser;
-use crate::error::{extract_field, ApiError};
-use actix_web::{get, post, web, HttpResponse};
+use super::model::{
+    ChangeEmail, ChangePassword, ChangeUserName, DeleteAccount, NewUser, Preferences,
+    PublicProfile, PublicStats,
+};
+use crate::auth::{self, AuthenticatedUser};
+use crate::collections;
+use crate::error::ApiError;
+use crate::mail::templates;
+use crate::password::verify;
+use crate::reviews::model::invalid;
+use crate::stats;
+use actix_web::{get, http::header, post, put, web, HttpResponse};
 use anyhow::Result;
 use sqlx::PgPool;
 use uuid::Uuid;
-use validator::Validate;
+use validator::{Validate, ValidationErrors};
 
 #[post("/sign-up")]
 pub async fn sign_up(
@@ -14,11 +23,7 @@ pub async fn sign_up(
 ) -> Result<HttpResponse, ApiError> {
     match form.validate() {
         Ok(_) => (),
-        Err(e) => {
-            return Err(ApiError::ValidationError {
-                fields: extract_field(e),
-            })
-        }
+        Err(e) => return Err(e.into()),
     }
 
     // check the user is already registered
@@ -42,21 +47,22 @@ pub async fn sign_up(
         Err(_) => return Err(ApiError::InternalError),
     };
 
-    let uid = Uuid::new_v4();
-
-    // send mail
-    match infrastructures::send_mail(&form.user_name, &form.email, &uid).await {
-        Ok(f) => {
-            if !f {
-                return Err(ApiError::BadRequest);
-            }
-        }
+    // an address is the one of a single user, the invitations to the groups are matched by it
+    match infrastructures::is_email_registered(pool.get_ref(), &form.email).await {
+        Ok(true) => return Err(ApiError::Conflict),
+        Ok(false) => (),
         Err(_) => return Err(ApiError::InternalError),
     }
 
+    let uid = Uuid::new_v4();
+    let verification_mail = templates::sign_up(&form.user_name, &form.email, &uid);
+
     // insert the user to temporarily registered users table
     let new_user = form.into_inner();
-    match infrastructures::register_temporarily(pool.get_ref(), new_user, uid).await {
+    // the mail is sent by the background worker
+    match infrastructures::register_temporarily(pool.get_ref(), new_user, uid, &verification_mail)
+        .await
+    {
         Ok(_) => (),
         Err(_) => return Err(ApiError::InternalError),
     };
@@ -79,6 +85,12 @@ pub async fn verify_user(
             }
         }
         Ok(user) => {
+            // the address may have been taken since the user signed up
+            match infrastructures::is_email_registered(pool.get_ref(), &user.email).await {
+                Ok(true) => return Err(ApiError::Conflict),
+                Ok(false) => (),
+                Err(_) => return Err(ApiError::InternalError),
+            }
             // register user
             match infrastructures::register_user(pool.get_ref(), user, &uid).await {
                 Ok(_) => (),
@@ -89,12 +101,336 @@ pub async fn verify_user(
     Ok(HttpResponse::Ok().json(""))
 }
 
+#[put("/account/password")]
+pub async fn change_password(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    form: web::Form<ChangePassword>,
+) -> Result<HttpResponse, ApiError> {
+    match form.validate() {
+        Ok(_) => (),
+        Err(e) => return Err(e.into()),
+    }
+
+    let current = match infrastructures::find_user(pool.get_ref(), &user.uid).await {
+        Ok(u) => u,
+        Err(_) => return Err(ApiError::InternalError),
+    };
+    match verify(&form.current_password, &current.password).await {
+        Ok(true) => (),
+        Ok(false) => return Err(ApiError::Unauthorized),
+        Err(_) => return Err(ApiError::InternalError),
+    }
+
+    match infrastructures::update_password(pool.get_ref(), &user.uid, &form.new_password).await {
+        Ok(_) => (),
+        Err(_) => return Err(ApiError::InternalError),
+    }
+
+    // other devices have to sign in again with the new password
+    match auth::infrastructures::delete_other_sessions(pool.get_ref(), &user.uid, &user.token).await
+    {
+        Ok(_) => (),
+        Err(_) => return Err(ApiError::InternalError),
+    }
+
+    Ok(HttpResponse::Ok().json(""))
+}
+
+#[put("/account/user-name")]
+pub async fn change_user_name(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    form: web::Form<ChangeUserName>,
+) -> Result<HttpResponse, ApiError> {
+    match form.validate() {
+        Ok(_) => (),
+        Err(e) => return Err(e.into()),
+    }
+
+    // the name must not be taken by a registered or temporarily registered user
+    match infrastructures::is_already_registered(pool.get_ref(), &form.user_name).await {
+        Ok(f) => {
+            if f {
+                return Err(ApiError::Conflict);
+            }
+        }
+        Err(_) => return Err(ApiError::InternalError),
+    };
+    match infrastructures::is_already_registered_temporarily(pool.get_ref(), &form.user_name).await
+    {
+        Ok(f) => {
+            if f {
+                return Err(ApiError::Conflict);
+            }
+        }
+        Err(_) => return Err(ApiError::InternalError),
+    };
+
+    match infrastructures::update_user_name(pool.get_ref(), &user.uid, &form.user_name).await {
+        Ok(_) => (),
+        Err(_) => return Err(ApiError::InternalError),
+    }
+
+    Ok(HttpResponse::Ok().json(""))
+}
+
+// Public, so that the profile can be shared without an account.
+#[get("/users/{user_name}")]
+pub async fn get_profile(
+    pool: web::Data<PgPool>,
+    web::Path(user_name): web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let uid = match infrastructures::find_uid_by_name(pool.get_ref(), &user_name).await {
+        Ok(Some(uid)) => uid,
+        Ok(None) => return Err(ApiError::NotFound),
+        Err(_) => return Err(ApiError::InternalError),
+    };
+    let preferences = match infrastructures::find_preferences(pool.get_ref(), &uid).await {
+        Ok(preferences) => preferences,
+        Err(_) => return Err(ApiError::InternalError),
+    };
+    let dashboard =
+        match stats::infrastructures::find_dashboard(pool.get_ref(), &uid, &preferences.timezone)
+            .await
+        {
+            Ok(dashboard) => dashboard,
+            Err(_) => return Err(ApiError::InternalError),
+        };
+
+    match collections::infrastructures::find_public_collections(pool.get_ref(), &uid).await {
+        Ok(public) => Ok(HttpResponse::Ok().json(PublicProfile {
+            user_name,
+            stats: PublicStats::new(dashboard),
+            collections: public,
+        })),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[get("/account/preferences")]
+pub async fn get_preferences(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, ApiError> {
+    match infrastructures::find_preferences(pool.get_ref(), &user.uid).await {
+        Ok(preferences) => Ok(HttpResponse::Ok().json(preferences)),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[put("/account/preferences")]
+pub async fn update_preferences(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    form: web::Form<Preferences>,
+) -> Result<HttpResponse, ApiError> {
+    match form.validate() {
+        Ok(_) => (),
+        Err(e) => return Err(e.into()),
+    }
+    match infrastructures::timezone_exists(pool.get_ref(), &form.timezone).await {
+        Ok(true) => (),
+        Ok(false) => {
+            let mut errors = ValidationErrors::new();
+            invalid(&mut errors, "timezone", "timezone must be a time zone name");
+            return Err(errors.into());
+        }
+        Err(_) => return Err(ApiError::InternalError),
+    }
+
+    match infrastructures::update_preferences(pool.get_ref(), &user.uid, &form).await {
+        Ok(_) => Ok(HttpResponse::Ok().json(form.into_inner())),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[put("/account/email")]
+pub async fn change_email(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    form: web::Form<ChangeEmail>,
+) -> Result<HttpResponse, ApiError> {
+    match form.validate() {
+        Ok(_) => (),
+        Err(e) => return Err(e.into()),
+    }
+
+    // check the address is not the one of another user
+    match infrastructures::is_email_registered(pool.get_ref(), &form.email).await {
+        Ok(true) => return Err(ApiError::Conflict),
+        Ok(false) => (),
+        Err(_) => return Err(ApiError::InternalError),
+    }
+
+    let current = match infrastructures::find_user(pool.get_ref(), &user.uid).await {
+        Ok(u) => u,
+        Err(_) => return Err(ApiError::InternalError),
+    };
+    let token = Uuid::new_v4();
+    let verification_mail = templates::email_change(&current.user_name, &form.email, &token);
+
+    // the address is changed only after the new one is verified
+    match infrastructures::register_email_change(
+        pool.get_ref(),
+        &user.uid,
+        &form.email,
+        &token,
+        &verification_mail,
+    )
+    .await
+    {
+        Ok(_) => (),
+        Err(_) => return Err(ApiError::InternalError),
+    }
+
+    Ok(HttpResponse::Ok().json(""))
+}
+
+#[get("/verify-email/{token}")]
+pub async fn verify_email(
+    pool: web::Data<PgPool>,
+    web::Path(token): web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    match infrastructures::extract_email_change(pool.get_ref(), &token).await {
+        Err(f) => {
+            if f {
+                return Err(ApiError::BadRequest);
+            } else {
+                return Err(ApiError::InternalError);
+            }
+        }
+        Ok(change) => {
+            // the address may have been taken since the change was requested
+            match infrastructures::is_email_registered(pool.get_ref(), &change.email).await {
+                Ok(true) => return Err(ApiError::Conflict),
+                Ok(false) => (),
+                Err(_) => return Err(ApiError::InternalError),
+            }
+            match infrastructures::update_email(pool.get_ref(), &change.uid, &change.email).await {
+                Ok(_) => (),
+                Err(_) => return Err(ApiError::InternalError),
+            }
+        }
+    }
+    Ok(HttpResponse::Ok().json(""))
+}
+
+#[get("/account/export")]
+pub async fn export_account(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, ApiError> {
+    match infrastructures::export_account(pool.get_ref(), &user.uid).await {
+        Ok(export) => Ok(HttpResponse::Ok()
+            .header(
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"export.json\"",
+            )
+            .json(export)),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[post("/account/delete")]
+pub async fn delete_account(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    form: web::Form<DeleteAccount>,
+) -> Result<HttpResponse, ApiError> {
+    let current = match infrastructures::find_user(pool.get_ref(), &user.uid).await {
+        Ok(u) => u,
+        Err(_) => return Err(ApiError::InternalError),
+    };
+    match verify(&form.password, &current.password).await {
+        Ok(true) => (),
+        Ok(false) => return Err(ApiError::Unauthorized),
+        Err(_) => return Err(ApiError::InternalError),
+    }
+
+    match infrastructures::schedule_deletion(pool.get_ref(), &user.uid).await {
+        Ok(_) => (),
+        Err(_) => return Err(ApiError::InternalError),
+    }
+    match auth::infrastructures::delete_all_sessions(pool.get_ref(), &user.uid).await {
+        Ok(_) => (),
+        Err(_) => return Err(ApiError::InternalError),
+    }
+
+    Ok(HttpResponse::Ok().json(""))
+}
+
+#[post("/account/delete-request")]
+pub async fn request_account_deletion(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, ApiError> {
+    let current = match infrastructures::find_user(pool.get_ref(), &user.uid).await {
+        Ok(u) => u,
+        Err(_) => return Err(ApiError::InternalError),
+    };
+    let token = Uuid::new_v4();
+    let confirmation_mail = templates::account_deletion(&current.user_name, &current.email, &token);
+
+    match infrastructures::register_deletion_request(
+        pool.get_ref(),
+        &user.uid,
+        &token,
+        &confirmation_mail,
+    )
+    .await
+    {
+        Ok(_) => (),
+        Err(_) => return Err(ApiError::InternalError),
+    }
+
+    Ok(HttpResponse::Ok().json(""))
+}
+
+#[get("/confirm-deletion/{token}")]
+pub async fn confirm_account_deletion(
+    pool: web::Data<PgPool>,
+    web::Path(token): web::Path<Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let uid = match infrastructures::confirm_deletion(pool.get_ref(), &token).await {
+        Ok(uid) => uid,
+        Err(f) => {
+            if f {
+                return Err(ApiError::BadRequest);
+            } else {
+                return Err(ApiError::InternalError);
+            }
+        }
+    };
+    match auth::infrastructures::delete_all_sessions(pool.get_ref(), &uid).await {
+        Ok(_) => (),
+        Err(_) => return Err(ApiError::InternalError),
+    }
+
+    Ok(HttpResponse::Ok().json(""))
+}
+
+// Signing in is still possible during the grace period, so the user can take the deletion back.
+#[post("/account/restore")]
+pub async fn restore_account(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, ApiError> {
+    match infrastructures::cancel_deletion(pool.get_ref(), &user.uid).await {
+        Ok(true) => Ok(HttpResponse::Ok().json("")),
+        Ok(false) => Err(ApiError::BadRequest),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::collections::model::NewCollection;
+    use crate::users::model::{AccountExport, Profile};
     use crate::{config, utils};
     use actix_web::{body::Body, test, App};
-    use bcrypt::verify;
+    use bcrypt::{hash, verify};
     use chrono::Utc;
     use serde_json::json;
 
@@ -106,7 +442,7 @@ mod tests {
         let user = NewUser {
             user_name: "".to_string(),
             email: "test@gmail.com".to_string(),
-            password: "password".to_string(),
+            password: "correct-horse-battery-staple".to_string(),
         };
         let req = test::TestRequest::post()
             .uri("/sign-up")
@@ -132,7 +468,7 @@ mod tests {
         let user = NewUser {
     		user_name: "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa".to_string(),
     		email: "test@gmail.com".to_string(),
-    		password: "password".to_string(),
+    		password: "correct-horse-battery-staple".to_string(),
     	};
         let req = test::TestRequest::post()
             .uri("/sign-up")
@@ -158,7 +494,7 @@ mod tests {
         let user = NewUser {
             user_name: "aaaあaaa".to_string(),
             email: "test@gmail.com".to_string(),
-            password: "password".to_string(),
+            password: "correct-horse-battery-staple".to_string(),
         };
         let req = test::TestRequest::post()
             .uri("/sign-up")
@@ -184,7 +520,7 @@ mod tests {
         let user = NewUser {
             user_name: "user_name".to_string(),
             email: "invalid_mail_example".to_string(),
-            password: "password".to_string(),
+            password: "correct-horse-battery-staple".to_string(),
         };
         let req = test::TestRequest::post()
             .uri("/sign-up")
@@ -219,9 +555,14 @@ mod tests {
         let resp_body = resp.take_body();
         let resp_body = resp_body.as_ref().unwrap();
         assert_eq!(
-            &Body::from(
-                json!({"code": 400, "message": "validation error on field: [\"password\"]"})
-            ),
+            &Body::from(json!({
+                "code": 400,
+                "message": "validation error on field: [\"password\"]",
+                "reasons": [
+                    "password must be at least 8 characters long",
+                    "password is too easy to guess (estimated 0 bits, 40 required)"
+                ]
+            })),
             resp_body
         );
     }
@@ -245,22 +586,24 @@ mod tests {
         let resp_body = resp.take_body();
         let resp_body = resp_body.as_ref().unwrap();
         assert_eq!(
-            &Body::from(
-                json!({"code": 400, "message": "validation error on field: [\"password\"]"})
-            ),
+            &Body::from(json!({
+                "code": 400,
+                "message": "validation error on field: [\"password\"]",
+                "reasons": ["password must be at most 72 bytes long"]
+            })),
             resp_body
         );
     }
 
     #[actix_rt::test]
-    async fn password_invalid_character() {
+    async fn password_too_common() {
         let config = config::Config::new();
         let pool = PgPool::connect(&config.database_url).await.unwrap();
         let mut app = test::init_service(App::new().data(pool.clone()).service(sign_up)).await;
         let user = NewUser {
             user_name: "user_name".to_string(),
             email: "test@gmail.com".to_string(),
-            password: "aaaあaaa".to_string(),
+            password: "Password123".to_string(),
         };
         let req = test::TestRequest::post()
             .uri("/sign-up")
@@ -271,9 +614,11 @@ mod tests {
         let resp_body = resp.take_body();
         let resp_body = resp_body.as_ref().unwrap();
         assert_eq!(
-            &Body::from(
-                json!({"code": 400, "message": "validation error on field: [\"password\"]"})
-            ),
+            &Body::from(json!({
+                "code": 400,
+                "message": "validation error on field: [\"password\"]",
+                "reasons": ["password is one of the most commonly used passwords"]
+            })),
             resp_body
         );
     }
@@ -293,7 +638,7 @@ mod tests {
         let user = NewUser {
             user_name: "test_user".to_string(),
             email: "test@gmail.com".to_string(),
-            password: "password".to_string(),
+            password: "correct-horse-battery-staple".to_string(),
         };
         let req = test::TestRequest::post()
             .uri("/sign-up")
@@ -325,7 +670,7 @@ mod tests {
         let user = NewUser {
             user_name: "test_user".to_string(),
             email: "test@gmail.com".to_string(),
-            password: "password".to_string(),
+            password: "correct-horse-battery-staple".to_string(),
         };
         let req = test::TestRequest::post()
             .uri("/sign-up")
@@ -341,59 +686,83 @@ mod tests {
     }
 
     #[actix_rt::test]
-    async fn sign_up_failed_mail_sending() {
+    async fn sign_up_ok() {
         let config = config::Config::new();
         let pool = PgPool::connect(&config.database_url).await.unwrap();
         let mut app = test::init_service(App::new().data(pool.clone()).service(sign_up)).await;
         let user = NewUser {
             user_name: "test_user".to_string(),
             email: "test@gmail.com".to_string(),
-            password: "password".to_string(),
+            password: "correct-horse-battery-staple".to_string(),
         };
         let req = test::TestRequest::post()
             .uri("/sign-up")
             .set_form(&user)
             .to_request();
         let mut resp = test::call_service(&mut app, req).await;
-        assert_eq!(400, resp.status());
+        assert_eq!(200, resp.status());
         let resp_body = resp.take_body();
         let resp_body = resp_body.as_ref().unwrap();
-        assert_eq!(
-            &Body::from(json!({"code": 400, "message": "bad request"})),
-            resp_body
-        );
+        assert_eq!(&Body::from(json!("")), resp_body);
+
+        // check the user is inserted tmp_users table.
+        let tmp_user = sqlx::query!("SELECT * FROM tmp_users")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(1, tmp_user.len());
+        assert_eq!("test_user".to_string(), tmp_user[0].user_name);
+        assert_eq!("test@gmail.com".to_string(), tmp_user[0].email);
+        assert!(verify("correct-horse-battery-staple", &tmp_user[0].password).unwrap());
+
+        // check the verification mail is queued
+        let mails = sqlx::query!("SELECT * FROM email_outbox")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(1, mails.len());
+        assert_eq!("test@gmail.com".to_string(), mails[0].recipient);
+        assert!(mails[0]
+            .body
+            .ends_with(&format!("https://verify/{}", tmp_user[0].uid)));
+
+        utils::clear_table(&pool).await.unwrap();
     }
 
-    #[ignore]
     #[actix_rt::test]
-    async fn sign_up_ok() {
+    async fn sign_up_email_taken() {
         let config = config::Config::new();
         let pool = PgPool::connect(&config.database_url).await.unwrap();
         let mut app = test::init_service(App::new().data(pool.clone()).service(sign_up)).await;
+        // insert predataset
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'test_user', 'password', 'test@gmail.com', $1)"#)
+			.bind(Uuid::new_v4())
+			.execute(&pool)
+			.await
+			.unwrap();
         let user = NewUser {
-            user_name: "test_user".to_string(),
-            email: "test@gmail.com".to_string(),
-            password: "password".to_string(),
+            user_name: "another_user".to_string(),
+            email: "Test@gmail.com".to_string(),
+            password: "correct-horse-battery-staple".to_string(),
         };
         let req = test::TestRequest::post()
             .uri("/sign-up")
             .set_form(&user)
             .to_request();
-        let mut resp = test::call_service(&mut app, req).await;
-        assert_eq!(200, resp.status());
-        let resp_body = resp.take_body();
-        let resp_body = resp_body.as_ref().unwrap();
-        assert_eq!(&Body::from(json!("")), resp_body);
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(409, resp.status());
 
-        // check the user is inserted tmp_users table.
+        // check nothing is registered nor mailed
         let tmp_user = sqlx::query!("SELECT * FROM tmp_users")
             .fetch_all(&pool)
             .await
             .unwrap();
-        assert_eq!(1, tmp_user.len());
-        assert_eq!("test_user".to_string(), tmp_user[0].user_name);
-        assert_eq!("test@gmail.com".to_string(), tmp_user[0].email);
-        assert!(verify("password", &tmp_user[0].password).unwrap());
+        assert!(tmp_user.is_empty());
+        let mails = sqlx::query!("SELECT * FROM email_outbox")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert!(mails.is_empty());
 
         utils::clear_table(&pool).await.unwrap();
     }
@@ -417,11 +786,11 @@ mod tests {
     }
 
     #[actix_rt::test]
-    async fn verify_user_ok() {
+    async fn verify_user_email_taken() {
         let config = config::Config::new();
         let pool = PgPool::connect(&config.database_url).await.unwrap();
 
-        // insert predataset
+        // insert predataset, the address is taken after signing up
         let uid = Uuid::new_v4();
         let now = chrono::Utc::now();
         sqlx::query(r#"INSERT INTO tmp_users (id, user_name, password, email, uid, created_at) VALUES (0, 'test_user', 'password', 'test@gmail.com', $1, $2)"#)
@@ -430,15 +799,52 @@ mod tests {
 			.execute(&pool)
 			.await
 			.unwrap();
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'another_user', 'password', 'TEST@gmail.com', $1)"#)
+			.bind(Uuid::new_v4())
+			.execute(&pool)
+			.await
+			.unwrap();
 
         let mut app = test::init_service(App::new().data(pool.clone()).service(verify_user)).await;
         let uri = format!("/verify/{}", &uid);
         let req = test::TestRequest::get().uri(&uri).to_request();
-        let mut resp = test::call_service(&mut app, req).await;
-        assert_eq!(200, resp.status());
-        let resp_body = resp.take_body();
-        let resp_body = resp_body.as_ref().unwrap();
-        assert_eq!(&Body::from(json!("")), resp_body);
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(409, resp.status());
+
+        // check the user is not registered
+        let user = sqlx::query!("SELECT * FROM users")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(1, user.len());
+        assert_eq!("another_user".to_string(), user[0].user_name);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn verify_user_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+
+        // insert predataset
+        let uid = Uuid::new_v4();
+        let now = chrono::Utc::now();
+        sqlx::query(r#"INSERT INTO tmp_users (id, user_name, password, email, uid, created_at) VALUES (0, 'test_user', 'password', 'test@gmail.com', $1, $2)"#)
+			.bind(uid)
+			.bind(now)
+			.execute(&pool)
+			.await
+			.unwrap();
+
+        let mut app = test::init_service(App::new().data(pool.clone()).service(verify_user)).await;
+        let uri = format!("/verify/{}", &uid);
+        let req = test::TestRequest::get().uri(&uri).to_request();
+        let mut resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let resp_body = resp.take_body();
+        let resp_body = resp_body.as_ref().unwrap();
+        assert_eq!(&Body::from(json!("")), resp_body);
 
         // check the user is deleted from tmp_users table.
         let tmp_user = sqlx::query!("SELECT * FROM tmp_users")
@@ -460,4 +866,939 @@ mod tests {
 
         utils::clear_table(&pool).await.unwrap();
     }
+
+    #[actix_rt::test]
+    async fn change_password_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app =
+            test::init_service(App::new().data(pool.clone()).service(change_password)).await;
+        // insert predataset
+        let uid = Uuid::new_v4();
+        let hashed_password = hash("password", 4).unwrap();
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'test_user', $1, 'test@gmail.com', $2)"#)
+			.bind(hashed_password)
+			.bind(uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+        let token = auth::infrastructures::create_session(&pool, &uid)
+            .await
+            .unwrap();
+        let other = auth::infrastructures::create_session(&pool, &uid)
+            .await
+            .unwrap();
+        let form = ChangePassword {
+            current_password: "password".to_string(),
+            new_password: "new_password".to_string(),
+        };
+        let req = test::TestRequest::put()
+            .uri("/account/password")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&form)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+
+        // check the password is updated
+        let user = sqlx::query!("SELECT * FROM users")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert!(verify("new_password", &user.password).unwrap());
+
+        // check the other sessions are signed out
+        let sessions = sqlx::query!("SELECT * FROM sessions")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(1, sessions.len());
+        assert_eq!(token, sessions[0].token);
+        assert_ne!(other, sessions[0].token);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn change_password_wrong_current_password() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app =
+            test::init_service(App::new().data(pool.clone()).service(change_password)).await;
+        // insert predataset
+        let uid = Uuid::new_v4();
+        let hashed_password = hash("password", 4).unwrap();
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'test_user', $1, 'test@gmail.com', $2)"#)
+			.bind(hashed_password)
+			.bind(uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+        let token = auth::infrastructures::create_session(&pool, &uid)
+            .await
+            .unwrap();
+        let form = ChangePassword {
+            current_password: "wrong_password".to_string(),
+            new_password: "new_password".to_string(),
+        };
+        let req = test::TestRequest::put()
+            .uri("/account/password")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&form)
+            .to_request();
+        let mut resp = test::call_service(&mut app, req).await;
+        assert_eq!(401, resp.status());
+        let resp_body = resp.take_body();
+        let resp_body = resp_body.as_ref().unwrap();
+        assert_eq!(
+            &Body::from(json!({"code": 401, "message": "unauthorized"})),
+            resp_body
+        );
+
+        // check the password is not updated
+        let user = sqlx::query!("SELECT * FROM users")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert!(verify("password", &user.password).unwrap());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn change_password_weak_new_password() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app =
+            test::init_service(App::new().data(pool.clone()).service(change_password)).await;
+        let uid = Uuid::new_v4();
+        let token = auth::infrastructures::create_session(&pool, &uid)
+            .await
+            .unwrap();
+        let form = ChangePassword {
+            current_password: "password".to_string(),
+            new_password: "qwerty".to_string(),
+        };
+        let req = test::TestRequest::put()
+            .uri("/account/password")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&form)
+            .to_request();
+        let mut resp = test::call_service(&mut app, req).await;
+        assert_eq!(400, resp.status());
+        let resp_body = resp.take_body();
+        let resp_body = resp_body.as_ref().unwrap();
+        assert_eq!(
+            &Body::from(json!({
+                "code": 400,
+                "message": "validation error on field: [\"new_password\"]",
+                "reasons": [
+                    "password must be at least 8 characters long",
+                    "password is too easy to guess (estimated 28 bits, 40 required)",
+                    "password is one of the most commonly used passwords"
+                ]
+            })),
+            resp_body
+        );
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn change_password_unauthorized() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app =
+            test::init_service(App::new().data(pool.clone()).service(change_password)).await;
+        let form = ChangePassword {
+            current_password: "password".to_string(),
+            new_password: "new_password".to_string(),
+        };
+        let req = test::TestRequest::put()
+            .uri("/account/password")
+            .set_form(&form)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(401, resp.status());
+    }
+
+    #[actix_rt::test]
+    async fn change_user_name_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app =
+            test::init_service(App::new().data(pool.clone()).service(change_user_name)).await;
+        // insert predataset
+        let uid = Uuid::new_v4();
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'test_user', 'password', 'test@gmail.com', $1)"#)
+			.bind(uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+        let token = auth::infrastructures::create_session(&pool, &uid)
+            .await
+            .unwrap();
+        let form = ChangeUserName {
+            user_name: "new_user".to_string(),
+        };
+        let req = test::TestRequest::put()
+            .uri("/account/user-name")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&form)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+
+        let user = sqlx::query!("SELECT * FROM users")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!("new_user".to_string(), user.user_name);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn preferences_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .data(pool.clone())
+                .service(get_preferences)
+                .service(update_preferences),
+        )
+        .await;
+        let uid = Uuid::new_v4();
+        let token = auth::infrastructures::create_session(&pool, &uid)
+            .await
+            .unwrap();
+
+        let req = test::TestRequest::get()
+            .uri("/account/preferences")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let preferences: Preferences = test::read_body_json(resp).await;
+        assert_eq!(Preferences::default(), preferences);
+
+        let changed = Preferences {
+            timezone: "Asia/Tokyo".to_string(),
+            locale: "ja".to_string(),
+            daily_review_limit: 50,
+            default_platform: Some(1),
+            email_digest: "daily".to_string(),
+            digest_hour: 7,
+        };
+        let req = test::TestRequest::put()
+            .uri("/account/preferences")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&changed)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        assert_eq!(
+            changed,
+            infrastructures::find_preferences(&pool, &uid)
+                .await
+                .unwrap()
+        );
+
+        let req = test::TestRequest::put()
+            .uri("/account/preferences")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&Preferences {
+                daily_review_limit: 0,
+                email_digest: "hourly".to_string(),
+                ..Preferences::default()
+            })
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(400, resp.status());
+        let req = test::TestRequest::put()
+            .uri("/account/preferences")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&Preferences {
+                timezone: "Asia/Edo".to_string(),
+                ..Preferences::default()
+            })
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(400, resp.status());
+        assert_eq!(
+            changed,
+            infrastructures::find_preferences(&pool, &uid)
+                .await
+                .unwrap()
+        );
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn change_user_name_invalid_character() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app =
+            test::init_service(App::new().data(pool.clone()).service(change_user_name)).await;
+        let uid = Uuid::new_v4();
+        let token = auth::infrastructures::create_session(&pool, &uid)
+            .await
+            .unwrap();
+        let form = ChangeUserName {
+            user_name: "aaaあaaa".to_string(),
+        };
+        let req = test::TestRequest::put()
+            .uri("/account/user-name")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&form)
+            .to_request();
+        let mut resp = test::call_service(&mut app, req).await;
+        assert_eq!(400, resp.status());
+        let resp_body = resp.take_body();
+        let resp_body = resp_body.as_ref().unwrap();
+        assert_eq!(
+            &Body::from(
+                json!({"code": 400, "message": "validation error on field: [\"user_name\"]"})
+            ),
+            resp_body
+        );
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn change_user_name_already_taken() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app =
+            test::init_service(App::new().data(pool.clone()).service(change_user_name)).await;
+        // insert predataset
+        let uid = Uuid::new_v4();
+        let now = Utc::now();
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'test_user', 'password', 'test@gmail.com', $1)"#)
+			.bind(uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+        sqlx::query(r#"INSERT INTO tmp_users (id, user_name, password, email, uid, created_at) VALUES (0, 'tmp_user', 'password', 'tmp@gmail.com', $1, $2)"#)
+			.bind(Uuid::new_v4())
+			.bind(now)
+			.execute(&pool)
+			.await
+			.unwrap();
+        let token = auth::infrastructures::create_session(&pool, &uid)
+            .await
+            .unwrap();
+        let form = ChangeUserName {
+            user_name: "tmp_user".to_string(),
+        };
+        let req = test::TestRequest::put()
+            .uri("/account/user-name")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&form)
+            .to_request();
+        let mut resp = test::call_service(&mut app, req).await;
+        assert_eq!(409, resp.status());
+        let resp_body = resp.take_body();
+        let resp_body = resp_body.as_ref().unwrap();
+        assert_eq!(
+            &Body::from(json!({"code": 409, "message": "conflict"})),
+            resp_body
+        );
+
+        // check the user name is not updated
+        let user = sqlx::query!("SELECT * FROM users")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!("test_user".to_string(), user.user_name);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn change_email_invalid() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(App::new().data(pool.clone()).service(change_email)).await;
+        let uid = Uuid::new_v4();
+        let token = auth::infrastructures::create_session(&pool, &uid)
+            .await
+            .unwrap();
+        let form = ChangeEmail {
+            email: "invalid_mail_example".to_string(),
+        };
+        let req = test::TestRequest::put()
+            .uri("/account/email")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&form)
+            .to_request();
+        let mut resp = test::call_service(&mut app, req).await;
+        assert_eq!(400, resp.status());
+        let resp_body = resp.take_body();
+        let resp_body = resp_body.as_ref().unwrap();
+        assert_eq!(
+            &Body::from(json!({"code": 400, "message": "validation error on field: [\"email\"]"})),
+            resp_body
+        );
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn change_email_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(App::new().data(pool.clone()).service(change_email)).await;
+        // insert predataset
+        let uid = Uuid::new_v4();
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'test_user', 'password', 'test@gmail.com', $1)"#)
+			.bind(uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+        let token = auth::infrastructures::create_session(&pool, &uid)
+            .await
+            .unwrap();
+        let form = ChangeEmail {
+            email: "new@gmail.com".to_string(),
+        };
+        let req = test::TestRequest::put()
+            .uri("/account/email")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&form)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+
+        // check the address is not changed until it is verified
+        let user = sqlx::query!("SELECT * FROM users")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!("test@gmail.com".to_string(), user.email);
+        let changes = sqlx::query!("SELECT * FROM email_changes")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(1, changes.len());
+        assert_eq!("new@gmail.com".to_string(), changes[0].email);
+
+        // check the confirmation mail is queued for the new address
+        let mails = sqlx::query!("SELECT * FROM email_outbox")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(1, mails.len());
+        assert_eq!("new@gmail.com".to_string(), mails[0].recipient);
+        assert!(mails[0]
+            .body
+            .ends_with(&format!("https://verify-email/{}", changes[0].token)));
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn change_email_already_registered() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(App::new().data(pool.clone()).service(change_email)).await;
+        // insert predataset
+        let uid = Uuid::new_v4();
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'test_user', 'password', 'test@gmail.com', $1), (1, 'other_user', 'password', 'other@gmail.com', $2)"#)
+			.bind(uid)
+			.bind(Uuid::new_v4())
+			.execute(&pool)
+			.await
+			.unwrap();
+        let token = auth::infrastructures::create_session(&pool, &uid)
+            .await
+            .unwrap();
+        let form = ChangeEmail {
+            email: "Other@gmail.com".to_string(),
+        };
+        let req = test::TestRequest::put()
+            .uri("/account/email")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&form)
+            .to_request();
+        let mut resp = test::call_service(&mut app, req).await;
+        assert_eq!(409, resp.status());
+        let resp_body = resp.take_body();
+        let resp_body = resp_body.as_ref().unwrap();
+        assert_eq!(
+            &Body::from(json!({"code": 409, "message": "conflict"})),
+            resp_body
+        );
+
+        // check no change request nor mail is registered
+        let changes = sqlx::query("SELECT * FROM email_changes")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(0, changes.len());
+        let mails = sqlx::query("SELECT * FROM email_outbox")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(0, mails.len());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn verify_email_not_exist() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(App::new().data(pool.clone()).service(verify_email)).await;
+        let uri = format!("/verify-email/{}", Uuid::new_v4());
+        let req = test::TestRequest::get().uri(&uri).to_request();
+        let mut resp = test::call_service(&mut app, req).await;
+        assert_eq!(400, resp.status());
+        let resp_body = resp.take_body();
+        let resp_body = resp_body.as_ref().unwrap();
+        assert_eq!(
+            &Body::from(json!({"code": 400, "message": "bad request"})),
+            resp_body
+        );
+    }
+
+    #[actix_rt::test]
+    async fn verify_email_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        // insert predataset
+        let uid = Uuid::new_v4();
+        let token = Uuid::new_v4();
+        let now = Utc::now();
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'test_user', 'password', 'test@gmail.com', $1)"#)
+			.bind(uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+        sqlx::query(r#"INSERT INTO email_changes (id, uid, email, token, created_at, expires_at) VALUES (0, $1, 'new@gmail.com', $2, $3, $4)"#)
+			.bind(uid)
+			.bind(token)
+			.bind(now)
+			.bind(now + chrono::Duration::hours(1))
+			.execute(&pool)
+			.await
+			.unwrap();
+
+        let mut app = test::init_service(App::new().data(pool.clone()).service(verify_email)).await;
+        let uri = format!("/verify-email/{}", token);
+        let req = test::TestRequest::get().uri(&uri).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+
+        // check the address is updated and the request is consumed
+        let user = sqlx::query!("SELECT * FROM users")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!("new@gmail.com".to_string(), user.email);
+        let changes = sqlx::query("SELECT * FROM email_changes")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(0, changes.len());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn verify_email_expired() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        // insert predataset
+        let uid = Uuid::new_v4();
+        let token = Uuid::new_v4();
+        let created_at = Utc::now() - chrono::Duration::hours(25);
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'test_user', 'password', 'test@gmail.com', $1)"#)
+			.bind(uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+        sqlx::query(r#"INSERT INTO email_changes (id, uid, email, token, created_at, expires_at) VALUES (0, $1, 'new@gmail.com', $2, $3, $4)"#)
+			.bind(uid)
+			.bind(token)
+			.bind(created_at)
+			.bind(created_at + chrono::Duration::hours(infrastructures::EMAIL_CHANGE_EXPIRY_HOURS))
+			.execute(&pool)
+			.await
+			.unwrap();
+
+        let mut app = test::init_service(App::new().data(pool.clone()).service(verify_email)).await;
+        let uri = format!("/verify-email/{}", token);
+        let req = test::TestRequest::get().uri(&uri).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(400, resp.status());
+
+        // check the address is not updated
+        let user = sqlx::query!("SELECT * FROM users")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!("test@gmail.com".to_string(), user.email);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn export_account_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app =
+            test::init_service(App::new().data(pool.clone()).service(export_account)).await;
+        // insert predataset
+        let uid = Uuid::new_v4();
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'test_user', 'password', 'test@gmail.com', $1)"#)
+			.bind(uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+        let token = auth::infrastructures::create_session(&pool, &uid)
+            .await
+            .unwrap();
+        let req = test::TestRequest::get()
+            .uri("/account/export")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        assert_eq!(
+            "attachment; filename=\"export.json\"",
+            resp.headers().get(header::CONTENT_DISPOSITION).unwrap()
+        );
+        let export: AccountExport = test::read_body_json(resp).await;
+        assert_eq!(
+            AccountExport {
+                profile: Profile {
+                    user_name: "test_user".to_string(),
+                    email: "test@gmail.com".to_string(),
+                    uid,
+                },
+                reviews: vec![],
+                tags: vec![],
+                submissions: vec![],
+                revisions: vec![],
+                preferences: Preferences::default(),
+                review_sessions: vec![],
+                attempts: vec![],
+                webhooks: vec![],
+                collections: vec![],
+                group_memberships: vec![],
+                problem_sets: vec![],
+                comments: vec![],
+            },
+            export
+        );
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn delete_account_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app =
+            test::init_service(App::new().data(pool.clone()).service(delete_account)).await;
+        // insert predataset
+        let uid = Uuid::new_v4();
+        let hashed_password = hash("password", 4).unwrap();
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'test_user', $1, 'test@gmail.com', $2)"#)
+			.bind(hashed_password)
+			.bind(uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+        let token = auth::infrastructures::create_session(&pool, &uid)
+            .await
+            .unwrap();
+        let form = DeleteAccount {
+            password: "password".to_string(),
+        };
+        let req = test::TestRequest::post()
+            .uri("/account/delete")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&form)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+
+        // check the deletion is scheduled and the user is signed out
+        let deletion = sqlx::query!("SELECT * FROM account_deletions")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(uid, deletion.uid);
+        assert!(deletion.confirmed_at.is_some());
+        let sessions = sqlx::query("SELECT * FROM sessions")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(0, sessions.len());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn delete_account_wrong_password() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app =
+            test::init_service(App::new().data(pool.clone()).service(delete_account)).await;
+        // insert predataset
+        let uid = Uuid::new_v4();
+        let hashed_password = hash("password", 4).unwrap();
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'test_user', $1, 'test@gmail.com', $2)"#)
+			.bind(hashed_password)
+			.bind(uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+        let token = auth::infrastructures::create_session(&pool, &uid)
+            .await
+            .unwrap();
+        let form = DeleteAccount {
+            password: "wrong_password".to_string(),
+        };
+        let req = test::TestRequest::post()
+            .uri("/account/delete")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&form)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(401, resp.status());
+
+        let deletions = sqlx::query("SELECT * FROM account_deletions")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(0, deletions.len());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn request_account_deletion_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .data(pool.clone())
+                .service(request_account_deletion),
+        )
+        .await;
+        // insert predataset
+        let uid = Uuid::new_v4();
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'test_user', 'password', 'test@gmail.com', $1)"#)
+			.bind(uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+        let token = auth::infrastructures::create_session(&pool, &uid)
+            .await
+            .unwrap();
+        let req = test::TestRequest::post()
+            .uri("/account/delete-request")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+
+        // check the deletion waits for the confirmation
+        let deletion = sqlx::query!("SELECT * FROM account_deletions")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(uid, deletion.uid);
+        assert!(deletion.confirmed_at.is_none());
+        let mails = sqlx::query!("SELECT * FROM email_outbox")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(1, mails.len());
+        assert_eq!("test@gmail.com".to_string(), mails[0].recipient);
+        assert!(mails[0]
+            .body
+            .ends_with(&format!("https://confirm-deletion/{}", deletion.token)));
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn confirm_account_deletion_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .data(pool.clone())
+                .service(confirm_account_deletion),
+        )
+        .await;
+        // insert predataset
+        let uid = Uuid::new_v4();
+        let token = Uuid::new_v4();
+        let now = Utc::now();
+        sqlx::query(
+            r#"INSERT INTO account_deletions (id, uid, token, created_at) VALUES (0, $1, $2, $3)"#,
+        )
+        .bind(uid)
+        .bind(token)
+        .bind(now)
+        .execute(&pool)
+        .await
+        .unwrap();
+        auth::infrastructures::create_session(&pool, &uid)
+            .await
+            .unwrap();
+        let uri = format!("/confirm-deletion/{}", token);
+        let req = test::TestRequest::get().uri(&uri).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+
+        let deletion = sqlx::query!("SELECT * FROM account_deletions")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert!(deletion.confirmed_at.is_some());
+        let sessions = sqlx::query("SELECT * FROM sessions")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(0, sessions.len());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn confirm_account_deletion_not_exist() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .data(pool.clone())
+                .service(confirm_account_deletion),
+        )
+        .await;
+        let uri = format!("/confirm-deletion/{}", Uuid::new_v4());
+        let req = test::TestRequest::get().uri(&uri).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(400, resp.status());
+    }
+
+    #[actix_rt::test]
+    async fn restore_account_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app =
+            test::init_service(App::new().data(pool.clone()).service(restore_account)).await;
+        let uid = Uuid::new_v4();
+        infrastructures::schedule_deletion(&pool, &uid)
+            .await
+            .unwrap();
+        let token = auth::infrastructures::create_session(&pool, &uid)
+            .await
+            .unwrap();
+        let req = test::TestRequest::post()
+            .uri("/account/restore")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+
+        let deletions = sqlx::query("SELECT * FROM account_deletions")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(0, deletions.len());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn restore_account_not_scheduled() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app =
+            test::init_service(App::new().data(pool.clone()).service(restore_account)).await;
+        let uid = Uuid::new_v4();
+        let token = auth::infrastructures::create_session(&pool, &uid)
+            .await
+            .unwrap();
+        let req = test::TestRequest::post()
+            .uri("/account/restore")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(400, resp.status());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn get_profile_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(App::new().data(pool.clone()).service(get_profile)).await;
+        let uid = Uuid::new_v4();
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'test_user', 'password', 'test@gmail.com', $1)"#)
+			.bind(uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+        sqlx::query(r#"INSERT INTO reviews (id, problem_name, url, memo, uid, platform, created_at) VALUES (0, 'test_prob_name', 'test_url', 'secret memo', $1, 1, now()), (1, 'test_prob_name', 'test_url', 'secret memo', $1, 2, now())"#)
+			.bind(uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+        for (name, visibility) in [
+            ("redo", "public"),
+            ("hidden", "private"),
+            ("link", "unlisted"),
+        ]
+        .iter()
+        {
+            let collection = NewCollection {
+                name: name.to_string(),
+                description: None,
+                visibility: visibility.to_string(),
+            };
+            collections::infrastructures::create_collection(&pool, &uid, &collection)
+                .await
+                .unwrap();
+        }
+
+        let req = test::TestRequest::get()
+            .uri("/users/test_user")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+        let body = test::read_body(resp).await;
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(!body.contains("test@gmail.com"));
+        assert!(!body.contains("secret memo"));
+        assert!(!body.contains("timezone"));
+        let profile: PublicProfile = serde_json::from_str(&body).unwrap();
+        assert_eq!("test_user", profile.user_name);
+        assert_eq!(2, profile.stats.reviews);
+        assert_eq!(2, profile.stats.platforms.len());
+        assert_eq!(
+            vec!["redo"],
+            profile
+                .collections
+                .iter()
+                .map(|c| c.name.as_str())
+                .collect::<Vec<&str>>()
+        );
+
+        let req = test::TestRequest::get().uri("/users/nobody").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(404, resp.status());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
 }
@@ -1,15 +1,30 @@
-use super::model::NewUser;
-use crate::config;
+use super::model::{
+    AccountExport, EmailChange, ExportedCollection, ExportedComment, ExportedProblemSet,
+    ExportedReview, ExportedSession, ExportedSessionItem, Membership, NewUser, Preferences,
+    Profile, User,
+};
+use crate::attempts::model::Attempt;
+use crate::collections;
+use crate::groups;
+use crate::mail::{self, model::NewMail};
+use crate::password::hash;
+use crate::reviews::model::Revision;
+use crate::submissions::model::Submission;
+use crate::webhooks;
 use anyhow::Result;
-use bcrypt::{hash, DEFAULT_COST};
-use chrono::Utc;
-use lettre::transport::smtp::authentication::Credentials;
-use lettre::{Message, SmtpTransport, Transport};
+use chrono::{DateTime, Duration, Utc};
 use sqlx::PgPool;
 use uuid::Uuid;
 
+// how long the link confirming a new email address works
+pub const EMAIL_CHANGE_EXPIRY_HOURS: i64 = 24;
+
+// confirmed deletions can be reverted for this long before the data is purged
+pub const DELETION_GRACE_PERIOD_DAYS: i64 = 14;
+
+// The names differing only in case are taken as the same.
 pub async fn is_already_registered(pool: &PgPool, user_name: &str) -> Result<bool> {
-    let user = sqlx::query("SELECT * FROM users WHERE user_name = $1")
+    let user = sqlx::query("SELECT * FROM users WHERE LOWER(user_name) = LOWER($1)")
         .bind(user_name)
         .fetch_optional(pool)
         .await?;
@@ -19,9 +34,10 @@ pub async fn is_already_registered(pool: &PgPool, user_name: &str) -> Result<boo
     }
 }
 
-pub async fn is_already_registered_temporarily(pool: &PgPool, user_name: &str) -> Result<bool> {
-    let user = sqlx::query("SELECT * FROM tmp_users WHERE user_name = $1")
-        .bind(user_name)
+// Whether the address is the one of a user already, whatever its case.
+pub async fn is_email_registered(pool: &PgPool, email: &str) -> Result<bool> {
+    let user = sqlx::query("SELECT * FROM users WHERE LOWER(email) = LOWER($1)")
+        .bind(email)
         .fetch_optional(pool)
         .await?;
     match user {
@@ -30,45 +46,37 @@ pub async fn is_already_registered_temporarily(pool: &PgPool, user_name: &str) -
     }
 }
 
-pub async fn send_mail(user_name: &str, mail_address: &str, uid: &Uuid) -> Result<bool> {
-    let config = config::Config::new();
-    let body = format!(
-        "Hi {}! Verify your account by clicking on https://verify/{}",
-        user_name, uid
-    );
-    let email = Message::builder()
-        .from(
-            "Competitive Programming Review Admin <info@granddaifuku.com>"
-                .parse()
-                .unwrap(),
-        )
-        .to(mail_address.parse().unwrap())
-        .subject("[DO NOT REPLY] SIGN-UP")
-        .body(body)
-        .unwrap();
-    let creds = Credentials::new(config.smtp_username, config.smtp_password);
-
-    let mailer = SmtpTransport::starttls_relay(&config.mailer)
-        .unwrap()
-        .credentials(creds)
-        .build();
-    match mailer.send(&email) {
-        Ok(_) => Ok(true),
-        _ => Ok(false),
+pub async fn is_already_registered_temporarily(pool: &PgPool, user_name: &str) -> Result<bool> {
+    let user = sqlx::query("SELECT * FROM tmp_users WHERE LOWER(user_name) = LOWER($1)")
+        .bind(user_name)
+        .fetch_optional(pool)
+        .await?;
+    match user {
+        None => Ok(false),
+        _ => Ok(true),
     }
 }
 
-pub async fn register_temporarily(pool: &PgPool, user: NewUser, uid: Uuid) -> Result<()> {
+// The verification mail is queued together with the user.
+pub async fn register_temporarily(
+    pool: &PgPool,
+    user: NewUser,
+    uid: Uuid,
+    verification_mail: &NewMail,
+) -> Result<()> {
     let now = Utc::now();
-    let hashed_password = hash(user.password, DEFAULT_COST).unwrap();
+    let hashed_password = hash(&user.password).await?;
+    let mut tx = pool.begin().await?;
     sqlx::query(r#"INSERT INTO tmp_users (user_name, password, uid, email, created_at) VALUES ($1, $2, $3, $4, $5)"#)
 		.bind(user.user_name)
 		.bind(hashed_password)
 		.bind(uid)
 		.bind(user.email)
 		.bind(now)
-		.execute(pool)
+		.execute(&mut tx)
 		.await?;
+    mail::infrastructures::enqueue(&mut tx, verification_mail).await?;
+    tx.commit().await?;
 
     Ok(())
 }
@@ -117,11 +125,451 @@ pub async fn register_user(pool: &PgPool, user: NewUser, uid: &Uuid) -> Result<(
     Ok(())
 }
 
+pub async fn find_user(pool: &PgPool, uid: &Uuid) -> Result<User> {
+    let user = sqlx::query_as::<_, User>(
+        "SELECT user_name, email, password, uid FROM users WHERE uid = $1",
+    )
+    .bind(uid)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(user)
+}
+
+// The user with the name, absent while the account is being deleted.
+pub async fn find_uid_by_name(pool: &PgPool, user_name: &str) -> Result<Option<Uuid>> {
+    let uid: Option<(Uuid,)> = sqlx::query_as(
+        r#"SELECT u.uid FROM users u WHERE u.user_name = $1
+        AND NOT EXISTS (SELECT 1 FROM account_deletions a WHERE a.uid = u.uid AND a.confirmed_at IS NOT NULL)"#,
+    )
+    .bind(user_name)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(uid.map(|(uid,)| uid))
+}
+
+pub async fn update_password(pool: &PgPool, uid: &Uuid, password: &str) -> Result<()> {
+    let hashed_password = hash(password).await?;
+    sqlx::query("UPDATE users SET password = $1 WHERE uid = $2")
+        .bind(hashed_password)
+        .bind(uid)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn update_user_name(pool: &PgPool, uid: &Uuid, user_name: &str) -> Result<()> {
+    sqlx::query("UPDATE users SET user_name = $1 WHERE uid = $2")
+        .bind(user_name)
+        .bind(uid)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+// The defaults until the user saves the preferences.
+pub async fn find_preferences(pool: &PgPool, uid: &Uuid) -> Result<Preferences> {
+    let preferences = sqlx::query_as::<_, Preferences>(
+        r#"SELECT timezone, locale, daily_review_limit, default_platform, email_digest, digest_hour FROM preferences WHERE uid = $1"#,
+    )
+    .bind(uid)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(preferences.unwrap_or_default())
+}
+
+pub async fn update_preferences(
+    pool: &PgPool,
+    uid: &Uuid,
+    preferences: &Preferences,
+) -> Result<()> {
+    sqlx::query(
+        r#"INSERT INTO preferences (uid, timezone, locale, daily_review_limit, default_platform, email_digest, digest_hour) VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (uid) DO UPDATE SET timezone = $2, locale = $3, daily_review_limit = $4, default_platform = $5, email_digest = $6, digest_hour = $7"#,
+    )
+    .bind(uid)
+    .bind(&preferences.timezone)
+    .bind(&preferences.locale)
+    .bind(preferences.daily_review_limit)
+    .bind(preferences.default_platform)
+    .bind(&preferences.email_digest)
+    .bind(preferences.digest_hour)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Whether Postgres knows the time zone, which would fail the queries otherwise.
+pub async fn timezone_exists(pool: &PgPool, tz: &str) -> Result<bool> {
+    let (exists,): (bool,) =
+        sqlx::query_as("SELECT EXISTS (SELECT 1 FROM pg_timezone_names WHERE name = $1)")
+            .bind(tz)
+            .fetch_one(pool)
+            .await?;
+
+    Ok(exists)
+}
+
+// Only the latest request per user is kept, so older confirmation links stop working.
+pub async fn register_email_change(
+    pool: &PgPool,
+    uid: &Uuid,
+    email: &str,
+    token: &Uuid,
+    verification_mail: &NewMail,
+) -> Result<()> {
+    let now = Utc::now();
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM email_changes WHERE uid = $1")
+        .bind(uid)
+        .execute(&mut tx)
+        .await?;
+    sqlx::query(
+        r#"INSERT INTO email_changes (uid, email, token, created_at, expires_at) VALUES ($1, $2, $3, $4, $5)"#,
+    )
+    .bind(uid)
+    .bind(email)
+    .bind(token)
+    .bind(now)
+    .bind(now + Duration::hours(EMAIL_CHANGE_EXPIRY_HOURS))
+    .execute(&mut tx)
+    .await?;
+    mail::infrastructures::enqueue(&mut tx, verification_mail).await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+// An expired request is as good as a missing one.
+pub async fn extract_email_change(pool: &PgPool, token: &Uuid) -> Result<EmailChange, bool> {
+    let change = sqlx::query_as::<_, EmailChange>(
+        "SELECT uid, email FROM email_changes WHERE token = $1 AND expires_at > $2",
+    )
+    .bind(token)
+    .bind(Utc::now())
+    .fetch_optional(pool)
+    .await;
+    if change.is_err() {
+        return Err(false);
+    }
+
+    match change.unwrap() {
+        None => Err(true),
+        Some(c) => {
+            if sqlx::query("DELETE FROM email_changes WHERE token = $1")
+                .bind(token)
+                .execute(pool)
+                .await
+                .is_err()
+            {
+                return Err(false);
+            }
+            Ok(c)
+        }
+    }
+}
+
+pub async fn update_email(pool: &PgPool, uid: &Uuid, email: &str) -> Result<()> {
+    sqlx::query("UPDATE users SET email = $1 WHERE uid = $2")
+        .bind(email)
+        .bind(uid)
+        .execute(pool)
+        .await?;
+
+    Ok(())
+}
+
+pub async fn export_account(pool: &PgPool, uid: &Uuid) -> Result<AccountExport> {
+    let profile =
+        sqlx::query_as::<_, Profile>("SELECT user_name, email, uid FROM users WHERE uid = $1")
+            .bind(uid)
+            .fetch_one(pool)
+            .await?;
+    let reviews = sqlx::query_as::<_, ExportedReview>(
+        r#"SELECT r.id, r.problem_name, r.url, r.memo, r.platform, r.difficulty, r.solved_without_hints, r.created_at, r.updated_at, s.due_at, s.interval_days, s.ease, s.repetitions,
+        COALESCE(ARRAY_AGG(t.name ORDER BY t.name) FILTER (WHERE t.name IS NOT NULL), '{}') AS tags
+        FROM reviews r
+        LEFT JOIN schedules s ON s.review_id = r.id
+        LEFT JOIN review_tags rt ON rt.review_id = r.id
+        LEFT JOIN tags t ON t.id = rt.tag_id
+        WHERE r.uid = $1 GROUP BY r.id, s.review_id ORDER BY r.id"#,
+    )
+    .bind(uid)
+    .fetch_all(pool)
+    .await?;
+    let tags: Vec<(String,)> = sqlx::query_as("SELECT name FROM tags WHERE uid = $1 ORDER BY name")
+        .bind(uid)
+        .fetch_all(pool)
+        .await?;
+
+    let submissions = sqlx::query_as::<_, Submission>(
+        r#"SELECT s.id, s.review_id, s.source_code, s.language, s.verdict, s.runtime_ms, s.without_editorial, s.created_at
+        FROM submissions s JOIN reviews r ON r.id = s.review_id WHERE r.uid = $1 ORDER BY s.id"#,
+    )
+    .bind(uid)
+    .fetch_all(pool)
+    .await?;
+
+    let revisions = sqlx::query_as::<_, Revision>(
+        r#"SELECT v.id, v.review_id, v.problem_name, v.memo, v.updated_at
+        FROM review_revisions v JOIN reviews r ON r.id = v.review_id WHERE r.uid = $1 ORDER BY v.id"#,
+    )
+    .bind(uid)
+    .fetch_all(pool)
+    .await?;
+    let preferences = find_preferences(pool, uid).await?;
+    let review_sessions = export_sessions(pool, uid).await?;
+    let mut collections = Vec::new();
+    for collection in collections::infrastructures::find_collections(pool, uid).await? {
+        let entries = collections::infrastructures::find_entries(pool, collection.id).await?;
+        collections.push(ExportedCollection::new(collection, entries));
+    }
+    let group_memberships = sqlx::query_as::<_, Membership>(
+        r#"SELECT g.id AS group_id, g.name, g.description, g.uid = $1 AS owner, m.joined_at
+        FROM group_members m JOIN study_groups g ON g.id = m.group_id WHERE m.uid = $1 ORDER BY g.id"#,
+    )
+    .bind(uid)
+    .fetch_all(pool)
+    .await?;
+    let problem_sets = export_problem_sets(pool, &group_memberships).await?;
+    let comments = sqlx::query_as::<_, ExportedComment>(
+        r#"SELECT id, problem_id, entry_id, parent_id, body, created_at, updated_at, deleted_at
+        FROM comments WHERE uid = $1 ORDER BY id"#,
+    )
+    .bind(uid)
+    .fetch_all(pool)
+    .await?;
+    let attempts = sqlx::query_as::<_, Attempt>(
+        r#"SELECT a.id, a.review_id, a.started_at, a.stopped_at, a.seconds
+        FROM review_attempts a JOIN reviews r ON r.id = a.review_id WHERE r.uid = $1 ORDER BY a.id"#,
+    )
+    .bind(uid)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(AccountExport {
+        profile,
+        reviews,
+        tags: tags.into_iter().map(|(name,)| name).collect(),
+        submissions,
+        revisions,
+        preferences,
+        review_sessions,
+        attempts,
+        webhooks: webhooks::infrastructures::find_webhooks(pool, uid).await?,
+        collections,
+        group_memberships,
+        problem_sets,
+        comments,
+    })
+}
+
+async fn export_problem_sets(
+    pool: &PgPool,
+    group_memberships: &[Membership],
+) -> Result<Vec<ExportedProblemSet>> {
+    let mut exported = Vec::new();
+    for membership in group_memberships.iter().filter(|m| m.owner) {
+        for set in groups::infrastructures::find_problem_sets(pool, membership.group_id).await? {
+            let problems = groups::infrastructures::find_set_problems(pool, set.id).await?;
+            exported.push(ExportedProblemSet::new(membership.group_id, set, problems));
+        }
+    }
+
+    Ok(exported)
+}
+
+async fn export_sessions(pool: &PgPool, uid: &Uuid) -> Result<Vec<ExportedSession>> {
+    let sessions: Vec<(i32, DateTime<Utc>, Option<DateTime<Utc>>)> = sqlx::query_as(
+        "SELECT id, started_at, finished_at FROM review_sessions WHERE uid = $1 ORDER BY id",
+    )
+    .bind(uid)
+    .fetch_all(pool)
+    .await?;
+    let mut items = sqlx::query_as::<_, ExportedSessionItem>(
+        r#"SELECT i.session_id, i.position, i.review_id, i.grade, i.seconds, i.graded_at
+        FROM session_items i JOIN review_sessions s ON s.id = i.session_id
+        WHERE s.uid = $1 ORDER BY i.session_id, i.position"#,
+    )
+    .bind(uid)
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .peekable();
+
+    // the items come in the order of the sessions
+    let mut exported = Vec::with_capacity(sessions.len());
+    for (id, started_at, finished_at) in sessions {
+        let mut session_items = Vec::new();
+        while let Some(item) = items.next_if(|item| item.session_id == id) {
+            session_items.push(item);
+        }
+        exported.push(ExportedSession {
+            id,
+            started_at,
+            finished_at,
+            items: session_items,
+        });
+    }
+
+    Ok(exported)
+}
+
+// A deletion confirmed by password starts the grace period immediately.
+pub async fn schedule_deletion(pool: &PgPool, uid: &Uuid) -> Result<()> {
+    let now = Utc::now();
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM account_deletions WHERE uid = $1")
+        .bind(uid)
+        .execute(&mut tx)
+        .await?;
+    sqlx::query(r#"INSERT INTO account_deletions (uid, token, confirmed_at, created_at) VALUES ($1, $2, $3, $4)"#)
+        .bind(uid)
+        .bind(Uuid::new_v4())
+        .bind(now)
+        .bind(now)
+        .execute(&mut tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+// A deletion requested by email starts the grace period once the mailed link is opened.
+pub async fn register_deletion_request(
+    pool: &PgPool,
+    uid: &Uuid,
+    token: &Uuid,
+    confirmation_mail: &NewMail,
+) -> Result<()> {
+    let now = Utc::now();
+    let mut tx = pool.begin().await?;
+    sqlx::query("DELETE FROM account_deletions WHERE uid = $1")
+        .bind(uid)
+        .execute(&mut tx)
+        .await?;
+    sqlx::query(r#"INSERT INTO account_deletions (uid, token, created_at) VALUES ($1, $2, $3)"#)
+        .bind(uid)
+        .bind(token)
+        .bind(now)
+        .execute(&mut tx)
+        .await?;
+    mail::infrastructures::enqueue(&mut tx, confirmation_mail).await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+pub async fn confirm_deletion(pool: &PgPool, token: &Uuid) -> Result<Uuid, bool> {
+    let now = Utc::now();
+    let uid: Result<Option<(Uuid,)>, sqlx::Error> = sqlx::query_as(
+        "UPDATE account_deletions SET confirmed_at = $1 WHERE token = $2 AND confirmed_at IS NULL RETURNING uid",
+    )
+    .bind(now)
+    .bind(token)
+    .fetch_optional(pool)
+    .await;
+
+    match uid {
+        Err(_) => Err(false),
+        Ok(None) => Err(true),
+        Ok(Some((uid,))) => Ok(uid),
+    }
+}
+
+// Returns whether there was a pending deletion to cancel.
+pub async fn cancel_deletion(pool: &PgPool, uid: &Uuid) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM account_deletions WHERE uid = $1")
+        .bind(uid)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn delete_account(pool: &PgPool, uid: &Uuid) -> Result<()> {
+    // every table holding data of the user
+    let tables = vec![
+        "reviews",
+        "tags",
+        "review_sessions",
+        "preferences",
+        "calendar_feeds",
+        "email_digests",
+        "webhook_deliveries",
+        "webhooks",
+        "collections",
+        "group_members",
+        "study_groups",
+        "sessions",
+        "email_changes",
+        "account_deletions",
+        "users",
+    ];
+    let mut tx = pool.begin().await?;
+    // the comments are in the threads of others, so they lose their bodies but keep their places
+    sqlx::query(
+        r#"UPDATE comments SET body = NULL, body_html = NULL, deleted_at = COALESCE(deleted_at, $1)
+        WHERE uid = $2"#,
+    )
+    .bind(Utc::now())
+    .bind(uid)
+    .execute(&mut tx)
+    .await?;
+    for table in tables {
+        // cannot bind the table and thus prepare the sql
+        let sql = format!("DELETE FROM {} WHERE uid = $1", table);
+        sqlx::query(&sql).bind(uid).execute(&mut tx).await?;
+    }
+    tx.commit().await?;
+
+    Ok(())
+}
+
+// Delete the accounts whose grace period has passed and return their uids.
+pub async fn purge_deleted_accounts(pool: &PgPool) -> Result<Vec<Uuid>> {
+    let deadline = Utc::now() - Duration::days(DELETION_GRACE_PERIOD_DAYS);
+    let uids: Vec<(Uuid,)> =
+        sqlx::query_as("SELECT uid FROM account_deletions WHERE confirmed_at < $1")
+            .bind(deadline)
+            .fetch_all(pool)
+            .await?;
+    let mut purged = Vec::new();
+    for (uid,) in uids {
+        delete_account(pool, &uid).await?;
+        purged.push(uid);
+    }
+
+    Ok(purged)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::utils;
+    use crate::{config, utils};
     use bcrypt::verify;
+    use std::time::Instant;
+
+    fn new_mail(recipient: &str) -> NewMail {
+        NewMail {
+            recipient: recipient.to_string(),
+            subject: "subject".to_string(),
+            body: "body".to_string(),
+        }
+    }
+
+    async fn count_mails(pool: &PgPool) -> i64 {
+        let (mails,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM email_outbox")
+            .fetch_one(pool)
+            .await
+            .unwrap();
+        mails
+    }
 
     #[actix_rt::test]
     async fn is_already_registered_exist() {
@@ -155,6 +603,33 @@ mod tests {
         utils::clear_table(&pool).await.unwrap();
     }
 
+    #[actix_rt::test]
+    async fn users_unique_whatever_the_case() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'test_user', 'password', 'test@gmail.com', $1)"#)
+			.bind(Uuid::new_v4())
+			.execute(&pool)
+			.await
+			.unwrap();
+
+        assert!(is_already_registered(&pool, "Test_User").await.unwrap());
+        assert!(is_email_registered(&pool, "TEST@gmail.com").await.unwrap());
+        // the checks racing with one another are settled by the database
+        let same_name = sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (1, 'TEST_USER', 'password', 'another@gmail.com', $1)"#)
+			.bind(Uuid::new_v4())
+			.execute(&pool)
+			.await;
+        assert!(same_name.is_err());
+        let same_email = sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (1, 'another_user', 'password', 'Test@Gmail.com', $1)"#)
+			.bind(Uuid::new_v4())
+			.execute(&pool)
+			.await;
+        assert!(same_email.is_err());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
     #[actix_rt::test]
     async fn is_already_registered_temporarily_exist() {
         let config = config::Config::new();
@@ -193,17 +668,6 @@ mod tests {
         utils::clear_table(&pool).await.unwrap();
     }
 
-    #[actix_rt::test]
-    #[ignore]
-    async fn send_mail_ok() {
-        let user_name = "dummy_user";
-        let mail_address = "dummy_mail";
-        let uid = Uuid::new_v4();
-        let expected = true;
-        let actual = send_mail(user_name, mail_address, &uid).await.unwrap();
-        assert_eq!(expected, actual);
-    }
-
     #[actix_rt::test]
     async fn register_temporarily_create() {
         let config = config::Config::new();
@@ -220,7 +684,9 @@ mod tests {
             .await
             .unwrap();
         assert_eq!(0, tmp_users_before.len());
-        register_temporarily(&pool, user, uid).await.unwrap();
+        register_temporarily(&pool, user, uid, &new_mail("test@gmail.com"))
+            .await
+            .unwrap();
 
         let tmp_users_after = sqlx::query!("SELECT * FROM tmp_users where user_name = 'user_name'")
             .fetch_one(&pool)
@@ -230,6 +696,51 @@ mod tests {
         assert_eq!("test@gmail.com".to_string(), tmp_users_after.email);
         assert!(verify("password", &tmp_users_after.password).unwrap());
         assert_eq!(uid_clone, tmp_users_after.uid);
+        // the verification mail is queued with the user
+        assert_eq!(1, count_mails(&pool).await);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    // Measures how many sign-ups per second can be hashed and stored. Run it with `make bench`.
+    #[ignore]
+    #[actix_rt::test]
+    async fn register_temporarily_throughput() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let count = 32;
+        let started = Instant::now();
+        let mut handles = Vec::new();
+        for i in 0..count {
+            let pool = pool.clone();
+            handles.push(actix_rt::spawn(async move {
+                let user = NewUser {
+                    user_name: format!("user_{}", i),
+                    email: format!("user_{}@gmail.com", i),
+                    password: "correct-horse-battery-staple".to_string(),
+                };
+                register_temporarily(&pool, user, Uuid::new_v4(), &new_mail("test@gmail.com"))
+                    .await
+                    .unwrap();
+            }));
+        }
+        for handle in handles {
+            handle.await.unwrap();
+        }
+        let elapsed = started.elapsed();
+        println!(
+            "{} sign-ups with cost {} in {:?} ({:.1} sign-ups/s)",
+            count,
+            config.bcrypt_cost,
+            elapsed,
+            count as f64 / elapsed.as_secs_f64()
+        );
+
+        let tmp_users = sqlx::query("SELECT * FROM tmp_users")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(count, tmp_users.len());
 
         utils::clear_table(&pool).await.unwrap();
     }
@@ -331,4 +842,686 @@ mod tests {
 
         utils::clear_table(&pool).await.unwrap();
     }
+
+    #[actix_rt::test]
+    async fn find_user_test() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'test_user', 'password', 'test@gmail.com', $1)"#)
+			.bind(uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+
+        let expected = User {
+            user_name: "test_user".to_string(),
+            email: "test@gmail.com".to_string(),
+            password: "password".to_string(),
+            uid,
+        };
+        let actual = find_user(&pool, &uid).await.unwrap();
+        assert_eq!(expected, actual);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn update_password_test() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'test_user', 'password', 'test@gmail.com', $1)"#)
+			.bind(uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+
+        update_password(&pool, &uid, "new_password").await.unwrap();
+
+        let user = sqlx::query!("SELECT * FROM users")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert!(verify("new_password", &user.password).unwrap());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn update_user_name_test() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'test_user', 'password', 'test@gmail.com', $1)"#)
+			.bind(uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+
+        update_user_name(&pool, &uid, "new_user").await.unwrap();
+
+        let user = sqlx::query!("SELECT * FROM users")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!("new_user".to_string(), user.user_name);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn register_email_change_replace() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        let old_token = Uuid::new_v4();
+        let new_token = Uuid::new_v4();
+
+        register_email_change(
+            &pool,
+            &uid,
+            "old@gmail.com",
+            &old_token,
+            &new_mail("old@gmail.com"),
+        )
+        .await
+        .unwrap();
+        register_email_change(
+            &pool,
+            &uid,
+            "new@gmail.com",
+            &new_token,
+            &new_mail("new@gmail.com"),
+        )
+        .await
+        .unwrap();
+
+        // check only the latest request remains
+        let changes = sqlx::query!("SELECT * FROM email_changes")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(1, changes.len());
+        assert_eq!("new@gmail.com".to_string(), changes[0].email);
+        assert_eq!(new_token, changes[0].token);
+        assert_eq!(uid, changes[0].uid);
+        assert_eq!(2, count_mails(&pool).await);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn extract_email_change_exist() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        let token = Uuid::new_v4();
+        register_email_change(
+            &pool,
+            &uid,
+            "new@gmail.com",
+            &token,
+            &new_mail("new@gmail.com"),
+        )
+        .await
+        .unwrap();
+
+        let expected = EmailChange {
+            uid,
+            email: "new@gmail.com".to_string(),
+        };
+        let actual = extract_email_change(&pool, &token).await.unwrap();
+        assert_eq!(expected, actual);
+
+        // check the request is consumed
+        let change = sqlx::query("SELECT * FROM email_changes")
+            .fetch_optional(&pool)
+            .await
+            .unwrap();
+        assert!(change.is_none());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn extract_email_change_not_exist() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+
+        let expected = true;
+        let actual = extract_email_change(&pool, &Uuid::new_v4())
+            .await
+            .unwrap_err();
+        assert_eq!(expected, actual);
+    }
+
+    #[actix_rt::test]
+    async fn update_email_test() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'test_user', 'password', 'test@gmail.com', $1)"#)
+			.bind(uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+
+        update_email(&pool, &uid, "new@gmail.com").await.unwrap();
+
+        let user = sqlx::query!("SELECT * FROM users")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!("new@gmail.com".to_string(), user.email);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn export_account_test() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        let now = Utc::now();
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'test_user', 'password', 'test@gmail.com', $1)"#)
+			.bind(uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+        sqlx::query(r#"INSERT INTO reviews (id, problem_name, url, memo, uid, platform, created_at, updated_at) VALUES (0, 'test_prob_name', 'test_url', 'test_memo', $1, 1, $2, $3)"#)
+			.bind(uid)
+			.bind(now)
+			.bind(now)
+			.execute(&pool)
+			.await
+			.unwrap();
+        // the review of another user must not be exported
+        sqlx::query(r#"INSERT INTO reviews (id, problem_name, url, memo, uid, platform, created_at, updated_at) VALUES (1, 'other_prob_name', 'other_url', 'other_memo', $1, 1, $2, $3)"#)
+			.bind(Uuid::new_v4())
+			.bind(now)
+			.bind(now)
+			.execute(&pool)
+			.await
+			.unwrap();
+
+        let actual = export_account(&pool, &uid).await.unwrap();
+        assert_eq!(
+            Profile {
+                user_name: "test_user".to_string(),
+                email: "test@gmail.com".to_string(),
+                uid,
+            },
+            actual.profile
+        );
+        assert_eq!(1, actual.reviews.len());
+        assert_eq!(0, actual.reviews[0].id);
+        assert_eq!("test_prob_name".to_string(), actual.reviews[0].problem_name);
+        assert_eq!(Some("test_memo".to_string()), actual.reviews[0].memo);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn export_review_sessions_test() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let (uid, another_uid) = (Uuid::new_v4(), Uuid::new_v4());
+        let now = Utc::now();
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'test_user', 'password', 'test@gmail.com', $1)"#)
+			.bind(uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+        sqlx::query(r#"INSERT INTO reviews (id, problem_name, url, memo, uid, platform, created_at, updated_at) VALUES (0, 'test_prob_name', 'test_url', 'test_memo', $1, 1, $2, $2), (1, 'other_prob_name', 'other_url', 'other_memo', $3, 1, $2, $2)"#)
+			.bind(uid)
+			.bind(now)
+			.bind(another_uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+        sqlx::query(r#"INSERT INTO schedules (review_id, due_at, interval_days, ease, repetitions) VALUES (0, $1, 6, 2.6, 2)"#)
+			.bind(now)
+			.execute(&pool)
+			.await
+			.unwrap();
+        sqlx::query(r#"INSERT INTO review_sessions (id, uid, started_at, finished_at) VALUES (0, $1, $3, $3), (1, $1, $3, NULL), (2, $2, $3, NULL)"#)
+			.bind(uid)
+			.bind(another_uid)
+			.bind(now)
+			.execute(&pool)
+			.await
+			.unwrap();
+        sqlx::query(r#"INSERT INTO session_items (session_id, position, review_id, grade, seconds, graded_at) VALUES (0, 0, 0, 4, 90, $1), (2, 0, 1, 5, 30, $1)"#)
+			.bind(now)
+			.execute(&pool)
+			.await
+			.unwrap();
+
+        let actual = export_account(&pool, &uid).await.unwrap();
+        assert_eq!(Some(6), actual.reviews[0].interval_days);
+        assert_eq!(Some(2.6), actual.reviews[0].ease);
+        assert_eq!(Some(2), actual.reviews[0].repetitions);
+        // the sessions of another user must not be exported
+        assert_eq!(
+            vec![0, 1],
+            actual
+                .review_sessions
+                .iter()
+                .map(|s| s.id)
+                .collect::<Vec<i32>>()
+        );
+        assert_eq!(1, actual.review_sessions[0].items.len());
+        assert_eq!(0, actual.review_sessions[0].items[0].review_id);
+        assert_eq!(Some(4), actual.review_sessions[0].items[0].grade);
+        assert_eq!(Some(90), actual.review_sessions[0].items[0].seconds);
+        assert!(actual.review_sessions[1].items.is_empty());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn export_attempts_test() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        let now = Utc::now();
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'test_user', 'password', 'test@gmail.com', $1)"#)
+			.bind(uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+        sqlx::query(r#"INSERT INTO reviews (id, problem_name, url, memo, uid, platform, created_at, updated_at) VALUES (0, 'test_prob_name', 'test_url', 'test_memo', $1, 1, $2, $2), (1, 'other_prob_name', 'other_url', 'other_memo', $3, 1, $2, $2)"#)
+			.bind(uid)
+			.bind(now)
+			.bind(Uuid::new_v4())
+			.execute(&pool)
+			.await
+			.unwrap();
+        sqlx::query(r#"INSERT INTO review_attempts (id, review_id, started_at, stopped_at, seconds) VALUES (0, 0, $1, $1, 120), (1, 0, $1, NULL, NULL), (2, 1, $1, $1, 60)"#)
+			.bind(now)
+			.execute(&pool)
+			.await
+			.unwrap();
+
+        let actual = export_account(&pool, &uid).await.unwrap();
+        // the attempts on the review of another user must not be exported
+        assert_eq!(
+            vec![0, 1],
+            actual.attempts.iter().map(|a| a.id).collect::<Vec<i32>>()
+        );
+        assert_eq!(Some(120), actual.attempts[0].seconds);
+        assert!(actual.attempts[1].stopped_at.is_none());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn export_webhooks_test() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        let now = Utc::now();
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'test_user', 'password', 'test@gmail.com', $1)"#)
+			.bind(uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+        sqlx::query(r#"INSERT INTO webhooks (id, uid, url, secret, created_at) VALUES (0, $1, 'https://hooks.example.com/review', 'test_secret', $2), (1, $3, 'https://hooks.example.com/other', 'other_secret', $2)"#)
+			.bind(uid)
+			.bind(now)
+			.bind(Uuid::new_v4())
+			.execute(&pool)
+			.await
+			.unwrap();
+
+        let actual = export_account(&pool, &uid).await.unwrap();
+        // the webhook of another user must not be exported
+        assert_eq!(1, actual.webhooks.len());
+        assert_eq!(0, actual.webhooks[0].id);
+        assert_eq!(
+            "https://hooks.example.com/review".to_string(),
+            actual.webhooks[0].url
+        );
+        // the secret is not in the export
+        assert!(!serde_json::to_string(&actual)
+            .unwrap()
+            .contains("test_secret"));
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn export_collections_test() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let (uid, another_uid) = (Uuid::new_v4(), Uuid::new_v4());
+        let share_token = Uuid::new_v4();
+        let now = Utc::now();
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'test_user', 'password', 'test@gmail.com', $1)"#)
+			.bind(uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+        sqlx::query(r#"INSERT INTO reviews (id, problem_name, url, memo, uid, platform, created_at, updated_at) VALUES (0, 'test_prob_name', 'test_url', 'test_memo', $1, 1, $2, $2), (1, 'other_prob_name', 'other_url', 'other_memo', $3, 1, $2, $2)"#)
+			.bind(uid)
+			.bind(now)
+			.bind(another_uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+        sqlx::query(r#"INSERT INTO collections (id, uid, name, visibility, share_token, created_at) VALUES (0, $1, 'test_collection', 'unlisted', $2, $3), (1, $4, 'other_collection', 'public', $5, $3)"#)
+			.bind(uid)
+			.bind(share_token)
+			.bind(now)
+			.bind(another_uid)
+			.bind(Uuid::new_v4())
+			.execute(&pool)
+			.await
+			.unwrap();
+        sqlx::query(r#"INSERT INTO collection_entries (id, collection_id, review_id, note, added_at) VALUES (0, 0, 0, 'test_note', $1), (1, 1, 1, NULL, $1)"#)
+			.bind(now)
+			.execute(&pool)
+			.await
+			.unwrap();
+
+        let actual = export_account(&pool, &uid).await.unwrap();
+        // the collection of another user must not be exported
+        assert_eq!(1, actual.collections.len());
+        assert_eq!("test_collection".to_string(), actual.collections[0].name);
+        assert_eq!("unlisted".to_string(), actual.collections[0].visibility);
+        assert_eq!(share_token, actual.collections[0].share_token);
+        assert_eq!(1, actual.collections[0].entries.len());
+        assert_eq!(0, actual.collections[0].entries[0].review_id);
+        assert_eq!(
+            Some("test_note".to_string()),
+            actual.collections[0].entries[0].note
+        );
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn export_groups_test() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let (uid, another_uid) = (Uuid::new_v4(), Uuid::new_v4());
+        let now = Utc::now();
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'test_user', 'password', 'test@gmail.com', $1), (1, 'another_user', 'password', 'another@gmail.com', $2)"#)
+			.bind(uid)
+			.bind(another_uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+        // the user owns the first group and is a member of the second one
+        sqlx::query(r#"INSERT INTO study_groups (id, uid, name, created_at) VALUES (0, $1, 'test_group', $3), (1, $2, 'other_group', $3), (2, $2, 'another_group', $3)"#)
+			.bind(uid)
+			.bind(another_uid)
+			.bind(now)
+			.execute(&pool)
+			.await
+			.unwrap();
+        sqlx::query(r#"INSERT INTO group_members (group_id, uid, joined_at) VALUES (0, $1, $3), (1, $2, $3), (1, $1, $3), (2, $2, $3)"#)
+			.bind(uid)
+			.bind(another_uid)
+			.bind(now)
+			.execute(&pool)
+			.await
+			.unwrap();
+        sqlx::query(r#"INSERT INTO problems (id, platform, external_id, title, url) VALUES (0, 4, '1', 'No.1', 'https://yukicoder.me/problems/no/1')"#)
+			.execute(&pool)
+			.await
+			.unwrap();
+        sqlx::query(r#"INSERT INTO problem_sets (id, group_id, name, created_at) VALUES (0, 0, 'test_set', $1), (1, 1, 'other_set', $1)"#)
+			.bind(now)
+			.execute(&pool)
+			.await
+			.unwrap();
+        sqlx::query(r#"INSERT INTO problem_set_items (problem_set_id, problem_id, title, added_at) VALUES (0, 0, 'test_title', $1), (1, 0, 'other_title', $1)"#)
+			.bind(now)
+			.execute(&pool)
+			.await
+			.unwrap();
+
+        let actual = export_account(&pool, &uid).await.unwrap();
+        // the group the user is not a member of must not be exported
+        assert_eq!(
+            vec![(0, true), (1, false)],
+            actual
+                .group_memberships
+                .iter()
+                .map(|m| (m.group_id, m.owner))
+                .collect::<Vec<(i32, bool)>>()
+        );
+        // only the problem sets of the group the user owns are exported
+        assert_eq!(1, actual.problem_sets.len());
+        assert_eq!("test_set".to_string(), actual.problem_sets[0].name);
+        assert_eq!(1, actual.problem_sets[0].problems.len());
+        assert_eq!(
+            "test_title".to_string(),
+            actual.problem_sets[0].problems[0].title
+        );
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn export_comments_test() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let (uid, another_uid) = (Uuid::new_v4(), Uuid::new_v4());
+        let now = Utc::now();
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'test_user', 'password', 'test@gmail.com', $1)"#)
+			.bind(uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+        sqlx::query(r#"INSERT INTO problems (id, platform, external_id, title, url) VALUES (0, 4, '1', 'No.1', 'https://yukicoder.me/problems/no/1')"#)
+			.execute(&pool)
+			.await
+			.unwrap();
+        sqlx::query(r#"INSERT INTO comments (id, uid, problem_id, parent_id, body, body_html, created_at, deleted_at) VALUES (0, $2, 0, NULL, 'other comment', '<p>other comment</p>', $3, NULL), (1, $1, 0, 0, 'test comment', '<p>test comment</p>', $3, NULL), (2, $1, 0, NULL, NULL, NULL, $3, $3)"#)
+			.bind(uid)
+			.bind(another_uid)
+			.bind(now)
+			.execute(&pool)
+			.await
+			.unwrap();
+
+        let actual = export_account(&pool, &uid).await.unwrap();
+        // the comment of another user must not be exported
+        assert_eq!(
+            vec![1, 2],
+            actual.comments.iter().map(|c| c.id).collect::<Vec<i32>>()
+        );
+        assert_eq!(Some(0), actual.comments[0].parent_id);
+        assert_eq!(Some("test comment".to_string()), actual.comments[0].body);
+        assert!(actual.comments[1].body.is_none());
+        assert!(actual.comments[1].deleted_at.is_some());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn schedule_deletion_test() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        register_deletion_request(&pool, &uid, &Uuid::new_v4(), &new_mail("test@gmail.com"))
+            .await
+            .unwrap();
+
+        schedule_deletion(&pool, &uid).await.unwrap();
+
+        // check the pending request is replaced by a confirmed one
+        let deletions = sqlx::query!("SELECT * FROM account_deletions")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(1, deletions.len());
+        assert_eq!(uid, deletions[0].uid);
+        assert!(deletions[0].confirmed_at.is_some());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn register_deletion_request_test() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        let token = Uuid::new_v4();
+
+        register_deletion_request(&pool, &uid, &token, &new_mail("test@gmail.com"))
+            .await
+            .unwrap();
+
+        let deletions = sqlx::query!("SELECT * FROM account_deletions")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(1, deletions.len());
+        assert_eq!(uid, deletions[0].uid);
+        assert_eq!(token, deletions[0].token);
+        assert!(deletions[0].confirmed_at.is_none());
+        assert_eq!(1, count_mails(&pool).await);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn confirm_deletion_exist() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        let token = Uuid::new_v4();
+        register_deletion_request(&pool, &uid, &token, &new_mail("test@gmail.com"))
+            .await
+            .unwrap();
+
+        let actual = confirm_deletion(&pool, &token).await.unwrap();
+        assert_eq!(uid, actual);
+        let deletion = sqlx::query!("SELECT * FROM account_deletions")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert!(deletion.confirmed_at.is_some());
+
+        // check the link cannot be used twice
+        let expected = true;
+        let actual = confirm_deletion(&pool, &token).await.unwrap_err();
+        assert_eq!(expected, actual);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn confirm_deletion_not_exist() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+
+        let expected = true;
+        let actual = confirm_deletion(&pool, &Uuid::new_v4()).await.unwrap_err();
+        assert_eq!(expected, actual);
+    }
+
+    #[actix_rt::test]
+    async fn cancel_deletion_test() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        schedule_deletion(&pool, &uid).await.unwrap();
+
+        assert!(cancel_deletion(&pool, &uid).await.unwrap());
+        assert!(!cancel_deletion(&pool, &uid).await.unwrap());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn delete_account_test() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        let another_uid = Uuid::new_v4();
+        let now = Utc::now();
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'test_user', 'password', 'test@gmail.com', $1), (1, 'another_user', 'password', 'another@gmail.com', $2)"#)
+			.bind(uid)
+			.bind(another_uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+        sqlx::query(r#"INSERT INTO reviews (id, problem_name, url, memo, uid, platform, created_at, updated_at) VALUES (0, 'test_prob_name', 'test_url', 'test_memo', $1, 1, $2, $3)"#)
+			.bind(uid)
+			.bind(now)
+			.bind(now)
+			.execute(&pool)
+			.await
+			.unwrap();
+        schedule_deletion(&pool, &uid).await.unwrap();
+
+        delete_account(&pool, &uid).await.unwrap();
+
+        let users = sqlx::query!("SELECT * FROM users")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(1, users.len());
+        assert_eq!(another_uid, users[0].uid);
+        let reviews = sqlx::query("SELECT * FROM reviews")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(0, reviews.len());
+        let deletions = sqlx::query("SELECT * FROM account_deletions")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(0, deletions.len());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn purge_deleted_accounts_test() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let expired_uid = Uuid::new_v4();
+        let recent_uid = Uuid::new_v4();
+        let pending_uid = Uuid::new_v4();
+        let now = Utc::now();
+        let expired = now - Duration::days(DELETION_GRACE_PERIOD_DAYS + 1);
+        sqlx::query(r#"INSERT INTO users (id, user_name, password, email, uid) VALUES (0, 'expired_user', 'password', 'expired@gmail.com', $1), (1, 'recent_user', 'password', 'recent@gmail.com', $2), (2, 'pending_user', 'password', 'pending@gmail.com', $3)"#)
+			.bind(expired_uid)
+			.bind(recent_uid)
+			.bind(pending_uid)
+			.execute(&pool)
+			.await
+			.unwrap();
+        sqlx::query(r#"INSERT INTO account_deletions (id, uid, token, confirmed_at, created_at) VALUES (0, $1, $2, $3, $3), (1, $4, $5, $6, $6), (2, $7, $8, NULL, $3)"#)
+			.bind(expired_uid)
+			.bind(Uuid::new_v4())
+			.bind(expired)
+			.bind(recent_uid)
+			.bind(Uuid::new_v4())
+			.bind(now)
+			.bind(pending_uid)
+			.bind(Uuid::new_v4())
+			.execute(&pool)
+			.await
+			.unwrap();
+
+        let actual = purge_deleted_accounts(&pool).await.unwrap();
+        assert_eq!(vec![expired_uid], actual);
+
+        let users = sqlx::query!("SELECT * FROM users ORDER BY id")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(2, users.len());
+        assert_eq!(recent_uid, users[0].uid);
+        assert_eq!(pending_uid, users[1].uid);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
 }
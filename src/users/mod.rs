@@ -1,3 +1,3 @@
 pub mod handler;
-mod infrastructures;
-mod model;
+pub mod infrastructures;
+pub mod model;
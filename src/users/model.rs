@@ -1,6 +1,16 @@
-use crate::utils::RE_ALP_NUM_SYM;
+use crate::attempts::model::Attempt;
+use crate::collections::model::{Collection, Entry};
+use crate::groups::model::{ProblemSet, SetProblem};
+use crate::password::validate_password;
+use crate::reviews::model::Revision;
+use crate::stats::model::{Dashboard, PlatformCount};
+use crate::submissions::model::Submission;
+use crate::utils::{RE_ALP_NUM_SYM, RE_LOCALE};
+use crate::webhooks::model::Webhook;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use validator::Validate;
+use uuid::Uuid;
+use validator::{Validate, ValidationError};
 
 #[derive(Debug, Serialize, Deserialize, Validate, sqlx::FromRow, PartialEq)]
 pub struct NewUser {
@@ -8,6 +18,255 @@ pub struct NewUser {
     pub user_name: String,
     #[validate(email)]
     pub email: String,
+    #[validate(custom = "validate_password")]
+    pub password: String,
+}
+
+#[derive(Debug, sqlx::FromRow, PartialEq)]
+pub struct User {
+    pub user_name: String,
+    pub email: String,
+    pub password: String,
+    pub uid: Uuid,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, PartialEq)]
+pub struct ChangePassword {
+    pub current_password: String,
+    #[validate(custom = "validate_password")]
+    pub new_password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, PartialEq)]
+pub struct ChangeUserName {
     #[validate(length(min = 1, max = 100), regex(path = "RE_ALP_NUM_SYM"))]
+    pub user_name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate, PartialEq)]
+pub struct ChangeEmail {
+    #[validate(email)]
+    pub email: String,
+}
+
+pub const DIGEST_FREQUENCIES: [&str; 3] = ["off", "daily", "weekly"];
+
+#[derive(Debug, Serialize, Deserialize, Validate, sqlx::FromRow, PartialEq, Clone)]
+pub struct Preferences {
+    // an IANA time zone name, checked against the ones Postgres knows
+    #[validate(length(min = 1, max = 64))]
+    pub timezone: String,
+    #[validate(length(min = 2, max = 35), regex(path = "RE_LOCALE"))]
+    pub locale: String,
+    // the number of reviews a day the review sessions draw at most
+    #[validate(range(min = 1, max = 500))]
+    pub daily_review_limit: i16,
+    #[validate(range(min = 0, max = 4))]
+    pub default_platform: Option<i16>,
+    #[validate(custom = "validate_digest")]
+    pub email_digest: String,
+    // the hour of the day in the time zone the digest is sent at
+    #[validate(range(min = 0, max = 23))]
+    pub digest_hour: i16,
+}
+
+impl Default for Preferences {
+    fn default() -> Preferences {
+        Preferences {
+            timezone: "UTC".to_string(),
+            locale: "en".to_string(),
+            daily_review_limit: 20,
+            default_platform: None,
+            email_digest: "off".to_string(),
+            digest_hour: 8,
+        }
+    }
+}
+
+fn validate_digest(email_digest: &str) -> Result<(), ValidationError> {
+    if DIGEST_FREQUENCIES.contains(&email_digest) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("invalid_digest"))
+    }
+}
+
+#[derive(Debug, sqlx::FromRow, PartialEq)]
+pub struct EmailChange {
+    pub uid: Uuid,
+    pub email: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct DeleteAccount {
     pub password: String,
 }
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct AccountExport {
+    pub profile: Profile,
+    pub reviews: Vec<ExportedReview>,
+    // the tags defined by the user
+    pub tags: Vec<String>,
+    pub submissions: Vec<Submission>,
+    pub revisions: Vec<Revision>,
+    pub preferences: Preferences,
+    pub review_sessions: Vec<ExportedSession>,
+    pub attempts: Vec<Attempt>,
+    // the endpoints without their secrets
+    pub webhooks: Vec<Webhook>,
+    pub collections: Vec<ExportedCollection>,
+    pub group_memberships: Vec<Membership>,
+    // the problem sets of the groups the user owns
+    pub problem_sets: Vec<ExportedProblemSet>,
+    pub comments: Vec<ExportedComment>,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, PartialEq)]
+pub struct Profile {
+    pub user_name: String,
+    pub email: String,
+    pub uid: Uuid,
+}
+
+// What anyone can see of a user by the user name, never the email address nor the memos.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct PublicProfile {
+    pub user_name: String,
+    pub stats: PublicStats,
+    pub collections: Vec<Collection>,
+}
+
+// The aggregates of the dashboard, without the time zone nor the days of activity.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct PublicStats {
+    pub reviews: i64,
+    pub platforms: Vec<PlatformCount>,
+    pub current_streak: i64,
+    pub longest_streak: i64,
+    pub recalls: i64,
+    pub retention: Option<f64>,
+}
+
+impl PublicStats {
+    pub fn new(dashboard: Dashboard) -> PublicStats {
+        PublicStats {
+            reviews: dashboard.platforms.iter().map(|p| p.reviews).sum(),
+            platforms: dashboard.platforms,
+            current_streak: dashboard.current_streak,
+            longest_streak: dashboard.longest_streak,
+            recalls: dashboard.recalls,
+            retention: dashboard.retention,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, PartialEq)]
+pub struct ExportedReview {
+    pub id: i32,
+    pub problem_name: String,
+    pub url: String,
+    pub memo: Option<String>,
+    pub platform: i16,
+    pub difficulty: Option<i16>,
+    pub solved_without_hints: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub due_at: Option<DateTime<Utc>>,
+    // the SM-2 state, absent until the review is scheduled
+    pub interval_days: Option<i32>,
+    pub ease: Option<f32>,
+    pub repetitions: Option<i32>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ExportedCollection {
+    pub id: i32,
+    pub name: String,
+    pub description: Option<String>,
+    pub visibility: String,
+    pub share_token: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub entries: Vec<Entry>,
+}
+
+impl ExportedCollection {
+    pub fn new(collection: Collection, entries: Vec<Entry>) -> ExportedCollection {
+        ExportedCollection {
+            id: collection.id,
+            name: collection.name,
+            description: collection.description,
+            visibility: collection.visibility,
+            share_token: collection.share_token,
+            created_at: collection.created_at,
+            updated_at: collection.updated_at,
+            entries,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, PartialEq)]
+pub struct Membership {
+    pub group_id: i32,
+    pub name: String,
+    pub description: Option<String>,
+    // whether the user owns the group
+    pub owner: bool,
+    pub joined_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ExportedProblemSet {
+    pub id: i32,
+    pub group_id: i32,
+    pub name: String,
+    pub description: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub problems: Vec<SetProblem>,
+}
+
+impl ExportedProblemSet {
+    pub fn new(group_id: i32, set: ProblemSet, problems: Vec<SetProblem>) -> ExportedProblemSet {
+        ExportedProblemSet {
+            id: set.id,
+            group_id,
+            name: set.name,
+            description: set.description,
+            created_at: set.created_at,
+            problems,
+        }
+    }
+}
+
+// A comment written by the user, the body is absent once deleted.
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, PartialEq)]
+pub struct ExportedComment {
+    pub id: i32,
+    pub problem_id: Option<i32>,
+    pub entry_id: Option<i32>,
+    pub parent_id: Option<i32>,
+    pub body: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct ExportedSession {
+    pub id: i32,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub items: Vec<ExportedSessionItem>,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, PartialEq)]
+pub struct ExportedSessionItem {
+    pub session_id: i32,
+    pub position: i16,
+    pub review_id: i32,
+    pub grade: Option<i16>,
+    pub seconds: Option<i32>,
+    pub graded_at: Option<DateTime<Utc>>,
+}
@@ -1,10 +1,17 @@
+use crate::auth;
+use crate::reviews::model::NewReview;
 use anyhow::Result;
 use regex::Regex;
 use sqlx::PgPool;
+use uuid::Uuid;
 
 lazy_static! {
     // alphabet, number, symbol
     pub static ref RE_ALP_NUM_SYM: Regex = Regex::new(r"^[a-zA-Z0-9!-/:-@¥\[-`{-~]*$").unwrap();
+    // lowercase alphabet, number, hyphen
+    pub static ref RE_TAG: Regex = Regex::new(r"^[a-z0-9][a-z0-9-]*$").unwrap();
+    // BCP 47 language tag, e.g. ja or en-US
+    pub static ref RE_LOCALE: Regex = Regex::new(r"^[a-z]{2,3}(-[A-Za-z0-9]{2,8})*$").unwrap();
 }
 
 // Clear table for testing
@@ -14,22 +21,77 @@ pub async fn clear_table(pool: &PgPool) -> Result<()> {
         "users".to_string(),
         "reviews".to_string(),
         "tmp_users".to_string(),
+        "sessions".to_string(),
+        "email_changes".to_string(),
+        "account_deletions".to_string(),
+        "email_outbox".to_string(),
+        "review_sessions".to_string(),
+        "preferences".to_string(),
+        "calendar_feeds".to_string(),
+        "email_digests".to_string(),
+        "webhook_deliveries".to_string(),
+        "webhooks".to_string(),
+        "collections".to_string(),
+        "study_groups".to_string(),
+        "comments".to_string(),
     ];
     for table in tables {
         // cannot bind the table and thus prepare the sql
         let sql = format!("DELETE FROM {}", table);
         sqlx::query(&sql).bind(table).execute(pool).await.unwrap();
     }
+    // the curated tags are part of the schema
+    sqlx::query("DELETE FROM tags WHERE uid IS NOT NULL")
+        .execute(pool)
+        .await
+        .unwrap();
+    sqlx::query("DELETE FROM problems")
+        .execute(pool)
+        .await
+        .unwrap();
 
     Ok(())
 }
 
+// Sign a new user in for testing, returning the uid and the session token
+#[allow(dead_code)]
+pub async fn sign_in(pool: &PgPool) -> (Uuid, Uuid) {
+    let uid = Uuid::new_v4();
+    let token = auth::infrastructures::create_session(pool, &uid)
+        .await
+        .unwrap();
+    (uid, token)
+}
+
+// Insert a bare review for testing, bypassing the catalog
+#[allow(dead_code)]
+pub async fn insert_review(pool: &PgPool, id: i32, uid: &Uuid) {
+    sqlx::query(r#"INSERT INTO reviews (id, problem_name, url, memo, uid, platform, created_at) VALUES ($1, 'test_prob_name', 'test_url', 'test_memo', $2, 1, now())"#)
+        .bind(id)
+        .bind(uid)
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+// A review of an AtCoder problem for testing
+#[allow(dead_code)]
+pub fn new_review(problem_name: &str) -> NewReview {
+    NewReview {
+        problem_name: problem_name.to_string(),
+        url: "https://atcoder.jp/contests/abc200/tasks/abc200_a".to_string(),
+        memo: Some("test_memo".to_string()),
+        platform: 1,
+        difficulty: None,
+        solved_without_hints: false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::config;
     use chrono::Utc;
-    use uuid::Uuid;
 
     #[actix_rt::test]
     async fn clear_table_test() {
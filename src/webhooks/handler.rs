@@ -0,0 +1,197 @@
+use super::infrastructures;
+use super::model::{resolve_endpoint, NewWebhook};
+use crate::auth::AuthenticatedUser;
+use crate::error::ApiError;
+use actix_web::{delete, get, post, web, HttpResponse};
+use anyhow::Result;
+use sqlx::PgPool;
+use validator::{Validate, ValidationError, ValidationErrors};
+
+#[post("/webhooks")]
+pub async fn create_webhook(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    form: web::Form<NewWebhook>,
+) -> Result<HttpResponse, ApiError> {
+    match form.validate() {
+        Ok(_) => (),
+        Err(e) => return Err(e.into()),
+    }
+    // the names pointing into the private network are refused like the addresses there
+    let url = form.url.clone();
+    match web::block(move || resolve_endpoint(&url)).await {
+        Ok(_) => (),
+        Err(_) => {
+            let mut errors = ValidationErrors::new();
+            errors.add("url", ValidationError::new("invalid_endpoint"));
+            return Err(errors.into());
+        }
+    }
+
+    match infrastructures::create_webhook(pool.get_ref(), &user.uid, &form.url).await {
+        Ok(webhook) => Ok(HttpResponse::Created().json(webhook)),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[get("/webhooks")]
+pub async fn list_webhooks(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+) -> Result<HttpResponse, ApiError> {
+    match infrastructures::find_webhooks(pool.get_ref(), &user.uid).await {
+        Ok(webhooks) => Ok(HttpResponse::Ok().json(webhooks)),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[delete("/webhooks/{id}")]
+pub async fn delete_webhook(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path(id): web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    match infrastructures::delete_webhook(pool.get_ref(), &user.uid, id).await {
+        Ok(true) => Ok(HttpResponse::Ok().json("")),
+        Ok(false) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+// The test event is queued like the others and shows up in the delivery log.
+#[post("/webhooks/{id}/test")]
+pub async fn send_test_event(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path(id): web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    match infrastructures::send_test_event(pool.get_ref(), &user.uid, id).await {
+        Ok(Some(delivery)) => Ok(HttpResponse::Created().json(delivery)),
+        Ok(None) => Err(ApiError::NotFound),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[get("/webhooks/{id}/deliveries")]
+pub async fn list_deliveries(
+    pool: web::Data<PgPool>,
+    user: AuthenticatedUser,
+    web::Path(id): web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    match infrastructures::find_webhook(pool.get_ref(), &user.uid, id).await {
+        Ok(Some(_)) => (),
+        Ok(None) => return Err(ApiError::NotFound),
+        Err(_) => return Err(ApiError::InternalError),
+    }
+
+    match infrastructures::find_deliveries(pool.get_ref(), &user.uid, id).await {
+        Ok(deliveries) => Ok(HttpResponse::Ok().json(deliveries)),
+        Err(_) => Err(ApiError::InternalError),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webhooks::model::{CreatedWebhook, Delivery, Webhook, EVENT_TEST};
+    use crate::{auth, config, utils};
+    use actix_web::{test, App};
+    use uuid::Uuid;
+
+    #[actix_rt::test]
+    async fn webhooks_ok() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let mut app = test::init_service(
+            App::new()
+                .data(pool.clone())
+                .service(create_webhook)
+                .service(list_webhooks)
+                .service(delete_webhook)
+                .service(send_test_event)
+                .service(list_deliveries),
+        )
+        .await;
+        let uid = Uuid::new_v4();
+        let token = auth::infrastructures::create_session(&pool, &uid)
+            .await
+            .unwrap();
+
+        for url in [
+            "ftp://example.com/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://localhost:8080/hook",
+        ]
+        .iter()
+        {
+            let req = test::TestRequest::post()
+                .uri("/webhooks")
+                .header("Authorization", format!("Bearer {}", token))
+                .set_form(&NewWebhook {
+                    url: url.to_string(),
+                })
+                .to_request();
+            let resp = test::call_service(&mut app, req).await;
+            assert_eq!(400, resp.status(), "{}", url);
+        }
+
+        let req = test::TestRequest::post()
+            .uri("/webhooks")
+            .header("Authorization", format!("Bearer {}", token))
+            .set_form(&NewWebhook {
+                url: "https://203.0.113.7/hook".to_string(),
+            })
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(201, resp.status());
+        let created: CreatedWebhook = test::read_body_json(resp).await;
+        assert_eq!(64, created.secret.len());
+
+        let req = test::TestRequest::get()
+            .uri("/webhooks")
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let webhooks: Vec<Webhook> = test::read_response_json(&mut app, req).await;
+        assert_eq!(
+            vec![created.id],
+            webhooks.iter().map(|w| w.id).collect::<Vec<i32>>()
+        );
+
+        let req = test::TestRequest::post()
+            .uri(&format!("/webhooks/{}/test", created.id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(201, resp.status());
+        let delivery: Delivery = test::read_body_json(resp).await;
+        assert_eq!(EVENT_TEST, delivery.event);
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/webhooks/{}/deliveries", created.id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let deliveries: Vec<Delivery> = test::read_response_json(&mut app, req).await;
+        assert_eq!(vec![delivery], deliveries);
+
+        let req = test::TestRequest::delete()
+            .uri(&format!("/webhooks/{}", created.id))
+            .header("Authorization", format!("Bearer {}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(200, resp.status());
+
+        for req in [
+            test::TestRequest::post().uri(&format!("/webhooks/{}/test", created.id)),
+            test::TestRequest::get().uri(&format!("/webhooks/{}/deliveries", created.id)),
+            test::TestRequest::delete().uri(&format!("/webhooks/{}", created.id)),
+        ] {
+            let req = req
+                .header("Authorization", format!("Bearer {}", token))
+                .to_request();
+            let resp = test::call_service(&mut app, req).await;
+            assert_eq!(404, resp.status());
+        }
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+}
@@ -0,0 +1,434 @@
+use super::model::{
+    payload, resolve_endpoint, CreatedWebhook, Delivery, PendingDelivery, Webhook, EVENT_TEST,
+    STATUS_DELIVERED, STATUS_FAILED, STATUS_PENDING,
+};
+use super::Sender;
+use crate::mail::infrastructures::backoff;
+use actix_web::web;
+use anyhow::Result;
+use chrono::{Duration, Utc};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::Serialize;
+use serde_json::json;
+use sqlx::{Executor, PgPool, Postgres};
+use uuid::Uuid;
+
+// a delivery is given up after this many failures
+pub const MAX_ATTEMPTS: i32 = 8;
+// claimed deliveries are hidden from other workers for this long
+const CLAIM_LEASE_SECS: i64 = 5 * 60;
+const BATCH_SIZE: i64 = 20;
+// the delivery log keeps the latest ones
+const DELIVERY_LOG_SIZE: i64 = 100;
+
+pub async fn create_webhook(pool: &PgPool, uid: &Uuid, url: &str) -> Result<CreatedWebhook> {
+    // 256 random bits in hex
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    let secret = hex::encode(bytes);
+    let webhook = sqlx::query_as::<_, CreatedWebhook>(
+        r#"INSERT INTO webhooks (uid, url, secret, created_at) VALUES ($1, $2, $3, $4)
+        RETURNING id, url, secret, created_at"#,
+    )
+    .bind(uid)
+    .bind(url)
+    .bind(secret)
+    .bind(Utc::now())
+    .fetch_one(pool)
+    .await?;
+
+    Ok(webhook)
+}
+
+pub async fn find_webhooks(pool: &PgPool, uid: &Uuid) -> Result<Vec<Webhook>> {
+    let webhooks = sqlx::query_as::<_, Webhook>(
+        "SELECT id, url, created_at FROM webhooks WHERE uid = $1 ORDER BY id",
+    )
+    .bind(uid)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(webhooks)
+}
+
+pub async fn find_webhook(pool: &PgPool, uid: &Uuid, id: i32) -> Result<Option<Webhook>> {
+    let webhook = sqlx::query_as::<_, Webhook>(
+        "SELECT id, url, created_at FROM webhooks WHERE uid = $1 AND id = $2",
+    )
+    .bind(uid)
+    .bind(id)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(webhook)
+}
+
+// The deliveries of the webhook go with it.
+pub async fn delete_webhook(pool: &PgPool, uid: &Uuid, id: i32) -> Result<bool> {
+    let result = sqlx::query("DELETE FROM webhooks WHERE uid = $1 AND id = $2")
+        .bind(uid)
+        .bind(id)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+// Queue the event for every webhook of the user and return how many were queued.
+// The writes the event is about pass their transaction, so that it is queued along with them.
+pub async fn dispatch<'e, E, T>(executor: E, uid: &Uuid, event: &str, data: &T) -> Result<u64>
+where
+    E: Executor<'e, Database = Postgres>,
+    T: Serialize,
+{
+    let now = Utc::now();
+    let result = sqlx::query(
+        r#"INSERT INTO webhook_deliveries (webhook_id, uid, event, payload, status, attempts, next_attempt_at, created_at)
+        SELECT id, uid, $2, $3, $4, 0, $5, $5 FROM webhooks WHERE uid = $1"#,
+    )
+    .bind(uid)
+    .bind(event)
+    .bind(payload(event, data, now))
+    .bind(STATUS_PENDING)
+    .bind(now)
+    .execute(executor)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+// Queue a test event for a single webhook, absent when the webhook is not found.
+pub async fn send_test_event(pool: &PgPool, uid: &Uuid, id: i32) -> Result<Option<Delivery>> {
+    let now = Utc::now();
+    let data = json!({ "webhook_id": id });
+    let delivery = sqlx::query_as::<_, Delivery>(
+        r#"INSERT INTO webhook_deliveries (webhook_id, uid, event, payload, status, attempts, next_attempt_at, created_at)
+        SELECT id, uid, $3, $4, $5, 0, $6, $6 FROM webhooks WHERE uid = $1 AND id = $2
+        RETURNING id, webhook_id, event, payload, status, attempts, response_status, last_error, created_at, delivered_at"#,
+    )
+    .bind(uid)
+    .bind(id)
+    .bind(EVENT_TEST)
+    .bind(payload(EVENT_TEST, &data, now))
+    .bind(STATUS_PENDING)
+    .bind(now)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(delivery)
+}
+
+// The latest deliveries of the webhook first.
+pub async fn find_deliveries(pool: &PgPool, uid: &Uuid, id: i32) -> Result<Vec<Delivery>> {
+    let deliveries = sqlx::query_as::<_, Delivery>(
+        r#"SELECT id, webhook_id, event, payload, status, attempts, response_status, last_error, created_at, delivered_at
+        FROM webhook_deliveries WHERE uid = $1 AND webhook_id = $2
+        ORDER BY id DESC LIMIT $3"#,
+    )
+    .bind(uid)
+    .bind(id)
+    .bind(DELIVERY_LOG_SIZE)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(deliveries)
+}
+
+// Take the pending deliveries which are due, pushing their next attempt back by a lease.
+pub async fn claim_due(pool: &PgPool, limit: i64) -> Result<Vec<PendingDelivery>> {
+    let now = Utc::now();
+    let lease = now + Duration::seconds(CLAIM_LEASE_SECS);
+    let mut deliveries = sqlx::query_as::<_, PendingDelivery>(
+        r#"UPDATE webhook_deliveries d SET next_attempt_at = $1
+        FROM webhooks w
+        WHERE w.id = d.webhook_id AND d.id IN (
+            SELECT id FROM webhook_deliveries WHERE status = $2 AND next_attempt_at <= $3
+            ORDER BY id LIMIT $4 FOR UPDATE SKIP LOCKED
+        )
+        RETURNING d.id, d.event, d.payload, d.attempts, w.url, w.secret"#,
+    )
+    .bind(lease)
+    .bind(STATUS_PENDING)
+    .bind(now)
+    .bind(limit)
+    .fetch_all(pool)
+    .await?;
+    // RETURNING does not keep the order of the subquery
+    deliveries.sort_by_key(|d| d.id);
+
+    Ok(deliveries)
+}
+
+pub async fn mark_delivered(pool: &PgPool, id: i32, response_status: i16) -> Result<()> {
+    sqlx::query(
+        r#"UPDATE webhook_deliveries SET status = $1, attempts = attempts + 1, response_status = $2,
+        last_error = NULL, delivered_at = $3 WHERE id = $4"#,
+    )
+    .bind(STATUS_DELIVERED)
+    .bind(response_status)
+    .bind(Utc::now())
+    .bind(id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Schedule the next attempt with the backoff of the mails, or give up after `MAX_ATTEMPTS`.
+pub async fn mark_failed(
+    pool: &PgPool,
+    delivery: &PendingDelivery,
+    response_status: Option<i16>,
+    error: &str,
+) -> Result<()> {
+    let attempts = delivery.attempts + 1;
+    let status = if attempts >= MAX_ATTEMPTS {
+        STATUS_FAILED
+    } else {
+        STATUS_PENDING
+    };
+    sqlx::query(
+        r#"UPDATE webhook_deliveries SET status = $1, attempts = $2, next_attempt_at = $3,
+        response_status = $4, last_error = $5 WHERE id = $6"#,
+    )
+    .bind(status)
+    .bind(attempts)
+    .bind(Utc::now() + backoff(attempts))
+    .bind(response_status)
+    .bind(error)
+    .bind(delivery.id)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+// Post a batch of due deliveries and return how many of them were attempted.
+// Only a 2xx response counts as delivered.
+pub async fn deliver_due<S: Sender>(pool: &PgPool, sender: &S) -> Result<usize> {
+    let deliveries = claim_due(pool, BATCH_SIZE).await?;
+    let attempted = deliveries.len();
+    for delivery in deliveries {
+        let url = delivery.url.clone();
+        // the lookup blocks, so it must not block the worker
+        let address = match web::block(move || resolve_endpoint(&url)).await {
+            Ok(address) => address,
+            Err(e) => {
+                mark_failed(pool, &delivery, None, &e.to_string()).await?;
+                continue;
+            }
+        };
+        match sender.post(delivery.request(Utc::now()), address).await {
+            Ok(status) if (200..300).contains(&status) => {
+                mark_delivered(pool, delivery.id, status as i16).await?
+            }
+            Ok(status) => {
+                let error = format!("HTTP {}", status);
+                mark_failed(pool, &delivery, Some(status as i16), &error).await?
+            }
+            Err(e) => mark_failed(pool, &delivery, None, &e.to_string()).await?,
+        }
+    }
+
+    Ok(attempted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webhooks::model::{sign, Request, EVENT_CREATED};
+    use crate::{config, utils};
+    use futures::future::{self, LocalBoxFuture};
+    use std::cell::RefCell;
+    use std::net::SocketAddr;
+    use std::rc::Rc;
+
+    // the documentation addresses pass as public ones, and nothing is posted to them
+    const ENDPOINT: &str = "https://203.0.113.7/hook";
+
+    // Answers the given statuses in turn, or fails once they run out, keeping what was posted.
+    #[derive(Clone)]
+    struct FakeSender {
+        statuses: Rc<RefCell<Vec<u16>>>,
+        posted: Rc<RefCell<Vec<(Request, SocketAddr)>>>,
+    }
+
+    impl FakeSender {
+        fn new(statuses: Vec<u16>) -> FakeSender {
+            FakeSender {
+                statuses: Rc::new(RefCell::new(statuses)),
+                posted: Rc::new(RefCell::new(vec![])),
+            }
+        }
+    }
+
+    impl Sender for FakeSender {
+        fn post(
+            &self,
+            request: Request,
+            address: SocketAddr,
+        ) -> LocalBoxFuture<'static, Result<u16>> {
+            self.posted.borrow_mut().push((request, address));
+            let mut statuses = self.statuses.borrow_mut();
+            let result = if statuses.is_empty() {
+                Err(anyhow::anyhow!("connection refused"))
+            } else {
+                Ok(statuses.remove(0))
+            };
+            Box::pin(future::ready(result))
+        }
+    }
+
+    fn header<'a>(request: &'a Request, name: &str) -> &'a str {
+        request
+            .headers
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+            .unwrap()
+    }
+
+    #[actix_rt::test]
+    async fn deliver_signed_payload() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        let sender = FakeSender::new(vec![204]);
+        let webhook = create_webhook(&pool, &uid, ENDPOINT).await.unwrap();
+        // the events of another user are not posted to the webhook
+        let another = create_webhook(&pool, &Uuid::new_v4(), "https://203.0.113.8/hook")
+            .await
+            .unwrap();
+        assert_eq!(32, hex::decode(&webhook.secret).unwrap().len());
+        assert_ne!(webhook.secret, another.secret);
+
+        let queued = dispatch(&pool, &uid, EVENT_CREATED, &json!({ "id": 1 }))
+            .await
+            .unwrap();
+        assert_eq!(1, queued);
+        assert_eq!(1, deliver_due(&pool, &sender).await.unwrap());
+
+        let posted = sender.posted.borrow().clone();
+        assert_eq!(1, posted.len());
+        let (request, address) = &posted[0];
+        assert_eq!(ENDPOINT, request.url);
+        assert_eq!("203.0.113.7:443".parse::<SocketAddr>().unwrap(), *address);
+        assert_eq!("review.created", header(request, "X-Webhook-Event"));
+        let timestamp: i64 = header(request, "X-Webhook-Timestamp").parse().unwrap();
+        assert_eq!(
+            sign(&webhook.secret, timestamp, &request.body),
+            header(request, "X-Webhook-Signature")
+        );
+        let body: serde_json::Value = serde_json::from_str(&request.body).unwrap();
+        assert_eq!(json!({ "id": 1 }), body["data"]);
+
+        let deliveries = find_deliveries(&pool, &uid, webhook.id).await.unwrap();
+        assert_eq!(1, deliveries.len());
+        assert_eq!(STATUS_DELIVERED, deliveries[0].status);
+        assert_eq!(Some(204), deliveries[0].response_status);
+        assert!(deliveries[0].delivered_at.is_some());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn retry_failed_delivery() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        let sender = FakeSender::new(vec![500, 200]);
+        let webhook = create_webhook(&pool, &uid, ENDPOINT).await.unwrap();
+        let delivery = send_test_event(&pool, &uid, webhook.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(STATUS_PENDING, delivery.status);
+        assert_eq!(
+            None,
+            send_test_event(&pool, &Uuid::new_v4(), webhook.id)
+                .await
+                .unwrap()
+        );
+
+        assert_eq!(1, deliver_due(&pool, &sender).await.unwrap());
+        let deliveries = find_deliveries(&pool, &uid, webhook.id).await.unwrap();
+        assert_eq!(STATUS_PENDING, deliveries[0].status);
+        assert_eq!(1, deliveries[0].attempts);
+        assert_eq!(Some(500), deliveries[0].response_status);
+        assert_eq!(Some("HTTP 500".to_string()), deliveries[0].last_error);
+        // not due before the backoff
+        assert_eq!(0, deliver_due(&pool, &sender).await.unwrap());
+
+        sqlx::query("UPDATE webhook_deliveries SET next_attempt_at = now()")
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(1, deliver_due(&pool, &sender).await.unwrap());
+        let posted = sender.posted.borrow().clone();
+        assert_eq!(2, posted.len());
+        assert_eq!("test", header(&posted[1].0, "X-Webhook-Event"));
+        let deliveries = find_deliveries(&pool, &uid, webhook.id).await.unwrap();
+        assert_eq!(STATUS_DELIVERED, deliveries[0].status);
+        assert_eq!(2, deliveries[0].attempts);
+        assert_eq!(None, deliveries[0].last_error);
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn give_up_unreachable_endpoint() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        let sender = FakeSender::new(vec![]);
+        let webhook = create_webhook(&pool, &uid, ENDPOINT).await.unwrap();
+        send_test_event(&pool, &uid, webhook.id).await.unwrap();
+        sqlx::query("UPDATE webhook_deliveries SET attempts = $1")
+            .bind(MAX_ATTEMPTS - 1)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        assert_eq!(1, deliver_due(&pool, &sender).await.unwrap());
+        let deliveries = find_deliveries(&pool, &uid, webhook.id).await.unwrap();
+        assert_eq!(STATUS_FAILED, deliveries[0].status);
+        assert_eq!(None, deliveries[0].response_status);
+        assert!(deliveries[0].last_error.is_some());
+
+        assert!(delete_webhook(&pool, &uid, webhook.id).await.unwrap());
+        assert!(find_deliveries(&pool, &uid, webhook.id)
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(!delete_webhook(&pool, &uid, webhook.id).await.unwrap());
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn refuse_private_endpoint() {
+        let config = config::Config::new();
+        let pool = PgPool::connect(&config.database_url).await.unwrap();
+        let uid = Uuid::new_v4();
+        let sender = FakeSender::new(vec![200]);
+        // the endpoint is checked again when posting, as its name may point elsewhere by then
+        let webhook = create_webhook(&pool, &uid, "http://localhost:8080/hook")
+            .await
+            .unwrap();
+        send_test_event(&pool, &uid, webhook.id).await.unwrap();
+
+        assert_eq!(1, deliver_due(&pool, &sender).await.unwrap());
+        assert!(sender.posted.borrow().is_empty());
+        let deliveries = find_deliveries(&pool, &uid, webhook.id).await.unwrap();
+        assert_eq!(STATUS_PENDING, deliveries[0].status);
+        assert_eq!(1, deliveries[0].attempts);
+        assert!(deliveries[0]
+            .last_error
+            .as_ref()
+            .unwrap()
+            .contains("non-public"));
+
+        utils::clear_table(&pool).await.unwrap();
+    }
+}
@@ -0,0 +1,145 @@
+pub mod handler;
+pub mod infrastructures;
+pub mod model;
+
+use actix_web::client::Client;
+use anyhow::{anyhow, Result};
+use futures::future::LocalBoxFuture;
+use model::Request;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+const TIMEOUT_SECS: u64 = 10;
+const USER_AGENT: &str = "competitive-programming-review-webhooks";
+
+// Posts a payload to the address the endpoint was resolved to and returns the HTTP status.
+pub trait Sender {
+    fn post(&self, request: Request, address: SocketAddr) -> LocalBoxFuture<'static, Result<u16>>;
+}
+
+// The redirects are not followed, as they could lead anywhere.
+#[derive(Clone)]
+pub struct HttpSender {
+    client: Client,
+}
+
+impl HttpSender {
+    pub fn new() -> HttpSender {
+        let client = Client::builder()
+            .timeout(Duration::from_secs(TIMEOUT_SECS))
+            .header("User-Agent", USER_AGENT)
+            .finish();
+        HttpSender { client }
+    }
+}
+
+impl Sender for HttpSender {
+    fn post(&self, request: Request, address: SocketAddr) -> LocalBoxFuture<'static, Result<u16>> {
+        // the host of the URL still goes into the Host header and the TLS handshake
+        let mut client_request = self
+            .client
+            .post(&request.url)
+            .address(address)
+            .content_type("application/json");
+        for (name, value) in request.headers.iter() {
+            client_request = client_request.header(name.as_str(), value.as_str());
+        }
+        Box::pin(async move {
+            let response = client_request
+                .send_body(request.body)
+                .await
+                .map_err(|e| anyhow!("{}", e))?;
+            Ok(response.status().as_u16())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::rt::System;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    // A local receiver answering once with the status, passing on the request it got.
+    fn receiver(status: u16) -> (SocketAddr, thread::JoinHandle<(Vec<String>, String)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut head = Vec::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                head.push(line.trim_end().to_string());
+            }
+            let length: usize = head
+                .iter()
+                .find_map(|h| {
+                    h.to_lowercase()
+                        .strip_prefix("content-length: ")
+                        .map(String::from)
+                })
+                .unwrap()
+                .parse()
+                .unwrap();
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            let response = format!(
+                "HTTP/1.1 {} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                status
+            );
+            reader.get_mut().write_all(response.as_bytes()).unwrap();
+            (head, String::from_utf8(body).unwrap())
+        });
+        (address, handle)
+    }
+
+    // The client runs on the runtime of actix-web, not on the one of the async tests.
+    #[test]
+    fn post_to_resolved_address() {
+        let (address, handle) = receiver(204);
+        let request = Request {
+            // the name is not looked up, the request goes to the address given
+            url: "http://hooks.example.com/hook?id=1".to_string(),
+            headers: vec![("X-Webhook-Event".to_string(), "test".to_string())],
+            body: r#"{"event":"test"}"#.to_string(),
+        };
+
+        let status = System::new("test")
+            .block_on(async move { HttpSender::new().post(request, address).await })
+            .unwrap();
+        assert_eq!(204, status);
+
+        let (head, body) = handle.join().unwrap();
+        let head: Vec<String> = head.iter().map(|h| h.to_lowercase()).collect();
+        assert_eq!("post /hook?id=1 http/1.1", head[0]);
+        assert!(head.contains(&"host: hooks.example.com".to_string()));
+        assert!(head.contains(&"x-webhook-event: test".to_string()));
+        assert!(head.contains(&"content-type: application/json".to_string()));
+        assert_eq!(r#"{"event":"test"}"#, body);
+    }
+
+    #[test]
+    fn post_unreachable_address() {
+        // nothing listens on the port once the listener is dropped
+        let address = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let request = Request {
+            url: format!("http://{}/hook", address),
+            headers: vec![],
+            body: "{}".to_string(),
+        };
+
+        let result = System::new("test")
+            .block_on(async move { HttpSender::new().post(request, address).await });
+        assert!(result.is_err());
+    }
+}
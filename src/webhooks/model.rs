@@ -0,0 +1,287 @@
+use actix_web::http::Uri;
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac, NewMac};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::Sha256;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use validator::{Validate, ValidationError};
+
+pub const EVENT_CREATED: &str = "review.created";
+pub const EVENT_UPDATED: &str = "review.updated";
+pub const EVENT_GRADED: &str = "review.graded";
+pub const EVENT_DELETED: &str = "review.deleted";
+// sent on demand to check an endpoint
+pub const EVENT_TEST: &str = "test";
+
+// the delivery status stored in `webhook_deliveries.status`
+pub const STATUS_PENDING: i16 = 0;
+pub const STATUS_DELIVERED: i16 = 1;
+pub const STATUS_FAILED: i16 = 2;
+
+#[derive(Debug, Serialize, Deserialize, Validate, PartialEq)]
+pub struct NewWebhook {
+    #[validate(length(max = 2048), custom = "validate_endpoint")]
+    pub url: String,
+}
+
+// The names are only resolved by `resolve_endpoint`, as the validation must not block.
+fn validate_endpoint(url: &str) -> Result<(), ValidationError> {
+    match endpoint(url) {
+        Ok((host, _)) => match host.parse::<IpAddr>() {
+            Ok(ip) if !is_public(&ip) => Err(ValidationError::new("invalid_endpoint")),
+            _ => Ok(()),
+        },
+        Err(_) => Err(ValidationError::new("invalid_endpoint")),
+    }
+}
+
+// The host and the port of an http(s) URL.
+fn endpoint(url: &str) -> Result<(String, u16)> {
+    let uri: Uri = url.parse()?;
+    let default_port = match uri.scheme_str() {
+        Some("http") => 80,
+        Some("https") => 443,
+        _ => bail!("not an http(s) URL"),
+    };
+    let host = match uri.host() {
+        Some(host) if !host.is_empty() => host,
+        _ => bail!("no host"),
+    };
+    // IPv6 literals are bracketed in URLs
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    Ok((host.to_string(), uri.port_u16().unwrap_or(default_port)))
+}
+
+// Whether the webhooks may post to the address. The ones on the loopback, in the private
+// networks and the link-local ones (where the cloud metadata services live) are kept out.
+pub fn is_public(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let octets = ip.octets();
+            !(octets[0] == 0
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                // the shared address space of the carrier-grade NATs, 100.64.0.0/10
+                || (octets[0] == 100 && octets[1] & 0xc0 == 64))
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(&IpAddr::V4(ip)),
+            None => {
+                let first = ip.segments()[0];
+                !(ip.is_unspecified()
+                    || ip.is_loopback()
+                    || ip.is_multicast()
+                    // the unique local addresses, fc00::/7
+                    || first & 0xfe00 == 0xfc00
+                    // the link-local addresses, fe80::/10
+                    || first & 0xffc0 == 0xfe80)
+            }
+        },
+    }
+}
+
+// Resolve the endpoint to the address to post to, failing when the host resolves to any address
+// which is not public. The name is checked when the webhook is created and again before each
+// delivery, and the request goes to the address checked, as the name may be pointed elsewhere
+// in the meantime. This blocks on the lookup.
+pub fn resolve_endpoint(url: &str) -> Result<SocketAddr> {
+    let (host, port) = endpoint(url)?;
+    let addresses: Vec<SocketAddr> = (host.as_str(), port).to_socket_addrs()?.collect();
+    if let Some(address) = addresses.iter().find(|a| !is_public(&a.ip())) {
+        bail!(
+            "{} resolves to the non-public address {}",
+            host,
+            address.ip()
+        );
+    }
+    addresses
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("cannot resolve {}", host))
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, PartialEq)]
+pub struct Webhook {
+    pub id: i32,
+    pub url: String,
+    pub created_at: DateTime<Utc>,
+}
+
+// The secret is only shown once, when the webhook is created.
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, PartialEq)]
+pub struct CreatedWebhook {
+    pub id: i32,
+    pub url: String,
+    pub secret: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, sqlx::FromRow, PartialEq)]
+pub struct Delivery {
+    pub id: i32,
+    pub webhook_id: i32,
+    pub event: String,
+    pub payload: String,
+    // 0: pending, 1: delivered, 2: failed for good
+    pub status: i16,
+    pub attempts: i32,
+    pub response_status: Option<i16>,
+    pub last_error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+// A delivery claimed by the worker, with the endpoint to post it to.
+#[derive(Debug, Clone, sqlx::FromRow, PartialEq)]
+pub struct PendingDelivery {
+    pub id: i32,
+    pub event: String,
+    pub payload: String,
+    pub attempts: i32,
+    pub url: String,
+    pub secret: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl PendingDelivery {
+    // The receivers check the signature over the timestamp and the body,
+    // and may reject old timestamps against replays.
+    pub fn request(&self, now: DateTime<Utc>) -> Request {
+        let timestamp = now.timestamp();
+        Request {
+            url: self.url.clone(),
+            headers: vec![
+                ("X-Webhook-Event".to_string(), self.event.clone()),
+                ("X-Webhook-Delivery".to_string(), self.id.to_string()),
+                ("X-Webhook-Timestamp".to_string(), timestamp.to_string()),
+                (
+                    "X-Webhook-Signature".to_string(),
+                    sign(&self.secret, timestamp, &self.payload),
+                ),
+            ],
+            body: self.payload.clone(),
+        }
+    }
+}
+
+// `sha256=` followed by the hex HMAC-SHA256 of `{timestamp}.{body}` keyed by the secret
+pub fn sign(secret: &str, timestamp: i64, body: &str) -> String {
+    // HMAC takes keys of any length
+    let mut mac = Hmac::<Sha256>::new_varkey(secret.as_bytes()).unwrap();
+    mac.update(format!("{}.{}", timestamp, body).as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+pub fn payload<T: Serialize>(event: &str, data: &T, now: DateTime<Utc>) -> String {
+    json!({
+        "event": event,
+        "created_at": now,
+        "data": data,
+    })
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn validate_url() {
+        for url in [
+            "http://203.0.113.7:8080/hook",
+            "https://example.com/hooks?id=1",
+        ]
+        .iter()
+        {
+            let webhook = NewWebhook {
+                url: url.to_string(),
+            };
+            assert!(webhook.validate().is_ok(), "{}", url);
+        }
+        for url in [
+            "ftp://example.com/hook",
+            "example.com/hook",
+            "http://",
+            "http://127.0.0.1:8080/hook",
+            "http://10.0.0.1/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://[::1]/hook",
+            "http://[fd00:ec2::254]/hook",
+            "http://[::ffff:192.168.0.1]/hook",
+        ]
+        .iter()
+        {
+            let webhook = NewWebhook {
+                url: url.to_string(),
+            };
+            assert!(webhook.validate().is_err(), "{}", url);
+        }
+    }
+
+    #[test]
+    fn resolve_public_endpoint() {
+        assert_eq!(
+            "203.0.113.7:443".parse::<SocketAddr>().unwrap(),
+            resolve_endpoint("https://203.0.113.7/hook").unwrap()
+        );
+        assert_eq!(
+            "[2001:db8::1]:8080".parse::<SocketAddr>().unwrap(),
+            resolve_endpoint("http://[2001:db8::1]:8080/hook").unwrap()
+        );
+        // the names are checked by the addresses they resolve to
+        assert!(resolve_endpoint("http://localhost:8080/hook").is_err());
+        assert!(resolve_endpoint("http://100.100.100.200/latest").is_err());
+        assert!(resolve_endpoint("ftp://203.0.113.7/hook").is_err());
+    }
+
+    #[test]
+    fn sign_payload() {
+        assert_eq!(
+            "sha256=106d68dd42e540b747ed8110d7d8105b65f2b839dec9fd00f2d3f4f7686dd739",
+            sign("whsec", 1621500000, r#"{"event":"ping"}"#)
+        );
+    }
+
+    #[test]
+    fn signed_request() {
+        let now = Utc.ymd(2021, 5, 20).and_hms(8, 40, 0);
+        let delivery = PendingDelivery {
+            id: 3,
+            event: EVENT_DELETED.to_string(),
+            payload: payload(EVENT_DELETED, &json!({ "id": 1 }), now),
+            attempts: 0,
+            url: "http://203.0.113.7:8080/hook".to_string(),
+            secret: "whsec".to_string(),
+        };
+        assert_eq!(
+            r#"{"created_at":"2021-05-20T08:40:00Z","data":{"id":1},"event":"review.deleted"}"#,
+            delivery.payload
+        );
+        let request = delivery.request(now);
+        assert_eq!(delivery.payload, request.body);
+        assert_eq!(
+            vec![
+                ("X-Webhook-Event".to_string(), "review.deleted".to_string()),
+                ("X-Webhook-Delivery".to_string(), "3".to_string()),
+                ("X-Webhook-Timestamp".to_string(), "1621500000".to_string()),
+                (
+                    "X-Webhook-Signature".to_string(),
+                    sign("whsec", 1621500000, &delivery.payload)
+                ),
+            ],
+            request.headers
+        );
+    }
+}